@@ -71,14 +71,167 @@ public enum DivelogCompute {
         )
     }
 
+    /// Compute Surface Gradient Factor with a selectable ZHL-16 coefficient set.
+    public static func computeSurfaceGfWithCoefficients(
+        samples: [SampleInput],
+        gasMixes: [GasMixInput],
+        surfacePressureBar: Double? = nil,
+        coefficientSet: BuhlmannCoefficientSet? = nil,
+        customCoefficients: BuhlmannCoefficients? = nil
+    ) throws -> [SurfaceGfPoint] {
+        try DivelogCore.computeSurfaceGfWithCoefficients(
+            samples: samples,
+            gasMixes: gasMixes,
+            surfacePressureBar: surfacePressureBar,
+            coefficientSet: coefficientSet,
+            customCoefficients: customCoefficients
+        )
+    }
+
+    /// Validate a gas mix. Returns nil if valid, or an error message.
+    public static func validateGasMix(_ gas: GasMixInput) -> String? {
+        DivelogCore.validateGasMix(gas: gas)
+    }
+
+    /// Maximum operating depth (metres) for an O2 fraction at a PPO2 limit.
+    public static func maxOperatingDepth(o2Fraction: Double, maxPpo2: Double, surfacePressureBar: Double? = nil) -> Double {
+        DivelogCore.maxOperatingDepth(o2Fraction: o2Fraction, maxPpo2: maxPpo2, surfacePressureBar: surfacePressureBar)
+    }
+
+    /// Minimum operating depth (metres) for an O2 fraction at a PPO2 floor.
+    public static func minOperatingDepth(o2Fraction: Double, minPpo2: Double, surfacePressureBar: Double? = nil) -> Double {
+        DivelogCore.minOperatingDepth(o2Fraction: o2Fraction, minPpo2: minPpo2, surfacePressureBar: surfacePressureBar)
+    }
+
+    /// Equivalent narcotic depth (metres) of a mix at a depth.
+    public static func equivalentNarcoticDepth(gas: GasMixInput, depthM: Double, o2Narcotic: Bool, surfacePressureBar: Double? = nil) -> Double {
+        DivelogCore.equivalentNarcoticDepth(gas: gas, depthM: depthM, o2Narcotic: o2Narcotic, surfacePressureBar: surfacePressureBar)
+    }
+
+    /// Gas density (g/L) of a mix at a depth.
+    public static func gasDensity(gas: GasMixInput, depthM: Double, surfacePressureBar: Double? = nil) -> Double {
+        DivelogCore.gasDensity(gas: gas, depthM: depthM, surfacePressureBar: surfacePressureBar)
+    }
+
+    /// Best nitrox/trimix for a target depth under PPO2, END and density limits.
+    public static func bestMix(params: BestMixParams) throws -> BestMixResult {
+        try DivelogCore.bestMix(params: params)
+    }
+
+    /// Inspired O2 fraction of a semi-closed rebreather loop at a depth.
+    public static func scrLoopO2Fraction(params: ScrParams, supplyO2Fraction: Double, depthM: Double, surfacePressureBar: Double? = nil) throws -> Double {
+        try DivelogCore.scrLoopO2Fraction(params: params, supplyO2Fraction: supplyO2Fraction, depthM: depthM, surfacePressureBar: surfacePressureBar)
+    }
+
+    /// Compute a partial-pressure or continuous blending fill sequence.
+    public static func computeBlend(params: BlendParams) throws -> BlendResult {
+        try DivelogCore.computeBlend(params: params)
+    }
+
+    /// Real-gas compressibility factor Z of a mix at a pressure.
+    public static func compressibilityFactor(gas: GasMixInput, pressureBar: Double) -> Double {
+        DivelogCore.compressibilityFactor(gas: gas, pressureBar: pressureBar)
+    }
+
     /// Run a multi-model deco simulation — ceilings, stops, TTS, NDL.
-    public static func computeDecoSimulation(params: DecoSimParams) throws -> DecoSimResult {
-        try DivelogCore.computeDecoSimulation(params: params)
+    /// Tripping `cancel` aborts the run with `DecoSimError.Cancelled`;
+    /// `progress` receives the completed fraction on the calling thread.
+    /// The planners below take the same two arguments.
+    public static func computeDecoSimulation(
+        params: DecoSimParams,
+        cancel: CancellationToken? = nil,
+        progress: ProgressObserver? = nil
+    ) throws -> DecoSimResult {
+        try DivelogCore.computeDecoSimulation(params: params, cancel: cancel, progress: progress)
+    }
+
+    /// Start a live deco session that accepts samples incrementally.
+    public static func makeDecoSession(params: DecoSimParams) throws -> DecoSession {
+        try DecoSession(params: params)
+    }
+
+    /// Run one profile under several deco model configurations.
+    public static func compareModels(
+        params: ModelComparisonParams,
+        cancel: CancellationToken? = nil,
+        progress: ProgressObserver? = nil
+    ) throws -> ModelComparisonResult {
+        try DivelogCore.compareModels(params: params, cancel: cancel, progress: progress)
     }
 
     /// Generate a synthetic dive profile with two-pass deco planning.
-    public static func generateDiveProfile(params: ProfileGenParams) throws -> ProfileGenResult {
-        try DivelogCore.generateDiveProfile(params: params)
+    public static func generateDiveProfile(
+        params: ProfileGenParams,
+        cancel: CancellationToken? = nil,
+        progress: ProgressObserver? = nil
+    ) throws -> ProfileGenResult {
+        try DivelogCore.generateDiveProfile(params: params, cancel: cancel, progress: progress)
+    }
+
+    /// Plan repetitive dives, carrying residual gas from each dive into the next.
+    public static func planDiveDay(
+        params: DiveDayParams,
+        cancel: CancellationToken? = nil,
+        progress: ProgressObserver? = nil
+    ) throws -> DiveDayResult {
+        try DivelogCore.planDiveDay(params: params, cancel: cancel, progress: progress)
+    }
+
+    /// Shortest surface interval after completed dives for the next dive's target.
+    public static func minSurfaceInterval(
+        params: MinSurfaceIntervalParams,
+        cancel: CancellationToken? = nil,
+        progress: ProgressObserver? = nil
+    ) throws -> MinSurfaceIntervalResult {
+        try DivelogCore.minSurfaceInterval(params: params, cancel: cancel, progress: progress)
+    }
+
+    /// Evaluate a user-authored ascent schedule against the deco model.
+    public static func evaluateAscentSchedule(params: ScheduleEvalParams) throws -> ScheduleEvalResult {
+        try DivelogCore.evaluateAscentSchedule(params: params)
+    }
+
+    /// Apply a what-if edit to a logged dive and replan its ascent.
+    public static func applyDiveEdit(params: DiveEditParams) throws -> DiveEditResult {
+        try DivelogCore.applyDiveEdit(params: params)
+    }
+
+    /// Choose deco gases and switch depths that minimise runtime or deco time.
+    public static func optimizeDecoGases(
+        params: DecoGasOptimizerParams,
+        cancel: CancellationToken? = nil,
+        progress: ProgressObserver? = nil
+    ) throws -> DecoGasOptimizerResult {
+        try DivelogCore.optimizeDecoGases(params: params, cancel: cancel, progress: progress)
+    }
+
+    /// Find the longest bottom time that keeps runtime, TTS, CNS and gas limits.
+    public static func solveMaxBottomTime(
+        params: MaxBottomTimeParams,
+        cancel: CancellationToken? = nil,
+        progress: ProgressObserver? = nil
+    ) throws -> MaxBottomTimeResult {
+        try DivelogCore.solveMaxBottomTime(params: params, cancel: cancel, progress: progress)
+    }
+
+    /// Gas volumes, reserves and turn pressures for a generated profile.
+    public static func planGasLogistics(params: GasLogisticsParams) throws -> GasLogisticsResult {
+        try DivelogCore.planGasLogistics(params: params)
+    }
+
+    /// Rule-of-fifths ICD analysis of every gas switch in a profile.
+    public static func analyzeGasSwitches(samples: [SampleInput], gasMixes: [GasMixInput], surfacePressureBar: Double? = nil) -> [GasSwitchIcd] {
+        DivelogCore.analyzeGasSwitches(samples: samples, gasMixes: gasMixes, surfacePressureBar: surfacePressureBar)
+    }
+
+    /// Build a structured runtime table from a generated profile.
+    public static func buildRuntimeTable(profile: ProfileGenResult, surfacePressureBar: Double? = nil) -> RuntimeTable {
+        DivelogCore.buildRuntimeTable(profile: profile, surfacePressureBar: surfacePressureBar)
+    }
+
+    /// Render a runtime table as plain text, Markdown or CSV.
+    public static func renderRuntimeTable(table: RuntimeTable, format: RuntimeTableFormat, units: UnitSystem) -> String {
+        DivelogCore.renderRuntimeTable(table: table, format: format, units: units)
     }
 }
 
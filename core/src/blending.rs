@@ -0,0 +1,755 @@
+//! Gas blending calculator with real-gas compressibility.
+//!
+//! Cylinder contents are tracked as gas amounts `n = V·P / Z(P)` (bar·litres,
+//! i.e. surface litres), with the compressibility factor Z from a cubic
+//! virial fit per component (the fit used by Subsurface, valid to ~500 bar
+//! at room temperature). Pressures are cylinder gauge pressures in bar and
+//! fills are assumed to have settled back to room temperature.
+//!
+//! Partial-pressure blending adds helium, then oxygen, then tops with the
+//! top gas (air or a nitrox/trimix bank). Continuous blending delivers the
+//! same amounts as a single premixed stream through the compressor. When
+//! the current contents make the target unreachable, the fill starts with a
+//! bleed-down to the highest pressure that still works.
+
+use crate::buhlmann::GasMixInput;
+use crate::deco::shared::AIR_FO2;
+use crate::deco::DecoSimError;
+use crate::gas;
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// Virial coefficients (bar⁻¹, bar⁻², bar⁻³) for O2.
+const O2_VIRIAL: [f64; 3] = [-7.18092073703e-04, 2.81852572808e-06, -1.50290620492e-09];
+/// Virial coefficients (bar⁻¹, bar⁻², bar⁻³) for N2.
+const N2_VIRIAL: [f64; 3] = [-2.19260353292e-04, 2.92844845532e-06, -2.07613482075e-09];
+/// Virial coefficients (bar⁻¹, bar⁻², bar⁻³) for He.
+const HE_VIRIAL: [f64; 3] = [4.87320026468e-04, -8.83632921053e-08, 5.33304543646e-11];
+
+/// Upper end of the compressibility fit (bar).
+const MAX_MODEL_PRESSURE: f64 = 500.0;
+
+/// Highest O2 fraction accepted at a continuous blender intake.
+const CONTINUOUS_MAX_O2: f64 = 0.40;
+
+/// Amounts below this (bar·L) are treated as zero.
+const AMOUNT_EPSILON: f64 = 1e-6;
+
+// ============================================================================
+// FFI Types
+// ============================================================================
+
+/// Blending method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMethod {
+    /// Helium, then oxygen, then top gas, each decanted or boosted separately.
+    PartialPressure,
+    /// O2/He injected into the compressor intake and pumped as one stream.
+    Continuous,
+}
+
+/// Kind of step in a fill sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendStepKind {
+    /// Release gas until the cylinder reaches the step's end pressure.
+    BleedDown,
+    /// Add pure helium.
+    AddHelium,
+    /// Add pure oxygen.
+    AddOxygen,
+    /// Top with the top gas (partial pressure) or the premixed stream (continuous).
+    TopUp,
+}
+
+/// Parameters for a blend.
+#[derive(Debug, Clone)]
+pub struct BlendParams {
+    /// Cylinder water volume in litres.
+    pub cylinder_volume_l: f64,
+    /// Current cylinder pressure in bar.
+    pub current_pressure_bar: f64,
+    /// Current cylinder contents (ignored when the cylinder is empty).
+    pub current_mix: GasMixInput,
+    /// Pressure after the fill in bar.
+    pub target_pressure_bar: f64,
+    /// Mix after the fill.
+    pub target_mix: GasMixInput,
+    /// Top gas (partial pressure) or compressor intake base gas (continuous),
+    /// usually air.
+    pub top_gas: GasMixInput,
+    /// Blending method.
+    pub method: BlendMethod,
+    /// Helium bank pressure in bar. `None` = unlimited.
+    pub helium_supply_bar: Option<f64>,
+    /// Oxygen bank pressure in bar. `None` = unlimited.
+    pub oxygen_supply_bar: Option<f64>,
+    /// Top gas bank pressure in bar. `None` = compressor.
+    pub top_supply_bar: Option<f64>,
+}
+
+/// One step of a fill sequence.
+#[derive(Debug, Clone)]
+pub struct BlendStep {
+    /// What happens in this step.
+    pub kind: BlendStepKind,
+    /// Gas added (or released, for bleed-down).
+    pub gas: GasMixInput,
+    /// Cylinder pressure before the step (bar).
+    pub start_pressure_bar: f64,
+    /// Cylinder pressure after the step, settled (bar).
+    pub end_pressure_bar: f64,
+    /// Gas added or released in surface litres.
+    pub amount_l: f64,
+    /// Cylinder contents after the step.
+    pub mix_after: GasMixInput,
+    /// True if the end pressure exceeds the supply bank pressure.
+    pub requires_boost: bool,
+}
+
+/// Result of a blend calculation.
+#[derive(Debug, Clone)]
+pub struct BlendResult {
+    /// False if the target cannot be reached with the given top gas.
+    pub feasible: bool,
+    /// Reason the target is infeasible.
+    pub message: Option<String>,
+    /// Pressure to bleed down to before filling, if required (bar).
+    pub bleed_down_to_bar: Option<f64>,
+    /// Fill sequence in order.
+    pub steps: Vec<BlendStep>,
+    /// Helium added in surface litres.
+    pub helium_l: f64,
+    /// Oxygen added in surface litres.
+    pub oxygen_l: f64,
+    /// Top gas added in surface litres.
+    pub top_gas_l: f64,
+    /// Premixed stream delivered by the compressor (continuous blending only).
+    pub continuous_stream: Option<GasMixInput>,
+    /// Final pressure an ideal-gas calculation would predict for the same amounts (bar).
+    pub ideal_final_pressure_bar: f64,
+}
+
+// ============================================================================
+// Compressibility
+// ============================================================================
+
+fn virial(coef: &[f64; 3], p: f64) -> f64 {
+    coef[0] * p + coef[1] * p * p + coef[2] * p * p * p
+}
+
+/// Compressibility factor Z of a mix at `pressure_bar`.
+pub fn compressibility_factor(mix: &GasMixInput, pressure_bar: f64) -> f64 {
+    let p = pressure_bar.clamp(0.0, MAX_MODEL_PRESSURE);
+    let fn2 = (1.0 - mix.o2_fraction - mix.he_fraction).max(0.0);
+    1.0 + mix.o2_fraction * virial(&O2_VIRIAL, p)
+        + fn2 * virial(&N2_VIRIAL, p)
+        + mix.he_fraction * virial(&HE_VIRIAL, p)
+}
+
+/// Gas amount (surface litres) in a cylinder at a pressure.
+fn amount_at(volume_l: f64, pressure_bar: f64, mix: &GasMixInput) -> f64 {
+    if pressure_bar <= 0.0 {
+        return 0.0;
+    }
+    volume_l * pressure_bar / compressibility_factor(mix, pressure_bar)
+}
+
+/// Pressure at which a cylinder holds `amount_l` of a mix.
+///
+/// `P / Z(P)` is monotonic over the model range, so bisection is enough.
+fn pressure_for(volume_l: f64, amount_l: f64, mix: &GasMixInput) -> f64 {
+    if amount_l <= 0.0 {
+        return 0.0;
+    }
+    let (mut lo, mut hi) = (0.0, MAX_MODEL_PRESSURE * 1.5);
+    for _ in 0..100 {
+        let mid = 0.5 * (lo + hi);
+        if amount_at(volume_l, mid, mix) < amount_l {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    0.5 * (lo + hi)
+}
+
+// ============================================================================
+// Component Amounts
+// ============================================================================
+
+/// Amounts of O2, He and N2 in surface litres.
+#[derive(Debug, Clone, Copy, Default)]
+struct Contents {
+    o2: f64,
+    he: f64,
+    n2: f64,
+}
+
+impl Contents {
+    fn of(mix: &GasMixInput, amount: f64) -> Self {
+        Contents {
+            o2: mix.o2_fraction * amount,
+            he: mix.he_fraction * amount,
+            n2: (1.0 - mix.o2_fraction - mix.he_fraction).max(0.0) * amount,
+        }
+    }
+
+    fn total(&self) -> f64 {
+        self.o2 + self.he + self.n2
+    }
+
+    fn add(&self, other: Contents) -> Self {
+        Contents {
+            o2: self.o2 + other.o2,
+            he: self.he + other.he,
+            n2: self.n2 + other.n2,
+        }
+    }
+
+    fn mix(&self) -> GasMixInput {
+        let total = self.total();
+        if total <= 0.0 {
+            return air();
+        }
+        GasMixInput {
+            mix_index: 0,
+            o2_fraction: self.o2 / total,
+            he_fraction: self.he / total,
+        }
+    }
+}
+
+fn air() -> GasMixInput {
+    GasMixInput {
+        mix_index: 0,
+        o2_fraction: AIR_FO2,
+        he_fraction: 0.0,
+    }
+}
+
+fn pure_o2() -> GasMixInput {
+    GasMixInput {
+        mix_index: 0,
+        o2_fraction: 1.0,
+        he_fraction: 0.0,
+    }
+}
+
+/// Pure helium. `GasMixInput` requires O2 > 0 for breathing gases, so this
+/// is only used internally for labelling steps.
+fn pure_he() -> GasMixInput {
+    GasMixInput {
+        mix_index: 0,
+        o2_fraction: 0.0,
+        he_fraction: 1.0,
+    }
+}
+
+/// Helium, oxygen and top gas needed to turn `current` into `target`.
+///
+/// Each amount is linear in the retained current amount, `a = k − s·m`,
+/// where `s` scales the current contents (1 = no bleed-down).
+struct AddAmounts {
+    k: [f64; 3],
+    m: [f64; 3],
+}
+
+impl AddAmounts {
+    fn solve(current: Contents, target: Contents, top: &GasMixInput) -> Self {
+        let top_n2 = 1.0 - top.o2_fraction - top.he_fraction;
+        // N2 only comes from the top gas
+        let k_top = target.n2 / top_n2;
+        let m_top = current.n2 / top_n2;
+        AddAmounts {
+            k: [
+                target.he - k_top * top.he_fraction,
+                target.o2 - k_top * top.o2_fraction,
+                k_top,
+            ],
+            m: [
+                current.he - m_top * top.he_fraction,
+                current.o2 - m_top * top.o2_fraction,
+                m_top,
+            ],
+        }
+    }
+
+    /// Amounts `[He, O2, top]` when keeping fraction `s` of the current contents.
+    fn at(&self, s: f64) -> [f64; 3] {
+        [0, 1, 2].map(|i| (self.k[i] - s * self.m[i]).max(0.0))
+    }
+
+    /// Largest fraction `s ∈ [0, 1]` of the current contents that can be
+    /// kept with every amount non-negative, or the index of a component that
+    /// cannot be satisfied for any `s`.
+    fn max_retained(&self) -> Result<f64, usize> {
+        let mut upper: f64 = 1.0;
+        let mut lower: (f64, Option<usize>) = (0.0, None);
+        for i in 0..3 {
+            let (k, m) = (self.k[i], self.m[i]);
+            if m.abs() <= AMOUNT_EPSILON {
+                if k < -AMOUNT_EPSILON {
+                    return Err(i);
+                }
+            } else if m > 0.0 {
+                upper = upper.min(k / m);
+            } else if k / m > lower.0 {
+                lower = (k / m, Some(i));
+            }
+        }
+        if upper < 0.0 {
+            // Even an empty cylinder leaves this component in excess
+            let i = (0..3)
+                .find(|&i| self.m[i] > 0.0 && self.k[i] < 0.0)
+                .unwrap_or(2);
+            return Err(i);
+        }
+        if lower.0 > upper + 1e-9 {
+            return Err(lower.1.unwrap_or(2));
+        }
+        Ok(upper)
+    }
+}
+
+// ============================================================================
+// Public API
+// ============================================================================
+
+/// Compute a fill sequence for the requested blend.
+///
+/// Returns `Err` for invalid inputs and `Ok` with `feasible: false` when the
+/// target mix cannot be made from the given top gas.
+pub fn compute_blend(params: &BlendParams) -> Result<BlendResult, DecoSimError> {
+    validate_params(params)?;
+
+    let volume = params.cylinder_volume_l;
+    let current_mix = if params.current_pressure_bar > 0.0 {
+        params.current_mix.clone()
+    } else {
+        air()
+    };
+    let current_amount = amount_at(volume, params.current_pressure_bar, &current_mix);
+    let current = Contents::of(&current_mix, current_amount);
+    let target_amount = amount_at(volume, params.target_pressure_bar, &params.target_mix);
+    let target = Contents::of(&params.target_mix, target_amount);
+
+    let amounts = AddAmounts::solve(current, target, &params.top_gas);
+    let retained = match amounts.max_retained() {
+        Ok(s) => s,
+        Err(component) => {
+            let message = match component {
+                0 => "target helium is below what the top gas contributes",
+                1 => "target O2 is below what the top gas contributes; add helium to the top gas or use a leaner top gas",
+                _ => "target mix cannot be made from this top gas",
+            };
+            return Ok(infeasible(message.to_string(), params.target_pressure_bar));
+        }
+    };
+    let [he_l, o2_l, top_l] = amounts.at(retained);
+
+    let mut steps = Vec::new();
+    let mut contents = Contents::of(&current_mix, current_amount * retained);
+    let mut pressure = params.current_pressure_bar;
+    let bleed_down_to_bar = if retained < 1.0 - 1e-9 {
+        let bled = pressure_for(volume, contents.total(), &current_mix);
+        steps.push(BlendStep {
+            kind: BlendStepKind::BleedDown,
+            gas: current_mix.clone(),
+            start_pressure_bar: pressure,
+            end_pressure_bar: bled,
+            amount_l: current_amount * (1.0 - retained),
+            mix_after: current_mix.clone(),
+            requires_boost: false,
+        });
+        pressure = bled;
+        Some(bled)
+    } else {
+        None
+    };
+
+    let continuous_stream = match params.method {
+        BlendMethod::PartialPressure => {
+            let additions = [
+                (
+                    BlendStepKind::AddHelium,
+                    pure_he(),
+                    he_l,
+                    params.helium_supply_bar,
+                ),
+                (
+                    BlendStepKind::AddOxygen,
+                    pure_o2(),
+                    o2_l,
+                    params.oxygen_supply_bar,
+                ),
+                (
+                    BlendStepKind::TopUp,
+                    params.top_gas.clone(),
+                    top_l,
+                    params.top_supply_bar,
+                ),
+            ];
+            for (kind, gas, amount, supply) in additions {
+                if amount <= AMOUNT_EPSILON {
+                    continue;
+                }
+                contents = contents.add(Contents::of(&gas, amount));
+                let mix_after = contents.mix();
+                let end = pressure_for(volume, contents.total(), &mix_after);
+                steps.push(BlendStep {
+                    kind,
+                    gas,
+                    start_pressure_bar: pressure,
+                    end_pressure_bar: end,
+                    amount_l: amount,
+                    mix_after,
+                    requires_boost: supply.is_some_and(|s| end > s),
+                });
+                pressure = end;
+            }
+            None
+        }
+        BlendMethod::Continuous => {
+            let stream = Contents::of(&pure_he(), he_l)
+                .add(Contents::of(&pure_o2(), o2_l))
+                .add(Contents::of(&params.top_gas, top_l));
+            if stream.total() > AMOUNT_EPSILON {
+                let stream_mix = stream.mix();
+                if stream_mix.o2_fraction > CONTINUOUS_MAX_O2 + 1e-9 {
+                    return Ok(infeasible(
+                        format!(
+                            "continuous blending needs {:.1}% O2 at the intake (max {:.0}%); use partial pressure blending",
+                            stream_mix.o2_fraction * 100.0,
+                            CONTINUOUS_MAX_O2 * 100.0
+                        ),
+                        params.target_pressure_bar,
+                    ));
+                }
+                contents = contents.add(stream);
+                let mix_after = contents.mix();
+                let end = pressure_for(volume, contents.total(), &mix_after);
+                steps.push(BlendStep {
+                    kind: BlendStepKind::TopUp,
+                    gas: stream_mix.clone(),
+                    start_pressure_bar: pressure,
+                    end_pressure_bar: end,
+                    amount_l: stream.total(),
+                    mix_after,
+                    requires_boost: false,
+                });
+                Some(stream_mix)
+            } else {
+                None
+            }
+        }
+    };
+
+    Ok(BlendResult {
+        feasible: true,
+        message: None,
+        bleed_down_to_bar,
+        steps,
+        helium_l: he_l,
+        oxygen_l: o2_l,
+        top_gas_l: top_l,
+        continuous_stream,
+        ideal_final_pressure_bar: contents.total() / volume,
+    })
+}
+
+fn infeasible(message: String, target_pressure_bar: f64) -> BlendResult {
+    BlendResult {
+        feasible: false,
+        message: Some(message),
+        bleed_down_to_bar: None,
+        steps: Vec::new(),
+        helium_l: 0.0,
+        oxygen_l: 0.0,
+        top_gas_l: 0.0,
+        continuous_stream: None,
+        ideal_final_pressure_bar: target_pressure_bar,
+    }
+}
+
+// ============================================================================
+// Validation
+// ============================================================================
+
+fn validate_params(params: &BlendParams) -> Result<(), DecoSimError> {
+    if params.cylinder_volume_l <= 0.0 {
+        return Err(DecoSimError::InvalidParam {
+            msg: format!(
+                "cylinder_volume_l ({}) must be > 0",
+                params.cylinder_volume_l
+            ),
+        });
+    }
+    if params.current_pressure_bar < 0.0 {
+        return Err(DecoSimError::InvalidParam {
+            msg: format!(
+                "current_pressure_bar ({}) must be >= 0",
+                params.current_pressure_bar
+            ),
+        });
+    }
+    if params.target_pressure_bar <= 0.0 || params.target_pressure_bar > MAX_MODEL_PRESSURE {
+        return Err(DecoSimError::InvalidParam {
+            msg: format!(
+                "target_pressure_bar ({}) must be > 0 and <= {MAX_MODEL_PRESSURE}",
+                params.target_pressure_bar
+            ),
+        });
+    }
+    if params.current_pressure_bar > MAX_MODEL_PRESSURE {
+        return Err(DecoSimError::InvalidParam {
+            msg: format!(
+                "current_pressure_bar ({}) must be <= {MAX_MODEL_PRESSURE}",
+                params.current_pressure_bar
+            ),
+        });
+    }
+    if params.current_pressure_bar > 0.0 {
+        gas::validate_gas_mix(&params.current_mix)?;
+    }
+    gas::validate_gas_mix(&params.target_mix)?;
+    gas::validate_gas_mix(&params.top_gas)?;
+    if 1.0 - params.top_gas.o2_fraction - params.top_gas.he_fraction <= AMOUNT_EPSILON {
+        return Err(DecoSimError::InvalidParam {
+            msg: "top_gas must contain nitrogen".to_string(),
+        });
+    }
+    for (name, value) in [
+        ("helium_supply_bar", params.helium_supply_bar),
+        ("oxygen_supply_bar", params.oxygen_supply_bar),
+        ("top_supply_bar", params.top_supply_bar),
+    ] {
+        if let Some(v) = value {
+            if v <= 0.0 {
+                return Err(DecoSimError::InvalidParam {
+                    msg: format!("{name} ({v}) must be > 0"),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mix(o2: f64, he: f64) -> GasMixInput {
+        GasMixInput {
+            mix_index: 0,
+            o2_fraction: o2,
+            he_fraction: he,
+        }
+    }
+
+    fn params(current_bar: f64, current: GasMixInput, target: GasMixInput) -> BlendParams {
+        BlendParams {
+            cylinder_volume_l: 12.0,
+            current_pressure_bar: current_bar,
+            current_mix: current,
+            target_pressure_bar: 200.0,
+            target_mix: target,
+            top_gas: air(),
+            method: BlendMethod::PartialPressure,
+            helium_supply_bar: None,
+            oxygen_supply_bar: None,
+            top_supply_bar: None,
+        }
+    }
+
+    fn final_mix(result: &BlendResult) -> GasMixInput {
+        result.steps.last().unwrap().mix_after.clone()
+    }
+
+    #[test]
+    fn test_compressibility_factor() {
+        assert!((compressibility_factor(&air(), 0.0) - 1.0).abs() < 1e-12);
+        // Air at 200 bar is slightly less compressible than ideal (Z ≈ 1.03–1.04)
+        let z_air = compressibility_factor(&air(), 200.0);
+        assert!(z_air > 1.02 && z_air < 1.05, "Z(air, 200) = {z_air}");
+        // Helium deviates much more (Z ≈ 1.09)
+        let z_he = compressibility_factor(&pure_he(), 200.0);
+        assert!(z_he > 1.08 && z_he < 1.11, "Z(He, 200) = {z_he}");
+    }
+
+    #[test]
+    fn test_pressure_amount_roundtrip() {
+        let tx = mix(0.21, 0.35);
+        let amount = amount_at(12.0, 232.0, &tx);
+        assert!((pressure_for(12.0, amount, &tx) - 232.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_trimix_from_empty() {
+        let target = mix(0.21, 0.35);
+        let result = compute_blend(&params(0.0, air(), target.clone())).unwrap();
+        assert!(result.feasible);
+        assert!(result.bleed_down_to_bar.is_none());
+        let kinds: Vec<BlendStepKind> = result.steps.iter().map(|s| s.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                BlendStepKind::AddHelium,
+                BlendStepKind::AddOxygen,
+                BlendStepKind::TopUp
+            ]
+        );
+        let last = result.steps.last().unwrap();
+        assert!((last.end_pressure_bar - 200.0).abs() < 1e-6);
+        let got = final_mix(&result);
+        assert!((got.o2_fraction - 0.21).abs() < 1e-9);
+        assert!((got.he_fraction - 0.35).abs() < 1e-9);
+        // Pressures increase monotonically through the fill
+        for pair in result.steps.windows(2) {
+            assert!(pair[1].start_pressure_bar == pair[0].end_pressure_bar);
+            assert!(pair[1].end_pressure_bar > pair[0].end_pressure_bar);
+        }
+    }
+
+    #[test]
+    fn test_real_gas_differs_from_ideal() {
+        let result = compute_blend(&params(0.0, air(), mix(0.18, 0.45))).unwrap();
+        // Helium is less compressible: the He step needs more pressure than its
+        // ideal share, and the same amounts would read lower on an ideal gauge.
+        let he = &result.steps[0];
+        let ideal_he_bar = he.amount_l / 12.0;
+        assert!(he.end_pressure_bar > ideal_he_bar);
+        assert!(result.ideal_final_pressure_bar < 200.0);
+    }
+
+    #[test]
+    fn test_nitrox_top_up_existing() {
+        // 50 bar of EAN32 topped to 200 bar EAN32: O2 + air only
+        let ean32 = mix(0.32, 0.0);
+        let result = compute_blend(&params(50.0, ean32.clone(), ean32)).unwrap();
+        assert!(result.feasible);
+        assert_eq!(result.helium_l, 0.0);
+        assert_eq!(result.steps[0].kind, BlendStepKind::AddOxygen);
+        assert!((result.steps[0].start_pressure_bar - 50.0).abs() < 1e-9);
+        assert!((final_mix(&result).o2_fraction - 0.32).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_bleed_down_required() {
+        // 150 bar of air cannot become 200 bar EAN50 without bleeding
+        let result = compute_blend(&params(150.0, air(), mix(0.50, 0.0))).unwrap();
+        assert!(result.feasible);
+        let bled = result.bleed_down_to_bar.unwrap();
+        assert!(bled < 150.0 && bled > 0.0);
+        assert_eq!(result.steps[0].kind, BlendStepKind::BleedDown);
+        assert!((result.steps[0].end_pressure_bar - bled).abs() < 1e-9);
+        assert!((final_mix(&result).o2_fraction - 0.50).abs() < 1e-9);
+        assert!((result.steps.last().unwrap().end_pressure_bar - 200.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_lower_target_pressure_bleeds() {
+        let ean32 = mix(0.32, 0.0);
+        let mut p = params(220.0, ean32.clone(), ean32);
+        p.target_pressure_bar = 200.0;
+        let result = compute_blend(&p).unwrap();
+        assert!(result.feasible);
+        assert!((result.bleed_down_to_bar.unwrap() - 200.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_infeasible_hypoxic_nitrox() {
+        // 18% O2 with no helium cannot be made by topping with air
+        let result = compute_blend(&params(0.0, air(), mix(0.18, 0.0))).unwrap();
+        assert!(!result.feasible);
+        assert!(result.message.unwrap().contains("O2"));
+        assert!(result.steps.is_empty());
+    }
+
+    #[test]
+    fn test_trimix_bank_top_up() {
+        let mut p = params(0.0, air(), mix(0.18, 0.45));
+        p.top_gas = mix(0.21, 0.35);
+        let result = compute_blend(&p).unwrap();
+        assert!(result.feasible);
+        let got = final_mix(&result);
+        assert!((got.o2_fraction - 0.18).abs() < 1e-9);
+        assert!((got.he_fraction - 0.45).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_existing_contents_make_bank_top_up_feasible() {
+        // From empty, a 21/35 bank brings too much helium for 21/20. With
+        // 100 bar of air already in the cylinder, less bank gas is needed.
+        let mut p = params(0.0, air(), mix(0.21, 0.20));
+        p.top_gas = mix(0.21, 0.35);
+        let empty = compute_blend(&p).unwrap();
+        assert!(!empty.feasible);
+
+        p.current_pressure_bar = 100.0;
+        let result = compute_blend(&p).unwrap();
+        assert!(result.feasible);
+        assert!(result.bleed_down_to_bar.is_none());
+        let got = final_mix(&result);
+        assert!((got.o2_fraction - 0.21).abs() < 1e-9);
+        assert!((got.he_fraction - 0.20).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_boost_flags() {
+        let mut p = params(0.0, air(), mix(0.21, 0.35));
+        p.helium_supply_bar = Some(50.0);
+        p.oxygen_supply_bar = Some(150.0);
+        p.top_supply_bar = Some(300.0);
+        let result = compute_blend(&p).unwrap();
+        let he = &result.steps[0];
+        assert!(he.end_pressure_bar > 50.0);
+        assert!(he.requires_boost);
+        assert!(!result.steps[1].requires_boost);
+        assert!(!result.steps[2].requires_boost);
+    }
+
+    #[test]
+    fn test_continuous_blending() {
+        let mut p = params(0.0, air(), mix(0.32, 0.0));
+        p.method = BlendMethod::Continuous;
+        let result = compute_blend(&p).unwrap();
+        assert!(result.feasible);
+        assert_eq!(result.steps.len(), 1);
+        let stream = result.continuous_stream.unwrap();
+        assert!((stream.o2_fraction - 0.32).abs() < 1e-9);
+        assert!((result.steps[0].end_pressure_bar - 200.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_continuous_intake_o2_limit() {
+        let mut p = params(0.0, air(), mix(0.50, 0.0));
+        p.method = BlendMethod::Continuous;
+        let result = compute_blend(&p).unwrap();
+        assert!(!result.feasible);
+        assert!(result.message.unwrap().contains("intake"));
+    }
+
+    #[test]
+    fn test_invalid_params() {
+        let mut p = params(0.0, air(), mix(0.32, 0.0));
+        p.cylinder_volume_l = 0.0;
+        assert!(compute_blend(&p).is_err());
+
+        let mut p = params(0.0, air(), mix(0.32, 0.0));
+        p.target_pressure_bar = 600.0;
+        assert!(compute_blend(&p).is_err());
+
+        let mut p = params(0.0, air(), mix(0.32, 0.0));
+        p.top_gas = mix(1.0, 0.0);
+        assert!(compute_blend(&p).is_err());
+
+        let p = params(0.0, air(), mix(0.6, 0.6));
+        assert!(compute_blend(&p).is_err());
+    }
+}
@@ -1,4 +1,4 @@
-//! Bühlmann ZHL-16C tissue simulation for Surface Gradient Factor computation.
+//! Bühlmann ZHL-16 tissue simulation for Surface Gradient Factor computation.
 //!
 //! Implements a full 16-compartment tissue model using the Schreiner equation
 //! to simulate inert gas loading from a depth/time/gas profile. Computes
@@ -6,6 +6,7 @@
 //! ascended directly to the surface — for each sample point.
 
 use crate::deco::shared::*;
+use crate::deco::DecoSimError;
 use crate::metrics::SampleInput;
 
 // ============================================================================
@@ -51,10 +52,189 @@ pub(crate) const B_HE: [f64; NUM_COMPARTMENTS] = [
     0.9122, 0.9171, 0.9217, 0.9267,
 ];
 
+// ============================================================================
+// Coefficient Sets
+// ============================================================================
+
+/// A ZHL-16 coefficient set: half-times and M-value coefficients per
+/// compartment.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct BuhlmannParamSet {
+    /// N2 half-times in minutes.
+    pub n2_half_times: [f64; NUM_COMPARTMENTS],
+    /// He half-times in minutes.
+    pub he_half_times: [f64; NUM_COMPARTMENTS],
+    /// N2 'a' coefficients (bar).
+    pub a_n2: [f64; NUM_COMPARTMENTS],
+    /// N2 'b' coefficients (dimensionless).
+    pub b_n2: [f64; NUM_COMPARTMENTS],
+    /// He 'a' coefficients (bar).
+    pub a_he: [f64; NUM_COMPARTMENTS],
+    /// He 'b' coefficients (dimensionless).
+    pub b_he: [f64; NUM_COMPARTMENTS],
+}
+
+impl BuhlmannParamSet {
+    /// Check that every half-time and 'a' coefficient is positive and every
+    /// 'b' coefficient is in (0, 1].
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        for i in 0..NUM_COMPARTMENTS {
+            if self.n2_half_times[i] <= 0.0 || self.he_half_times[i] <= 0.0 {
+                return Err(format!("half-times for compartment {} must be > 0", i + 1));
+            }
+            if self.a_n2[i] <= 0.0 || self.a_he[i] <= 0.0 {
+                return Err(format!(
+                    "a coefficients for compartment {} must be > 0",
+                    i + 1
+                ));
+            }
+            let b_valid = |b: f64| b > 0.0 && b <= 1.0;
+            if !b_valid(self.b_n2[i]) || !b_valid(self.b_he[i]) {
+                return Err(format!(
+                    "b coefficients for compartment {} must be in (0, 1]",
+                    i + 1
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Build and validate a set from user-supplied coefficients.
+    pub(crate) fn from_custom(custom: &BuhlmannCoefficients) -> Result<Self, String> {
+        let table = |name: &str, values: &[f64]| -> Result<[f64; NUM_COMPARTMENTS], String> {
+            values.try_into().map_err(|_| {
+                format!(
+                    "{name} must have {NUM_COMPARTMENTS} entries, got {}",
+                    values.len()
+                )
+            })
+        };
+        let set = Self {
+            n2_half_times: table("n2_half_times_min", &custom.n2_half_times_min)?,
+            he_half_times: table("he_half_times_min", &custom.he_half_times_min)?,
+            a_n2: table("a_n2", &custom.a_n2)?,
+            b_n2: table("b_n2", &custom.b_n2)?,
+            a_he: table("a_he", &custom.a_he)?,
+            b_he: table("b_he", &custom.b_he)?,
+        };
+        set.validate()?;
+        Ok(set)
+    }
+
+    /// Resolve the selected coefficient set (default ZHL-16C).
+    ///
+    /// `custom` is required for `Custom` and rejected otherwise.
+    pub(crate) fn resolve(
+        set: Option<BuhlmannCoefficientSet>,
+        custom: Option<&BuhlmannCoefficients>,
+    ) -> Result<Self, DecoSimError> {
+        let invalid = |msg: String| DecoSimError::InvalidParam { msg };
+        match (set.unwrap_or(BuhlmannCoefficientSet::Zhl16c), custom) {
+            (BuhlmannCoefficientSet::Custom, Some(custom)) => {
+                Self::from_custom(custom).map_err(invalid)
+            }
+            (BuhlmannCoefficientSet::Custom, None) => Err(invalid(
+                "Custom Bühlmann coefficients require custom_buhlmann_coefficients".to_string(),
+            )),
+            (_, Some(_)) => Err(invalid(
+                "custom_buhlmann_coefficients require the Custom coefficient set".to_string(),
+            )),
+            (BuhlmannCoefficientSet::Zhl16a, None) => Ok(ZHL16A.clone()),
+            (BuhlmannCoefficientSet::Zhl16b, None) => Ok(ZHL16B.clone()),
+            (BuhlmannCoefficientSet::Zhl16c, None) => Ok(ZHL16C.clone()),
+        }
+    }
+}
+
+/// ZHL-16A: Bühlmann's original experimental set with the 4-minute
+/// compartment 1 (Bühlmann, Tauchmedizin).
+pub(crate) static ZHL16A: BuhlmannParamSet = BuhlmannParamSet {
+    n2_half_times: [
+        4.0, 8.0, 12.5, 18.5, 27.0, 38.3, 54.3, 77.0, 109.0, 146.0, 187.0, 239.0, 305.0, 390.0,
+        498.0, 635.0,
+    ],
+    he_half_times: [
+        1.51, 3.02, 4.72, 6.99, 10.21, 14.48, 20.53, 29.11, 41.20, 55.19, 70.69, 90.34, 115.29,
+        147.42, 188.24, 240.03,
+    ],
+    a_n2: [
+        1.2599, 1.0000, 0.8618, 0.7562, 0.6667, 0.5933, 0.5282, 0.4701, 0.4187, 0.3798, 0.3497,
+        0.3223, 0.2971, 0.2737, 0.2523, 0.2327,
+    ],
+    b_n2: [
+        0.5050, 0.6514, 0.7222, 0.7825, 0.8126, 0.8434, 0.8693, 0.8910, 0.9092, 0.9222, 0.9319,
+        0.9403, 0.9477, 0.9544, 0.9602, 0.9653,
+    ],
+    a_he: [
+        1.7424, 1.3830, 1.1919, 1.0458, 0.9220, 0.8205, 0.7305, 0.6502, 0.5950, 0.5545, 0.5333,
+        0.5189, 0.5181, 0.5176, 0.5172, 0.5119,
+    ],
+    b_he: [
+        0.4245, 0.5747, 0.6527, 0.7223, 0.7582, 0.7957, 0.8279, 0.8553, 0.8757, 0.8903, 0.8997,
+        0.9073, 0.9122, 0.9171, 0.9217, 0.9267,
+    ],
+};
+
+/// ZHL-16B: the table-calculation set, with compartment 1b and reduced N2
+/// 'a' values in compartments 6, 7, 8 and 13.
+pub(crate) static ZHL16B: BuhlmannParamSet = BuhlmannParamSet {
+    n2_half_times: N2_HALF_TIMES,
+    he_half_times: HE_HALF_TIMES,
+    a_n2: [
+        1.1696, 1.0000, 0.8618, 0.7562, 0.6667, 0.5600, 0.4947, 0.4500, 0.4187, 0.3798, 0.3497,
+        0.3223, 0.2850, 0.2737, 0.2523, 0.2327,
+    ],
+    b_n2: B_N2,
+    a_he: A_HE,
+    b_he: B_HE,
+};
+
+/// ZHL-16C: the dive-computer set (the constants above).
+pub(crate) static ZHL16C: BuhlmannParamSet = BuhlmannParamSet {
+    n2_half_times: N2_HALF_TIMES,
+    he_half_times: HE_HALF_TIMES,
+    a_n2: A_N2,
+    b_n2: B_N2,
+    a_he: A_HE,
+    b_he: B_HE,
+};
+
 // ============================================================================
 // FFI Types
 // ============================================================================
 
+/// ZHL-16 coefficient set used by the Bühlmann model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuhlmannCoefficientSet {
+    /// ZHL-16A — the original experimental set (reference).
+    Zhl16a,
+    /// ZHL-16B — reduced mid-compartment M-values for table calculation.
+    Zhl16b,
+    /// ZHL-16C — further reduced M-values for dive computers (default).
+    Zhl16c,
+    /// User-supplied coefficients (`BuhlmannCoefficients`).
+    Custom,
+}
+
+/// User-supplied ZHL-16 coefficients, one entry per compartment (16 each).
+///
+/// Compartment 1 may hold either the 1 or the 1b values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuhlmannCoefficients {
+    /// N2 half-times in minutes.
+    pub n2_half_times_min: Vec<f64>,
+    /// He half-times in minutes.
+    pub he_half_times_min: Vec<f64>,
+    /// N2 'a' coefficients (bar).
+    pub a_n2: Vec<f64>,
+    /// N2 'b' coefficients (dimensionless, 0–1).
+    pub b_n2: Vec<f64>,
+    /// He 'a' coefficients (bar).
+    pub a_he: Vec<f64>,
+    /// He 'b' coefficients (dimensionless, 0–1).
+    pub b_he: Vec<f64>,
+}
+
 /// Gas mix definition for the simulation.
 #[derive(Debug, Clone)]
 pub struct GasMixInput {
@@ -86,20 +266,23 @@ pub struct SurfaceGfPoint {
 
 /// State of the 16 tissue compartments.
 #[derive(Debug, Clone)]
-struct TissueState {
+struct TissueState<'a> {
     /// N2 partial pressure in each compartment (bar).
     p_n2: [f64; NUM_COMPARTMENTS],
     /// He partial pressure in each compartment (bar).
     p_he: [f64; NUM_COMPARTMENTS],
+    /// Coefficient set in use.
+    coeffs: &'a BuhlmannParamSet,
 }
 
-impl TissueState {
+impl<'a> TissueState<'a> {
     /// Initialise tissues at surface equilibrium (breathing air).
-    fn surface_equilibrium(surface_pressure: f64) -> Self {
+    fn surface_equilibrium(surface_pressure: f64, coeffs: &'a BuhlmannParamSet) -> Self {
         let p_n2_surface = (surface_pressure - P_WATER_VAPOR) * AIR_FN2;
         let mut state = TissueState {
             p_n2: [0.0; NUM_COMPARTMENTS],
             p_he: [0.0; NUM_COMPARTMENTS],
+            coeffs,
         };
         for i in 0..NUM_COMPARTMENTS {
             state.p_n2[i] = p_n2_surface;
@@ -118,11 +301,11 @@ impl TissueState {
         }
         for i in 0..NUM_COMPARTMENTS {
             // N2
-            let k_n2 = (2.0_f64.ln()) / (N2_HALF_TIMES[i] * 60.0);
+            let k_n2 = (2.0_f64.ln()) / (self.coeffs.n2_half_times[i] * 60.0);
             self.p_n2[i] = p_inspired_n2 + (self.p_n2[i] - p_inspired_n2) * (-k_n2 * dt_sec).exp();
 
             // He
-            let k_he = (2.0_f64.ln()) / (HE_HALF_TIMES[i] * 60.0);
+            let k_he = (2.0_f64.ln()) / (self.coeffs.he_half_times[i] * 60.0);
             self.p_he[i] = p_inspired_he + (self.p_he[i] - p_inspired_he) * (-k_he * dt_sec).exp();
         }
     }
@@ -158,12 +341,13 @@ impl TissueState {
 
         // Weighted a, b using Workman/Baker method:
         // a = (a_n2 * p_n2 + a_he * p_he) / p_total  (or fallback to N2-only)
+        let c = self.coeffs;
         let (a, b) = if p_total > 1e-10 {
-            let a = (A_N2[i] * self.p_n2[i] + A_HE[i] * self.p_he[i]) / p_total;
-            let b = (B_N2[i] * self.p_n2[i] + B_HE[i] * self.p_he[i]) / p_total;
+            let a = (c.a_n2[i] * self.p_n2[i] + c.a_he[i] * self.p_he[i]) / p_total;
+            let b = (c.b_n2[i] * self.p_n2[i] + c.b_he[i] * self.p_he[i]) / p_total;
             (a, b)
         } else {
-            (A_N2[i], B_N2[i])
+            (c.a_n2[i], c.b_n2[i])
         };
 
         // M-value at surface: M_surface = a + P_surface / b
@@ -200,13 +384,42 @@ pub fn compute_surface_gf(
     samples: &[SampleInput],
     gas_mixes: &[GasMixInput],
     surface_pressure_bar: Option<f64>,
+) -> Vec<SurfaceGfPoint> {
+    surface_gf_with(samples, gas_mixes, surface_pressure_bar, &ZHL16C)
+}
+
+/// Compute Surface Gradient Factor with a selectable ZHL-16 coefficient set.
+///
+/// Same as [`compute_surface_gf`]; `coefficient_set` defaults to ZHL-16C and
+/// `custom_coefficients` is required for (and only accepted with) `Custom`.
+pub fn compute_surface_gf_with_coefficients(
+    samples: &[SampleInput],
+    gas_mixes: &[GasMixInput],
+    surface_pressure_bar: Option<f64>,
+    coefficient_set: Option<BuhlmannCoefficientSet>,
+    custom_coefficients: Option<&BuhlmannCoefficients>,
+) -> Result<Vec<SurfaceGfPoint>, DecoSimError> {
+    let coeffs = BuhlmannParamSet::resolve(coefficient_set, custom_coefficients)?;
+    Ok(surface_gf_with(
+        samples,
+        gas_mixes,
+        surface_pressure_bar,
+        &coeffs,
+    ))
+}
+
+fn surface_gf_with(
+    samples: &[SampleInput],
+    gas_mixes: &[GasMixInput],
+    surface_pressure_bar: Option<f64>,
+    coeffs: &BuhlmannParamSet,
 ) -> Vec<SurfaceGfPoint> {
     if samples.is_empty() {
         return Vec::new();
     }
 
     let surface_p = surface_pressure_bar.unwrap_or(DEFAULT_SURFACE_PRESSURE);
-    let mut tissues = TissueState::surface_equilibrium(surface_p);
+    let mut tissues = TissueState::surface_equilibrium(surface_p, coeffs);
 
     // Build gas mix lookup: index → (fO2, fHe)
     let mut gas_lookup: std::collections::HashMap<i32, (f64, f64)> =
@@ -319,7 +532,7 @@ mod tests {
 
     #[test]
     fn test_surface_equilibrium() {
-        let tissues = TissueState::surface_equilibrium(DEFAULT_SURFACE_PRESSURE);
+        let tissues = TissueState::surface_equilibrium(DEFAULT_SURFACE_PRESSURE, &ZHL16C);
         let (sgf, _) = tissues.surface_gf_and_leading(DEFAULT_SURFACE_PRESSURE);
         // At surface equilibrium, SurfGF should be ~0
         assert!(
@@ -331,7 +544,7 @@ mod tests {
     #[test]
     fn test_surface_equilibrium_exact() {
         // p_n2 = (1.01325 - 0.0627) * 0.7902
-        let tissues = TissueState::surface_equilibrium(DEFAULT_SURFACE_PRESSURE);
+        let tissues = TissueState::surface_equilibrium(DEFAULT_SURFACE_PRESSURE, &ZHL16C);
         let expected = (DEFAULT_SURFACE_PRESSURE - P_WATER_VAPOR) * AIR_FN2;
         for i in 0..NUM_COMPARTMENTS {
             assert!(
@@ -349,7 +562,7 @@ mod tests {
     #[test]
     fn test_tissue_update_n2_exact() {
         // Single compartment 0, one 60s step at 30m on air
-        let mut tissues = TissueState::surface_equilibrium(DEFAULT_SURFACE_PRESSURE);
+        let mut tissues = TissueState::surface_equilibrium(DEFAULT_SURFACE_PRESSURE, &ZHL16C);
         let ambient = DEFAULT_SURFACE_PRESSURE + 30.0 * BAR_PER_METER;
         let p_inspired_n2 = (ambient - P_WATER_VAPOR) * AIR_FN2;
         let p_inspired_he = 0.0;
@@ -372,7 +585,7 @@ mod tests {
     #[test]
     fn test_tissue_update_he_exact() {
         // Single compartment 0, one 60s step at 30m on trimix 21/35
-        let mut tissues = TissueState::surface_equilibrium(DEFAULT_SURFACE_PRESSURE);
+        let mut tissues = TissueState::surface_equilibrium(DEFAULT_SURFACE_PRESSURE, &ZHL16C);
         let ambient = DEFAULT_SURFACE_PRESSURE + 30.0 * BAR_PER_METER;
         let fo2 = 0.21;
         let fhe = 0.35;
@@ -412,6 +625,7 @@ mod tests {
         let mut tissues = TissueState {
             p_n2: [0.0; NUM_COMPARTMENTS],
             p_he: [0.0; NUM_COMPARTMENTS],
+            coeffs: &ZHL16C,
         };
         // Set compartment 0 to a known supersaturated state
         tissues.p_n2[0] = 3.0; // bar (supersaturated at surface)
@@ -437,6 +651,7 @@ mod tests {
         let mut tissues = TissueState {
             p_n2: [0.0; NUM_COMPARTMENTS],
             p_he: [0.0; NUM_COMPARTMENTS],
+            coeffs: &ZHL16C,
         };
         // Set compartments 0 and 1 to produce the same GF
         tissues.p_n2[0] = 2.0;
@@ -467,6 +682,7 @@ mod tests {
         let mut tissues = TissueState {
             p_n2: [0.0; NUM_COMPARTMENTS],
             p_he: [0.0; NUM_COMPARTMENTS],
+            coeffs: &ZHL16C,
         };
         // Set compartment 0 so p_total = 1e-10 exactly
         tissues.p_n2[0] = 5e-11;
@@ -522,6 +738,7 @@ mod tests {
         let mut tissues = TissueState {
             p_n2: [0.0; NUM_COMPARTMENTS],
             p_he: [0.0; NUM_COMPARTMENTS],
+            coeffs: &ZHL16C,
         };
 
         // For compartment 0: a=1.1696, b=0.5578
@@ -545,7 +762,7 @@ mod tests {
     #[test]
     fn test_tissue_update_zero_dt() {
         // dt <= 0 should be a no-op
-        let mut tissues = TissueState::surface_equilibrium(DEFAULT_SURFACE_PRESSURE);
+        let mut tissues = TissueState::surface_equilibrium(DEFAULT_SURFACE_PRESSURE, &ZHL16C);
         let before = tissues.p_n2[0];
         tissues.update(0.0, 5.0, 1.0);
         assert_eq!(tissues.p_n2[0], before);
@@ -628,7 +845,7 @@ mod tests {
 
         // Simulate manually for the final point
         let surface_p = DEFAULT_SURFACE_PRESSURE;
-        let mut manual_tissues = TissueState::surface_equilibrium(surface_p);
+        let mut manual_tissues = TissueState::surface_equilibrium(surface_p, &ZHL16C);
 
         // Interval 0→1: avg depth = 30m
         let avg_depth = 30.0;
@@ -719,7 +936,7 @@ mod tests {
         let result = compute_surface_gf(&ccr_samples, &mixes, None);
 
         // Manually compute the tissue state
-        let mut manual = TissueState::surface_equilibrium(surface_p);
+        let mut manual = TissueState::surface_equilibrium(surface_p, &ZHL16C);
 
         // Interval 0→1: prev ppo2=0.7, avg depth=(0+30)/2=15m
         let avg_d1 = 15.0;
@@ -819,7 +1036,7 @@ mod tests {
         let result = compute_surface_gf(&samples, &mixes, None);
 
         // Manual simulation
-        let mut manual = TissueState::surface_equilibrium(surface_p);
+        let mut manual = TissueState::surface_equilibrium(surface_p, &ZHL16C);
 
         // Interval 0→1: prev ppo2=0.7, avg depth=30m
         let ambient1 = surface_p + 30.0 * BAR_PER_METER;
@@ -945,7 +1162,7 @@ mod tests {
         // This means all inert gas is N2.
         // Manually compute:
         let surface_p = DEFAULT_SURFACE_PRESSURE;
-        let mut manual = TissueState::surface_equilibrium(surface_p);
+        let mut manual = TissueState::surface_equilibrium(surface_p, &ZHL16C);
 
         // Interval 0→1: prev ppo2=0.7, avg depth=15m
         let ambient1 = surface_p + 15.0 * BAR_PER_METER;
@@ -1399,7 +1616,7 @@ mod tests {
     #[test]
     fn test_gf99_at_surface_is_near_zero() {
         // Equilibrium tissues at surface → GF99 at surface pressure ≈ 0
-        let tissues = TissueState::surface_equilibrium(DEFAULT_SURFACE_PRESSURE);
+        let tissues = TissueState::surface_equilibrium(DEFAULT_SURFACE_PRESSURE, &ZHL16C);
         let gf99 = tissues.max_gf_at_pressure(DEFAULT_SURFACE_PRESSURE);
         assert!(
             gf99.abs() < 1.0,
@@ -1486,4 +1703,108 @@ mod tests {
             last.surface_gf
         );
     }
+
+    // ── Coefficient sets ─────────────────────────────────────────────────
+
+    fn custom_from(set: &BuhlmannParamSet) -> BuhlmannCoefficients {
+        BuhlmannCoefficients {
+            n2_half_times_min: set.n2_half_times.to_vec(),
+            he_half_times_min: set.he_half_times.to_vec(),
+            a_n2: set.a_n2.to_vec(),
+            b_n2: set.b_n2.to_vec(),
+            a_he: set.a_he.to_vec(),
+            b_he: set.b_he.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_builtin_coefficient_sets_validate() {
+        for set in [&ZHL16A, &ZHL16B, &ZHL16C] {
+            assert!(set.validate().is_ok());
+        }
+        // A uses the 4-minute compartment 1, B and C the 5-minute 1b.
+        assert_eq!(ZHL16A.n2_half_times[0], 4.0);
+        assert_eq!(ZHL16B.n2_half_times[0], 5.0);
+        // M-values tighten from A to B to C in the middle compartments.
+        assert!(ZHL16A.a_n2[5] > ZHL16B.a_n2[5]);
+        assert!(ZHL16B.a_n2[5] > ZHL16C.a_n2[5]);
+    }
+
+    #[test]
+    fn test_custom_coefficients_validated() {
+        let ok = BuhlmannParamSet::from_custom(&custom_from(&ZHL16C)).unwrap();
+        assert_eq!(ok, ZHL16C);
+
+        let mut short = custom_from(&ZHL16C);
+        short.a_n2.pop();
+        let err = BuhlmannParamSet::from_custom(&short).unwrap_err();
+        assert!(err.contains("a_n2"), "{err}");
+
+        let mut zero_ht = custom_from(&ZHL16C);
+        zero_ht.he_half_times_min[3] = 0.0;
+        assert!(BuhlmannParamSet::from_custom(&zero_ht).is_err());
+
+        let mut neg_a = custom_from(&ZHL16C);
+        neg_a.a_he[0] = -0.1;
+        assert!(BuhlmannParamSet::from_custom(&neg_a).is_err());
+
+        let mut big_b = custom_from(&ZHL16C);
+        big_b.b_n2[15] = 1.2;
+        let err = BuhlmannParamSet::from_custom(&big_b).unwrap_err();
+        assert!(err.contains("compartment 16"), "{err}");
+    }
+
+    #[test]
+    fn test_resolve_coefficient_set() {
+        assert_eq!(BuhlmannParamSet::resolve(None, None).unwrap(), ZHL16C);
+        assert_eq!(
+            BuhlmannParamSet::resolve(Some(BuhlmannCoefficientSet::Zhl16a), None).unwrap(),
+            ZHL16A
+        );
+        assert!(BuhlmannParamSet::resolve(Some(BuhlmannCoefficientSet::Custom), None).is_err());
+        let custom = custom_from(&ZHL16B);
+        assert!(
+            BuhlmannParamSet::resolve(Some(BuhlmannCoefficientSet::Zhl16c), Some(&custom)).is_err()
+        );
+        assert_eq!(
+            BuhlmannParamSet::resolve(Some(BuhlmannCoefficientSet::Custom), Some(&custom)).unwrap(),
+            ZHL16B
+        );
+    }
+
+    #[test]
+    fn test_surface_gf_with_coefficients() {
+        // 20 m for 3 h on air: the slower compartments lead.
+        let mut samples = vec![sample(0, 0.0, None), sample(120, 20.0, None)];
+        samples.push(sample(10800, 20.0, None));
+
+        let default = compute_surface_gf(&samples, &[], None);
+        let c = compute_surface_gf_with_coefficients(&samples, &[], None, None, None).unwrap();
+        let a = compute_surface_gf_with_coefficients(
+            &samples,
+            &[],
+            None,
+            Some(BuhlmannCoefficientSet::Zhl16a),
+            None,
+        )
+        .unwrap();
+        let last = samples.len() - 1;
+        assert_eq!(c[last].surface_gf, default[last].surface_gf);
+        // Larger A M-values give a lower SurfGF for the same loading.
+        assert!(
+            a[last].surface_gf < c[last].surface_gf,
+            "A {} vs C {}",
+            a[last].surface_gf,
+            c[last].surface_gf
+        );
+
+        let result = compute_surface_gf_with_coefficients(
+            &samples,
+            &[],
+            None,
+            Some(BuhlmannCoefficientSet::Custom),
+            None,
+        );
+        assert!(matches!(result, Err(DecoSimError::InvalidParam { .. })));
+    }
 }
@@ -3,9 +3,16 @@
 //! Computes ceilings, GF99, SurfGF, TTS, NDL, and deco stop schedules
 //! using the Baker gradient factor method for conservatism adjustment.
 
+use std::borrow::Cow;
+
+use super::cadence::{simulate_points, tts_interval, SampleStepper};
+use super::deep_stops::{plan_with_deep_stops, DeepStopPlan};
+use super::progress::Progress;
+use super::scr::{interval_ppo2, scr_loop_ppo2, ScrParams};
 use super::shared::*;
 use super::types::*;
-use crate::buhlmann::{A_HE, A_N2, B_HE, B_N2, HE_HALF_TIMES, N2_HALF_TIMES, NUM_COMPARTMENTS};
+use crate::buhlmann::{BuhlmannParamSet, NUM_COMPARTMENTS};
+use crate::metrics::SampleInput;
 
 // ============================================================================
 // Bühlmann Engine
@@ -14,7 +21,11 @@ use crate::buhlmann::{A_HE, A_N2, B_HE, B_N2, HE_HALF_TIMES, N2_HALF_TIMES, NUM_
 pub(crate) struct BuhlmannEngine;
 
 impl BuhlmannEngine {
-    pub(crate) fn simulate(&self, params: &DecoSimParams) -> Result<DecoSimResult, DecoSimError> {
+    pub(crate) fn simulate(
+        &self,
+        params: &DecoSimParams,
+        progress: Progress,
+    ) -> Result<DecoSimResult, DecoSimError> {
         // Validate inputs
         if params.samples.is_empty() {
             return Err(DecoSimError::EmptySamples {
@@ -22,6 +33,192 @@ impl BuhlmannEngine {
             });
         }
 
+        let sim = BuhlmannSim::new(params)?;
+        let mut state = sim.initial_state();
+        let mut max_ceiling_m: f32 = 0.0;
+        let mut max_gf99: f32 = 0.0;
+        let mut max_tts_sec: i32 = 0;
+
+        let points = simulate_points(
+            &sim,
+            &mut state,
+            &params.samples,
+            sim.tts_interval,
+            progress,
+        )?;
+        for point in &points {
+            // Track maxima
+            if point.ceiling_m > max_ceiling_m {
+                max_ceiling_m = point.ceiling_m;
+            }
+            if point.gf99 > max_gf99 {
+                max_gf99 = point.gf99;
+            }
+            if point.tts_sec > max_tts_sec {
+                max_tts_sec = point.tts_sec;
+            }
+        }
+
+        // Deco stop planning from final state
+        let (plan, ascent_path) = if params.plan_ascent {
+            sim.plan_ascent(&state, params.samples.last().unwrap())
+        } else {
+            (
+                DeepStopPlan {
+                    stops: Vec::new(),
+                    truncated: false,
+                    deep_stop_time_sec: 0,
+                    penalty_sec: 0,
+                },
+                Vec::new(),
+            )
+        };
+        let DeepStopPlan {
+            stops: deco_stops,
+            truncated,
+            deep_stop_time_sec,
+            penalty_sec: deep_stop_penalty_sec,
+        } = plan;
+
+        let total_deco_time_sec: i32 = deco_stops.iter().map(|s| s.duration_sec).sum();
+
+        Ok(DecoSimResult {
+            points,
+            deco_stops,
+            total_deco_time_sec,
+            max_ceiling_m,
+            max_gf99,
+            max_tts_sec,
+            model: DecoModel::BuhlmannZhl16c,
+            truncated,
+            deep_stop_time_sec,
+            deep_stop_penalty_sec,
+            ascent_path,
+            desaturation_time_sec: sim.desaturation_time_sec(&state),
+        })
+    }
+
+    /// Unrounded ceiling depth (metres) at selected samples, using the stop
+    /// planner's departure criterion.
+    ///
+    /// Each probe is `(sample_index, next_depth_m)`: the ceiling is evaluated
+    /// at the Baker GF for `next_depth_m`, anchored at `first_stop_depth_m`,
+    /// so a departure is permitted iff the returned ceiling is ≤ `next_depth_m`.
+    /// Probes must be sorted by sample index.
+    pub(crate) fn departure_ceilings(
+        &self,
+        params: &DecoSimParams,
+        probes: &[(usize, f64)],
+        first_stop_depth_m: f64,
+    ) -> Result<Vec<f64>, DecoSimError> {
+        let gf_low = params.gf_low.unwrap_or(100) as f64 / 100.0;
+        let gf_high = params.gf_high.unwrap_or(100) as f64 / 100.0;
+        let surface_p = params
+            .surface_pressure_bar
+            .unwrap_or(DEFAULT_SURFACE_PRESSURE);
+        let coeffs = BuhlmannParamSet::resolve(
+            params.buhlmann_coefficients,
+            params.custom_buhlmann_coefficients.as_ref(),
+        )?;
+
+        let mut gas_lookup: std::collections::HashMap<i32, (f64, f64)> =
+            std::collections::HashMap::new();
+        for mix in &params.gas_mixes {
+            gas_lookup.insert(mix.mix_index, (mix.o2_fraction, mix.he_fraction));
+        }
+        let (mut current_fo2, mut current_fhe) =
+            gas_lookup.get(&0).copied().unwrap_or((AIR_FO2, 0.0));
+
+        let mut tissues = EngineTissueState::surface_equilibrium(surface_p, &coeffs);
+        let mut ceilings = Vec::with_capacity(probes.len());
+        let mut probe_iter = probes.iter().peekable();
+
+        for (idx, sample) in params.samples.iter().enumerate() {
+            if idx > 0 {
+                let prev = &params.samples[idx - 1];
+                let dt_sec = (sample.t_sec - prev.t_sec) as f64;
+                let avg_depth_m = ((prev.depth_m as f64 + sample.depth_m as f64) / 2.0).max(0.0);
+                let ambient_p = depth_to_pressure(avg_depth_m, surface_p);
+                let (fn2, fhe) = inspired_fractions(
+                    current_fo2,
+                    current_fhe,
+                    interval_ppo2(params.scr.as_ref(), prev.ppo2, current_fo2, ambient_p),
+                    ambient_p,
+                );
+                tissues.update(
+                    dt_sec,
+                    (ambient_p - P_WATER_VAPOR) * fn2,
+                    (ambient_p - P_WATER_VAPOR) * fhe,
+                );
+            }
+            if let Some(&(fo2, fhe)) = sample.gasmix_index.and_then(|i| gas_lookup.get(&i)) {
+                current_fo2 = fo2;
+                current_fhe = fhe;
+            }
+
+            while let Some(&&(probe_idx, next_depth)) = probe_iter.peek() {
+                if probe_idx != idx {
+                    break;
+                }
+                let gf = gf_at_depth(next_depth, first_stop_depth_m, gf_low, gf_high);
+                let ceil_p = tissues.raw_gf_ceiling_at(gf, surface_p);
+                ceilings.push(pressure_to_depth(ceil_p, surface_p));
+                probe_iter.next();
+            }
+        }
+        Ok(ceilings)
+    }
+}
+
+// ============================================================================
+// Sample Stepping
+// ============================================================================
+
+/// Validated model settings for one simulation.
+///
+/// Shared by the one-shot `simulate` and by live sessions, which keep a
+/// `BuhlmannState` between calls and step it one sample at a time.
+pub(crate) struct BuhlmannSim<'p> {
+    params: Cow<'p, DecoSimParams>,
+    coeffs: BuhlmannParamSet,
+    gas_lookup: std::collections::HashMap<i32, (f64, f64)>,
+    surface_p: f64,
+    ascent_rate: f64,
+    last_stop_depth: f64,
+    stop_interval: f64,
+    gf_low: f64,
+    gf_high: f64,
+    /// TTS/NDL cadence of `simulate` (seconds, 0 = every sample).
+    tts_interval: i32,
+}
+
+/// Tissue and breathing-gas state after the last simulated sample.
+#[derive(Debug, Clone)]
+pub(crate) struct BuhlmannState {
+    p_n2: [f64; NUM_COMPARTMENTS],
+    p_he: [f64; NUM_COMPARTMENTS],
+    fo2: f64,
+    fhe: f64,
+    /// Deepest ceiling so far in the current dive (first stop depth for
+    /// Baker GF interpolation). Cleared at the surface once the ceiling is.
+    first_stop_depth_m: Option<f64>,
+    /// Stops behind the last TTS, used to seed the next sample's plan.
+    tts_stops: Vec<DecoStop>,
+}
+
+impl<'p> BuhlmannSim<'p> {
+    /// Validate the model parameters. Samples are not inspected.
+    pub(crate) fn new(params: &'p DecoSimParams) -> Result<Self, DecoSimError> {
+        Self::from_cow(Cow::Borrowed(params))
+    }
+
+    /// Like `new`, but the simulation keeps its own copy of `params`, so it
+    /// can outlive the caller (live sessions).
+    pub(crate) fn owned(params: DecoSimParams) -> Result<BuhlmannSim<'static>, DecoSimError> {
+        BuhlmannSim::from_cow(Cow::Owned(params))
+    }
+
+    fn from_cow(params: Cow<'p, DecoSimParams>) -> Result<Self, DecoSimError> {
         let gf_low_pct = params.gf_low.unwrap_or(100);
         let gf_high_pct = params.gf_high.unwrap_or(100);
         if gf_low_pct > gf_high_pct {
@@ -35,11 +232,6 @@ impl BuhlmannEngine {
             });
         }
 
-        let gf_low = gf_low_pct as f64 / 100.0;
-        let gf_high = gf_high_pct as f64 / 100.0;
-        let surface_p = params
-            .surface_pressure_bar
-            .unwrap_or(DEFAULT_SURFACE_PRESSURE);
         let ascent_rate = params.ascent_rate_m_min.unwrap_or(9.0);
         let last_stop_depth = params.last_stop_depth_m.unwrap_or(3.0);
         let stop_interval = params.stop_interval_m.unwrap_or(3.0);
@@ -53,161 +245,240 @@ impl BuhlmannEngine {
             });
         }
 
+        let coeffs = BuhlmannParamSet::resolve(
+            params.buhlmann_coefficients,
+            params.custom_buhlmann_coefficients.as_ref(),
+        )?;
+
         // Build gas mix lookup
         let mut gas_lookup: std::collections::HashMap<i32, (f64, f64)> =
             std::collections::HashMap::new();
         for mix in &params.gas_mixes {
             gas_lookup.insert(mix.mix_index, (mix.o2_fraction, mix.he_fraction));
         }
-        let default_gas = gas_lookup.get(&0).copied().unwrap_or((AIR_FO2, 0.0));
-        let mut current_fo2 = default_gas.0;
-        let mut current_fhe = default_gas.1;
 
-        let mut tissues = EngineTissueState::surface_equilibrium(surface_p);
-        let mut points = Vec::with_capacity(params.samples.len());
-        let mut max_ceiling_m: f32 = 0.0;
-        let mut max_gf99: f32 = 0.0;
-        let mut max_tts_sec: i32 = 0;
+        Ok(Self {
+            surface_p: params
+                .surface_pressure_bar
+                .unwrap_or(DEFAULT_SURFACE_PRESSURE),
+            tts_interval: tts_interval(&params)?,
+            params,
+            coeffs,
+            gas_lookup,
+            ascent_rate,
+            last_stop_depth,
+            stop_interval,
+            gf_low: gf_low_pct as f64 / 100.0,
+            gf_high: gf_high_pct as f64 / 100.0,
+        })
+    }
 
-        // Track first stop depth for GF interpolation
-        let mut first_stop_depth_m: Option<f64> = None;
+    /// Surface-equilibrated tissues breathing gas 0 (air if not listed).
+    pub(crate) fn initial_state(&self) -> BuhlmannState {
+        let (fo2, fhe) = self.gas_lookup.get(&0).copied().unwrap_or((AIR_FO2, 0.0));
+        let tissues = EngineTissueState::surface_equilibrium(self.surface_p, &self.coeffs);
+        BuhlmannState {
+            p_n2: tissues.p_n2,
+            p_he: tissues.p_he,
+            fo2,
+            fhe,
+            first_stop_depth_m: None,
+            tts_stops: Vec::new(),
+        }
+    }
 
-        for (idx, sample) in params.samples.iter().enumerate() {
-            // Update tissues for time interval
-            if idx > 0 {
-                let dt_sec = (sample.t_sec - params.samples[idx - 1].t_sec) as f64;
-                let avg_depth_m =
-                    ((params.samples[idx - 1].depth_m as f64 + sample.depth_m as f64) / 2.0)
-                        .max(0.0);
-                let ambient_p = depth_to_pressure(avg_depth_m, surface_p);
+    fn tissues(&self, state: &BuhlmannState) -> EngineTissueState<'_> {
+        EngineTissueState {
+            p_n2: state.p_n2,
+            p_he: state.p_he,
+            coeffs: &self.coeffs,
+        }
+    }
 
-                let (fn2, fhe) = inspired_fractions(
-                    current_fo2,
-                    current_fhe,
-                    params.samples[idx - 1].ppo2.map(|v| v as f64),
-                    ambient_p,
-                );
+    /// Planner parameters for an ascent starting at `sample`.
+    fn plan_params(&self, sample: &SampleInput) -> PlanParams {
+        PlanParams::from_engine(
+            &self.gas_lookup,
+            sample.gasmix_index.unwrap_or(0),
+            &self.params.ascent_gas_switches,
+            sample.ppo2.map(|v| v as f64),
+            &self.params.ascent_setpoint_switches,
+            self.params.scr.as_ref(),
+            self.surface_p,
+            self.ascent_rate,
+            self.last_stop_depth,
+            self.stop_interval,
+            self.gf_low,
+            self.gf_high,
+        )
+    }
 
-                let p_inspired_n2 = (ambient_p - P_WATER_VAPOR) * fn2;
-                let p_inspired_he = (ambient_p - P_WATER_VAPOR) * fhe;
-                tissues.update(dt_sec, p_inspired_n2, p_inspired_he);
+    /// Plan the ascent from `state` at `last_sample`, honouring deep stops
+    /// and the ascent mode. The path is empty unless the ascent is continuous.
+    pub(crate) fn plan_ascent(
+        &self,
+        state: &BuhlmannState,
+        last_sample: &SampleInput,
+    ) -> (DeepStopPlan, Vec<AscentWaypoint>) {
+        let params = &*self.params;
+        let tissues = self.tissues(state);
+        let current_depth_m = (last_sample.depth_m as f64).max(0.0);
+        let pp = self.plan_params(last_sample);
+        match &params.deep_stops {
+            Some(deep) => (
+                plan_with_deep_stops(
+                    &tissues,
+                    current_depth_m,
+                    self.stop_interval,
+                    deep,
+                    |t, d| plan_deco_stops(t, d, &pp),
+                    |t, d, to, secs| hold_at_depth(t, d, to, secs, &pp),
+                ),
+                Vec::new(),
+            ),
+            None if params.ascent_mode == Some(AscentMode::Continuous) => {
+                let margin = params.ceiling_margin_m.unwrap_or(0.0);
+                let (path, stops, truncated) =
+                    plan_continuous_ascent(&tissues, current_depth_m, margin, &pp);
+                (
+                    DeepStopPlan {
+                        stops,
+                        truncated,
+                        deep_stop_time_sec: 0,
+                        penalty_sec: 0,
+                    },
+                    path,
+                )
             }
-
-            // Gas switch
-            if let Some(mix_idx) = sample.gasmix_index {
-                if let Some(&(fo2, fhe)) = gas_lookup.get(&mix_idx) {
-                    current_fo2 = fo2;
-                    current_fhe = fhe;
-                }
+            None => {
+                let (stops, truncated) = plan_deco_stops(&tissues, current_depth_m, &pp);
+                (
+                    DeepStopPlan {
+                        stops,
+                        truncated,
+                        deep_stop_time_sec: 0,
+                        penalty_sec: 0,
+                    },
+                    Vec::new(),
+                )
             }
+        }
+    }
 
-            let current_depth_m = (sample.depth_m as f64).max(0.0);
-            let current_ambient_p = depth_to_pressure(current_depth_m, surface_p);
-
-            // Compute GF-adjusted ceiling
-            let raw_ceiling_p = tissues.gf_ceiling(gf_low, gf_high, first_stop_depth_m, surface_p);
-            let ceiling_depth_m = pressure_to_depth(raw_ceiling_p, surface_p);
-            let ceiling_m = round_up_to_stop(ceiling_depth_m, stop_interval);
+    /// Seconds at the surface until the tissues in `state` are desaturated.
+    pub(crate) fn desaturation_time_sec(&self, state: &BuhlmannState) -> i32 {
+        self.tissues(state).desaturation_time_sec(self.surface_p)
+    }
+}
 
-            // Track deepest ceiling (first stop depth for Baker GF interpolation).
-            // During descent/bottom, the ceiling deepens as tissues load; we need
-            // the deepest value for correct GF interpolation in TTS computation.
-            if ceiling_m > 0.0 {
-                first_stop_depth_m =
-                    Some(first_stop_depth_m.map_or(ceiling_m, |prev: f64| prev.max(ceiling_m)));
-            }
+impl SampleStepper for BuhlmannSim<'_> {
+    type State = BuhlmannState;
 
-            // GF99 and SurfGF
-            let gf99 = tissues.max_gf_at_pressure(current_ambient_p);
-            let (surface_gf, leading) = tissues.surface_gf_and_leading(surface_p);
-
-            // TTS and NDL
-            let pp = PlanParams::from_engine(
-                &gas_lookup,
-                sample.gasmix_index.unwrap_or(0),
-                sample.ppo2.map(|v| v as f64),
-                surface_p,
-                ascent_rate,
-                last_stop_depth,
-                stop_interval,
-                gf_low,
-                gf_high,
+    fn advance(
+        &self,
+        state: &mut BuhlmannState,
+        prev: Option<&SampleInput>,
+        sample: &SampleInput,
+    ) -> DecoSimPoint {
+        let params = &*self.params;
+        let surface_p = self.surface_p;
+        let mut tissues = self.tissues(state);
+
+        // Update tissues for time interval
+        if let Some(prev) = prev {
+            let dt_sec = (sample.t_sec - prev.t_sec) as f64;
+            let avg_depth_m = ((prev.depth_m as f64 + sample.depth_m as f64) / 2.0).max(0.0);
+            let ambient_p = depth_to_pressure(avg_depth_m, surface_p);
+
+            let (fn2, fhe) = inspired_fractions(
+                state.fo2,
+                state.fhe,
+                interval_ppo2(params.scr.as_ref(), prev.ppo2, state.fo2, ambient_p),
+                ambient_p,
             );
-            let (tts_sec, ndl_sec) = if ceiling_m > 0.0 {
-                let tts = compute_tts(&tissues, current_depth_m, &pp);
-                (tts, 0)
-            } else {
-                let gas = pp.gas_at_depth(current_depth_m);
-                let ndl = compute_ndl(
-                    &tissues,
-                    current_depth_m,
-                    gas.fo2,
-                    gas.fhe,
-                    pp.ppo2,
-                    surface_p,
-                    gf_low,
-                    gf_high,
-                );
-                (0, ndl)
-            };
 
-            // Track maxima
-            let ceil_f32 = ceiling_m as f32;
-            let gf99_f32 = gf99 as f32;
-            if ceil_f32 > max_ceiling_m {
-                max_ceiling_m = ceil_f32;
-            }
-            if gf99_f32 > max_gf99 {
-                max_gf99 = gf99_f32;
-            }
-            if tts_sec > max_tts_sec {
-                max_tts_sec = tts_sec;
-            }
+            let p_inspired_n2 = (ambient_p - P_WATER_VAPOR) * fn2;
+            let p_inspired_he = (ambient_p - P_WATER_VAPOR) * fhe;
+            tissues.update(dt_sec, p_inspired_n2, p_inspired_he);
+            state.p_n2 = tissues.p_n2;
+            state.p_he = tissues.p_he;
+        }
 
-            points.push(DecoSimPoint {
-                t_sec: sample.t_sec,
-                depth_m: sample.depth_m,
-                ceiling_m: ceil_f32,
-                gf99: gf99_f32,
-                surface_gf: surface_gf as f32,
-                tts_sec,
-                leading_compartment: leading as u8,
-                ndl_sec,
-            });
+        // Gas switch
+        if let Some(&(fo2, fhe)) = sample.gasmix_index.and_then(|i| self.gas_lookup.get(&i)) {
+            state.fo2 = fo2;
+            state.fhe = fhe;
         }
 
-        // Deco stop planning from final state
-        let (deco_stops, truncated) = if params.plan_ascent {
-            let last_sample = params.samples.last().unwrap();
-            let current_depth_m = (last_sample.depth_m as f64).max(0.0);
-            let pp = PlanParams::from_engine(
-                &gas_lookup,
-                last_sample.gasmix_index.unwrap_or(0),
-                last_sample.ppo2.map(|v| v as f64),
-                surface_p,
-                ascent_rate,
-                last_stop_depth,
-                stop_interval,
-                gf_low,
-                gf_high,
+        let current_depth_m = (sample.depth_m as f64).max(0.0);
+        let current_ambient_p = depth_to_pressure(current_depth_m, surface_p);
+
+        // Compute GF-adjusted ceiling
+        let raw_ceiling_p = tissues.gf_ceiling(
+            self.gf_low,
+            self.gf_high,
+            state.first_stop_depth_m,
+            surface_p,
+        );
+        let ceiling_depth_m = pressure_to_depth(raw_ceiling_p, surface_p);
+        let ceiling_m = round_up_to_stop(ceiling_depth_m, self.stop_interval);
+
+        // Track deepest ceiling (first stop depth for Baker GF interpolation).
+        // During descent/bottom, the ceiling deepens as tissues load; we need
+        // the deepest value for correct GF interpolation in TTS computation.
+        // Surfacing with a clear ceiling ends the dive, so a repetitive dive
+        // starts a new anchor while keeping its tissue loading.
+        if current_depth_m <= 0.0 && ceiling_m <= 0.0 {
+            state.first_stop_depth_m = None;
+        } else if ceiling_m > 0.0 {
+            state.first_stop_depth_m = Some(
+                state
+                    .first_stop_depth_m
+                    .map_or(ceiling_m, |prev: f64| prev.max(ceiling_m)),
             );
-            plan_deco_stops(&tissues, current_depth_m, &pp)
-        } else {
-            (Vec::new(), false)
-        };
+        }
 
-        let total_deco_time_sec: i32 = deco_stops.iter().map(|s| s.duration_sec).sum();
+        // GF99 and SurfGF
+        let gf99 = tissues.max_gf_at_pressure(current_ambient_p);
+        let (surface_gf, leading) = tissues.surface_gf_and_leading(surface_p);
+
+        DecoSimPoint {
+            t_sec: sample.t_sec,
+            depth_m: sample.depth_m,
+            ceiling_m: ceiling_m as f32,
+            gf99: gf99 as f32,
+            surface_gf: surface_gf as f32,
+            tts_sec: 0,
+            leading_compartment: leading as u8,
+            ndl_sec: 0,
+        }
+    }
 
-        Ok(DecoSimResult {
-            points,
-            deco_stops,
-            total_deco_time_sec,
-            max_ceiling_m,
-            max_gf99,
-            max_tts_sec,
-            model: DecoModel::BuhlmannZhl16c,
-            truncated,
-        })
+    fn surface_times(
+        &self,
+        state: &mut BuhlmannState,
+        sample: &SampleInput,
+        point: &mut DecoSimPoint,
+    ) {
+        let tissues = self.tissues(state);
+        let current_depth_m = (sample.depth_m as f64).max(0.0);
+        let pp = self.plan_params(sample);
+        if point.ceiling_m > 0.0 {
+            point.tts_sec = compute_tts(&tissues, current_depth_m, &pp, &mut state.tts_stops);
+        } else {
+            let current_ambient_p = depth_to_pressure(current_depth_m, self.surface_p);
+            let gas = pp.gas_at_depth(current_depth_m);
+            point.ndl_sec = compute_ndl(
+                &tissues,
+                current_depth_m,
+                gas.fo2,
+                gas.fhe,
+                pp.current_ppo2(gas.fo2, current_ambient_p),
+                self.surface_p,
+                self.gf_low,
+                self.gf_high,
+            );
+        }
     }
 }
 
@@ -215,23 +486,41 @@ impl BuhlmannEngine {
 // Internal Tissue State (extends buhlmann.rs TissueState with ceiling logic)
 // ============================================================================
 
+/// Schreiner decay factors `exp(-k·dt)` per compartment for a fixed interval.
+struct DecayFactors {
+    n2: [f64; NUM_COMPARTMENTS],
+    he: [f64; NUM_COMPARTMENTS],
+}
+
+impl DecayFactors {
+    fn new(coeffs: &BuhlmannParamSet, dt_sec: f64) -> Self {
+        let factor = |half_time_min: f64| (-(2.0_f64.ln()) / (half_time_min * 60.0) * dt_sec).exp();
+        Self {
+            n2: coeffs.n2_half_times.map(factor),
+            he: coeffs.he_half_times.map(factor),
+        }
+    }
+}
+
 /// Internal tissue state with ceiling/stop computation methods.
 ///
 /// This is a separate type from buhlmann::TissueState to avoid coupling
 /// the existing SurfGF API to the new deco engine types.
 #[derive(Debug, Clone)]
-pub(crate) struct EngineTissueState {
+pub(crate) struct EngineTissueState<'a> {
     p_n2: [f64; NUM_COMPARTMENTS],
     p_he: [f64; NUM_COMPARTMENTS],
+    coeffs: &'a BuhlmannParamSet,
 }
 
-impl EngineTissueState {
+impl<'a> EngineTissueState<'a> {
     /// Initialise tissues at surface equilibrium (breathing air).
-    pub(crate) fn surface_equilibrium(surface_pressure: f64) -> Self {
+    pub(crate) fn surface_equilibrium(surface_pressure: f64, coeffs: &'a BuhlmannParamSet) -> Self {
         let p_n2_surface = (surface_pressure - P_WATER_VAPOR) * AIR_FN2;
         let mut state = Self {
             p_n2: [0.0; NUM_COMPARTMENTS],
             p_he: [0.0; NUM_COMPARTMENTS],
+            coeffs,
         };
         for i in 0..NUM_COMPARTMENTS {
             state.p_n2[i] = p_n2_surface;
@@ -244,21 +533,54 @@ impl EngineTissueState {
         if dt_sec <= 0.0 {
             return;
         }
+        let c = self.coeffs;
+        for i in 0..NUM_COMPARTMENTS {
+            self.p_n2[i] = schreiner_step(self.p_n2[i], p_inspired_n2, c.n2_half_times[i], dt_sec);
+            self.p_he[i] = schreiner_step(self.p_he[i], p_inspired_he, c.he_half_times[i], dt_sec);
+        }
+    }
+
+    /// Advance `steps` intervals of constant inspired pressure, using decay
+    /// factors precomputed for the interval.
+    fn update_steps(
+        &mut self,
+        factors: &DecayFactors,
+        steps: i32,
+        p_inspired_n2: f64,
+        p_inspired_he: f64,
+    ) {
+        if steps <= 0 {
+            return;
+        }
         for i in 0..NUM_COMPARTMENTS {
-            self.p_n2[i] = schreiner_step(self.p_n2[i], p_inspired_n2, N2_HALF_TIMES[i], dt_sec);
-            self.p_he[i] = schreiner_step(self.p_he[i], p_inspired_he, HE_HALF_TIMES[i], dt_sec);
+            let f_n2 = factors.n2[i].powi(steps);
+            let f_he = factors.he[i].powi(steps);
+            self.p_n2[i] = p_inspired_n2 + (self.p_n2[i] - p_inspired_n2) * f_n2;
+            self.p_he[i] = p_inspired_he + (self.p_he[i] - p_inspired_he) * f_he;
         }
     }
 
+    /// Seconds on air at the surface until every compartment is desaturated.
+    fn desaturation_time_sec(&self, surface_pressure: f64) -> i32 {
+        let p_n2_surface = (surface_pressure - P_WATER_VAPOR) * AIR_FN2;
+        let limit = p_n2_surface * (1.0 + DESATURATION_TOLERANCE);
+        desaturation_time_sec(
+            self,
+            |t, dt| t.update(dt, p_n2_surface, 0.0),
+            |t| (0..NUM_COMPARTMENTS).all(|i| t.p_n2[i] + t.p_he[i] <= limit),
+        )
+    }
+
     /// Weighted a, b coefficients for compartment i.
     fn weighted_ab(&self, i: usize) -> (f64, f64) {
+        let c = self.coeffs;
         let p_total = self.p_n2[i] + self.p_he[i];
         if p_total > 1e-10 {
-            let a = (A_N2[i] * self.p_n2[i] + A_HE[i] * self.p_he[i]) / p_total;
-            let b = (B_N2[i] * self.p_n2[i] + B_HE[i] * self.p_he[i]) / p_total;
+            let a = (c.a_n2[i] * self.p_n2[i] + c.a_he[i] * self.p_he[i]) / p_total;
+            let b = (c.b_n2[i] * self.p_n2[i] + c.b_he[i] * self.p_he[i]) / p_total;
             (a, b)
         } else {
-            (A_N2[i], B_N2[i])
+            (c.a_n2[i], c.b_n2[i])
         }
     }
 
@@ -387,6 +709,10 @@ struct PlanParams {
     gases: Vec<PlanGas>,
     /// CCR setpoint PPO2 in bar. `None` = open circuit.
     ppo2: Option<f64>,
+    /// Planned CCR setpoint switches on the ascent.
+    setpoint_switches: Vec<SetpointSwitch>,
+    /// SCR loop on the current gas; overrides `ppo2`.
+    scr: Option<ScrParams>,
     surface_p: f64,
     ascent_rate_m_min: f64,
     last_stop_depth: f64,
@@ -429,12 +755,50 @@ impl PlanParams {
         })
     }
 
+    /// CCR setpoint at a depth on the ascent: the shallowest planned switch
+    /// at or below this depth, else the current setpoint. `None` = open circuit.
+    fn setpoint_at_depth(&self, depth_m: f64) -> Option<f64> {
+        let current = self.ppo2?;
+        Some(
+            self.setpoint_switches
+                .iter()
+                .filter(|s| depth_m <= s.depth_m)
+                .min_by(|a, b| {
+                    a.depth_m
+                        .partial_cmp(&b.depth_m)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                })
+                .map_or(current, |s| s.setpoint_ppo2),
+        )
+    }
+
+    /// Inspired PPO2 on the ascent at `depth_m`, evaluated at `ambient_p`:
+    /// the SCR loop on the supply gas, else the CCR setpoint clamped to
+    /// ambient. `None` = open circuit.
+    fn loop_ppo2(&self, depth_m: f64, fo2: f64, ambient_p: f64) -> Option<f64> {
+        match &self.scr {
+            Some(scr) => Some(scr_loop_ppo2(scr, fo2, ambient_p)),
+            None => self.setpoint_at_depth(depth_m).map(|sp| sp.min(ambient_p)),
+        }
+    }
+
+    /// Inspired PPO2 at the current depth (no ascent switches applied).
+    fn current_ppo2(&self, fo2: f64, ambient_p: f64) -> Option<f64> {
+        match &self.scr {
+            Some(scr) => Some(scr_loop_ppo2(scr, fo2, ambient_p)),
+            None => self.ppo2.map(|sp| sp.min(ambient_p)),
+        }
+    }
+
     /// Build a PlanParams from the engine's current state and gas mixes.
     #[allow(clippy::too_many_arguments)]
     fn from_engine(
         gas_mixes: &std::collections::HashMap<i32, (f64, f64)>,
         current_gas_index: i32,
+        ascent_switches: &[GasSwitchDepth],
         ppo2: Option<f64>,
+        setpoint_switches: &[SetpointSwitch],
+        scr: Option<&ScrParams>,
         surface_p: f64,
         ascent_rate: f64,
         last_stop_depth: f64,
@@ -444,22 +808,41 @@ impl PlanParams {
     ) -> Self {
         let mut gases: Vec<PlanGas> = Vec::new();
         let mut bottom_gas: Option<PlanGas> = None;
+        let current_known = gas_mixes.contains_key(&current_gas_index);
 
         for (&mix_idx, &(fo2, fhe)) in gas_mixes {
-            if mix_idx == current_gas_index || mix_idx == 0 {
-                // Bottom gas or current gas — no switch depth
+            let planned = ascent_switches.iter().find(|s| s.mix_index == mix_idx);
+            if ascent_switches.is_empty() && mix_idx == 0 && mix_idx != current_gas_index {
+                // Gas 0 is the bottom gas only if the current gas is unknown;
+                // after switching off it, it is not an ascent gas.
+                if !current_known {
+                    bottom_gas = Some(PlanGas {
+                        fo2,
+                        fhe,
+                        switch_depth_m: None,
+                    });
+                }
+            } else if mix_idx == current_gas_index {
+                // Current gas — the bottom of the ascent, no switch depth
                 bottom_gas = Some(PlanGas {
                     fo2,
                     fhe,
                     switch_depth_m: None,
                 });
-            } else if fo2 > 0.0 {
+            } else if let Some(planned) = planned {
+                // Explicitly planned ascent switch
+                gases.push(PlanGas {
+                    fo2,
+                    fhe,
+                    switch_depth_m: Some(planned.depth_m.max(0.0)),
+                });
+            } else if ascent_switches.is_empty() && fo2 > 0.0 {
                 // Deco gas — compute MOD at 1.6 PPO2 as default switch depth
-                let mod_m = (MAX_PPO2_SWITCH / fo2 - 1.0) * 10.0;
+                let mod_m = default_switch_depth(fo2, MAX_PPO2_SWITCH, surface_p);
                 gases.push(PlanGas {
                     fo2,
                     fhe,
-                    switch_depth_m: Some(mod_m.max(0.0)),
+                    switch_depth_m: Some(mod_m),
                 });
             }
         }
@@ -486,6 +869,8 @@ impl PlanParams {
         PlanParams {
             gases,
             ppo2,
+            setpoint_switches: setpoint_switches.to_vec(),
+            scr: scr.cloned(),
             surface_p,
             ascent_rate_m_min: ascent_rate,
             last_stop_depth,
@@ -503,8 +888,15 @@ impl PlanParams {
 /// Compute Time-To-Surface from current depth and tissue state.
 ///
 /// Clones tissues, simulates ascent with stops, returns total time in seconds.
-fn compute_tts(tissues: &EngineTissueState, current_depth_m: f64, pp: &PlanParams) -> i32 {
-    let (stops, _truncated) = plan_deco_stops(tissues, current_depth_m, pp);
+/// `previous_stops` holds the stops behind the last TTS; they seed the stop
+/// search and are replaced with this TTS's stops.
+fn compute_tts(
+    tissues: &EngineTissueState,
+    current_depth_m: f64,
+    pp: &PlanParams,
+    previous_stops: &mut Vec<DecoStop>,
+) -> i32 {
+    let (stops, _truncated) = plan_deco_stops_hinted(tissues, current_depth_m, pp, previous_stops);
 
     let mut total_sec = 0.0;
     let mut depth = current_depth_m;
@@ -524,6 +916,7 @@ fn compute_tts(tissues: &EngineTissueState, current_depth_m: f64, pp: &PlanParam
         total_sec += (depth / pp.ascent_rate_m_min) * 60.0;
     }
 
+    *previous_stops = stops;
     total_sec.ceil() as i32
 }
 
@@ -607,6 +1000,61 @@ fn compute_ndl(
 // Deco Stop Planner
 // ============================================================================
 
+/// Longest wait at a single stop before the planner gives up (10 hours).
+const MAX_STOP_MINUTES: i32 = 600;
+
+/// Smallest `n` in `0..=max` with `clears(n)`, or `None`.
+///
+/// Equivalent to testing each minute in turn, provided that once the ceiling
+/// clears it stays clear — true while the leading compartments off-gas at
+/// the stop. Gallops away from `hint`, then bisects, so a good hint (the
+/// same stop one sample earlier) needs two tests instead of `n`.
+fn first_clear_minute(clears: impl Fn(i32) -> bool, hint: i32, max: i32) -> Option<i32> {
+    // Invariant once bracketed: `lo` does not clear (-1 = none), `hi` clears.
+    let hint = hint.clamp(0, max);
+    let (mut lo, mut hi);
+    let mut step = 1;
+    if clears(hint) {
+        hi = hint;
+        loop {
+            let probe = hi - step;
+            if probe < 0 {
+                lo = -1;
+                break;
+            }
+            if !clears(probe) {
+                lo = probe;
+                break;
+            }
+            hi = probe;
+            step *= 2;
+        }
+    } else {
+        lo = hint;
+        loop {
+            if lo >= max {
+                return None;
+            }
+            let probe = (lo + step).min(max);
+            if clears(probe) {
+                hi = probe;
+                break;
+            }
+            lo = probe;
+            step *= 2;
+        }
+    }
+    while hi - lo > 1 {
+        let mid = (lo + hi) / 2;
+        if clears(mid) {
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+    }
+    Some(hi)
+}
+
 /// Plan deco stops from current depth/tissue state to the surface.
 ///
 /// For CCR profiles (ppo2 is Some), inspired gas fractions at each stop
@@ -622,6 +1070,17 @@ fn plan_deco_stops(
     tissues: &EngineTissueState,
     current_depth_m: f64,
     pp: &PlanParams,
+) -> (Vec<DecoStop>, bool) {
+    plan_deco_stops_hinted(tissues, current_depth_m, pp, &[])
+}
+
+/// `plan_deco_stops`, seeding each stop's wait from the stop at the same
+/// depth in `hint`. The hint only affects speed, not the plan.
+fn plan_deco_stops_hinted(
+    tissues: &EngineTissueState,
+    current_depth_m: f64,
+    pp: &PlanParams,
+    hint: &[DecoStop],
 ) -> (Vec<DecoStop>, bool) {
     let mut tissues = tissues.clone();
     let mut stops = Vec::new();
@@ -656,7 +1115,11 @@ fn plan_deco_stops(
 
     // Process stops from deep to shallow
     let mut current_stop = stop_depth;
-    let max_total_stop_time = 36000.0; // 10 hour safety limit
+    let minute = DecayFactors::new(tissues.coeffs, 60.0);
+    let travel = DecayFactors::new(
+        tissues.coeffs,
+        pp.stop_interval / pp.ascent_rate_m_min * 60.0,
+    );
 
     while current_stop >= pp.last_stop_depth {
         let next_stop = if current_stop > pp.last_stop_depth {
@@ -665,52 +1128,69 @@ fn plan_deco_stops(
             0.0 // After last stop, need to clear to surface
         };
 
-        let mut stop_time_sec: f64 = 0.0;
-
-        // Simulate 1-minute increments until ceiling clears to next stop.
-        // Use GF at the NEXT stop depth (Baker method): to ascend from D to D',
-        // the ceiling at GF(D') must be ≤ D'.
-        loop {
-            let gf = gf_at_depth(next_stop, first_stop, pp.gf_low, pp.gf_high);
-            let ceil_p = tissues.raw_gf_ceiling_at(gf, pp.surface_p);
-            let ceil_depth = pressure_to_depth(ceil_p, pp.surface_p);
-
-            if ceil_depth <= next_stop {
-                break;
-            }
-
-            // Wait 60 seconds at this stop (using the gas available at this depth)
-            let gas = pp.gas_at_depth(current_stop);
-            let ambient_p = depth_to_pressure(current_stop, pp.surface_p);
-            let ppo2_at_stop = pp.ppo2.map(|sp| sp.min(ambient_p));
-            let (fn2, fhe_frac) = inspired_fractions(gas.fo2, gas.fhe, ppo2_at_stop, ambient_p);
-            let p_inspired_n2 = (ambient_p - P_WATER_VAPOR) * fn2;
-            let p_inspired_he = (ambient_p - P_WATER_VAPOR) * fhe_frac;
-            tissues.update(60.0, p_inspired_n2, p_inspired_he);
-            stop_time_sec += 60.0;
+        // Wait in 1-minute increments until the ceiling clears to the next
+        // stop. Use GF at the NEXT stop depth (Baker method): to ascend from
+        // D to D', the ceiling at GF(D') must be ≤ D'.
+        let gf = gf_at_depth(next_stop, first_stop, pp.gf_low, pp.gf_high);
+        let clears = |t: &EngineTissueState| {
+            pressure_to_depth(t.raw_gf_ceiling_at(gf, pp.surface_p), pp.surface_p) <= next_stop
+        };
 
-            if stop_time_sec > max_total_stop_time {
-                truncated = true;
-                break; // Safety limit
+        // Inspired pressures are constant at the stop, so the tissues after n
+        // minutes have a closed form and the wait can be searched for.
+        let gas = pp.gas_at_depth(current_stop);
+        let ambient_p = depth_to_pressure(current_stop, pp.surface_p);
+        let ppo2_at_stop = pp.loop_ppo2(current_stop, gas.fo2, ambient_p);
+        let (fn2, fhe_frac) = inspired_fractions(gas.fo2, gas.fhe, ppo2_at_stop, ambient_p);
+        let p_inspired_n2 = (ambient_p - P_WATER_VAPOR) * fn2;
+        let p_inspired_he = (ambient_p - P_WATER_VAPOR) * fhe_frac;
+        let after = |minutes: i32| {
+            let mut t = tissues.clone();
+            t.update_steps(&minute, minutes, p_inspired_n2, p_inspired_he);
+            t
+        };
+        let hint = hint
+            .iter()
+            .find(|s| s.depth_m == current_stop as f32)
+            .map_or(0, |s| s.duration_sec / 60);
+
+        // Usually the wait is the hint's: confirm with one full evaluation
+        // and a one-minute step, else search.
+        let confirmed = if hint >= 1 {
+            let before = after(hint - 1);
+            if clears(&before) {
+                (hint == 1).then_some((0, before))
+            } else {
+                let mut at = before;
+                at.update_steps(&minute, 1, p_inspired_n2, p_inspired_he);
+                clears(&at).then_some((hint, at))
             }
-        }
+        } else {
+            None
+        };
+        let (minutes, waited) = match confirmed {
+            Some(found) => found,
+            None => match first_clear_minute(|n| clears(&after(n)), hint, MAX_STOP_MINUTES) {
+                Some(n) => (n, after(n)),
+                None => {
+                    truncated = true; // Safety limit
+                    (MAX_STOP_MINUTES + 1, after(MAX_STOP_MINUTES + 1))
+                }
+            },
+        };
+        tissues = waited;
 
         // Enforce minimum 1-minute stop at each depth (standard practice:
         // the diver pauses at each stop increment during ascent).
-        if stop_time_sec < 60.0 {
-            let gas = pp.gas_at_depth(current_stop);
-            let ambient_p = depth_to_pressure(current_stop, pp.surface_p);
-            let ppo2_at_stop = pp.ppo2.map(|sp| sp.min(ambient_p));
-            let (fn2, fhe_frac) = inspired_fractions(gas.fo2, gas.fhe, ppo2_at_stop, ambient_p);
-            let p_inspired_n2 = (ambient_p - P_WATER_VAPOR) * fn2;
-            let p_inspired_he = (ambient_p - P_WATER_VAPOR) * fhe_frac;
-            tissues.update(60.0 - stop_time_sec, p_inspired_n2, p_inspired_he);
-            stop_time_sec = 60.0;
+        if minutes == 0 {
+            tissues.update(60.0, p_inspired_n2, p_inspired_he);
         }
+        let stop_time_sec = minutes.max(1) * 60;
         stops.push(DecoStop {
             depth_m: current_stop as f32,
-            duration_sec: stop_time_sec as i32,
+            duration_sec: stop_time_sec,
             gas_mix_index: -1,
+            is_deep_stop: false,
         });
 
         if current_stop <= pp.last_stop_depth {
@@ -719,22 +1199,159 @@ fn plan_deco_stops(
 
         // Ascend to next stop (using gas available at the shallower depth)
         let next_gas = pp.gas_at_depth(next_stop);
-        ascend_to(
-            &mut tissues,
-            &mut depth,
-            next_stop,
-            next_gas.fo2,
-            next_gas.fhe,
-            pp.ppo2,
-            pp.surface_p,
-            pp.ascent_rate_m_min,
-        );
+        let next_ppo2 = |ambient_p| pp.loop_ppo2(next_stop, next_gas.fo2, ambient_p);
+        if depth - next_stop == pp.stop_interval {
+            // Regular stop-to-stop travel: same interval every time
+            let ambient_p = depth_to_pressure((depth + next_stop) / 2.0, pp.surface_p);
+            let (fn2, fhe_frac) =
+                inspired_fractions(next_gas.fo2, next_gas.fhe, next_ppo2(ambient_p), ambient_p);
+            tissues.update_steps(
+                &travel,
+                1,
+                (ambient_p - P_WATER_VAPOR) * fn2,
+                (ambient_p - P_WATER_VAPOR) * fhe_frac,
+            );
+            depth = next_stop;
+        } else {
+            ascend_to(
+                &mut tissues,
+                &mut depth,
+                next_stop,
+                next_gas.fo2,
+                next_gas.fhe,
+                next_ppo2,
+                pp.surface_p,
+                pp.ascent_rate_m_min,
+            );
+        }
         current_stop = next_stop;
     }
 
     (stops, truncated)
 }
 
+// ============================================================================
+// Continuous Ascent Planner
+// ============================================================================
+
+/// Time step for the continuous ascent simulation (seconds).
+const CONTINUOUS_STEP_SEC: f64 = 10.0;
+
+/// Plan a continuous ascent that follows the GF-adjusted ceiling.
+///
+/// Each step ascends at `ascent_rate_m_min` unless that would come within
+/// `margin_m` of the ceiling, in which case the diver moves only as far as the
+/// ceiling plus margin. GF is interpolated from the raw GF-low ceiling at the
+/// start of the ascent to GF-high at the surface, as for staged stops.
+///
+/// Returns the path, a simplified stop list (time spent below the ascent rate,
+/// binned onto the stop grid, deepest first) and whether the 10-hour safety
+/// limit was hit. Without a ceiling, the path and stops are empty.
+fn plan_continuous_ascent(
+    tissues: &EngineTissueState,
+    current_depth_m: f64,
+    margin_m: f64,
+    pp: &PlanParams,
+) -> (Vec<AscentWaypoint>, Vec<DecoStop>, bool) {
+    let mut tissues = tissues.clone();
+    let first_stop = pressure_to_depth(
+        tissues.raw_gf_ceiling_at(pp.gf_low, pp.surface_p),
+        pp.surface_p,
+    );
+    if first_stop <= 0.0 {
+        return (Vec::new(), Vec::new(), false);
+    }
+
+    let max_step_m = pp.ascent_rate_m_min * CONTINUOUS_STEP_SEC / 60.0;
+    let max_total_sec = 36000.0; // 10 hour safety limit
+    let mut depth = current_depth_m;
+    let mut t = 0.0;
+    let mut truncated = false;
+    let mut path = vec![AscentWaypoint {
+        t_offset_sec: 0,
+        depth_m: depth as f32,
+    }];
+    // (grid depth, seconds held) in order of first visit, i.e. deepest first
+    let mut held_by_stop: Vec<(f64, f64)> = Vec::new();
+
+    while depth > 0.0 {
+        if t > max_total_sec {
+            truncated = true;
+            break;
+        }
+        let gf = gf_at_depth(depth, first_stop, pp.gf_low, pp.gf_high);
+        let ceiling = pressure_to_depth(tissues.raw_gf_ceiling_at(gf, pp.surface_p), pp.surface_p);
+        let floor = if ceiling > 0.0 {
+            ceiling + margin_m
+        } else {
+            0.0
+        };
+        let next = (depth - max_step_m).max(floor).min(depth).max(0.0);
+
+        let travel_sec = (depth - next) / pp.ascent_rate_m_min * 60.0;
+        let dt = if next <= 0.0 {
+            travel_sec
+        } else {
+            CONTINUOUS_STEP_SEC
+        };
+        let held_sec = dt - travel_sec;
+        if held_sec > 0.0 {
+            let stop = round_up_to_stop(depth, pp.stop_interval).max(pp.last_stop_depth);
+            match held_by_stop.iter_mut().find(|(d, _)| *d == stop) {
+                Some((_, secs)) => *secs += held_sec,
+                None => held_by_stop.push((stop, held_sec)),
+            }
+        }
+
+        let gas = pp.gas_at_depth(next);
+        let ambient_p = depth_to_pressure((depth + next) / 2.0, pp.surface_p);
+        let ppo2 = pp.loop_ppo2(next, gas.fo2, ambient_p);
+        let (fn2, fhe_frac) = inspired_fractions(gas.fo2, gas.fhe, ppo2, ambient_p);
+        tissues.update(
+            dt,
+            (ambient_p - P_WATER_VAPOR) * fn2,
+            (ambient_p - P_WATER_VAPOR) * fhe_frac,
+        );
+
+        depth = next;
+        t += dt;
+        path.push(AscentWaypoint {
+            t_offset_sec: t.round() as i32,
+            depth_m: depth as f32,
+        });
+    }
+
+    let stops = held_by_stop
+        .into_iter()
+        .map(|(d, secs)| DecoStop {
+            depth_m: d as f32,
+            duration_sec: secs.round() as i32,
+            gas_mix_index: -1,
+            is_deep_stop: false,
+        })
+        .filter(|s| s.duration_sec > 0)
+        .collect();
+    (path, stops, truncated)
+}
+
+/// Ascend to a depth and stay there, breathing the gas available at that depth.
+fn hold_at_depth(
+    tissues: &mut EngineTissueState,
+    current_depth: &mut f64,
+    target_depth: f64,
+    duration_sec: f64,
+    pp: &PlanParams,
+) {
+    ascend_with_gas_switches(tissues, current_depth, target_depth, pp);
+    let gas = pp.gas_at_depth(target_depth);
+    let ambient_p = depth_to_pressure(target_depth, pp.surface_p);
+    let ppo2_at_stop = pp.loop_ppo2(target_depth, gas.fo2, ambient_p);
+    let (fn2, fhe_frac) = inspired_fractions(gas.fo2, gas.fhe, ppo2_at_stop, ambient_p);
+    let p_inspired_n2 = (ambient_p - P_WATER_VAPOR) * fn2;
+    let p_inspired_he = (ambient_p - P_WATER_VAPOR) * fhe_frac;
+    tissues.update(duration_sec, p_inspired_n2, p_inspired_he);
+}
+
 /// Ascend from current depth to target, segmenting at gas switch boundaries.
 /// This ensures correct tissue loading when passing through switch depths.
 fn ascend_with_gas_switches(
@@ -743,11 +1360,13 @@ fn ascend_with_gas_switches(
     target_depth: f64,
     pp: &PlanParams,
 ) {
-    // Collect switch depths between current and target (descending order)
+    // Collect gas and setpoint switch depths between current and target
+    // (descending order)
     let mut waypoints: Vec<f64> = pp
         .gases
         .iter()
         .filter_map(|g| g.switch_depth_m)
+        .chain(pp.setpoint_switches.iter().map(|s| s.depth_m))
         .filter(|&d| d < *current_depth && d > target_depth)
         .collect();
     waypoints.sort_by(|a, b| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
@@ -755,13 +1374,14 @@ fn ascend_with_gas_switches(
 
     for wp in waypoints {
         let gas = pp.gas_at_depth(*current_depth);
+        let loop_depth = *current_depth;
         ascend_to(
             tissues,
             current_depth,
             wp,
             gas.fo2,
             gas.fhe,
-            pp.ppo2,
+            |ambient_p| pp.loop_ppo2(loop_depth, gas.fo2, ambient_p),
             pp.surface_p,
             pp.ascent_rate_m_min,
         );
@@ -769,6 +1389,8 @@ fn ascend_with_gas_switches(
 }
 
 /// Simulate ascent between two depths, updating tissue state during travel.
+///
+/// `ppo2` gives the inspired PPO2 at an ambient pressure (`None` = open circuit).
 #[allow(clippy::too_many_arguments)]
 fn ascend_to(
     tissues: &mut EngineTissueState,
@@ -776,7 +1398,7 @@ fn ascend_to(
     target_depth: f64,
     fo2: f64,
     fhe: f64,
-    ppo2: Option<f64>,
+    ppo2: impl Fn(f64) -> Option<f64>,
     surface_p: f64,
     ascent_rate_m_min: f64,
 ) {
@@ -789,8 +1411,7 @@ fn ascend_to(
     let travel_sec = (travel_m / ascent_rate_m_min) * 60.0;
     let avg_depth = (*current_depth + target_depth) / 2.0;
     let ambient_p = depth_to_pressure(avg_depth, surface_p);
-    let ppo2_clamped = ppo2.map(|sp| sp.min(ambient_p));
-    let (fn2, fhe_frac) = inspired_fractions(fo2, fhe, ppo2_clamped, ambient_p);
+    let (fn2, fhe_frac) = inspired_fractions(fo2, fhe, ppo2(ambient_p), ambient_p);
     let p_inspired_n2 = (ambient_p - P_WATER_VAPOR) * fn2;
     let p_inspired_he = (ambient_p - P_WATER_VAPOR) * fhe_frac;
     tissues.update(travel_sec, p_inspired_n2, p_inspired_he);
@@ -805,6 +1426,8 @@ fn ascend_to(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::buhlmann::{A_N2, B_N2, ZHL16C};
+    use crate::deco::scr::ScrMode;
     use crate::metrics::SampleInput;
 
     fn sample(t_sec: i32, depth_m: f32) -> SampleInput {
@@ -847,7 +1470,7 @@ mod tests {
     fn test_gf_ceiling_known_tissue_state() {
         // Manually set tissue state and verify ceiling computation
         let surface_p = DEFAULT_SURFACE_PRESSURE;
-        let mut tissues = EngineTissueState::surface_equilibrium(surface_p);
+        let mut tissues = EngineTissueState::surface_equilibrium(surface_p, &ZHL16C);
 
         // Simulate 20 min at 30m on air to load tissues
         let ambient_p = depth_to_pressure(30.0, surface_p);
@@ -879,6 +1502,7 @@ mod tests {
         let mut tissues = EngineTissueState {
             p_n2: [0.0; NUM_COMPARTMENTS],
             p_he: [0.0; NUM_COMPARTMENTS],
+            coeffs: &ZHL16C,
         };
         // Set compartment 0 to a known pressure
         tissues.p_n2[0] = 3.5; // bar — heavily loaded fast tissue
@@ -934,7 +1558,7 @@ mod tests {
     fn test_ndl_18m_air() {
         // 18m on air with GF 100/100 should give NDL around 51–57 min
         // (PADI: 56 min, DSAT tables)
-        let tissues = EngineTissueState::surface_equilibrium(DEFAULT_SURFACE_PRESSURE);
+        let tissues = EngineTissueState::surface_equilibrium(DEFAULT_SURFACE_PRESSURE, &ZHL16C);
         let ndl = compute_ndl(
             &tissues,
             18.0,
@@ -956,7 +1580,7 @@ mod tests {
     fn test_ndl_30m_air() {
         // 30m on air with GF 100/100 should give NDL around 16–22 min
         // (PADI: 20 min, Bühlmann raw is ~16-20 min)
-        let tissues = EngineTissueState::surface_equilibrium(DEFAULT_SURFACE_PRESSURE);
+        let tissues = EngineTissueState::surface_equilibrium(DEFAULT_SURFACE_PRESSURE, &ZHL16C);
         let ndl = compute_ndl(
             &tissues,
             30.0,
@@ -977,7 +1601,7 @@ mod tests {
     #[test]
     fn test_ndl_surface() {
         // At surface, NDL should be 0 (no depth)
-        let tissues = EngineTissueState::surface_equilibrium(DEFAULT_SURFACE_PRESSURE);
+        let tissues = EngineTissueState::surface_equilibrium(DEFAULT_SURFACE_PRESSURE, &ZHL16C);
         let ndl = compute_ndl(
             &tissues,
             0.0,
@@ -1015,9 +1639,19 @@ mod tests {
             gf_high: Some(85),
             thalmann_pdcs: None,
             plan_ascent: true,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
         };
 
-        let result = engine.simulate(&params).unwrap();
+        let result = engine.simulate(&params, Progress::NONE).unwrap();
 
         // Should have deco stops
         assert!(
@@ -1077,9 +1711,19 @@ mod tests {
             gf_high: Some(85),
             thalmann_pdcs: None,
             plan_ascent: true,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
         };
 
-        let result = engine.simulate(&params).unwrap();
+        let result = engine.simulate(&params, Progress::NONE).unwrap();
 
         // Deep profile with low GF should have multiple stops
         assert!(
@@ -1120,6 +1764,16 @@ mod tests {
             gf_high: Some(100),
             thalmann_pdcs: None,
             plan_ascent: true,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
         };
 
         let params_50_85 = DecoSimParams {
@@ -1134,10 +1788,20 @@ mod tests {
             gf_high: Some(85),
             thalmann_pdcs: None,
             plan_ascent: true,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
         };
 
-        let result_100 = engine.simulate(&params_100).unwrap();
-        let result_50_85 = engine.simulate(&params_50_85).unwrap();
+        let result_100 = engine.simulate(&params_100, Progress::NONE).unwrap();
+        let result_50_85 = engine.simulate(&params_50_85, Progress::NONE).unwrap();
 
         assert!(
             result_100.total_deco_time_sec <= result_50_85.total_deco_time_sec,
@@ -1178,8 +1842,18 @@ mod tests {
             gf_high: Some(100),
             thalmann_pdcs: None,
             plan_ascent: false,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
         };
-        let result = engine.simulate(&params).unwrap();
+        let result = engine.simulate(&params, Progress::NONE).unwrap();
 
         assert_eq!(existing.len(), result.points.len());
         for (i, (old, new)) in existing.iter().zip(result.points.iter()).enumerate() {
@@ -1223,9 +1897,19 @@ mod tests {
             gf_high: Some(85),
             thalmann_pdcs: None,
             plan_ascent: false,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
         };
 
-        let result = engine.simulate(&params).unwrap();
+        let result = engine.simulate(&params, Progress::NONE).unwrap();
 
         // Surface point should have low GF99 and SurfGF
         assert!(result.points[0].surface_gf < 1.0);
@@ -1265,9 +1949,19 @@ mod tests {
             gf_high: None,
             thalmann_pdcs: None,
             plan_ascent: false,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
         };
 
-        let result = engine.simulate(&params);
+        let result = engine.simulate(&params, Progress::NONE);
         assert!(matches!(result, Err(DecoSimError::EmptySamples { .. })));
     }
 
@@ -1286,9 +1980,19 @@ mod tests {
             gf_high: Some(70),
             thalmann_pdcs: None,
             plan_ascent: false,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
         };
 
-        let result = engine.simulate(&params);
+        let result = engine.simulate(&params, Progress::NONE);
         assert!(matches!(result, Err(DecoSimError::InvalidParam { .. })));
     }
 
@@ -1307,9 +2011,19 @@ mod tests {
             gf_high: Some(85),
             thalmann_pdcs: None,
             plan_ascent: false,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
         };
 
-        let result = engine.simulate(&params);
+        let result = engine.simulate(&params, Progress::NONE);
         assert!(matches!(result, Err(DecoSimError::InvalidParam { .. })));
     }
 
@@ -1329,9 +2043,19 @@ mod tests {
             gf_high: None,
             thalmann_pdcs: None,
             plan_ascent: false,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
         };
         assert!(matches!(
-            engine.simulate(&params),
+            engine.simulate(&params, Progress::NONE),
             Err(DecoSimError::InvalidParam { .. })
         ));
 
@@ -1348,9 +2072,19 @@ mod tests {
             gf_high: None,
             thalmann_pdcs: None,
             plan_ascent: false,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
         };
         assert!(matches!(
-            engine.simulate(&params),
+            engine.simulate(&params, Progress::NONE),
             Err(DecoSimError::InvalidParam { .. })
         ));
     }
@@ -1422,9 +2156,19 @@ mod tests {
             gf_high: Some(100),
             thalmann_pdcs: None,
             plan_ascent: false,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
         };
 
-        let result = engine.simulate(&params).unwrap();
+        let result = engine.simulate(&params, Progress::NONE).unwrap();
         // With CCR at PPO2=1.3 on trimix diluent, N2 loading should be lower
         // than OC air, so SurfGF should be lower
         let last_pt = result.points.last().unwrap();
@@ -1446,8 +2190,18 @@ mod tests {
             gf_high: Some(100),
             thalmann_pdcs: None,
             plan_ascent: false,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
         };
-        let oc_result = engine.simulate(&oc_params).unwrap();
+        let oc_result = engine.simulate(&oc_params, Progress::NONE).unwrap();
         let oc_last = oc_result.points.last().unwrap();
 
         // CCR with higher O2 fraction means less inert gas loading
@@ -1479,9 +2233,19 @@ mod tests {
             gf_high: Some(85),
             thalmann_pdcs: None,
             plan_ascent: false,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
         };
 
-        let result = engine.simulate(&params).unwrap();
+        let result = engine.simulate(&params, Progress::NONE).unwrap();
         let last_pt = result.points.last().unwrap();
 
         // If ceiling > 0, TTS must be positive (ascent travel + stop time)
@@ -1520,10 +2284,20 @@ mod tests {
             gf_high: Some(85),
             thalmann_pdcs: None,
             plan_ascent: false,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
         };
 
-        let result_short = engine.simulate(&make_params(1200)).unwrap();
-        let result_long = engine.simulate(&make_params(1800)).unwrap();
+        let result_short = engine.simulate(&make_params(1200), Progress::NONE).unwrap();
+        let result_long = engine.simulate(&make_params(1800), Progress::NONE).unwrap();
 
         let tts_short = result_short.points.last().unwrap().tts_sec;
         let tts_long = result_long.points.last().unwrap().tts_sec;
@@ -1552,10 +2326,20 @@ mod tests {
             gf_high: Some(100),
             thalmann_pdcs: None,
             plan_ascent: false,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
         };
 
-        let result_18 = engine.simulate(&make_params(18.0)).unwrap();
-        let result_30 = engine.simulate(&make_params(30.0)).unwrap();
+        let result_18 = engine.simulate(&make_params(18.0), Progress::NONE).unwrap();
+        let result_30 = engine.simulate(&make_params(30.0), Progress::NONE).unwrap();
 
         let ndl_18 = result_18.points.last().unwrap().ndl_sec;
         let ndl_30 = result_30.points.last().unwrap().ndl_sec;
@@ -1584,9 +2368,19 @@ mod tests {
             gf_high: Some(85),
             thalmann_pdcs: None,
             plan_ascent: true,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
         };
 
-        let result = engine.simulate(&params).unwrap();
+        let result = engine.simulate(&params, Progress::NONE).unwrap();
         for stop in &result.deco_stops {
             assert!(
                 stop.duration_sec > 0,
@@ -1627,7 +2421,7 @@ mod tests {
     #[test]
     fn test_tissue_update_zero_dt() {
         // Covers line 240: dt_sec <= 0 guard in EngineTissueState::update
-        let mut tissues = EngineTissueState::surface_equilibrium(DEFAULT_SURFACE_PRESSURE);
+        let mut tissues = EngineTissueState::surface_equilibrium(DEFAULT_SURFACE_PRESSURE, &ZHL16C);
         let p_before = tissues.p_n2[0];
         tissues.update(0.0, 3.0, 0.0);
         assert_eq!(
@@ -1647,6 +2441,7 @@ mod tests {
         let tissues = EngineTissueState {
             p_n2: [0.0; NUM_COMPARTMENTS],
             p_he: [0.0; NUM_COMPARTMENTS],
+            coeffs: &ZHL16C,
         };
         // compartment_gf calls weighted_ab; with zero tissue pressure it should not panic
         let gf = tissues.compartment_gf(0, DEFAULT_SURFACE_PRESSURE);
@@ -1663,6 +2458,7 @@ mod tests {
         let tissues = EngineTissueState {
             p_n2: [0.0; NUM_COMPARTMENTS],
             p_he: [0.0; NUM_COMPARTMENTS],
+            coeffs: &ZHL16C,
         };
         let ceil = tissues.raw_gf_ceiling_at(1.0, DEFAULT_SURFACE_PRESSURE);
         // Zero tissue load means no ceiling
@@ -1690,7 +2486,7 @@ mod tests {
     #[test]
     fn test_ndl_very_shallow_returns_max() {
         // Covers lines 461-466: NDL at very shallow depth — no ceiling even at 200 min
-        let tissues = EngineTissueState::surface_equilibrium(DEFAULT_SURFACE_PRESSURE);
+        let tissues = EngineTissueState::surface_equilibrium(DEFAULT_SURFACE_PRESSURE, &ZHL16C);
         // 3m on air — no ceiling even after 200 min, returns max_time
         let ndl = compute_ndl(
             &tissues,
@@ -1715,7 +2511,7 @@ mod tests {
         // Need a depth where ceiling appears between 7680 sec and 12000 sec.
         // At 15m GF 0.5 on air, NDL is shorter than at shallower depths but
         // the doubling may still overshoot. Try multiple conservative depths.
-        let tissues = EngineTissueState::surface_equilibrium(DEFAULT_SURFACE_PRESSURE);
+        let tissues = EngineTissueState::surface_equilibrium(DEFAULT_SURFACE_PRESSURE, &ZHL16C);
         // At 12m with GF 0.4, ceiling should appear sooner
         let ndl = compute_ndl(
             &tissues,
@@ -1756,9 +2552,19 @@ mod tests {
             gf_high: Some(85),
             thalmann_pdcs: None,
             plan_ascent: true,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
         };
 
-        let result = engine.simulate(&params).unwrap();
+        let result = engine.simulate(&params, Progress::NONE).unwrap();
         // All stops should be >= last_stop_depth (6m)
         for stop in &result.deco_stops {
             assert!(
@@ -1787,9 +2593,19 @@ mod tests {
             gf_high: Some(85),
             thalmann_pdcs: None,
             plan_ascent: true,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
         };
 
-        let result = engine.simulate(&params).unwrap();
+        let result = engine.simulate(&params, Progress::NONE).unwrap();
         assert!(!result.truncated, "Normal profile should not be truncated");
     }
 
@@ -1804,7 +2620,7 @@ mod tests {
         let ambient_p = depth_to_pressure(depth_m, surface_p);
 
         // Descent: model as avg depth for 150 sec
-        let mut tissues = EngineTissueState::surface_equilibrium(surface_p);
+        let mut tissues = EngineTissueState::surface_equilibrium(surface_p, &ZHL16C);
         let avg_descent_depth = depth_m / 2.0;
         let avg_descent_p = depth_to_pressure(avg_descent_depth, surface_p);
         let (fn2_d, fhe_d) = inspired_fractions(0.21, 0.35, None, avg_descent_p);
@@ -1862,7 +2678,7 @@ mod tests {
         let surface_p = DEFAULT_SURFACE_PRESSURE;
         let depth_m = 45.72;
 
-        let mut tissues = EngineTissueState::surface_equilibrium(surface_p);
+        let mut tissues = EngineTissueState::surface_equilibrium(surface_p, &ZHL16C);
 
         // Descent 150 sec at avg depth
         let avg_p = depth_to_pressure(depth_m / 2.0, surface_p);
@@ -1953,6 +2769,8 @@ mod tests {
                 },
             ],
             ppo2: None,
+            setpoint_switches: vec![],
+            scr: None,
             surface_p: 1.013,
             ascent_rate_m_min: 9.0,
             last_stop_depth: 3.0,
@@ -1987,6 +2805,271 @@ mod tests {
         assert!((gas.fo2 - 0.50).abs() < 1e-6);
     }
 
+    #[test]
+    fn test_from_engine_explicit_ascent_switches() {
+        // Travel 21/35 (mix 0), bottom 10/70 (mix 1), EAN50 (mix 2)
+        let gas_mixes = std::collections::HashMap::from([
+            (0, (0.21, 0.35)),
+            (1, (0.10, 0.70)),
+            (2, (0.50, 0.0)),
+        ]);
+        let switches = vec![
+            GasSwitchDepth {
+                mix_index: 0,
+                depth_m: 57.0,
+            },
+            GasSwitchDepth {
+                mix_index: 2,
+                depth_m: 12.0,
+            },
+        ];
+        let pp = PlanParams::from_engine(
+            &gas_mixes,
+            1,
+            &switches,
+            None,
+            &[],
+            None,
+            1.013,
+            9.0,
+            3.0,
+            3.0,
+            0.5,
+            0.8,
+        );
+
+        // Only the current gas is bottom gas, even though mix 0 exists
+        assert_eq!(pp.gases.len(), 3);
+        assert!((pp.gas_at_depth(70.0).fo2 - 0.10).abs() < 1e-9);
+        assert_eq!(pp.gas_at_depth(30.0).switch_depth_m, Some(57.0));
+        // EAN50 uses the planned 12 m, not its 22 m MOD
+        assert_eq!(pp.gas_at_depth(15.0).switch_depth_m, Some(57.0));
+        assert_eq!(pp.gas_at_depth(12.0).switch_depth_m, Some(12.0));
+
+        // Without explicit switches, the MOD default applies
+        let pp = PlanParams::from_engine(
+            &gas_mixes,
+            1,
+            &[],
+            None,
+            &[],
+            None,
+            1.013,
+            9.0,
+            3.0,
+            3.0,
+            0.5,
+            0.8,
+        );
+        assert!((pp.gas_at_depth(15.0).fo2 - 0.50).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_pyle_deep_stops_before_required_stops() {
+        // 50m for 25 min on air, GF 50/85, with Pyle stops of 2 min
+        let samples = vec![sample(0, 0.0), sample(180, 50.0), sample(1500, 50.0)];
+        let mut params = DecoSimParams {
+            model: DecoModel::BuhlmannZhl16c,
+            samples,
+            gas_mixes: vec![],
+            surface_pressure_bar: None,
+            ascent_rate_m_min: Some(9.0),
+            last_stop_depth_m: Some(3.0),
+            stop_interval_m: Some(3.0),
+            gf_low: Some(50),
+            gf_high: Some(85),
+            thalmann_pdcs: None,
+            plan_ascent: true,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
+        };
+        let baseline = BuhlmannEngine.simulate(&params, Progress::NONE).unwrap();
+        assert_eq!(baseline.deep_stop_time_sec, 0);
+        assert!(baseline.deco_stops.iter().all(|s| !s.is_deep_stop));
+        let first_required = baseline.deco_stops[0].depth_m;
+
+        params.deep_stops = Some(DeepStopParams {
+            strategy: DeepStopStrategy::Pyle,
+            stop_duration_sec: None,
+            custom_fractions: vec![],
+        });
+        let result = BuhlmannEngine.simulate(&params, Progress::NONE).unwrap();
+        let deep: Vec<_> = result
+            .deco_stops
+            .iter()
+            .filter(|s| s.is_deep_stop)
+            .collect();
+        assert!(!deep.is_empty());
+        assert!(deep
+            .iter()
+            .all(|s| s.depth_m > first_required && s.duration_sec == 120));
+        // Deep stops come first, deepest first
+        assert!(result.deco_stops[..deep.len()]
+            .iter()
+            .all(|s| s.is_deep_stop));
+        assert!(deep.windows(2).all(|w| w[0].depth_m > w[1].depth_m));
+        assert_eq!(result.deep_stop_time_sec, 120 * deep.len() as i32);
+
+        // Penalty is the change in required stop time versus the baseline
+        let required: i32 = result
+            .deco_stops
+            .iter()
+            .filter(|s| !s.is_deep_stop)
+            .map(|s| s.duration_sec)
+            .sum();
+        assert_eq!(
+            result.deep_stop_penalty_sec,
+            required - baseline.total_deco_time_sec
+        );
+        assert_eq!(
+            result.total_deco_time_sec,
+            required + result.deep_stop_time_sec
+        );
+    }
+
+    #[test]
+    fn test_invalid_deep_stops_rejected() {
+        let params = DecoSimParams {
+            model: DecoModel::BuhlmannZhl16c,
+            samples: vec![sample(0, 0.0), sample(120, 30.0)],
+            gas_mixes: vec![],
+            surface_pressure_bar: None,
+            ascent_rate_m_min: None,
+            last_stop_depth_m: None,
+            stop_interval_m: None,
+            gf_low: None,
+            gf_high: None,
+            thalmann_pdcs: None,
+            plan_ascent: true,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: Some(DeepStopParams {
+                strategy: DeepStopStrategy::Custom,
+                stop_duration_sec: None,
+                custom_fractions: vec![],
+            }),
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
+        };
+        assert!(matches!(
+            crate::deco::compute_deco_simulation(params),
+            Err(DecoSimError::InvalidParam { .. })
+        ));
+    }
+
+    #[test]
+    fn test_scr_loop_replaces_sample_ppo2() {
+        // 40m for 20 min on air, GF 50/85
+        let samples = vec![sample(0, 0.0), sample(160, 40.0), sample(1200, 40.0)];
+        let mut params = DecoSimParams {
+            model: DecoModel::BuhlmannZhl16c,
+            samples,
+            gas_mixes: vec![],
+            surface_pressure_bar: None,
+            ascent_rate_m_min: Some(9.0),
+            last_stop_depth_m: Some(3.0),
+            stop_interval_m: Some(3.0),
+            gf_low: Some(50),
+            gf_high: Some(85),
+            thalmann_pdcs: None,
+            plan_ascent: true,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
+        };
+        let oc = BuhlmannEngine.simulate(&params, Progress::NONE).unwrap();
+
+        params.scr = Some(ScrParams {
+            mode: ScrMode::Passive,
+            o2_consumption_l_min: 1.0,
+            dump_ratio: Some(0.1),
+            injection_rate_l_min: None,
+        });
+        let scr = BuhlmannEngine.simulate(&params, Progress::NONE).unwrap();
+        // The O2-depleted loop loads more nitrogen than open circuit
+        assert!(scr.total_deco_time_sec > oc.total_deco_time_sec);
+
+        // Recorded sample PPO2 is ignored while the SCR loop is configured
+        for s in &mut params.samples {
+            s.ppo2 = Some(1.3);
+        }
+        let with_ppo2 = BuhlmannEngine.simulate(&params, Progress::NONE).unwrap();
+        assert_eq!(with_ppo2.total_deco_time_sec, scr.total_deco_time_sec);
+    }
+
+    #[test]
+    fn test_continuous_ascent_stays_below_ceiling() {
+        // 45m for 25 min on air, GF 50/85, 1 m margin
+        let surface_p = DEFAULT_SURFACE_PRESSURE;
+        let mut tissues = EngineTissueState::surface_equilibrium(surface_p, &ZHL16C);
+        let ambient_p = depth_to_pressure(45.0, surface_p);
+        tissues.update(1500.0, (ambient_p - P_WATER_VAPOR) * 0.79, 0.0);
+        let gases = std::collections::HashMap::from([(0, (0.21, 0.0))]);
+        let pp = PlanParams::from_engine(
+            &gases,
+            0,
+            &[],
+            None,
+            &[],
+            None,
+            surface_p,
+            9.0,
+            3.0,
+            3.0,
+            0.5,
+            0.85,
+        );
+
+        let (path, stops, truncated) = plan_continuous_ascent(&tissues, 45.0, 1.0, &pp);
+        assert!(!truncated);
+        assert_eq!(path.first().unwrap().depth_m, 45.0);
+        assert_eq!(path.last().unwrap().depth_m, 0.0);
+        assert!(!stops.is_empty());
+
+        // Replay the path: the diver never rises above ceiling + margin
+        let first_stop = pressure_to_depth(tissues.raw_gf_ceiling_at(0.5, surface_p), surface_p);
+        for w in path.windows(2) {
+            let (from, to) = (w[0].depth_m as f64, w[1].depth_m as f64);
+            let gf = gf_at_depth(from, first_stop, 0.5, 0.85);
+            let ceiling = pressure_to_depth(tissues.raw_gf_ceiling_at(gf, surface_p), surface_p);
+            assert!(to <= from);
+            if ceiling > 0.0 {
+                assert!(
+                    to >= ceiling + 1.0 - 1e-6,
+                    "{to} m above ceiling {ceiling} m"
+                );
+            }
+            let p = depth_to_pressure((from + to) / 2.0, surface_p);
+            let dt = (w[1].t_offset_sec - w[0].t_offset_sec) as f64;
+            tissues.update(dt, (p - P_WATER_VAPOR) * 0.79, 0.0);
+        }
+
+        // Held time plus travel at the ascent rate makes up the runtime
+        let held: i32 = stops.iter().map(|s| s.duration_sec).sum();
+        let travel = (45.0 / 9.0 * 60.0) as i32;
+        assert!((held + travel - path.last().unwrap().t_offset_sec).abs() <= stops.len() as i32);
+    }
+
     // ── Coverage: truncation safety limit (lines 693-694) ────────────────
 
     #[test]
@@ -1999,6 +3082,7 @@ mod tests {
         let mut tissues = EngineTissueState {
             p_n2: [0.0; NUM_COMPARTMENTS],
             p_he: [0.0; NUM_COMPARTMENTS],
+            coeffs: &ZHL16C,
         };
         // Load ALL compartments to extremely high pressure (20 bar N2).
         // Even the slowest compartments (HT > 600 min) would need many
@@ -2014,6 +3098,8 @@ mod tests {
                 switch_depth_m: None,
             }],
             ppo2: None,
+            setpoint_switches: vec![],
+            scr: None,
             surface_p,
             ascent_rate_m_min: 9.0,
             last_stop_depth: 3.0,
@@ -2031,4 +3117,172 @@ mod tests {
         // Should still produce stops even when truncated
         assert!(!stops.is_empty(), "Truncated plan should still have stops");
     }
+
+    // ── Coefficient sets ─────────────────────────────────────────────────
+
+    fn coefficient_params(
+        set: Option<crate::buhlmann::BuhlmannCoefficientSet>,
+        custom: Option<crate::buhlmann::BuhlmannCoefficients>,
+    ) -> DecoSimParams {
+        DecoSimParams {
+            model: DecoModel::BuhlmannZhl16c,
+            samples: vec![sample(0, 0.0), sample(150, 40.0), sample(1800, 40.0)],
+            gas_mixes: vec![],
+            surface_pressure_bar: None,
+            ascent_rate_m_min: None,
+            last_stop_depth_m: None,
+            stop_interval_m: None,
+            gf_low: Some(50),
+            gf_high: Some(80),
+            thalmann_pdcs: None,
+            plan_ascent: true,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: set,
+            custom_buhlmann_coefficients: custom,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
+        }
+    }
+
+    #[test]
+    fn test_coefficient_sets_order_deco_time() {
+        use crate::buhlmann::BuhlmannCoefficientSet as Set;
+        let engine = BuhlmannEngine;
+        let deco = |set| {
+            engine
+                .simulate(&coefficient_params(Some(set), None), Progress::NONE)
+                .unwrap()
+                .total_deco_time_sec
+        };
+        let (a, b, c) = (deco(Set::Zhl16a), deco(Set::Zhl16b), deco(Set::Zhl16c));
+        assert!(a <= b && b <= c, "A {a}, B {b}, C {c}");
+        assert!(a < c, "ZHL-16C should need more deco than ZHL-16A");
+
+        let default = engine
+            .simulate(&coefficient_params(None, None), Progress::NONE)
+            .unwrap();
+        assert_eq!(default.total_deco_time_sec, c);
+    }
+
+    #[test]
+    fn test_custom_coefficients_match_builtin_set() {
+        use crate::buhlmann::{BuhlmannCoefficientSet, BuhlmannCoefficients, ZHL16B};
+        let custom = BuhlmannCoefficients {
+            n2_half_times_min: ZHL16B.n2_half_times.to_vec(),
+            he_half_times_min: ZHL16B.he_half_times.to_vec(),
+            a_n2: ZHL16B.a_n2.to_vec(),
+            b_n2: ZHL16B.b_n2.to_vec(),
+            a_he: ZHL16B.a_he.to_vec(),
+            b_he: ZHL16B.b_he.to_vec(),
+        };
+        let engine = BuhlmannEngine;
+        let b = engine
+            .simulate(
+                &coefficient_params(Some(BuhlmannCoefficientSet::Zhl16b), None),
+                Progress::NONE,
+            )
+            .unwrap();
+        let mut params = coefficient_params(Some(BuhlmannCoefficientSet::Custom), Some(custom));
+        let custom_result = engine.simulate(&params, Progress::NONE).unwrap();
+        assert_eq!(custom_result.total_deco_time_sec, b.total_deco_time_sec);
+        assert_eq!(custom_result.max_ceiling_m, b.max_ceiling_m);
+
+        // An invalid custom set is rejected.
+        params.custom_buhlmann_coefficients.as_mut().unwrap().b_he[2] = 0.0;
+        assert!(matches!(
+            engine.simulate(&params, Progress::NONE),
+            Err(DecoSimError::InvalidParam { .. })
+        ));
+    }
+
+    #[test]
+    fn test_update_steps_matches_schreiner_within_tolerance() {
+        // Precomputed factors raised to a power are not bit-identical to one
+        // exponential over the whole interval; they agree to within 1e-9 bar.
+        const TOLERANCE_BAR: f64 = 1e-9;
+        let surface_p = DEFAULT_SURFACE_PRESSURE;
+        let start = EngineTissueState {
+            p_n2: [0.79 * (surface_p - P_WATER_VAPOR); NUM_COMPARTMENTS],
+            p_he: [0.0; NUM_COMPARTMENTS],
+            coeffs: &ZHL16C,
+        };
+        let minute = DecayFactors::new(&ZHL16C, 60.0);
+        for (p_n2, p_he) in [(2.9, 1.8), (0.74, 0.0)] {
+            for minutes in [1, 7, 60, 600] {
+                let mut stepped = start.clone();
+                stepped.update_steps(&minute, minutes, p_n2, p_he);
+                let mut direct = start.clone();
+                direct.update(minutes as f64 * 60.0, p_n2, p_he);
+                for i in 0..NUM_COMPARTMENTS {
+                    assert!((stepped.p_n2[i] - direct.p_n2[i]).abs() < TOLERANCE_BAR);
+                    assert!((stepped.p_he[i] - direct.p_he[i]).abs() < TOLERANCE_BAR);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_first_clear_minute_matches_linear_scan() {
+        for answer in [0, 1, 2, 7, 31, 64, 599, 600] {
+            let clears = |m: i32| m >= answer;
+            for hint in [0, 1, 5, 30, 64, 600, 700] {
+                assert_eq!(
+                    first_clear_minute(clears, hint, 600),
+                    Some(answer),
+                    "answer {answer}, hint {hint}"
+                );
+            }
+        }
+        assert_eq!(first_clear_minute(|_| false, 10, 600), None);
+    }
+
+    #[test]
+    fn test_hinted_stops_match_unhinted() {
+        let surface_p = DEFAULT_SURFACE_PRESSURE;
+        // Tissues after 25 minutes at 50 m on air.
+        let mut tissues = EngineTissueState {
+            p_n2: [0.79 * (surface_p - P_WATER_VAPOR); NUM_COMPARTMENTS],
+            p_he: [0.0; NUM_COMPARTMENTS],
+            coeffs: &ZHL16C,
+        };
+        let p_amb = surface_p + 5.0;
+        tissues.update(1500.0, 0.79 * (p_amb - P_WATER_VAPOR), 0.0);
+        let pp = PlanParams {
+            gases: vec![PlanGas {
+                fo2: AIR_FO2,
+                fhe: 0.0,
+                switch_depth_m: None,
+            }],
+            ppo2: None,
+            setpoint_switches: vec![],
+            scr: None,
+            surface_p,
+            ascent_rate_m_min: 9.0,
+            last_stop_depth: 3.0,
+            stop_interval: 3.0,
+            gf_low: 0.3,
+            gf_high: 0.7,
+        };
+        let (fresh, _) = plan_deco_stops(&tissues, 50.0, &pp);
+        assert!(fresh.len() > 2);
+        // A stale hint only changes where the search starts.
+        let stale: Vec<DecoStop> = fresh
+            .iter()
+            .map(|s| DecoStop {
+                duration_sec: s.duration_sec + 600,
+                ..s.clone()
+            })
+            .collect();
+        let (hinted, _) = plan_deco_stops_hinted(&tissues, 50.0, &pp, &stale);
+        assert_eq!(fresh.len(), hinted.len());
+        for (a, b) in fresh.iter().zip(&hinted) {
+            assert_eq!(a.depth_m, b.depth_m);
+            assert_eq!(a.duration_sec, b.duration_sec);
+        }
+    }
 }
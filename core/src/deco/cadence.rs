@@ -0,0 +1,388 @@
+//! TTS/NDL cadence for one-shot simulations.
+//!
+//! Tissues, ceilings and GF99 are computed at every sample, but a full TTS
+//! or NDL search costs far more than a tissue update. At fine sample
+//! intervals the searches run only at anchor samples and are interpolated
+//! linearly in time between them:
+//!
+//! 1. The first and last samples are anchors.
+//! 2. A sample is an anchor once `interval_sec` has passed since the last one.
+//! 3. Where the ceiling appears or clears, or the gas changes, the samples on
+//!    both sides are anchors, so interpolation never spans a jump between
+//!    NDL and TTS or between planners' gas choices.
+//! 4. Where neighbouring anchors differ by more than `REFINE_STEP_SEC`, the
+//!    middle sample is searched too, recursively, so steep stretches (e.g.
+//!    leaving the NDL cap on descent) are resolved rather than smoothed.
+//!
+//! Live sessions compute every sample: interpolation needs the next anchor.
+
+use super::progress::Progress;
+use super::types::*;
+use crate::metrics::SampleInput;
+
+/// Default TTS/NDL cadence (seconds).
+///
+/// Against a search at every sample this keeps TTS within 60 s and NDL
+/// within 10 s on 2-second profiles (see the tests), at about a third of
+/// the cost.
+pub(crate) const DEFAULT_TTS_INTERVAL_SEC: i32 = 10;
+
+/// Largest TTS or NDL difference between neighbouring anchors that is
+/// interpolated; wider gaps are split with a search at the middle sample.
+const REFINE_STEP_SEC: i32 = 30;
+
+/// A deco engine seen one sample at a time.
+pub(crate) trait SampleStepper {
+    type State: Clone;
+
+    /// Advance `state` from `prev` to `sample` and compute the sample's
+    /// point, leaving `tts_sec` and `ndl_sec` at 0.
+    fn advance(
+        &self,
+        state: &mut Self::State,
+        prev: Option<&SampleInput>,
+        sample: &SampleInput,
+    ) -> DecoSimPoint;
+
+    /// Fill `tts_sec` and `ndl_sec` of the point `advance` just returned.
+    fn surface_times(
+        &self,
+        state: &mut Self::State,
+        sample: &SampleInput,
+        point: &mut DecoSimPoint,
+    );
+
+    /// Advance `state` from `prev` to `sample` and compute the sample's point.
+    fn step(
+        &self,
+        state: &mut Self::State,
+        prev: Option<&SampleInput>,
+        sample: &SampleInput,
+    ) -> DecoSimPoint {
+        let mut point = self.advance(state, prev, sample);
+        self.surface_times(state, sample, &mut point);
+        point
+    }
+}
+
+/// Validate `tts_interval_sec` and apply the default.
+pub(crate) fn tts_interval(params: &DecoSimParams) -> Result<i32, DecoSimError> {
+    match params.tts_interval_sec {
+        Some(interval) if interval < 0 => Err(DecoSimError::InvalidParam {
+            msg: format!("tts_interval_sec ({interval}) must be >= 0"),
+        }),
+        Some(interval) => Ok(interval),
+        None => Ok(DEFAULT_TTS_INTERVAL_SEC),
+    }
+}
+
+/// Points for all samples, with TTS/NDL searched every `interval_sec`
+/// (0 = every sample) and interpolated in between.
+///
+/// Checks for cancellation and reports progress about once per percent of
+/// the samples.
+pub(crate) fn simulate_points<S: SampleStepper>(
+    stepper: &S,
+    state: &mut S::State,
+    samples: &[SampleInput],
+    interval_sec: i32,
+    progress: Progress,
+) -> Result<Vec<DecoSimPoint>, DecoSimError> {
+    let stride = (samples.len() / 100).max(1);
+    let mut points: Vec<DecoSimPoint> = Vec::with_capacity(samples.len());
+    // States after `advance` for the samples since the last anchor, so a
+    // stretch can be refined or closed without replaying it.
+    let mut pending: Vec<S::State> = Vec::new();
+    let mut last_anchor: Option<usize> = None;
+
+    for (idx, sample) in samples.iter().enumerate() {
+        if idx % stride == 0 {
+            progress.step(idx as f64 / samples.len() as f64)?;
+        }
+        let prev = idx.checked_sub(1).map(|i| &samples[i]);
+        let mut point = stepper.advance(state, prev, sample);
+        if interval_sec <= 0 {
+            stepper.surface_times(state, sample, &mut point);
+            points.push(point);
+            continue;
+        }
+
+        let regime_change = prev.is_some_and(|prev| {
+            let prev_point = &points[idx - 1];
+            (point.ceiling_m > 0.0) != (prev_point.ceiling_m > 0.0)
+                || sample.gasmix_index != prev.gasmix_index
+        });
+        if regime_change && last_anchor.is_some_and(|a| a + 1 < idx) {
+            // Close the stretch before the change with a full search.
+            let mut before = pending.pop().expect("state for the previous sample");
+            stepper.surface_times(&mut before, &samples[idx - 1], &mut points[idx - 1]);
+            close_stretch(
+                stepper,
+                &mut pending,
+                samples,
+                &mut points,
+                last_anchor,
+                idx - 1,
+            );
+            last_anchor = Some(idx - 1);
+        }
+
+        let due = last_anchor.is_none_or(|a| sample.t_sec - samples[a].t_sec >= interval_sec);
+        let is_last = idx + 1 == samples.len();
+        points.push(point);
+        if regime_change || due || is_last {
+            stepper.surface_times(state, sample, &mut points[idx]);
+            close_stretch(
+                stepper,
+                &mut pending,
+                samples,
+                &mut points,
+                last_anchor,
+                idx,
+            );
+            last_anchor = Some(idx);
+        } else {
+            pending.push(state.clone());
+        }
+    }
+    Ok(points)
+}
+
+/// Fill TTS/NDL between anchors `from` and `to` from the states in
+/// `pending` (one per sample strictly between them), then clear it.
+fn close_stretch<S: SampleStepper>(
+    stepper: &S,
+    pending: &mut Vec<S::State>,
+    samples: &[SampleInput],
+    points: &mut [DecoSimPoint],
+    from: Option<usize>,
+    to: usize,
+) {
+    if let Some(from) = from {
+        debug_assert_eq!(pending.len(), to - from - 1);
+        refine(stepper, pending, samples, points, from, to);
+    }
+    pending.clear();
+}
+
+/// Interpolate between anchors `from` and `to` where TTS and NDL agree
+/// closely; otherwise search the middle sample and recurse on both halves.
+fn refine<S: SampleStepper>(
+    stepper: &S,
+    pending: &mut [S::State],
+    samples: &[SampleInput],
+    points: &mut [DecoSimPoint],
+    from: usize,
+    to: usize,
+) {
+    if to - from < 2 {
+        return;
+    }
+    let (a, b) = (&points[from], &points[to]);
+    if (a.tts_sec - b.tts_sec).abs() <= REFINE_STEP_SEC
+        && (a.ndl_sec - b.ndl_sec).abs() <= REFINE_STEP_SEC
+    {
+        interpolate(points, from, to);
+        return;
+    }
+    let mid = from + (to - from) / 2;
+    // `pending[0]` belongs to sample `from + 1` of the outermost stretch;
+    // each recursion passes the matching sub-slice.
+    let (left, rest) = pending.split_at_mut(mid - from - 1);
+    let (mid_state, right) = rest.split_first_mut().expect("state for the middle sample");
+    stepper.surface_times(mid_state, &samples[mid], &mut points[mid]);
+    refine(stepper, left, samples, points, from, mid);
+    refine(stepper, right, samples, points, mid, to);
+}
+
+/// Fill TTS/NDL of the points strictly between anchors `from` and `to`.
+fn interpolate(points: &mut [DecoSimPoint], from: usize, to: usize) {
+    let (a, b) = (points[from].clone(), points[to].clone());
+    let span = (b.t_sec - a.t_sec) as f64;
+    for point in &mut points[from + 1..to] {
+        let frac = if span > 0.0 {
+            (point.t_sec - a.t_sec) as f64 / span
+        } else {
+            0.0
+        };
+        let lerp = |x: i32, y: i32| (x as f64 + (y - x) as f64 * frac).round() as i32;
+        point.tts_sec = lerp(a.tts_sec, b.tts_sec);
+        point.ndl_sec = lerp(a.ndl_sec, b.ndl_sec);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deco::compute_deco_simulation;
+    use crate::deco::profile_generator::{generate_dive_profile, GasSwitchPlan, ProfileGenParams};
+    use crate::GasMixInput;
+
+    /// Documented TTS tolerance of the default cadence (seconds).
+    const CADENCE_TTS_TOLERANCE_SEC: i32 = 60;
+
+    /// Documented NDL tolerance of the default cadence (seconds).
+    const CADENCE_NDL_TOLERANCE_SEC: i32 = 10;
+
+    fn gas(mix_index: i32, o2: f64, he: f64, switch_depth_m: Option<f64>) -> GasSwitchPlan {
+        GasSwitchPlan {
+            gas: GasMixInput {
+                mix_index,
+                o2_fraction: o2,
+                he_fraction: he,
+            },
+            switch_depth_m,
+            descent_switch_depth_m: None,
+        }
+    }
+
+    /// A 2-second trimix deco dive: 60 m / 30 min with EAN50 and O2.
+    fn fine_dive(model: DecoModel, tts_interval_sec: Option<i32>) -> DecoSimParams {
+        let profile = generate_dive_profile(ProfileGenParams {
+            target_depth_m: 60.0,
+            bottom_time_sec: 1800,
+            descent_rate_m_min: None,
+            ascent_rate_m_min: None,
+            gas_plan: vec![
+                gas(0, 0.18, 0.45, None),
+                gas(1, 0.50, 0.0, Some(21.0)),
+                gas(2, 1.0, 0.0, Some(6.0)),
+            ],
+            model,
+            surface_pressure_bar: None,
+            gf_low: Some(30),
+            gf_high: Some(70),
+            last_stop_depth_m: None,
+            stop_interval_m: None,
+            setpoint_ppo2: None,
+            thalmann_pdcs: None,
+            sample_interval_sec: Some(2),
+            temp_c: None,
+            gas_switch_pause_sec: None,
+            max_ppo2: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            setpoint_schedule: None,
+            scr: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            safety_stop: None,
+            final_ascent_rate_m_min: None,
+            surface_tail_sec: None,
+        })
+        .unwrap();
+        DecoSimParams {
+            model,
+            samples: profile.samples,
+            gas_mixes: profile.gas_mixes,
+            surface_pressure_bar: None,
+            ascent_rate_m_min: None,
+            last_stop_depth_m: None,
+            stop_interval_m: None,
+            gf_low: Some(30),
+            gf_high: Some(70),
+            thalmann_pdcs: None,
+            plan_ascent: false,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec,
+        }
+    }
+
+    fn assert_within_tolerance(model: DecoModel) {
+        let exact = compute_deco_simulation(fine_dive(model, Some(0))).unwrap();
+        let cadenced = compute_deco_simulation(fine_dive(model, None)).unwrap();
+        assert_eq!(exact.points.len(), cadenced.points.len());
+        for (e, c) in exact.points.iter().zip(&cadenced.points) {
+            assert_eq!(e.t_sec, c.t_sec);
+            assert_eq!(
+                e.ceiling_m, c.ceiling_m,
+                "tissues are exact at t={}",
+                e.t_sec
+            );
+            assert_eq!(e.gf99, c.gf99, "tissues are exact at t={}", e.t_sec);
+            assert!(
+                (e.tts_sec - c.tts_sec).abs() <= CADENCE_TTS_TOLERANCE_SEC,
+                "TTS at t={}: exact {} vs cadenced {}",
+                e.t_sec,
+                e.tts_sec,
+                c.tts_sec
+            );
+            assert!(
+                (e.ndl_sec - c.ndl_sec).abs() <= CADENCE_NDL_TOLERANCE_SEC,
+                "NDL at t={}: exact {} vs cadenced {}",
+                e.t_sec,
+                e.ndl_sec,
+                c.ndl_sec
+            );
+        }
+        assert!(
+            (exact.max_tts_sec - cadenced.max_tts_sec).abs() <= CADENCE_TTS_TOLERANCE_SEC,
+            "max TTS: exact {} vs cadenced {}",
+            exact.max_tts_sec,
+            cadenced.max_tts_sec
+        );
+    }
+
+    #[test]
+    fn test_default_cadence_within_tolerance_buhlmann() {
+        assert_within_tolerance(DecoModel::BuhlmannZhl16c);
+    }
+
+    #[test]
+    fn test_default_cadence_within_tolerance_thalmann() {
+        assert_within_tolerance(DecoModel::ThalmannElDca);
+    }
+
+    #[test]
+    fn test_anchors_are_exact() {
+        // Interpolation never touches the first, last or gas-switch samples.
+        let exact = compute_deco_simulation(fine_dive(DecoModel::BuhlmannZhl16c, Some(0))).unwrap();
+        let params = fine_dive(DecoModel::BuhlmannZhl16c, Some(30));
+        let switches: Vec<usize> = params
+            .samples
+            .windows(2)
+            .enumerate()
+            .filter(|(_, w)| w[0].gasmix_index != w[1].gasmix_index)
+            .flat_map(|(i, _)| [i, i + 1])
+            .collect();
+        assert!(!switches.is_empty());
+        let cadenced = compute_deco_simulation(params).unwrap();
+        let last = exact.points.len() - 1;
+        for idx in [0, last].into_iter().chain(switches) {
+            assert_eq!(
+                exact.points[idx].tts_sec, cadenced.points[idx].tts_sec,
+                "idx {idx}"
+            );
+            assert_eq!(
+                exact.points[idx].ndl_sec, cadenced.points[idx].ndl_sec,
+                "idx {idx}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_negative_interval_rejected() {
+        let err =
+            compute_deco_simulation(fine_dive(DecoModel::BuhlmannZhl16c, Some(-5))).unwrap_err();
+        assert!(matches!(err, DecoSimError::InvalidParam { .. }));
+        let err =
+            compute_deco_simulation(fine_dive(DecoModel::ThalmannElDca, Some(-1))).unwrap_err();
+        assert!(matches!(err, DecoSimError::InvalidParam { .. }));
+    }
+
+    #[test]
+    fn test_default_interval_applied() {
+        let params = fine_dive(DecoModel::BuhlmannZhl16c, None);
+        assert_eq!(tts_interval(&params).unwrap(), DEFAULT_TTS_INTERVAL_SEC);
+    }
+}
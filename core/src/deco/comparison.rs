@@ -0,0 +1,529 @@
+//! Side-by-side comparison of deco models on one profile.
+//!
+//! The profile and shared settings are validated once and kept in a single
+//! `DecoSimParams`; each configuration only swaps the model settings before
+//! the engine runs. Repeated configurations reuse the earlier run. Every
+//! engine emits one point per sample, so the series are aligned by sample
+//! index.
+
+use super::progress::{ComputeControl, Progress};
+use super::types::*;
+use crate::buhlmann::{BuhlmannCoefficientSet, BuhlmannCoefficients};
+
+// ============================================================================
+// FFI Types
+// ============================================================================
+
+/// One model configuration to compare.
+///
+/// Settings left `None` are taken from `ModelComparisonParams.base`. A
+/// custom payload is used only while the selected set is `Custom`: a
+/// configuration that picks a built-in set drops the base's payload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelConfig {
+    /// Decompression model.
+    pub model: DecoModel,
+    /// Gradient factor low (Bühlmann only, 0–100).
+    pub gf_low: Option<u8>,
+    /// Gradient factor high (Bühlmann only, 0–100).
+    pub gf_high: Option<u8>,
+    /// Thalmann parameter set (Thalmann only).
+    pub thalmann_pdcs: Option<ThalmannPdcs>,
+    /// ZHL-16 coefficient set (Bühlmann only).
+    pub buhlmann_coefficients: Option<BuhlmannCoefficientSet>,
+    /// Coefficients for the `Custom` Bühlmann set.
+    pub custom_buhlmann_coefficients: Option<BuhlmannCoefficients>,
+    /// Parameters for the `Custom` Thalmann set.
+    pub custom_thalmann_params: Option<ThalmannParameters>,
+}
+
+/// Parameters for a model comparison.
+#[derive(Debug, Clone)]
+pub struct ModelComparisonParams {
+    /// Profile and shared settings. Its model settings are the defaults
+    /// that each configuration overrides.
+    pub base: DecoSimParams,
+    /// Configurations to run. The first is the reference for the deltas.
+    pub configs: Vec<ModelConfig>,
+}
+
+/// Per-point series and summary for one configuration.
+#[derive(Debug, Clone)]
+pub struct ModelRun {
+    /// The configuration that was run.
+    pub config: ModelConfig,
+    /// Ceiling per sample in metres.
+    pub ceiling_m: Vec<f32>,
+    /// GF99 (Bühlmann) or MPTT utilisation (Thalmann) per sample.
+    pub gf99: Vec<f32>,
+    /// TTS per sample in seconds.
+    pub tts_sec: Vec<i32>,
+    /// NDL per sample in seconds.
+    pub ndl_sec: Vec<i32>,
+    /// Maximum ceiling in metres.
+    pub max_ceiling_m: f32,
+    /// Maximum TTS in seconds.
+    pub max_tts_sec: i32,
+    /// Smallest NDL below the surface in seconds (0 = deco obligation).
+    pub min_ndl_sec: i32,
+    /// Planned deco time in seconds (0 unless `plan_ascent`).
+    pub total_deco_time_sec: i32,
+    /// Planned deco stops (empty unless `plan_ascent`).
+    pub deco_stops: Vec<DecoStop>,
+    /// True if the planner hit a safety limit.
+    pub truncated: bool,
+    /// `max_ceiling_m` minus the reference's.
+    pub max_ceiling_delta_m: f32,
+    /// `max_tts_sec` minus the reference's.
+    pub max_tts_delta_sec: i32,
+    /// `min_ndl_sec` minus the reference's.
+    pub min_ndl_delta_sec: i32,
+    /// `total_deco_time_sec` minus the reference's.
+    pub deco_time_delta_sec: i32,
+}
+
+/// Result of a model comparison.
+#[derive(Debug, Clone)]
+pub struct ModelComparisonResult {
+    /// Sample times shared by every series, in seconds.
+    pub t_sec: Vec<i32>,
+    /// Sample depths in metres.
+    pub depth_m: Vec<f32>,
+    /// One run per configuration, in input order.
+    pub runs: Vec<ModelRun>,
+}
+
+// ============================================================================
+// Public API
+// ============================================================================
+
+/// Run one profile under several model configurations.
+pub fn compare_models(
+    params: ModelComparisonParams,
+) -> Result<ModelComparisonResult, DecoSimError> {
+    compare(params, Progress::NONE)
+}
+
+/// `compare_models` with cancellation and progress reporting; each
+/// configuration gets an equal share of the progress range.
+pub fn compare_models_with_control(
+    params: ModelComparisonParams,
+    control: &ComputeControl,
+) -> Result<ModelComparisonResult, DecoSimError> {
+    let result = compare(params, control.progress())?;
+    control.finish();
+    Ok(result)
+}
+
+fn compare(
+    params: ModelComparisonParams,
+    progress: Progress,
+) -> Result<ModelComparisonResult, DecoSimError> {
+    let ModelComparisonParams { mut base, configs } = params;
+    if configs.is_empty() {
+        return Err(DecoSimError::InvalidParam {
+            msg: "configs must not be empty".to_string(),
+        });
+    }
+    super::validate_shared(&base)?;
+
+    let defaults = ModelConfig {
+        model: base.model,
+        gf_low: base.gf_low,
+        gf_high: base.gf_high,
+        thalmann_pdcs: base.thalmann_pdcs,
+        buhlmann_coefficients: base.buhlmann_coefficients,
+        custom_buhlmann_coefficients: base.custom_buhlmann_coefficients.take(),
+        custom_thalmann_params: base.custom_thalmann_params.take(),
+    };
+    let mut results: Vec<DecoSimResult> = Vec::with_capacity(configs.len());
+    for (n, config) in configs.iter().enumerate() {
+        if let Some(prev) = configs[..n].iter().position(|c| c == config) {
+            results.push(results[prev].clone());
+            continue;
+        }
+        config.over(&defaults).apply(&mut base);
+        super::validate_ascent_mode(&base)?;
+        results.push(super::dispatch(&base, progress.part(n, configs.len()))?);
+    }
+
+    let reference = summarize(&results[0]);
+    let runs = configs
+        .into_iter()
+        .zip(&results)
+        .map(|(config, result)| {
+            let summary = summarize(result);
+            ModelRun {
+                config,
+                ceiling_m: result.points.iter().map(|p| p.ceiling_m).collect(),
+                gf99: result.points.iter().map(|p| p.gf99).collect(),
+                tts_sec: result.points.iter().map(|p| p.tts_sec).collect(),
+                ndl_sec: result.points.iter().map(|p| p.ndl_sec).collect(),
+                max_ceiling_m: summary.max_ceiling_m,
+                max_tts_sec: summary.max_tts_sec,
+                min_ndl_sec: summary.min_ndl_sec,
+                total_deco_time_sec: summary.deco_time_sec,
+                deco_stops: result.deco_stops.clone(),
+                truncated: result.truncated,
+                max_ceiling_delta_m: summary.max_ceiling_m - reference.max_ceiling_m,
+                max_tts_delta_sec: summary.max_tts_sec - reference.max_tts_sec,
+                min_ndl_delta_sec: summary.min_ndl_sec - reference.min_ndl_sec,
+                deco_time_delta_sec: summary.deco_time_sec - reference.deco_time_sec,
+            }
+        })
+        .collect();
+
+    Ok(ModelComparisonResult {
+        t_sec: base.samples.iter().map(|s| s.t_sec).collect(),
+        depth_m: base.samples.iter().map(|s| s.depth_m).collect(),
+        runs,
+    })
+}
+
+// ============================================================================
+// Internal Helpers
+// ============================================================================
+
+impl ModelConfig {
+    /// This configuration with its unset settings taken from `base`.
+    fn over(&self, base: &ModelConfig) -> ModelConfig {
+        let buhlmann_coefficients = self.buhlmann_coefficients.or(base.buhlmann_coefficients);
+        let thalmann_pdcs = self.thalmann_pdcs.or(base.thalmann_pdcs);
+        ModelConfig {
+            model: self.model,
+            gf_low: self.gf_low.or(base.gf_low),
+            gf_high: self.gf_high.or(base.gf_high),
+            custom_buhlmann_coefficients: match buhlmann_coefficients {
+                Some(BuhlmannCoefficientSet::Custom) => self
+                    .custom_buhlmann_coefficients
+                    .clone()
+                    .or_else(|| base.custom_buhlmann_coefficients.clone()),
+                _ => self.custom_buhlmann_coefficients.clone(),
+            },
+            custom_thalmann_params: match thalmann_pdcs {
+                Some(ThalmannPdcs::Custom) => self
+                    .custom_thalmann_params
+                    .clone()
+                    .or_else(|| base.custom_thalmann_params.clone()),
+                _ => self.custom_thalmann_params.clone(),
+            },
+            buhlmann_coefficients,
+            thalmann_pdcs,
+        }
+    }
+
+    /// Write the model settings into `params`.
+    fn apply(self, params: &mut DecoSimParams) {
+        params.model = self.model;
+        params.gf_low = self.gf_low;
+        params.gf_high = self.gf_high;
+        params.thalmann_pdcs = self.thalmann_pdcs;
+        params.buhlmann_coefficients = self.buhlmann_coefficients;
+        params.custom_buhlmann_coefficients = self.custom_buhlmann_coefficients;
+        params.custom_thalmann_params = self.custom_thalmann_params;
+    }
+}
+
+struct Summary {
+    max_ceiling_m: f32,
+    max_tts_sec: i32,
+    min_ndl_sec: i32,
+    deco_time_sec: i32,
+}
+
+fn summarize(result: &DecoSimResult) -> Summary {
+    Summary {
+        max_ceiling_m: result.max_ceiling_m,
+        max_tts_sec: result.max_tts_sec,
+        min_ndl_sec: result
+            .points
+            .iter()
+            .filter(|p| p.depth_m > 0.0)
+            .map(|p| p.ndl_sec)
+            .min()
+            .unwrap_or(0),
+        deco_time_sec: result.total_deco_time_sec,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deco::compute_deco_simulation;
+    use crate::metrics::SampleInput;
+
+    fn sample(t_sec: i32, depth_m: f32) -> SampleInput {
+        SampleInput {
+            t_sec,
+            depth_m,
+            temp_c: 20.0,
+            setpoint_ppo2: None,
+            ceiling_m: None,
+            gf99: None,
+            gasmix_index: None,
+            ppo2: None,
+            tts_sec: None,
+            ndl_sec: None,
+            deco_stop_depth_m: None,
+            at_plus_five_tts_min: None,
+        }
+    }
+
+    fn base(plan_ascent: bool) -> DecoSimParams {
+        // 40 m for 25 min on air
+        DecoSimParams {
+            model: DecoModel::BuhlmannZhl16c,
+            samples: vec![sample(0, 0.0), sample(140, 40.0), sample(1500, 40.0)],
+            gas_mixes: vec![],
+            surface_pressure_bar: None,
+            ascent_rate_m_min: None,
+            last_stop_depth_m: None,
+            stop_interval_m: None,
+            gf_low: None,
+            gf_high: None,
+            thalmann_pdcs: None,
+            plan_ascent,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
+        }
+    }
+
+    fn buhlmann(gf_low: u8, gf_high: u8) -> ModelConfig {
+        ModelConfig {
+            model: DecoModel::BuhlmannZhl16c,
+            gf_low: Some(gf_low),
+            gf_high: Some(gf_high),
+            thalmann_pdcs: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+        }
+    }
+
+    fn thalmann(pdcs: ThalmannPdcs) -> ModelConfig {
+        ModelConfig {
+            model: DecoModel::ThalmannElDca,
+            gf_low: None,
+            gf_high: None,
+            thalmann_pdcs: Some(pdcs),
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+        }
+    }
+
+    /// XVal-He-9_023 with every surfacing M-value lowered by 10%.
+    fn conservative_thalmann() -> ThalmannParameters {
+        ThalmannParameters {
+            half_times_min: vec![10.0, 20.0, 20.0, 120.0, 210.0],
+            sdr: vec![1.0, 2.0, 0.67, 1.0, 1.0],
+            m0_fsw: vec![76.5, 57.6, 74.7, 37.558, 30.749],
+            beta1: vec![1.0, 1.0, 1.0, 2.0, 1.0],
+            pbovp_fsw: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_runs_match_individual_simulations() {
+        let configs = vec![
+            buhlmann(30, 70),
+            buhlmann(50, 85),
+            thalmann(ThalmannPdcs::Pdcs23),
+            thalmann(ThalmannPdcs::Pdcs50),
+        ];
+        let result = compare_models(ModelComparisonParams {
+            base: base(true),
+            configs: configs.clone(),
+        })
+        .unwrap();
+        assert_eq!(result.t_sec, vec![0, 140, 1500]);
+        assert_eq!(result.runs.len(), configs.len());
+
+        for (run, config) in result.runs.iter().zip(&configs) {
+            let mut params = base(true);
+            params.model = config.model;
+            params.gf_low = config.gf_low;
+            params.gf_high = config.gf_high;
+            params.thalmann_pdcs = config.thalmann_pdcs;
+            let single = compute_deco_simulation(params).unwrap();
+            assert_eq!(&run.config, config);
+            assert_eq!(run.ceiling_m.len(), result.t_sec.len());
+            assert_eq!(
+                run.tts_sec,
+                single.points.iter().map(|p| p.tts_sec).collect::<Vec<_>>()
+            );
+            assert_eq!(run.max_ceiling_m, single.max_ceiling_m);
+            assert_eq!(run.total_deco_time_sec, single.total_deco_time_sec);
+        }
+    }
+
+    #[test]
+    fn test_deltas_against_reference() {
+        let result = compare_models(ModelComparisonParams {
+            base: base(true),
+            configs: vec![
+                buhlmann(50, 85),
+                buhlmann(30, 70),
+                thalmann(ThalmannPdcs::Pdcs23),
+                thalmann(ThalmannPdcs::Pdcs50),
+            ],
+        })
+        .unwrap();
+        let reference = &result.runs[0];
+        assert_eq!(reference.max_tts_delta_sec, 0);
+        assert_eq!(reference.deco_time_delta_sec, 0);
+
+        // Lower gradient factors are more conservative
+        let conservative = &result.runs[1];
+        assert!(conservative.deco_time_delta_sec > 0);
+        assert_eq!(
+            conservative.deco_time_delta_sec,
+            conservative.total_deco_time_sec - reference.total_deco_time_sec
+        );
+        assert!(conservative.max_ceiling_delta_m >= 0.0);
+
+        // The P_DCS sets differ only in the slowest compartment, which does
+        // not control this dive's stops
+        assert!(result.runs[2].total_deco_time_sec >= result.runs[3].total_deco_time_sec);
+        assert!(result.runs[2].total_deco_time_sec > 0);
+    }
+
+    #[test]
+    fn test_ndl_summary_without_deco() {
+        let mut params = base(false);
+        params.samples = vec![sample(0, 0.0), sample(60, 18.0), sample(1200, 18.0)];
+        let result = compare_models(ModelComparisonParams {
+            base: params,
+            configs: vec![buhlmann(100, 100), buhlmann(40, 70)],
+        })
+        .unwrap();
+        let (lenient, strict) = (&result.runs[0], &result.runs[1]);
+        assert!(lenient.min_ndl_sec > 0);
+        assert!(strict.min_ndl_sec < lenient.min_ndl_sec);
+        assert_eq!(
+            strict.min_ndl_delta_sec,
+            strict.min_ndl_sec - lenient.min_ndl_sec
+        );
+        assert_eq!(lenient.deco_stops.len(), 0);
+    }
+
+    #[test]
+    fn test_repeated_config_reused() {
+        let result = compare_models(ModelComparisonParams {
+            base: base(true),
+            configs: vec![
+                buhlmann(50, 85),
+                thalmann(ThalmannPdcs::Pdcs40),
+                buhlmann(50, 85),
+            ],
+        })
+        .unwrap();
+        assert_eq!(result.runs[0].tts_sec, result.runs[2].tts_sec);
+        assert_eq!(result.runs[2].max_tts_delta_sec, 0);
+    }
+
+    #[test]
+    fn test_config_overrides_base_settings() {
+        // Unset configuration settings come from the base
+        let mut gf_base = base(true);
+        gf_base.gf_low = Some(30);
+        gf_base.gf_high = Some(70);
+        let inherit = ModelConfig {
+            gf_low: None,
+            gf_high: None,
+            ..buhlmann(0, 0)
+        };
+        let result = compare_models(ModelComparisonParams {
+            base: gf_base,
+            configs: vec![buhlmann(30, 70), inherit, buhlmann(50, 85)],
+        })
+        .unwrap();
+        assert_eq!(result.runs[0].tts_sec, result.runs[1].tts_sec);
+        assert_ne!(result.runs[0].tts_sec, result.runs[2].tts_sec);
+
+        // A base custom Thalmann set is used by configurations that leave
+        // the set unset, and dropped by those that pick a built-in set
+        let mut custom_base = base(true);
+        custom_base.thalmann_pdcs = Some(ThalmannPdcs::Custom);
+        custom_base.custom_thalmann_params = Some(conservative_thalmann());
+        let inherit_custom = ModelConfig {
+            thalmann_pdcs: None,
+            ..thalmann(ThalmannPdcs::Pdcs23)
+        };
+        let result = compare_models(ModelComparisonParams {
+            base: custom_base,
+            configs: vec![thalmann(ThalmannPdcs::Pdcs23), inherit_custom],
+        })
+        .unwrap();
+        let (builtin, custom) = (&result.runs[0], &result.runs[1]);
+        assert!(custom.total_deco_time_sec > builtin.total_deco_time_sec);
+
+        // Configurations may carry their own coefficient and parameter sets
+        let mut params = base(true);
+        params.model = DecoModel::ThalmannElDca;
+        params.thalmann_pdcs = Some(ThalmannPdcs::Custom);
+        params.custom_thalmann_params = Some(conservative_thalmann());
+        let single = compute_deco_simulation(params).unwrap();
+        let result = compare_models(ModelComparisonParams {
+            base: base(true),
+            configs: vec![
+                ModelConfig {
+                    buhlmann_coefficients: Some(BuhlmannCoefficientSet::Zhl16b),
+                    ..buhlmann(50, 85)
+                },
+                buhlmann(50, 85),
+                ModelConfig {
+                    custom_thalmann_params: Some(conservative_thalmann()),
+                    ..thalmann(ThalmannPdcs::Custom)
+                },
+            ],
+        })
+        .unwrap();
+        // ZHL-16C lowers ZHL-16B's M-values
+        assert!(result.runs[0].total_deco_time_sec < result.runs[1].total_deco_time_sec);
+        assert_eq!(
+            result.runs[2].total_deco_time_sec,
+            single.total_deco_time_sec
+        );
+    }
+
+    #[test]
+    fn test_invalid_comparison_rejected() {
+        let empty = ModelComparisonParams {
+            base: base(true),
+            configs: vec![],
+        };
+        assert!(compare_models(empty).is_err());
+
+        let mut no_samples = base(true);
+        no_samples.samples.clear();
+        let result = compare_models(ModelComparisonParams {
+            base: no_samples,
+            configs: vec![buhlmann(50, 85)],
+        });
+        assert!(matches!(result, Err(DecoSimError::EmptySamples { .. })));
+
+        let bad_gf = ModelComparisonParams {
+            base: base(true),
+            configs: vec![buhlmann(50, 85), buhlmann(90, 40)],
+        };
+        assert!(compare_models(bad_gf).is_err());
+
+        // Continuous ascent is Bühlmann-only, checked per configuration
+        let mut continuous = base(true);
+        continuous.ascent_mode = Some(AscentMode::Continuous);
+        let result = compare_models(ModelComparisonParams {
+            base: continuous,
+            configs: vec![buhlmann(50, 85), thalmann(ThalmannPdcs::Pdcs23)],
+        });
+        assert!(result.is_err());
+    }
+}
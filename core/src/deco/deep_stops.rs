@@ -0,0 +1,217 @@
+//! Deep stops layered on top of the model planners.
+//!
+//! The engine plans a baseline ascent to find the first required stop, then
+//! the deep stops are flown in the tissue simulation and the required stops
+//! are re-planned from the last deep stop. Comparing the two plans gives the
+//! change in required (shallow) stop time the deep stops cost.
+
+use super::types::{DecoSimError, DecoStop, DeepStopParams, DeepStopStrategy};
+
+/// Default deep stop duration (Pyle's two minutes).
+const DEFAULT_DEEP_STOP_SEC: i32 = 120;
+
+/// Planned ascent with deep stops merged in.
+pub(crate) struct DeepStopPlan {
+    pub(crate) stops: Vec<DecoStop>,
+    pub(crate) truncated: bool,
+    pub(crate) deep_stop_time_sec: i32,
+    pub(crate) penalty_sec: i32,
+}
+
+pub(crate) fn validate_deep_stops(params: &DeepStopParams) -> Result<(), DecoSimError> {
+    if let Some(d) = params.stop_duration_sec {
+        if d <= 0 {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!("deep stop_duration_sec ({d}) must be > 0"),
+            });
+        }
+    }
+    if params.strategy == DeepStopStrategy::Custom {
+        if params.custom_fractions.is_empty() {
+            return Err(DecoSimError::InvalidParam {
+                msg: "custom deep stops need at least one fraction".to_string(),
+            });
+        }
+        if let Some(f) = params
+            .custom_fractions
+            .iter()
+            .find(|f| !(**f > 0.0 && **f < 1.0))
+        {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!("deep stop fraction ({f}) must be between 0 and 1"),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Deep stop depths (deepest first) strictly between the starting depth and
+/// the first required stop.
+pub(crate) fn deep_stop_depths(
+    params: &DeepStopParams,
+    start_depth_m: f64,
+    first_stop_m: f64,
+    stop_interval: f64,
+) -> Vec<f64> {
+    let to_grid = |d: f64| (d / stop_interval).round() * stop_interval;
+    let mut depths = Vec::new();
+    match params.strategy {
+        DeepStopStrategy::Pyle => {
+            let mut prev = start_depth_m;
+            while prev - first_stop_m > 2.0 * stop_interval {
+                let next = to_grid((prev + first_stop_m) / 2.0);
+                if next >= prev || next <= first_stop_m {
+                    break;
+                }
+                depths.push(next);
+                prev = next;
+            }
+        }
+        DeepStopStrategy::Custom => {
+            depths = params
+                .custom_fractions
+                .iter()
+                .map(|f| to_grid(start_depth_m * f))
+                .filter(|&d| d > first_stop_m && d < start_depth_m)
+                .collect();
+            depths.sort_by(|a, b| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+            depths.dedup();
+        }
+    }
+    depths
+}
+
+/// Plan an ascent with deep stops.
+///
+/// `plan` is the engine planner from a tissue state at a depth; `hold`
+/// ascends (with gas switches) to a depth and stays there for the given
+/// seconds. Without a required stop in the baseline, no deep stops are added.
+pub(crate) fn plan_with_deep_stops<T: Clone>(
+    tissues: &T,
+    start_depth_m: f64,
+    stop_interval: f64,
+    params: &DeepStopParams,
+    plan: impl Fn(&T, f64) -> (Vec<DecoStop>, bool),
+    hold: impl Fn(&mut T, &mut f64, f64, f64),
+) -> DeepStopPlan {
+    let (baseline, baseline_truncated) = plan(tissues, start_depth_m);
+    let Some(first_stop) = baseline.first().map(|s| s.depth_m as f64) else {
+        return DeepStopPlan {
+            stops: baseline,
+            truncated: baseline_truncated,
+            deep_stop_time_sec: 0,
+            penalty_sec: 0,
+        };
+    };
+    let baseline_sec: i32 = baseline.iter().map(|s| s.duration_sec).sum();
+
+    let duration = params.stop_duration_sec.unwrap_or(DEFAULT_DEEP_STOP_SEC);
+    let depths = deep_stop_depths(params, start_depth_m, first_stop, stop_interval);
+
+    let mut state = tissues.clone();
+    let mut depth = start_depth_m;
+    let mut stops: Vec<DecoStop> = Vec::with_capacity(depths.len() + baseline.len());
+    for &d in &depths {
+        hold(&mut state, &mut depth, d, duration as f64);
+        stops.push(DecoStop {
+            depth_m: d as f32,
+            duration_sec: duration,
+            gas_mix_index: -1,
+            is_deep_stop: true,
+        });
+    }
+
+    let (required, truncated) = plan(&state, depth);
+    let required_sec: i32 = required.iter().map(|s| s.duration_sec).sum();
+    stops.extend(required);
+
+    DeepStopPlan {
+        stops,
+        truncated,
+        deep_stop_time_sec: duration * depths.len() as i32,
+        penalty_sec: required_sec - baseline_sec,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pyle() -> DeepStopParams {
+        DeepStopParams {
+            strategy: DeepStopStrategy::Pyle,
+            stop_duration_sec: None,
+            custom_fractions: vec![],
+        }
+    }
+
+    #[test]
+    fn test_pyle_depths() {
+        // 60 m with a first stop at 21 m: 40.5→42, 31.5→33, 27
+        assert_eq!(
+            deep_stop_depths(&pyle(), 60.0, 21.0, 3.0),
+            vec![42.0, 33.0, 27.0]
+        );
+        // Gap of two stop intervals or less: no deep stops
+        assert!(deep_stop_depths(&pyle(), 27.0, 21.0, 3.0).is_empty());
+    }
+
+    #[test]
+    fn test_custom_depths() {
+        let params = DeepStopParams {
+            strategy: DeepStopStrategy::Custom,
+            stop_duration_sec: Some(60),
+            custom_fractions: vec![0.5, 0.75, 0.2, 0.5],
+        };
+        // 0.2 × 60 = 12 m is shallower than the first stop and dropped
+        assert_eq!(deep_stop_depths(&params, 60.0, 21.0, 3.0), vec![45.0, 30.0]);
+    }
+
+    #[test]
+    fn test_validate_deep_stops() {
+        assert!(validate_deep_stops(&pyle()).is_ok());
+        let mut p = pyle();
+        p.stop_duration_sec = Some(0);
+        assert!(validate_deep_stops(&p).is_err());
+        let mut p = pyle();
+        p.strategy = DeepStopStrategy::Custom;
+        assert!(validate_deep_stops(&p).is_err());
+        p.custom_fractions = vec![1.2];
+        assert!(validate_deep_stops(&p).is_err());
+        p.custom_fractions = vec![0.5];
+        assert!(validate_deep_stops(&p).is_ok());
+    }
+
+    #[test]
+    fn test_plan_with_deep_stops_merges_and_reports_penalty() {
+        // Toy "tissue": seconds of required deco, which deep stops add to.
+        let plan = |load: &f64, _depth: f64| {
+            let stop = DecoStop {
+                depth_m: 21.0,
+                duration_sec: *load as i32,
+                gas_mix_index: -1,
+                is_deep_stop: false,
+            };
+            (vec![stop], false)
+        };
+        let hold = |load: &mut f64, depth: &mut f64, to: f64, secs: f64| {
+            *depth = to;
+            *load += secs / 2.0;
+        };
+        let result = plan_with_deep_stops(&600.0, 60.0, 3.0, &pyle(), plan, hold);
+        let deep: Vec<_> = result.stops.iter().filter(|s| s.is_deep_stop).collect();
+        assert_eq!(deep.len(), 3);
+        assert_eq!(result.deep_stop_time_sec, 360);
+        assert_eq!(result.penalty_sec, 180);
+        assert_eq!(result.stops.last().unwrap().duration_sec, 780);
+    }
+
+    #[test]
+    fn test_no_deep_stops_without_obligation() {
+        let plan = |_: &f64, _: f64| (Vec::new(), false);
+        let hold = |_: &mut f64, _: &mut f64, _: f64, _: f64| panic!("no hold expected");
+        let result = plan_with_deep_stops(&0.0, 30.0, 3.0, &pyle(), plan, hold);
+        assert!(result.stops.is_empty());
+        assert_eq!(result.penalty_sec, 0);
+    }
+}
@@ -0,0 +1,811 @@
+//! Multi-dive day planner.
+//!
+//! Plans an ordered list of dives separated by surface intervals. Each dive
+//! is generated by the profile generator with every earlier dive (and the
+//! surface intervals, breathed on air) replayed ahead of it, so residual
+//! inert gas carries over. Each dive is also planned on clean tissues to
+//! flag obligations that only the residual gas creates.
+//!
+//! The minimum surface interval search replays completed dives the same way
+//! and bisects the interval before the next dive, relying on residual gas
+//! only shrinking with time at the surface.
+
+use std::cell::Cell;
+
+use super::profile_generator::{self, PriorDives, ProfileGenParams, ProfileGenResult};
+use super::progress::{ComputeControl, Progress};
+use super::shared::AIR_FO2;
+use super::types::*;
+use crate::buhlmann::GasMixInput;
+use crate::metrics::SampleInput;
+
+// ============================================================================
+// FFI Types
+// ============================================================================
+
+/// One dive of the day and the surface interval before it.
+#[derive(Debug, Clone)]
+pub struct PlannedDive {
+    /// Profile of the dive, as for `generate_dive_profile`.
+    pub dive: ProfileGenParams,
+    /// Surface interval before this dive in seconds. Ignored for the first dive.
+    pub surface_interval_sec: i32,
+}
+
+/// Parameters for planning a dive day.
+#[derive(Debug, Clone)]
+pub struct DiveDayParams {
+    /// Dives in order. All must share the deco model and surface pressure.
+    pub dives: Vec<PlannedDive>,
+}
+
+/// A planned dive within the day.
+#[derive(Debug, Clone)]
+pub struct DiveDayDive {
+    /// Profile, deco overlay and planned stops, with residual gas carried over.
+    /// Times are relative to the start of this dive.
+    pub profile: ProfileGenResult,
+    /// Start of the dive in seconds from the start of the first dive.
+    pub start_t_sec: i32,
+    /// NDL when the ascent starts in seconds (0 = deco obligation).
+    pub bottom_ndl_sec: i32,
+    /// Planned stop time in seconds.
+    pub deco_time_sec: i32,
+    /// Planned stop time for the same dive on clean tissues in seconds.
+    pub standalone_deco_time_sec: i32,
+    /// True if the dive needs deco only because of residual gas.
+    pub deco_from_residual: bool,
+    /// True if the ascent plan hits a planner limit only because of residual gas.
+    pub truncated_from_residual: bool,
+}
+
+/// Result of planning a dive day.
+#[derive(Debug, Clone)]
+pub struct DiveDayResult {
+    /// The planned dives, in order.
+    pub dives: Vec<DiveDayDive>,
+    /// End of the last dive in seconds from the start of the first dive.
+    pub total_time_sec: i32,
+    /// Surface time after the last dive until the tissues are desaturated,
+    /// in seconds.
+    pub desaturation_time_sec: i32,
+}
+
+/// What the next dive must satisfy after the surface interval.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SurfaceIntervalTarget {
+    /// No planned deco stops.
+    NoDeco,
+    /// Planned stop time at most `max_deco_time_sec`.
+    MaxDecoTime,
+    /// Surface GF on surfacing at most `max_surface_gf`.
+    MaxSurfaceGf,
+}
+
+/// Parameters for the minimum surface interval search.
+#[derive(Debug, Clone)]
+pub struct MinSurfaceIntervalParams {
+    /// Completed dive(s) on one timeline, ending at the surface. A day of
+    /// dives includes its surface intervals.
+    pub previous_samples: Vec<SampleInput>,
+    /// Gas mixes referenced by `previous_samples`.
+    pub previous_gas_mixes: Vec<GasMixInput>,
+    /// The next planned dive.
+    pub next_dive: ProfileGenParams,
+    /// Condition the next dive must meet.
+    pub target: SurfaceIntervalTarget,
+    /// `MaxDecoTime` only: allowed planned stop time in seconds.
+    pub max_deco_time_sec: Option<i32>,
+    /// `MaxSurfaceGf` only: allowed surface GF (0–100+) when the next dive
+    /// surfaces.
+    pub max_surface_gf: Option<f64>,
+    /// Longest surface interval searched in seconds (default 24 h).
+    pub max_interval_sec: Option<i32>,
+}
+
+/// Result of the minimum surface interval search.
+#[derive(Debug, Clone)]
+pub struct MinSurfaceIntervalResult {
+    /// False if the target is not met within `max_interval_sec`.
+    pub achievable: bool,
+    /// Shortest surface interval in seconds, to the minute (0 if the next
+    /// dive can start straight away). The search limit if not achievable.
+    pub surface_interval_sec: i32,
+    /// The next dive planned after that interval (`None` if not achievable).
+    pub next_dive: Option<ProfileGenResult>,
+}
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+const DEFAULT_MAX_INTERVAL_SEC: i32 = 24 * 3600;
+
+// ============================================================================
+// Public API
+// ============================================================================
+
+/// Plan a dive day, carrying tissue loading from each dive into the next.
+pub fn plan_dive_day(params: &DiveDayParams) -> Result<DiveDayResult, DecoSimError> {
+    plan_day(params, Progress::NONE)
+}
+
+/// `plan_dive_day` with cancellation and progress reporting; each dive gets
+/// an equal share of the progress range.
+pub fn plan_dive_day_with_control(
+    params: &DiveDayParams,
+    control: &ComputeControl,
+) -> Result<DiveDayResult, DecoSimError> {
+    let result = plan_day(params, control.progress())?;
+    control.finish();
+    Ok(result)
+}
+
+fn plan_day(params: &DiveDayParams, progress: Progress) -> Result<DiveDayResult, DecoSimError> {
+    validate_params(params)?;
+
+    let mut prior = PriorDives::default();
+    let mut dives = Vec::with_capacity(params.dives.len());
+    for (n, planned) in params.dives.iter().enumerate() {
+        if n > 0 {
+            add_surface_interval(&mut prior, planned.surface_interval_sec);
+        }
+        let start_t_sec = prior.end_t_sec();
+        let share = progress.part(n, params.dives.len());
+        let profile =
+            profile_generator::generate_after(planned.dive.clone(), &prior, share.slice(0.0, 0.5))
+                .map_err(|e| for_dive(e, n))?;
+        let standalone = if n == 0 {
+            profile.clone()
+        } else {
+            profile_generator::generate_after(
+                planned.dive.clone(),
+                &PriorDives::default(),
+                share.slice(0.5, 1.0),
+            )
+            .map_err(|e| for_dive(e, n))?
+        };
+
+        let deco_time_sec = planned_stop_time(&profile);
+        let standalone_deco_time_sec = planned_stop_time(&standalone);
+        add_dive(
+            &mut prior,
+            &profile.samples,
+            &profile.gas_mixes,
+            start_t_sec,
+        );
+        dives.push(DiveDayDive {
+            bottom_ndl_sec: profile
+                .deco_result
+                .points
+                .iter()
+                .filter(|p| p.depth_m > 0.0 && p.t_sec <= profile.bottom_end_t_sec)
+                .map(|p| p.ndl_sec)
+                .min()
+                .unwrap_or(0),
+            start_t_sec,
+            deco_time_sec,
+            standalone_deco_time_sec,
+            deco_from_residual: deco_time_sec > 0 && standalone_deco_time_sec == 0,
+            truncated_from_residual: profile.truncated && !standalone.truncated,
+            profile,
+        });
+    }
+
+    let last = dives.last().expect("validated non-empty");
+    Ok(DiveDayResult {
+        total_time_sec: last.start_t_sec + last.profile.total_time_sec,
+        desaturation_time_sec: last.profile.deco_result.desaturation_time_sec,
+        dives,
+    })
+}
+
+/// Shortest surface interval after completed dives for which the next dive
+/// meets the target.
+pub fn min_surface_interval(
+    params: &MinSurfaceIntervalParams,
+) -> Result<MinSurfaceIntervalResult, DecoSimError> {
+    search_interval(params, Progress::NONE)
+}
+
+/// `min_surface_interval` with cancellation and progress reporting; each
+/// planned interval gets an equal share of the bisection's worst case.
+pub fn min_surface_interval_with_control(
+    params: &MinSurfaceIntervalParams,
+    control: &ComputeControl,
+) -> Result<MinSurfaceIntervalResult, DecoSimError> {
+    let result = search_interval(params, control.progress())?;
+    control.finish();
+    Ok(result)
+}
+
+fn search_interval(
+    params: &MinSurfaceIntervalParams,
+    progress: Progress,
+) -> Result<MinSurfaceIntervalResult, DecoSimError> {
+    validate_interval_params(params)?;
+    let max_min = (params.max_interval_sec.unwrap_or(DEFAULT_MAX_INTERVAL_SEC) + 59) / 60;
+    // Both ends plus at most ceil(log2(max_min)) bisection steps.
+    let max_plans = 3 + max_min.max(1).ilog2() as usize;
+    let plans = Cell::new(0);
+
+    let mut completed = PriorDives::default();
+    add_dive(
+        &mut completed,
+        &params.previous_samples,
+        &params.previous_gas_mixes,
+        0,
+    );
+    let plan_after = |minutes: i32| {
+        let mut prior = completed.clone();
+        add_surface_interval(&mut prior, minutes * 60);
+        let share = progress.part(plans.get(), max_plans);
+        plans.set(plans.get() + 1);
+        let profile = profile_generator::generate_after(params.next_dive.clone(), &prior, share)?;
+        let met = meets_target(params, &profile);
+        Ok::<_, DecoSimError>((met, profile))
+    };
+
+    let (met, profile) = plan_after(0)?;
+    if met {
+        return Ok(MinSurfaceIntervalResult {
+            achievable: true,
+            surface_interval_sec: 0,
+            next_dive: Some(profile),
+        });
+    }
+    let (met, mut best) = plan_after(max_min)?;
+    if !met {
+        return Ok(MinSurfaceIntervalResult {
+            achievable: false,
+            surface_interval_sec: max_min * 60,
+            next_dive: None,
+        });
+    }
+    // Invariant: the target fails after `lo` minutes and holds after `hi`
+    let (mut lo, mut hi) = (0, max_min);
+    while hi - lo > 1 {
+        let mid = (lo + hi) / 2;
+        let (met, profile) = plan_after(mid)?;
+        if met {
+            hi = mid;
+            best = profile;
+        } else {
+            lo = mid;
+        }
+    }
+    Ok(MinSurfaceIntervalResult {
+        achievable: true,
+        surface_interval_sec: hi * 60,
+        next_dive: Some(best),
+    })
+}
+
+// ============================================================================
+// Internal Helpers
+// ============================================================================
+
+fn validate_interval_params(params: &MinSurfaceIntervalParams) -> Result<(), DecoSimError> {
+    if params.previous_samples.is_empty() {
+        return Err(DecoSimError::EmptySamples {
+            msg: "No previous dive samples provided".to_string(),
+        });
+    }
+    if let Some(max) = params.max_interval_sec {
+        if max <= 0 {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!("max_interval_sec ({max}) must be > 0"),
+            });
+        }
+    }
+    if params.next_dive.scr.is_some() {
+        return Err(DecoSimError::InvalidParam {
+            msg: "SCR dives cannot be replayed after a surface interval".to_string(),
+        });
+    }
+    match params.target {
+        SurfaceIntervalTarget::NoDeco => Ok(()),
+        SurfaceIntervalTarget::MaxDecoTime => match params.max_deco_time_sec {
+            Some(t) if t >= 0 => Ok(()),
+            Some(t) => Err(DecoSimError::InvalidParam {
+                msg: format!("max_deco_time_sec ({t}) must be >= 0"),
+            }),
+            None => Err(DecoSimError::InvalidParam {
+                msg: "MaxDecoTime target requires max_deco_time_sec".to_string(),
+            }),
+        },
+        SurfaceIntervalTarget::MaxSurfaceGf => match params.max_surface_gf {
+            Some(gf) if gf > 0.0 => Ok(()),
+            Some(gf) => Err(DecoSimError::InvalidParam {
+                msg: format!("max_surface_gf ({gf}) must be > 0"),
+            }),
+            None => Err(DecoSimError::InvalidParam {
+                msg: "MaxSurfaceGf target requires max_surface_gf".to_string(),
+            }),
+        },
+    }
+}
+
+/// A truncated ascent plan never meets a target.
+fn meets_target(params: &MinSurfaceIntervalParams, profile: &ProfileGenResult) -> bool {
+    if profile.truncated {
+        return false;
+    }
+    match params.target {
+        SurfaceIntervalTarget::NoDeco => planned_stop_time(profile) == 0,
+        SurfaceIntervalTarget::MaxDecoTime => {
+            planned_stop_time(profile) <= params.max_deco_time_sec.unwrap_or(0)
+        }
+        SurfaceIntervalTarget::MaxSurfaceGf => profile
+            .deco_result
+            .points
+            .last()
+            .is_some_and(|p| p.surface_gf as f64 <= params.max_surface_gf.unwrap_or(0.0)),
+    }
+}
+
+fn validate_params(params: &DiveDayParams) -> Result<(), DecoSimError> {
+    let Some(first) = params.dives.first() else {
+        return Err(DecoSimError::InvalidParam {
+            msg: "dives must not be empty".to_string(),
+        });
+    };
+    for (n, planned) in params.dives.iter().enumerate() {
+        if n > 0 && planned.surface_interval_sec < 0 {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!(
+                    "dive {}: surface_interval_sec ({}) must be >= 0",
+                    n + 1,
+                    planned.surface_interval_sec
+                ),
+            });
+        }
+        if planned.dive.model != first.dive.model
+            || planned.dive.surface_pressure_bar != first.dive.surface_pressure_bar
+        {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!(
+                    "dive {}: all dives must share the deco model and surface pressure",
+                    n + 1
+                ),
+            });
+        }
+        // The SCR loop applies to every interval of a simulation, including
+        // the surface intervals, so it cannot be replayed across dives.
+        if planned.dive.scr.is_some() {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!("dive {}: SCR dives cannot be planned in a dive day", n + 1),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Prefix a dive's parameter errors with its 1-based position in the day.
+fn for_dive(err: DecoSimError, n: usize) -> DecoSimError {
+    match err {
+        DecoSimError::InvalidParam { msg } => DecoSimError::InvalidParam {
+            msg: format!("dive {}: {msg}", n + 1),
+        },
+        other => other,
+    }
+}
+
+fn planned_stop_time(profile: &ProfileGenResult) -> i32 {
+    profile.planned_stops.iter().map(|s| s.duration_sec).sum()
+}
+
+/// Append a dive to the history, on day time and with its gases after those
+/// already in the history. The diver breathes air from the dive's last
+/// sample onward.
+fn add_dive(
+    prior: &mut PriorDives,
+    samples: &[SampleInput],
+    gas_mixes: &[GasMixInput],
+    start_t_sec: i32,
+) {
+    let next_index = |mixes: &[GasMixInput]| mixes.iter().map(|g| g.mix_index + 1).max();
+    let shift = next_index(&prior.gas_mixes).unwrap_or(0);
+    let air_index = shift + next_index(gas_mixes).unwrap_or(0);
+    // Engines start on mix 0 (or air) until a sample names a gas
+    let start_gas = if gas_mixes.iter().any(|g| g.mix_index == 0) {
+        shift
+    } else {
+        air_index
+    };
+    prior
+        .gas_mixes
+        .extend(gas_mixes.iter().map(|g| GasMixInput {
+            mix_index: g.mix_index + shift,
+            ..g.clone()
+        }));
+    prior.gas_mixes.push(GasMixInput {
+        mix_index: air_index,
+        o2_fraction: AIR_FO2,
+        he_fraction: 0.0,
+    });
+    let first = prior.samples.len();
+    prior.samples.extend(samples.iter().map(|s| SampleInput {
+        t_sec: s.t_sec + start_t_sec,
+        gasmix_index: s.gasmix_index.map(|g| g + shift),
+        ..s.clone()
+    }));
+    if let Some(s) = prior.samples.get_mut(first) {
+        s.gasmix_index = s.gasmix_index.or(Some(start_gas));
+    }
+    if let Some(last) = prior.samples.last_mut() {
+        last.gasmix_index = Some(air_index);
+        last.setpoint_ppo2 = None;
+        last.ppo2 = None;
+    }
+}
+
+/// Extend the history with time at the surface on air.
+fn add_surface_interval(prior: &mut PriorDives, interval_sec: i32) {
+    let Some(last) = prior.samples.last() else {
+        return;
+    };
+    let surface = SampleInput {
+        t_sec: last.t_sec + interval_sec,
+        depth_m: 0.0,
+        ..last.clone()
+    };
+    prior.samples.push(surface);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deco::profile_generator::GasSwitchPlan;
+
+    fn dive(depth: f64, bottom_time: i32, surface_interval_sec: i32) -> PlannedDive {
+        PlannedDive {
+            dive: ProfileGenParams {
+                target_depth_m: depth,
+                bottom_time_sec: bottom_time,
+                descent_rate_m_min: None,
+                ascent_rate_m_min: None,
+                gas_plan: vec![],
+                model: DecoModel::BuhlmannZhl16c,
+                surface_pressure_bar: None,
+                gf_low: None,
+                gf_high: None,
+                last_stop_depth_m: None,
+                stop_interval_m: None,
+                setpoint_ppo2: None,
+                thalmann_pdcs: None,
+                sample_interval_sec: None,
+                temp_c: None,
+                gas_switch_pause_sec: None,
+                max_ppo2: None,
+                deep_stops: None,
+                ascent_mode: None,
+                ceiling_margin_m: None,
+                setpoint_schedule: None,
+                scr: None,
+                buhlmann_coefficients: None,
+                custom_buhlmann_coefficients: None,
+                custom_thalmann_params: None,
+                safety_stop: None,
+                final_ascent_rate_m_min: None,
+                surface_tail_sec: None,
+            },
+            surface_interval_sec,
+        }
+    }
+
+    #[test]
+    fn test_first_dive_matches_standalone() {
+        let day = plan_dive_day(&DiveDayParams {
+            dives: vec![dive(30.0, 1200, 0)],
+        })
+        .unwrap();
+        let alone = profile_generator::generate_dive_profile(dive(30.0, 1200, 0).dive).unwrap();
+        let first = &day.dives[0];
+        assert_eq!(first.start_t_sec, 0);
+        assert_eq!(first.profile.samples.len(), alone.samples.len());
+        assert_eq!(first.deco_time_sec, first.standalone_deco_time_sec);
+        assert_eq!(day.total_time_sec, alone.total_time_sec);
+        assert_eq!(
+            day.desaturation_time_sec,
+            alone.deco_result.desaturation_time_sec
+        );
+        assert!(day.desaturation_time_sec > 0);
+    }
+
+    #[test]
+    fn test_residual_gas_shortens_second_dive_ndl() {
+        let day = plan_dive_day(&DiveDayParams {
+            dives: vec![dive(30.0, 1500, 0), dive(30.0, 1500, 1800)],
+        })
+        .unwrap();
+        let (first, second) = (&day.dives[0], &day.dives[1]);
+        assert_eq!(second.start_t_sec, first.profile.total_time_sec + 1800);
+        assert_eq!(
+            day.total_time_sec,
+            second.start_t_sec + second.profile.total_time_sec
+        );
+        // Overlay points are on dive time
+        assert_eq!(second.profile.deco_result.points[0].t_sec, 0);
+        assert_eq!(
+            second.profile.deco_result.points.len(),
+            second.profile.samples.len()
+        );
+        // The repeat dive carries more gas than the same dive on clean tissues
+        assert!(second.deco_time_sec > second.standalone_deco_time_sec);
+        let alone = profile_generator::generate_dive_profile(dive(30.0, 1500, 0).dive).unwrap();
+        assert!(second.profile.deco_result.max_tts_sec > alone.deco_result.max_tts_sec);
+
+        // A no-stop repeat dive has less NDL left than the first
+        let day = plan_dive_day(&DiveDayParams {
+            dives: vec![dive(18.0, 2700, 0), dive(18.0, 2700, 3600)],
+        })
+        .unwrap();
+        assert_eq!(day.dives[1].deco_time_sec, 0);
+        assert!(day.dives[1].bottom_ndl_sec > 0);
+        assert!(day.dives[1].bottom_ndl_sec < day.dives[0].bottom_ndl_sec);
+    }
+
+    #[test]
+    fn test_residual_gas_turns_repeat_dive_into_deco() {
+        // 18 m for 50 min is a no-stop dive on air at GF 100/100, but not
+        // an hour after the same dive
+        let day = plan_dive_day(&DiveDayParams {
+            dives: vec![dive(18.0, 3000, 0), dive(18.0, 3000, 3600)],
+        })
+        .unwrap();
+        assert_eq!(day.dives[0].deco_time_sec, 0);
+        assert!(day.dives[0].bottom_ndl_sec > 0);
+        assert!(!day.dives[0].deco_from_residual);
+        let second = &day.dives[1];
+        assert_eq!(second.standalone_deco_time_sec, 0);
+        assert!(second.deco_time_sec > 0);
+        assert_eq!(second.bottom_ndl_sec, 0);
+        assert!(second.deco_from_residual);
+    }
+
+    #[test]
+    fn test_long_interval_approaches_clean_tissues() {
+        let short = plan_dive_day(&DiveDayParams {
+            dives: vec![dive(30.0, 1200, 0), dive(30.0, 1200, 1800)],
+        })
+        .unwrap();
+        let long = plan_dive_day(&DiveDayParams {
+            dives: vec![dive(30.0, 1200, 0), dive(30.0, 1200, 6 * 3600)],
+        })
+        .unwrap();
+        assert!(
+            long.dives[1].profile.deco_result.max_tts_sec
+                < short.dives[1].profile.deco_result.max_tts_sec
+        );
+        // The longer day ends later but with less gas left to wash out
+        assert!(long.desaturation_time_sec <= short.desaturation_time_sec);
+    }
+
+    #[test]
+    fn test_second_dive_anchors_gradient_factors_afresh() {
+        // After a week at the surface the tissues are clean again, so the
+        // shallower second dive must match the same dive done alone.
+        // Carrying the first dive's deeper first stop as the Baker GF
+        // anchor would raise the GF at the second dive's first ceilings.
+        let with_gf = |depth, bottom_time, surface_interval_sec| {
+            let mut planned = dive(depth, bottom_time, surface_interval_sec);
+            planned.dive.gf_low = Some(30);
+            planned.dive.gf_high = Some(70);
+            planned
+        };
+        let day = plan_dive_day(&DiveDayParams {
+            dives: vec![with_gf(50.0, 1800, 0), with_gf(27.0, 2400, 7 * 86400)],
+        })
+        .unwrap();
+        let alone = profile_generator::generate_dive_profile(with_gf(27.0, 2400, 0).dive).unwrap();
+        let (first, second) = (&day.dives[0], &day.dives[1]);
+        assert!(second.profile.planned_stops[0].depth_m < first.profile.planned_stops[0].depth_m);
+        assert_eq!(
+            second.profile.planned_stops.len(),
+            alone.planned_stops.len()
+        );
+        for (a, b) in second
+            .profile
+            .planned_stops
+            .iter()
+            .zip(&alone.planned_stops)
+        {
+            assert_eq!(a.depth_m, b.depth_m);
+            assert_eq!(a.duration_sec, b.duration_sec);
+        }
+        let (points, alone_points) = (
+            &second.profile.deco_result.points,
+            &alone.deco_result.points,
+        );
+        assert_eq!(points.len(), alone_points.len());
+        for (a, b) in points.iter().zip(alone_points) {
+            assert_eq!(a.ceiling_m, b.ceiling_m, "ceiling at {} s", a.t_sec);
+        }
+    }
+
+    #[test]
+    fn test_multi_gas_dives_keep_their_own_gases() {
+        let mut deco_dive = dive(45.0, 1500, 0);
+        deco_dive.dive.gas_plan = vec![
+            GasSwitchPlan {
+                gas: GasMixInput {
+                    mix_index: 0,
+                    o2_fraction: 0.21,
+                    he_fraction: 0.35,
+                },
+                switch_depth_m: None,
+                descent_switch_depth_m: None,
+            },
+            GasSwitchPlan {
+                gas: GasMixInput {
+                    mix_index: 1,
+                    o2_fraction: 0.50,
+                    he_fraction: 0.0,
+                },
+                switch_depth_m: Some(21.0),
+                descent_switch_depth_m: None,
+            },
+        ];
+        // A single-gas air dive after a deco dive must not switch to the
+        // earlier dive's EAN50
+        let day = plan_dive_day(&DiveDayParams {
+            dives: vec![deco_dive, dive(30.0, 1200, 1800)],
+        })
+        .unwrap();
+        let second = &day.dives[1];
+        assert_eq!(second.profile.gas_mixes.len(), 1);
+        assert!(second
+            .profile
+            .planned_stops
+            .iter()
+            .all(|s| s.gas_mix_index <= 0));
+        let alone = profile_generator::generate_dive_profile(dive(30.0, 1200, 0).dive).unwrap();
+        assert!(second.profile.deco_result.max_gf99 > alone.deco_result.max_gf99);
+    }
+
+    #[test]
+    fn test_invalid_day_rejected() {
+        assert!(plan_dive_day(&DiveDayParams { dives: vec![] }).is_err());
+
+        let negative = DiveDayParams {
+            dives: vec![dive(30.0, 900, 0), dive(30.0, 900, -60)],
+        };
+        assert!(plan_dive_day(&negative).is_err());
+
+        let mut thalmann = dive(30.0, 900, 3600);
+        thalmann.dive.model = DecoModel::ThalmannElDca;
+        let mixed = DiveDayParams {
+            dives: vec![dive(30.0, 900, 0), thalmann],
+        };
+        assert!(plan_dive_day(&mixed).is_err());
+
+        // Errors from a dive name it
+        let bad = DiveDayParams {
+            dives: vec![dive(30.0, 900, 0), dive(0.0, 900, 3600)],
+        };
+        match plan_dive_day(&bad) {
+            Err(DecoSimError::InvalidParam { msg }) => assert!(msg.starts_with("dive 2:")),
+            other => panic!("expected InvalidParam, got {other:?}"),
+        }
+    }
+
+    fn interval_params(
+        next: PlannedDive,
+        target: SurfaceIntervalTarget,
+    ) -> MinSurfaceIntervalParams {
+        let first = profile_generator::generate_dive_profile(dive(30.0, 1500, 0).dive).unwrap();
+        MinSurfaceIntervalParams {
+            previous_samples: first.samples,
+            previous_gas_mixes: first.gas_mixes,
+            next_dive: next.dive,
+            target,
+            max_deco_time_sec: None,
+            max_surface_gf: None,
+            max_interval_sec: None,
+        }
+    }
+
+    #[test]
+    fn test_min_surface_interval_no_deco() {
+        let params = interval_params(dive(18.0, 3000, 0), SurfaceIntervalTarget::NoDeco);
+        let result = min_surface_interval(&params).unwrap();
+        assert!(result.achievable);
+        let si = result.surface_interval_sec;
+        assert!(si > 3600 && si % 60 == 0, "interval {si}");
+        assert_eq!(planned_stop_time(result.next_dive.as_ref().unwrap()), 0);
+
+        // Matches the day planner either side of the interval
+        let day = |si| {
+            plan_dive_day(&DiveDayParams {
+                dives: vec![dive(30.0, 1500, 0), dive(18.0, 3000, si)],
+            })
+            .unwrap()
+        };
+        assert_eq!(day(si).dives[1].deco_time_sec, 0);
+        assert!(day(si - 60).dives[1].deco_time_sec > 0);
+    }
+
+    #[test]
+    fn test_min_surface_interval_targets() {
+        // A shallow dive can follow straight away
+        let params = interval_params(dive(9.0, 1800, 0), SurfaceIntervalTarget::NoDeco);
+        let result = min_surface_interval(&params).unwrap();
+        assert!(result.achievable);
+        assert_eq!(result.surface_interval_sec, 0);
+
+        // Allowing some deco shortens the wait
+        let no_deco = min_surface_interval(&interval_params(
+            dive(18.0, 3000, 0),
+            SurfaceIntervalTarget::NoDeco,
+        ))
+        .unwrap();
+        let mut params = interval_params(dive(18.0, 3000, 0), SurfaceIntervalTarget::MaxDecoTime);
+        params.max_deco_time_sec = Some(300);
+        let with_deco = min_surface_interval(&params).unwrap();
+        assert!(with_deco.achievable);
+        assert!(with_deco.surface_interval_sec < no_deco.surface_interval_sec);
+        assert!(planned_stop_time(with_deco.next_dive.as_ref().unwrap()) <= 300);
+
+        let mut params = interval_params(dive(18.0, 3000, 0), SurfaceIntervalTarget::MaxSurfaceGf);
+        params.max_surface_gf = Some(90.0);
+        let result = min_surface_interval(&params).unwrap();
+        assert!(result.achievable);
+        assert!(result.surface_interval_sec > 0);
+        let last = result
+            .next_dive
+            .unwrap()
+            .deco_result
+            .points
+            .last()
+            .unwrap()
+            .surface_gf;
+        assert!(last <= 90.0);
+    }
+
+    #[test]
+    fn test_min_surface_interval_not_achievable() {
+        // A deco dive stays a deco dive however long the interval
+        let mut params = interval_params(dive(40.0, 2400, 0), SurfaceIntervalTarget::NoDeco);
+        params.max_interval_sec = Some(6 * 3600);
+        let result = min_surface_interval(&params).unwrap();
+        assert!(!result.achievable);
+        assert_eq!(result.surface_interval_sec, 6 * 3600);
+        assert!(result.next_dive.is_none());
+    }
+
+    #[test]
+    fn test_invalid_min_surface_interval_rejected() {
+        let mut params = interval_params(dive(18.0, 3000, 0), SurfaceIntervalTarget::NoDeco);
+        params.previous_samples.clear();
+        assert!(matches!(
+            min_surface_interval(&params),
+            Err(DecoSimError::EmptySamples { .. })
+        ));
+
+        let mut params = interval_params(dive(18.0, 3000, 0), SurfaceIntervalTarget::NoDeco);
+        params.max_interval_sec = Some(0);
+        assert!(min_surface_interval(&params).is_err());
+
+        let params = interval_params(dive(18.0, 3000, 0), SurfaceIntervalTarget::MaxDecoTime);
+        assert!(min_surface_interval(&params).is_err());
+
+        let mut params = interval_params(dive(18.0, 3000, 0), SurfaceIntervalTarget::MaxSurfaceGf);
+        params.max_surface_gf = Some(0.0);
+        assert!(min_surface_interval(&params).is_err());
+    }
+
+    #[test]
+    fn test_thalmann_day() {
+        let mut first = dive(30.0, 1200, 0);
+        first.dive.model = DecoModel::ThalmannElDca;
+        let mut second = dive(30.0, 1200, 3600);
+        second.dive.model = DecoModel::ThalmannElDca;
+        let day = plan_dive_day(&DiveDayParams {
+            dives: vec![first, second],
+        })
+        .unwrap();
+        assert!(day.dives[1].deco_time_sec >= day.dives[1].standalone_deco_time_sec);
+        assert!(day.desaturation_time_sec > 0);
+    }
+}
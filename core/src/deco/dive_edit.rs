@@ -0,0 +1,804 @@
+//! "What-if" edits to a logged dive with automatic ascent replanning.
+//!
+//! Takes a real logged profile and one edit — stay longer at depth, dive
+//! deeper or shallower from a point on, or lose a gas — and:
+//!
+//! 1. Cuts the logged profile at the edit point.
+//! 2. Splices the edit in (extra time at depth, the rest of the bottom shifted
+//!    in depth, or a gas dropped) and appends the rest of the logged bottom —
+//!    later by the added time for an extension — so the ascent still starts
+//!    where the bottom ends.
+//! 3. Replans the ascent from the end of the edited bottom with the model.
+//! 4. Runs the deco engine over the modified profile for the overlay.
+
+use super::compute_deco_simulation;
+use super::profile_generator::{
+    generate_ascent, generate_continuous_ascent, SampleCtx, DEFAULT_ASCENT_RATE,
+    DEFAULT_DESCENT_RATE, DEFAULT_SAMPLE_INTERVAL,
+};
+use super::shared::*;
+use super::types::*;
+use crate::buhlmann::GasMixInput;
+use crate::gas;
+use crate::metrics::SampleInput;
+
+// ============================================================================
+// Input / Output Types
+// ============================================================================
+
+/// Kind of change applied to a logged dive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiveEditKind {
+    /// Stay at the edit point's depth for `duration_sec` longer, then finish
+    /// the logged bottom and ascend.
+    ExtendBottom,
+    /// Dive `depth_delta_m` deeper (negative = shallower) from the edit point
+    /// to the end of the bottom, then ascend.
+    ChangeDepth,
+    /// Gas `mix_index` is unavailable from the edit point on; the ascent is
+    /// replanned from the end of the bottom without it.
+    LoseGas,
+}
+
+/// One edit to a logged dive.
+#[derive(Debug, Clone)]
+pub struct DiveEdit {
+    /// What to change.
+    pub kind: DiveEditKind,
+    /// Edit point in seconds from dive start (default: end of the bottom).
+    pub t_sec: Option<i32>,
+    /// Extra time at depth in seconds (`ExtendBottom`).
+    pub duration_sec: Option<i32>,
+    /// Depth change in metres, positive = deeper (`ChangeDepth`).
+    pub depth_delta_m: Option<f64>,
+    /// Gas lost (`LoseGas`), matching `GasMixInput.mix_index`.
+    pub mix_index: Option<i32>,
+}
+
+/// Parameters for replanning a logged dive after an edit.
+#[derive(Debug, Clone)]
+pub struct DiveEditParams {
+    /// Logged samples, gases and model settings. `plan_ascent` is ignored;
+    /// `ascent_gas_switches` and `ascent_mode` shape the replanned ascent.
+    pub sim: DecoSimParams,
+    /// The change to apply.
+    pub edit: DiveEdit,
+    /// End of the logged bottom phase in seconds (default: detected as in
+    /// `DiveStats.bottom_end_t`).
+    pub bottom_end_t_sec: Option<i32>,
+}
+
+/// A logged dive after an edit, with its replanned ascent.
+#[derive(Debug, Clone)]
+pub struct DiveEditResult {
+    /// Modified profile: the logged dive up to the edit point, the edit, the
+    /// rest of the logged bottom, and the replanned ascent.
+    pub samples: Vec<SampleInput>,
+    /// Gases available on the modified dive.
+    pub gas_mixes: Vec<GasMixInput>,
+    /// Per-point deco overlay for `samples`.
+    pub deco_result: DecoSimResult,
+    /// Stops of the replanned ascent.
+    pub planned_stops: Vec<DecoStop>,
+    /// Edit point (seconds from dive start).
+    pub edit_t_sec: i32,
+    /// Time the replanned ascent starts (seconds from dive start).
+    pub ascent_start_t_sec: i32,
+    /// Total runtime of the modified dive (seconds).
+    pub total_time_sec: i32,
+    /// Modified runtime minus the logged runtime (seconds).
+    pub runtime_delta_sec: i32,
+    /// True if the replanned ascent hit a planner safety limit.
+    pub truncated: bool,
+}
+
+// ============================================================================
+// Public API
+// ============================================================================
+
+/// Apply an edit to a logged dive and replan its ascent.
+pub fn apply_dive_edit(params: DiveEditParams) -> Result<DiveEditResult, DecoSimError> {
+    let logged = &params.sim.samples;
+    let (Some(first), Some(last)) = (logged.first(), logged.last()) else {
+        return Err(DecoSimError::EmptySamples {
+            msg: "No samples provided".to_string(),
+        });
+    };
+    let bottom_end_t = params.bottom_end_t_sec.unwrap_or_else(|| {
+        let max_depth = logged.iter().map(|s| s.depth_m).fold(0.0, f32::max);
+        crate::metrics::compute_bottom_end_t(logged, max_depth)
+    });
+    let edit_t = params.edit.t_sec.unwrap_or(bottom_end_t);
+    if edit_t < first.t_sec || edit_t > last.t_sec {
+        return Err(DecoSimError::InvalidParam {
+            msg: format!(
+                "edit point ({edit_t} s) must be within the dive ({} to {} s)",
+                first.t_sec, last.t_sec
+            ),
+        });
+    }
+
+    let mut samples = cut_at(logged, edit_t);
+    let mut gas_mixes = params.sim.gas_mixes.clone();
+    let mut ascent_gas_switches = params.sim.ascent_gas_switches.clone();
+    let interval = sample_interval(logged);
+
+    match params.edit.kind {
+        DiveEditKind::ExtendBottom => {
+            let duration = match params.edit.duration_sec {
+                Some(d) if d > 0 => d,
+                _ => {
+                    return Err(DecoSimError::InvalidParam {
+                        msg: "ExtendBottom requires duration_sec > 0".to_string(),
+                    })
+                }
+            };
+            extend_bottom(&mut samples, duration, interval);
+            // The rest of the logged bottom follows the hold, later by `duration`.
+            samples.extend(
+                logged_bottom_after(logged, edit_t, bottom_end_t)
+                    .into_iter()
+                    .map(|s| SampleInput {
+                        t_sec: s.t_sec + duration,
+                        ..s
+                    }),
+            );
+        }
+        DiveEditKind::ChangeDepth => {
+            let delta = match params.edit.depth_delta_m {
+                Some(d) if d != 0.0 => d,
+                _ => {
+                    return Err(DecoSimError::InvalidParam {
+                        msg: "ChangeDepth requires a non-zero depth_delta_m".to_string(),
+                    })
+                }
+            };
+            if edit_t >= bottom_end_t {
+                return Err(DecoSimError::InvalidParam {
+                    msg: format!(
+                        "ChangeDepth edit point ({edit_t} s) must be before the end of \
+                         the bottom ({bottom_end_t} s)"
+                    ),
+                });
+            }
+            let rest = logged_bottom_after(logged, edit_t, bottom_end_t);
+            shift_depth(&mut samples, &rest, edit_t, delta);
+        }
+        DiveEditKind::LoseGas => {
+            let Some(lost) = params.edit.mix_index else {
+                return Err(DecoSimError::InvalidParam {
+                    msg: "LoseGas requires mix_index".to_string(),
+                });
+            };
+            lose_gas(&mut samples, &mut gas_mixes, &mut ascent_gas_switches, lost)?;
+            // The logged bottom is kept up to the ascent, minus any later
+            // switch onto the lost gas.
+            samples.extend(
+                logged_bottom_after(logged, edit_t, bottom_end_t)
+                    .into_iter()
+                    .map(|s| SampleInput {
+                        gasmix_index: s.gasmix_index.filter(|&g| g != lost),
+                        ..s
+                    }),
+            );
+        }
+    }
+
+    // ── Replan the ascent from the end of the edited bottom ─────────────
+    let plan_sim = DecoSimParams {
+        samples: samples.clone(),
+        gas_mixes: gas_mixes.clone(),
+        plan_ascent: true,
+        ascent_gas_switches: ascent_gas_switches.clone(),
+        ..params.sim.clone()
+    };
+    let plan = compute_deco_simulation(plan_sim)?;
+
+    let end = samples
+        .last()
+        .expect("cut keeps at least one sample")
+        .clone();
+    let ascent_start_t = end.t_sec;
+    let ascent_rate = params.sim.ascent_rate_m_min.unwrap_or(DEFAULT_ASCENT_RATE);
+    let ctx = SampleCtx {
+        setpoint_ppo2: end.ppo2.map(|v| v as f64),
+        surface_pressure: params
+            .sim
+            .surface_pressure_bar
+            .unwrap_or(DEFAULT_SURFACE_PRESSURE),
+        temp_c: end.temp_c,
+        sample_interval: interval,
+        ascent_rate,
+        final_ascent_rate: ascent_rate,
+    };
+    let current_gas = samples
+        .iter()
+        .rev()
+        .find_map(|s| s.gasmix_index)
+        .unwrap_or(0);
+    let switches = ascent_schedule(
+        &gas_mixes,
+        &ascent_gas_switches,
+        current_gas,
+        ctx.surface_pressure,
+    );
+    if plan.ascent_path.is_empty() {
+        generate_ascent(
+            &mut samples,
+            (end.depth_m as f64).max(0.0),
+            ascent_start_t,
+            &plan.deco_stops,
+            &switches,
+            current_gas,
+            &ctx,
+        );
+    } else {
+        generate_continuous_ascent(
+            &mut samples,
+            ascent_start_t,
+            &plan.ascent_path,
+            &switches,
+            current_gas,
+            &ctx,
+        );
+    }
+
+    // ── Overlay on the modified profile ─────────────────────────────────
+    let deco_result = compute_deco_simulation(DecoSimParams {
+        samples: samples.clone(),
+        gas_mixes: gas_mixes.clone(),
+        plan_ascent: false,
+        ascent_gas_switches,
+        deep_stops: None,
+        ascent_mode: None,
+        ceiling_margin_m: None,
+        ..params.sim.clone()
+    })?;
+
+    let total_time_sec = samples.last().map_or(0, |s| s.t_sec);
+    Ok(DiveEditResult {
+        samples,
+        gas_mixes,
+        deco_result,
+        planned_stops: plan.deco_stops,
+        edit_t_sec: edit_t,
+        ascent_start_t_sec: ascent_start_t,
+        total_time_sec,
+        runtime_delta_sec: total_time_sec - last.t_sec,
+        truncated: plan.truncated,
+    })
+}
+
+// ============================================================================
+// Profile Surgery
+// ============================================================================
+
+/// The logged samples up to `t_sec`, ending with a sample at `t_sec`
+/// (interpolated between the neighbouring samples if needed).
+fn cut_at(logged: &[SampleInput], t_sec: i32) -> Vec<SampleInput> {
+    let keep = logged.partition_point(|s| s.t_sec <= t_sec);
+    let mut samples = logged[..keep].to_vec();
+    if let (Some(prev), Some(next)) = (samples.last(), logged.get(keep)) {
+        if prev.t_sec < t_sec {
+            let frac = (t_sec - prev.t_sec) as f32 / (next.t_sec - prev.t_sec) as f32;
+            let depth = prev.depth_m + frac * (next.depth_m - prev.depth_m);
+            samples.push(spliced(prev, t_sec, depth));
+        }
+    }
+    samples
+}
+
+/// A sample derived from a logged one, without the dive computer's own
+/// deco readings (they no longer apply) or a gas switch.
+fn spliced(from: &SampleInput, t_sec: i32, depth_m: f32) -> SampleInput {
+    SampleInput {
+        t_sec,
+        depth_m,
+        ceiling_m: None,
+        gf99: None,
+        gasmix_index: None,
+        tts_sec: None,
+        ndl_sec: None,
+        deco_stop_depth_m: None,
+        at_plus_five_tts_min: None,
+        ..from.clone()
+    }
+}
+
+/// Logged sample interval: the last interval, or the default.
+fn sample_interval(logged: &[SampleInput]) -> i32 {
+    logged
+        .windows(2)
+        .last()
+        .map_or(DEFAULT_SAMPLE_INTERVAL, |w| w[1].t_sec - w[0].t_sec)
+        .max(1)
+}
+
+/// The logged bottom after `edit_t` up to `bottom_end_t` (empty once the
+/// bottom has ended), with the dive computer's deco readings dropped and gas
+/// switches kept.
+fn logged_bottom_after(logged: &[SampleInput], edit_t: i32, bottom_end_t: i32) -> Vec<SampleInput> {
+    if edit_t >= bottom_end_t {
+        return Vec::new();
+    }
+    let bottom = cut_at(logged, bottom_end_t);
+    bottom[bottom.partition_point(|s| s.t_sec <= edit_t)..]
+        .iter()
+        .map(|s| SampleInput {
+            gasmix_index: s.gasmix_index,
+            ..spliced(s, s.t_sec, s.depth_m)
+        })
+        .collect()
+}
+
+/// Hold at the last sample's depth for `duration_sec`.
+fn extend_bottom(samples: &mut Vec<SampleInput>, duration_sec: i32, interval: i32) {
+    let end = samples
+        .last()
+        .expect("cut keeps at least one sample")
+        .clone();
+    let end_t = end.t_sec + duration_sec;
+    let mut t = end.t_sec + interval;
+    while t < end_t {
+        samples.push(spliced(&end, t, end.depth_m));
+        t += interval;
+    }
+    samples.push(spliced(&end, end_t, end.depth_m));
+}
+
+/// Append the rest of the logged bottom shifted by `delta_m`, reaching the
+/// new depth at the default descent rate from `edit_t`.
+fn shift_depth(samples: &mut Vec<SampleInput>, rest: &[SampleInput], edit_t: i32, delta_m: f64) {
+    let ramp_sec = (delta_m.abs() / DEFAULT_DESCENT_RATE * 60.0).max(1.0);
+    for s in rest {
+        let frac = ((s.t_sec - edit_t) as f64 / ramp_sec).min(1.0);
+        let depth = (s.depth_m as f64 + delta_m * frac).max(0.0) as f32;
+        samples.push(SampleInput {
+            depth_m: depth,
+            ..s.clone()
+        });
+    }
+}
+
+/// Drop gas `lost` from the dive from the last sample on. Fails if the gas
+/// is being breathed before the edit point.
+fn lose_gas(
+    samples: &mut [SampleInput],
+    gas_mixes: &mut Vec<GasMixInput>,
+    ascent_gas_switches: &mut Vec<GasSwitchDepth>,
+    lost: i32,
+) -> Result<(), DecoSimError> {
+    if !gas_mixes.iter().any(|g| g.mix_index == lost) {
+        return Err(DecoSimError::InvalidParam {
+            msg: format!("LoseGas mix_index {lost} is not in gas_mixes"),
+        });
+    }
+    // A switch onto the lost gas exactly at the edit point never happens.
+    if let Some(end) = samples.last_mut() {
+        if end.gasmix_index == Some(lost) {
+            end.gasmix_index = None;
+        }
+    }
+    let in_use = samples
+        .iter()
+        .rev()
+        .find_map(|s| s.gasmix_index)
+        .unwrap_or(0);
+    if in_use == lost {
+        return Err(DecoSimError::InvalidParam {
+            msg: format!("gas {lost} is being breathed at the edit point"),
+        });
+    }
+    gas_mixes.retain(|g| g.mix_index != lost);
+    ascent_gas_switches.retain(|s| s.mix_index != lost);
+    Ok(())
+}
+
+/// Ascent gas switches as `(depth, mix_index)`, deepest first, matching the
+/// engines: the planned switches, or every other gas from its MOD.
+fn ascent_schedule(
+    gas_mixes: &[GasMixInput],
+    ascent_gas_switches: &[GasSwitchDepth],
+    current_gas: i32,
+    surface_pressure: f64,
+) -> Vec<(f64, i32)> {
+    let mut schedule: Vec<(f64, i32)> = if ascent_gas_switches.is_empty() {
+        gas_mixes
+            .iter()
+            .filter(|g| g.mix_index != current_gas && g.o2_fraction > 0.0)
+            .map(|g| {
+                let mod_m =
+                    default_switch_depth(g.o2_fraction, gas::DEFAULT_MAX_PPO2, surface_pressure);
+                (mod_m, g.mix_index)
+            })
+            .collect()
+    } else {
+        ascent_gas_switches
+            .iter()
+            .filter(|s| s.mix_index != current_gas)
+            .map(|s| (s.depth_m, s.mix_index))
+            .collect()
+    };
+    schedule.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    schedule
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deco::profile_generator::{generate_dive_profile, GasSwitchPlan, ProfileGenParams};
+
+    /// A "logged" dive: a generated 30 m / 25 min profile on air with EAN50
+    /// for deco, GF 50/80.
+    fn logged_dive() -> (DecoSimParams, i32) {
+        let profile = generate_dive_profile(ProfileGenParams {
+            target_depth_m: 30.0,
+            bottom_time_sec: 1500,
+            descent_rate_m_min: None,
+            ascent_rate_m_min: None,
+            gas_plan: vec![
+                GasSwitchPlan {
+                    gas: GasMixInput {
+                        mix_index: 0,
+                        o2_fraction: 0.21,
+                        he_fraction: 0.0,
+                    },
+                    switch_depth_m: None,
+                    descent_switch_depth_m: None,
+                },
+                GasSwitchPlan {
+                    gas: GasMixInput {
+                        mix_index: 1,
+                        o2_fraction: 0.50,
+                        he_fraction: 0.0,
+                    },
+                    switch_depth_m: Some(21.0),
+                    descent_switch_depth_m: None,
+                },
+            ],
+            model: DecoModel::BuhlmannZhl16c,
+            surface_pressure_bar: None,
+            gf_low: Some(50),
+            gf_high: Some(80),
+            last_stop_depth_m: None,
+            stop_interval_m: None,
+            setpoint_ppo2: None,
+            thalmann_pdcs: None,
+            sample_interval_sec: None,
+            temp_c: None,
+            gas_switch_pause_sec: None,
+            max_ppo2: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            setpoint_schedule: None,
+            scr: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            safety_stop: None,
+            final_ascent_rate_m_min: None,
+            surface_tail_sec: None,
+        })
+        .unwrap();
+        let sim = DecoSimParams {
+            model: DecoModel::BuhlmannZhl16c,
+            samples: profile.samples,
+            gas_mixes: profile.gas_mixes,
+            surface_pressure_bar: None,
+            ascent_rate_m_min: None,
+            last_stop_depth_m: None,
+            stop_interval_m: None,
+            gf_low: Some(50),
+            gf_high: Some(80),
+            thalmann_pdcs: None,
+            plan_ascent: false,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
+        };
+        (sim, profile.bottom_end_t_sec)
+    }
+
+    fn edit(kind: DiveEditKind) -> DiveEdit {
+        DiveEdit {
+            kind,
+            t_sec: None,
+            duration_sec: None,
+            depth_delta_m: None,
+            mix_index: None,
+        }
+    }
+
+    fn total_deco(stops: &[DecoStop]) -> i32 {
+        stops.iter().map(|s| s.duration_sec).sum()
+    }
+
+    #[test]
+    fn test_extend_bottom_adds_deco() {
+        let (sim, bottom_end) = logged_dive();
+        let logged_end = sim.samples.last().unwrap().t_sec;
+        let unchanged = apply_dive_edit(DiveEditParams {
+            sim: sim.clone(),
+            edit: DiveEdit {
+                duration_sec: Some(1),
+                ..edit(DiveEditKind::ExtendBottom)
+            },
+            bottom_end_t_sec: Some(bottom_end),
+        })
+        .unwrap();
+        let extended = apply_dive_edit(DiveEditParams {
+            sim,
+            edit: DiveEdit {
+                duration_sec: Some(300),
+                ..edit(DiveEditKind::ExtendBottom)
+            },
+            bottom_end_t_sec: Some(bottom_end),
+        })
+        .unwrap();
+
+        assert_eq!(extended.edit_t_sec, bottom_end);
+        assert_eq!(extended.ascent_start_t_sec, bottom_end + 300);
+        assert!(total_deco(&extended.planned_stops) > total_deco(&unchanged.planned_stops));
+        assert_eq!(
+            extended.runtime_delta_sec,
+            extended.total_time_sec - logged_end
+        );
+        assert!(extended.runtime_delta_sec > 300);
+
+        // The logged profile is untouched up to the edit point.
+        let at_bottom = extended
+            .samples
+            .iter()
+            .filter(|s| s.t_sec > bottom_end && s.t_sec <= bottom_end + 300);
+        assert!(at_bottom
+            .into_iter()
+            .all(|s| (s.depth_m - 30.0).abs() < 1e-3));
+        assert_eq!(extended.samples.last().unwrap().depth_m, 0.0);
+        assert_eq!(extended.deco_result.points.len(), extended.samples.len());
+    }
+
+    #[test]
+    fn test_extend_bottom_mid_bottom_keeps_rest_of_bottom() {
+        let (sim, bottom_end) = logged_dive();
+        let logged_end = sim.samples.last().unwrap().t_sec;
+        let logged = sim.samples.clone();
+        let extended = apply_dive_edit(DiveEditParams {
+            sim,
+            edit: DiveEdit {
+                t_sec: Some(720),
+                duration_sec: Some(300),
+                ..edit(DiveEditKind::ExtendBottom)
+            },
+            bottom_end_t_sec: Some(bottom_end),
+        })
+        .unwrap();
+
+        assert_eq!(extended.edit_t_sec, 720);
+        assert_eq!(extended.ascent_start_t_sec, bottom_end + 300);
+        assert!(
+            extended.runtime_delta_sec > 300,
+            "runtime delta {} s",
+            extended.runtime_delta_sec
+        );
+        // Held at the edit depth, then the logged bottom resumes 300 s later.
+        let depth_at = |samples: &[SampleInput], t: i32| {
+            samples.iter().find(|s| s.t_sec == t).unwrap().depth_m
+        };
+        let edit_depth = depth_at(&logged, 720);
+        for s in extended
+            .samples
+            .iter()
+            .filter(|s| s.t_sec > 720 && s.t_sec <= 1020)
+        {
+            assert_eq!(s.depth_m, edit_depth);
+        }
+        for s in logged
+            .iter()
+            .filter(|s| s.t_sec > 720 && s.t_sec <= bottom_end)
+        {
+            assert_eq!(depth_at(&extended.samples, s.t_sec + 300), s.depth_m);
+        }
+        assert!(extended.total_time_sec > logged_end);
+    }
+
+    #[test]
+    fn test_lose_gas_mid_bottom_keeps_logged_bottom() {
+        let (sim, bottom_end) = logged_dive();
+        let logged = sim.samples.clone();
+        let result = apply_dive_edit(DiveEditParams {
+            sim,
+            edit: DiveEdit {
+                t_sec: Some(720),
+                mix_index: Some(1),
+                ..edit(DiveEditKind::LoseGas)
+            },
+            bottom_end_t_sec: Some(bottom_end),
+        })
+        .unwrap();
+
+        assert_eq!(result.ascent_start_t_sec, bottom_end);
+        let bottom: Vec<_> = result
+            .samples
+            .iter()
+            .filter(|s| s.t_sec <= bottom_end)
+            .collect();
+        let logged_bottom: Vec<_> = logged.iter().filter(|s| s.t_sec <= bottom_end).collect();
+        assert_eq!(bottom.len(), logged_bottom.len());
+        for (a, b) in bottom.iter().zip(&logged_bottom) {
+            assert_eq!((a.t_sec, a.depth_m), (b.t_sec, b.depth_m));
+        }
+        assert!(result.samples.iter().all(|s| s.gasmix_index != Some(1)));
+        assert!(result.runtime_delta_sec > 0);
+    }
+
+    #[test]
+    fn test_change_depth_from_edit_point() {
+        let (sim, bottom_end) = logged_dive();
+        let deeper = apply_dive_edit(DiveEditParams {
+            sim: sim.clone(),
+            edit: DiveEdit {
+                t_sec: Some(720),
+                depth_delta_m: Some(3.0),
+                ..edit(DiveEditKind::ChangeDepth)
+            },
+            bottom_end_t_sec: Some(bottom_end),
+        })
+        .unwrap();
+        let same = apply_dive_edit(DiveEditParams {
+            sim,
+            edit: DiveEdit {
+                duration_sec: Some(1),
+                ..edit(DiveEditKind::ExtendBottom)
+            },
+            bottom_end_t_sec: Some(bottom_end),
+        })
+        .unwrap();
+
+        assert_eq!(deeper.ascent_start_t_sec, bottom_end);
+        let before: Vec<_> = deeper.samples.iter().filter(|s| s.t_sec <= 720).collect();
+        assert!(before.iter().all(|s| s.depth_m <= 30.0 + 1e-3));
+        let shifted = deeper
+            .samples
+            .iter()
+            .find(|s| s.t_sec == bottom_end)
+            .unwrap();
+        assert!((shifted.depth_m - 33.0).abs() < 1e-3);
+        assert!(total_deco(&deeper.planned_stops) > total_deco(&same.planned_stops));
+    }
+
+    #[test]
+    fn test_lose_gas_replans_on_remaining_gas() {
+        let (sim, bottom_end) = logged_dive();
+        let switch_t = sim
+            .samples
+            .iter()
+            .find(|s| s.gasmix_index == Some(1))
+            .unwrap()
+            .t_sec;
+        let result = apply_dive_edit(DiveEditParams {
+            sim: sim.clone(),
+            edit: DiveEdit {
+                t_sec: Some(switch_t),
+                mix_index: Some(1),
+                ..edit(DiveEditKind::LoseGas)
+            },
+            bottom_end_t_sec: Some(bottom_end),
+        })
+        .unwrap();
+
+        assert_eq!(result.gas_mixes.len(), 1);
+        assert!(result.samples.iter().all(|s| s.gasmix_index != Some(1)));
+        let logged_deco = sim.samples.last().unwrap().t_sec - switch_t;
+        let replanned = result.total_time_sec - switch_t;
+        assert!(
+            replanned > logged_deco,
+            "air-only ascent {replanned} s should be longer than {logged_deco} s"
+        );
+    }
+
+    #[test]
+    fn test_invalid_edits_rejected() {
+        let (sim, bottom_end) = logged_dive();
+        let run = |e: DiveEdit| {
+            apply_dive_edit(DiveEditParams {
+                sim: sim.clone(),
+                edit: e,
+                bottom_end_t_sec: Some(bottom_end),
+            })
+        };
+        assert!(run(edit(DiveEditKind::ExtendBottom)).is_err());
+        assert!(run(edit(DiveEditKind::ChangeDepth)).is_err());
+        assert!(run(edit(DiveEditKind::LoseGas)).is_err());
+        assert!(run(DiveEdit {
+            t_sec: Some(1_000_000),
+            duration_sec: Some(60),
+            ..edit(DiveEditKind::ExtendBottom)
+        })
+        .is_err());
+        // Change depth after the bottom has ended
+        assert!(run(DiveEdit {
+            t_sec: Some(bottom_end + 60),
+            depth_delta_m: Some(3.0),
+            ..edit(DiveEditKind::ChangeDepth)
+        })
+        .is_err());
+        // Losing the bottom gas while breathing it
+        assert!(run(DiveEdit {
+            t_sec: Some(600),
+            mix_index: Some(0),
+            ..edit(DiveEditKind::LoseGas)
+        })
+        .is_err());
+        assert!(run(DiveEdit {
+            mix_index: Some(7),
+            ..edit(DiveEditKind::LoseGas)
+        })
+        .is_err());
+
+        let mut empty = sim.clone();
+        empty.samples.clear();
+        let result = apply_dive_edit(DiveEditParams {
+            sim: empty,
+            edit: edit(DiveEditKind::ExtendBottom),
+            bottom_end_t_sec: None,
+        });
+        assert!(matches!(result, Err(DecoSimError::EmptySamples { .. })));
+    }
+
+    #[test]
+    fn test_default_edit_point_is_detected_bottom_end() {
+        let (sim, bottom_end) = logged_dive();
+        let result = apply_dive_edit(DiveEditParams {
+            sim,
+            edit: DiveEdit {
+                duration_sec: Some(120),
+                ..edit(DiveEditKind::ExtendBottom)
+            },
+            bottom_end_t_sec: None,
+        })
+        .unwrap();
+        assert!(
+            (result.edit_t_sec - bottom_end).abs() <= 60,
+            "detected {} vs generated {bottom_end}",
+            result.edit_t_sec
+        );
+    }
+
+    #[test]
+    fn test_cut_interpolates_between_samples() {
+        let (sim, _) = logged_dive();
+        let cut = cut_at(&sim.samples, 5);
+        let last = cut.last().unwrap();
+        assert_eq!(last.t_sec, 5);
+        assert!(last.depth_m > 0.0 && last.depth_m < sim.samples[1].depth_m);
+        assert_eq!(last.gasmix_index, None);
+    }
+
+    #[test]
+    fn test_ascent_schedule_default_mods_follow_surface_pressure() {
+        let mix = |mix_index: i32, o2_fraction: f64| GasMixInput {
+            mix_index,
+            o2_fraction,
+            he_fraction: 0.0,
+        };
+        let mixes = [mix(0, 0.21), mix(1, 0.50), mix(2, 1.0)];
+        let sea = ascent_schedule(&mixes, &[], 0, DEFAULT_SURFACE_PRESSURE);
+        assert_eq!(sea.iter().map(|s| s.1).collect::<Vec<_>>(), vec![1, 2]);
+        assert!((sea[0].0 - 22.0).abs() < 1e-9 && (sea[1].0 - 6.0).abs() < 1e-9);
+
+        let altitude = ascent_schedule(&mixes, &[], 0, 0.8);
+        assert!(altitude.iter().zip(&sea).all(|(a, s)| a.0 > s.0));
+    }
+}
@@ -0,0 +1,757 @@
+//! Deco gas selection optimizer.
+//!
+//! Given a bottom profile and a pool of candidate deco mixes, searches the
+//! combinations of up to N deco gases for the one that minimises total
+//! runtime (or total stop time). Each candidate is switched to at the deepest
+//! stop depth allowed by the ppO2, END and density limits, and every
+//! combination is evaluated with the regular profile generator so results
+//! match what the planner would produce for that gas plan.
+
+use super::profile_generator::{
+    generate_after, GasSwitchPlan, PriorDives, ProfileGenParams, ProfileGenResult,
+};
+use super::progress::{ComputeControl, Progress};
+use super::shared::*;
+use super::types::DecoSimError;
+use crate::buhlmann::GasMixInput;
+use crate::gas;
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// Quantity minimised by the optimizer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GasOptimizerObjective {
+    /// Total dive time from leaving the surface to surfacing.
+    TotalRuntime,
+    /// Sum of all planned stop durations.
+    TotalDeco,
+}
+
+/// Limit that determined a candidate's switch depth.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwitchDepthLimit {
+    /// Maximum operating depth at the ppO2 limit.
+    Ppo2,
+    /// Equivalent narcotic depth limit.
+    End,
+    /// Gas density limit.
+    Density,
+    /// Candidate is breathable at the target depth; capped there.
+    TargetDepth,
+}
+
+/// A grid of O2/He fractions to generate candidate mixes from.
+#[derive(Debug, Clone)]
+pub struct GasMixRange {
+    /// Lowest O2 fraction (0.0–1.0).
+    pub min_o2_fraction: f64,
+    /// Highest O2 fraction (0.0–1.0).
+    pub max_o2_fraction: f64,
+    /// Lowest He fraction (0.0–1.0).
+    pub min_he_fraction: f64,
+    /// Highest He fraction (0.0–1.0).
+    pub max_he_fraction: f64,
+    /// Grid spacing for both O2 and He (e.g. 0.05).
+    pub step_fraction: f64,
+}
+
+/// Parameters for the deco gas optimizer.
+#[derive(Debug, Clone)]
+pub struct DecoGasOptimizerParams {
+    /// Bottom profile. Only the bottom gas of `gas_plan` is kept; any deco
+    /// gases in it are replaced by the optimizer's selection.
+    pub profile: ProfileGenParams,
+    /// Explicit candidate deco mixes (`mix_index` is ignored).
+    pub candidate_gases: Vec<GasMixInput>,
+    /// Optional O2/He grid added to the explicit candidates.
+    pub candidate_range: Option<GasMixRange>,
+    /// Maximum number of deco gases to carry (1–4).
+    pub max_deco_gases: u32,
+    /// Quantity to minimise.
+    pub objective: GasOptimizerObjective,
+    /// Maximum ppO2 at the switch depth in bar (default 1.6).
+    pub max_ppo2: Option<f64>,
+    /// Maximum END at the switch depth in metres, O2 counted narcotic (default 30).
+    pub max_end_m: Option<f64>,
+    /// Maximum gas density at the switch depth in g/L (default 6.2).
+    pub max_density_g_l: Option<f64>,
+}
+
+/// A usable candidate mix and its computed switch depth.
+#[derive(Debug, Clone)]
+pub struct DecoGasCandidate {
+    /// The candidate mix.
+    pub gas: GasMixInput,
+    /// Deepest stop depth at which the mix satisfies all limits (metres).
+    pub switch_depth_m: f64,
+    /// Limit that determined the switch depth.
+    pub limit: SwitchDepthLimit,
+}
+
+/// Result of the deco gas optimizer.
+#[derive(Debug, Clone)]
+pub struct DecoGasOptimizerResult {
+    /// Best gas plan: the bottom gas followed by the selected deco gases.
+    pub best_plan: Vec<GasSwitchPlan>,
+    /// Generated profile for the best plan.
+    pub profile: ProfileGenResult,
+    /// Objective value of the best plan (seconds).
+    pub objective_sec: i32,
+    /// Objective value on the bottom gas alone (seconds).
+    pub baseline_objective_sec: i32,
+    /// Candidates that passed the limits, deepest switch first.
+    pub candidates: Vec<DecoGasCandidate>,
+    /// Number of gas combinations evaluated (including the baseline).
+    pub evaluated_combinations: u32,
+}
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+const DEFAULT_MAX_END_M: f64 = 30.0;
+const DEFAULT_MAX_DENSITY_G_L: f64 = 6.2;
+const DEFAULT_STOP_INTERVAL: f64 = 3.0;
+const MAX_DECO_GASES: u32 = 4;
+/// Upper bound on the number of profiles generated per call.
+const MAX_EVALUATIONS: usize = 2000;
+
+// ============================================================================
+// Public API
+// ============================================================================
+
+/// Find the combination of deco gases that minimises the chosen objective.
+///
+/// The bottom gas alone is always evaluated as the baseline; the result may
+/// therefore carry no deco gases if none of the candidates helps. Plans whose
+/// deco schedule was truncated are only chosen when nothing else is feasible.
+pub fn optimize_deco_gases(
+    params: DecoGasOptimizerParams,
+) -> Result<DecoGasOptimizerResult, DecoSimError> {
+    optimize(params, Progress::NONE)
+}
+
+/// `optimize_deco_gases` with cancellation and progress reporting; each gas
+/// combination gets an equal share of the progress range.
+pub fn optimize_deco_gases_with_control(
+    params: DecoGasOptimizerParams,
+    control: &ComputeControl,
+) -> Result<DecoGasOptimizerResult, DecoSimError> {
+    let result = optimize(params, control.progress())?;
+    control.finish();
+    Ok(result)
+}
+
+fn optimize(
+    params: DecoGasOptimizerParams,
+    progress: Progress,
+) -> Result<DecoGasOptimizerResult, DecoSimError> {
+    validate_params(&params)?;
+
+    let bottom = bottom_gas(&params.profile);
+    let candidates = usable_candidates(&params, &bottom);
+
+    let mut combos: Vec<Vec<usize>> = vec![Vec::new()];
+    let max_gases = params.max_deco_gases as usize;
+    let mut current = Vec::new();
+    collect_combinations(&candidates, 0, max_gases, &mut current, &mut combos);
+    if combos.len() > MAX_EVALUATIONS {
+        return Err(DecoSimError::InvalidParam {
+            msg: format!(
+                "{} gas combinations exceed the limit of {MAX_EVALUATIONS}; narrow the candidate range or max_deco_gases",
+                combos.len()
+            ),
+        });
+    }
+
+    let mut best: Option<(Score, Vec<GasSwitchPlan>, ProfileGenResult)> = None;
+    let mut baseline_objective_sec = 0;
+    for (i, combo) in combos.iter().enumerate() {
+        let plan = build_plan(&bottom, combo, &candidates);
+        let mut profile_params = params.profile.clone();
+        profile_params.gas_plan = plan.clone();
+        // Deco gases may be planned to a higher PPO2 than the bottom gas
+        profile_params.max_ppo2 = params
+            .max_ppo2
+            .into_iter()
+            .chain(params.profile.max_ppo2)
+            .reduce(f64::max);
+        let profile = generate_after(
+            profile_params,
+            &PriorDives::default(),
+            progress.part(i, combos.len()),
+        )?;
+        let objective = objective_sec(&profile, params.objective);
+        if i == 0 {
+            baseline_objective_sec = objective;
+        }
+        let score = Score {
+            truncated: profile.truncated,
+            objective,
+            gas_count: combo.len(),
+        };
+        if best.as_ref().is_none_or(|(b, _, _)| score < *b) {
+            best = Some((score, plan, profile));
+        }
+    }
+
+    let (score, best_plan, profile) = best.expect("baseline is always evaluated");
+    Ok(DecoGasOptimizerResult {
+        best_plan,
+        profile,
+        objective_sec: score.objective,
+        baseline_objective_sec,
+        candidates,
+        evaluated_combinations: combos.len() as u32,
+    })
+}
+
+// ============================================================================
+// Validation
+// ============================================================================
+
+fn validate_params(params: &DecoGasOptimizerParams) -> Result<(), DecoSimError> {
+    if params.max_deco_gases == 0 || params.max_deco_gases > MAX_DECO_GASES {
+        return Err(DecoSimError::InvalidParam {
+            msg: format!(
+                "max_deco_gases ({}) must be between 1 and {MAX_DECO_GASES}",
+                params.max_deco_gases
+            ),
+        });
+    }
+    if params.candidate_gases.is_empty() && params.candidate_range.is_none() {
+        return Err(DecoSimError::InvalidParam {
+            msg: "candidate_gases or candidate_range must be provided".to_string(),
+        });
+    }
+    for gas in &params.candidate_gases {
+        gas::validate_gas_mix(gas)?;
+    }
+    if let Some(range) = &params.candidate_range {
+        if range.step_fraction <= 0.0 {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!("step_fraction ({}) must be > 0", range.step_fraction),
+            });
+        }
+        if range.min_o2_fraction > range.max_o2_fraction
+            || range.min_he_fraction > range.max_he_fraction
+        {
+            return Err(DecoSimError::InvalidParam {
+                msg: "candidate_range minimums must be <= maximums".to_string(),
+            });
+        }
+        if range.min_o2_fraction <= 0.0
+            || range.max_o2_fraction > 1.0
+            || range.min_he_fraction < 0.0
+            || range.max_he_fraction > 1.0
+        {
+            return Err(DecoSimError::InvalidParam {
+                msg: "candidate_range fractions must be within 0.0–1.0 (O2 > 0)".to_string(),
+            });
+        }
+    }
+    for (name, value) in [
+        ("max_ppo2", params.max_ppo2),
+        ("max_end_m", params.max_end_m),
+        ("max_density_g_l", params.max_density_g_l),
+    ] {
+        if let Some(v) = value {
+            if v <= 0.0 {
+                return Err(DecoSimError::InvalidParam {
+                    msg: format!("{name} ({v}) must be > 0"),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Candidates
+// ============================================================================
+
+/// The plan's bottom gas, or air when the plan is empty.
+fn bottom_gas(profile: &ProfileGenParams) -> GasMixInput {
+    profile
+        .gas_plan
+        .iter()
+        .find(|g| g.switch_depth_m.is_none())
+        .map(|g| g.gas.clone())
+        .unwrap_or(GasMixInput {
+            mix_index: 0,
+            o2_fraction: AIR_FO2,
+            he_fraction: 0.0,
+        })
+}
+
+/// Explicit candidates plus the range grid, deduplicated.
+fn candidate_pool(params: &DecoGasOptimizerParams) -> Vec<(f64, f64)> {
+    let mut pool: Vec<(f64, f64)> = params
+        .candidate_gases
+        .iter()
+        .map(|g| (g.o2_fraction, g.he_fraction))
+        .collect();
+    if let Some(range) = &params.candidate_range {
+        let o2_steps = ((range.max_o2_fraction - range.min_o2_fraction) / range.step_fraction
+            + 1e-9)
+            .floor() as usize;
+        let he_steps = ((range.max_he_fraction - range.min_he_fraction) / range.step_fraction
+            + 1e-9)
+            .floor() as usize;
+        for i in 0..=o2_steps {
+            for j in 0..=he_steps {
+                let fo2 = round_fraction(range.min_o2_fraction + i as f64 * range.step_fraction);
+                let fhe = round_fraction(range.min_he_fraction + j as f64 * range.step_fraction);
+                if fo2 > 0.0 && fo2 + fhe <= 1.0 + 1e-9 {
+                    pool.push((fo2, fhe));
+                }
+            }
+        }
+    }
+    let mut unique: Vec<(f64, f64)> = Vec::with_capacity(pool.len());
+    for (fo2, fhe) in pool {
+        if !unique
+            .iter()
+            .any(|&(o, h)| (o - fo2).abs() < 1e-6 && (h - fhe).abs() < 1e-6)
+        {
+            unique.push((fo2, fhe));
+        }
+    }
+    unique
+}
+
+fn round_fraction(f: f64) -> f64 {
+    (f * 10_000.0).round() / 10_000.0
+}
+
+/// Candidates richer than the bottom gas with a usable switch depth.
+///
+/// A candidate is dropped when another candidate with the same helium, at
+/// least as much oxygen and at least as deep a switch exists, since it can
+/// never produce a shorter schedule. Sorted deepest switch first.
+fn usable_candidates(
+    params: &DecoGasOptimizerParams,
+    bottom: &GasMixInput,
+) -> Vec<DecoGasCandidate> {
+    let surface_p = params
+        .profile
+        .surface_pressure_bar
+        .unwrap_or(DEFAULT_SURFACE_PRESSURE);
+    let stop_interval = params
+        .profile
+        .stop_interval_m
+        .unwrap_or(DEFAULT_STOP_INTERVAL);
+    let limits = Limits {
+        max_ppo2: params.max_ppo2.unwrap_or(gas::DEFAULT_MAX_PPO2),
+        max_end_m: params.max_end_m.unwrap_or(DEFAULT_MAX_END_M),
+        max_density_g_l: params.max_density_g_l.unwrap_or(DEFAULT_MAX_DENSITY_G_L),
+    };
+
+    let mut usable: Vec<DecoGasCandidate> = Vec::new();
+    for (fo2, fhe) in candidate_pool(params) {
+        if fo2 <= bottom.o2_fraction {
+            continue;
+        }
+        let (limit_depth, mut limit) = max_switch_depth(fo2, fhe, &limits, surface_p);
+        let mut depth = limit_depth;
+        if depth >= params.profile.target_depth_m {
+            depth = params.profile.target_depth_m;
+            limit = SwitchDepthLimit::TargetDepth;
+        }
+        let switch_depth_m =
+            ((depth + gas::OPERATING_DEPTH_TOLERANCE_M) / stop_interval).floor() * stop_interval;
+        let switch_depth_m = switch_depth_m.min(params.profile.target_depth_m);
+        if switch_depth_m <= 0.0 {
+            continue;
+        }
+        usable.push(DecoGasCandidate {
+            gas: GasMixInput {
+                mix_index: 0,
+                o2_fraction: fo2,
+                he_fraction: fhe,
+            },
+            switch_depth_m,
+            limit,
+        });
+    }
+
+    let dominated = |c: &DecoGasCandidate| {
+        usable.iter().any(|o| {
+            (o.gas.he_fraction - c.gas.he_fraction).abs() < 1e-9
+                && o.gas.o2_fraction >= c.gas.o2_fraction
+                && o.switch_depth_m >= c.switch_depth_m
+                && (o.gas.o2_fraction > c.gas.o2_fraction || o.switch_depth_m > c.switch_depth_m)
+        })
+    };
+    let mut kept: Vec<DecoGasCandidate> =
+        usable.iter().filter(|c| !dominated(c)).cloned().collect();
+    kept.sort_by(|a, b| {
+        b.switch_depth_m
+            .partial_cmp(&a.switch_depth_m)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(
+                a.gas
+                    .o2_fraction
+                    .partial_cmp(&b.gas.o2_fraction)
+                    .unwrap_or(std::cmp::Ordering::Equal),
+            )
+    });
+    kept
+}
+
+struct Limits {
+    max_ppo2: f64,
+    max_end_m: f64,
+    max_density_g_l: f64,
+}
+
+/// Deepest depth (metres) at which a mix satisfies all limits, and which
+/// limit binds.
+fn max_switch_depth(
+    fo2: f64,
+    fhe: f64,
+    limits: &Limits,
+    surface_p: f64,
+) -> (f64, SwitchDepthLimit) {
+    let mix = GasMixInput {
+        mix_index: 0,
+        o2_fraction: fo2,
+        he_fraction: fhe,
+    };
+    [
+        (
+            gas::max_operating_depth(fo2, limits.max_ppo2, surface_p),
+            SwitchDepthLimit::Ppo2,
+        ),
+        (
+            gas::max_end_depth(&mix, limits.max_end_m, true, surface_p),
+            SwitchDepthLimit::End,
+        ),
+        (
+            gas::max_density_depth(&mix, limits.max_density_g_l, surface_p),
+            SwitchDepthLimit::Density,
+        ),
+    ]
+    .into_iter()
+    .fold((f64::INFINITY, SwitchDepthLimit::Ppo2), |acc, x| {
+        if x.0 < acc.0 {
+            x
+        } else {
+            acc
+        }
+    })
+}
+
+// ============================================================================
+// Search
+// ============================================================================
+
+/// Enumerate combinations of candidate indices (deepest first) in which each
+/// added gas switches strictly shallower and is strictly richer in O2 than
+/// the previous one; other orderings only delay the richer gas.
+fn collect_combinations(
+    candidates: &[DecoGasCandidate],
+    start: usize,
+    remaining: usize,
+    current: &mut Vec<usize>,
+    out: &mut Vec<Vec<usize>>,
+) {
+    if remaining == 0 || out.len() > MAX_EVALUATIONS {
+        return;
+    }
+    for i in start..candidates.len() {
+        if let Some(&last) = current.last() {
+            let prev = &candidates[last];
+            let next = &candidates[i];
+            if next.switch_depth_m >= prev.switch_depth_m
+                || next.gas.o2_fraction <= prev.gas.o2_fraction
+            {
+                continue;
+            }
+        }
+        current.push(i);
+        out.push(current.clone());
+        collect_combinations(candidates, i + 1, remaining - 1, current, out);
+        current.pop();
+    }
+}
+
+fn build_plan(
+    bottom: &GasMixInput,
+    combo: &[usize],
+    candidates: &[DecoGasCandidate],
+) -> Vec<GasSwitchPlan> {
+    let mut plan = vec![GasSwitchPlan {
+        gas: bottom.clone(),
+        switch_depth_m: None,
+        descent_switch_depth_m: None,
+    }];
+    for &i in combo {
+        plan.push(GasSwitchPlan {
+            gas: candidates[i].gas.clone(),
+            switch_depth_m: Some(candidates[i].switch_depth_m),
+            descent_switch_depth_m: None,
+        });
+    }
+    plan
+}
+
+fn objective_sec(profile: &ProfileGenResult, objective: GasOptimizerObjective) -> i32 {
+    match objective {
+        GasOptimizerObjective::TotalRuntime => profile.surface_t_sec,
+        GasOptimizerObjective::TotalDeco => {
+            profile.planned_stops.iter().map(|s| s.duration_sec).sum()
+        }
+    }
+}
+
+/// Ranking key: complete schedules first, then objective, then fewer gases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Score {
+    truncated: bool,
+    objective: i32,
+    gas_count: usize,
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deco::types::DecoModel;
+
+    fn gas(o2: f64, he: f64) -> GasMixInput {
+        GasMixInput {
+            mix_index: 0,
+            o2_fraction: o2,
+            he_fraction: he,
+        }
+    }
+
+    fn profile(depth: f64, bottom_min: i32, bottom: GasMixInput) -> ProfileGenParams {
+        ProfileGenParams {
+            target_depth_m: depth,
+            bottom_time_sec: bottom_min * 60,
+            descent_rate_m_min: None,
+            ascent_rate_m_min: None,
+            gas_plan: vec![GasSwitchPlan {
+                gas: bottom,
+                switch_depth_m: None,
+                descent_switch_depth_m: None,
+            }],
+            model: DecoModel::BuhlmannZhl16c,
+            surface_pressure_bar: None,
+            gf_low: Some(50),
+            gf_high: Some(80),
+            last_stop_depth_m: None,
+            stop_interval_m: None,
+            setpoint_ppo2: None,
+            thalmann_pdcs: None,
+            sample_interval_sec: Some(30),
+            temp_c: None,
+            gas_switch_pause_sec: None,
+            max_ppo2: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            setpoint_schedule: None,
+            scr: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            safety_stop: None,
+            final_ascent_rate_m_min: None,
+            surface_tail_sec: None,
+        }
+    }
+
+    fn params(
+        profile: ProfileGenParams,
+        candidates: Vec<GasMixInput>,
+        max: u32,
+    ) -> DecoGasOptimizerParams {
+        DecoGasOptimizerParams {
+            profile,
+            candidate_gases: candidates,
+            candidate_range: None,
+            max_deco_gases: max,
+            objective: GasOptimizerObjective::TotalRuntime,
+            max_ppo2: None,
+            max_end_m: None,
+            max_density_g_l: None,
+        }
+    }
+
+    #[test]
+    fn test_switch_depth_limits() {
+        let limits = Limits {
+            max_ppo2: 1.6,
+            max_end_m: 30.0,
+            max_density_g_l: 6.2,
+        };
+        let surface = DEFAULT_SURFACE_PRESSURE;
+        // EAN50 is ppO2-limited at ~22 m
+        let (d, limit) = max_switch_depth(0.5, 0.0, &limits, surface);
+        assert_eq!(limit, SwitchDepthLimit::Ppo2);
+        assert!((d - pressure_to_depth(3.2, surface)).abs() < 1e-9);
+        // Air is END-limited (O2 narcotic → END = depth)
+        let (d, limit) = max_switch_depth(0.21, 0.0, &limits, surface);
+        assert_eq!(limit, SwitchDepthLimit::End);
+        assert!((d - 30.0).abs() < 1e-9);
+        // With a generous END limit, air hits the density limit near 38 m
+        let loose = Limits {
+            max_end_m: 100.0,
+            ..limits
+        };
+        let (d, limit) = max_switch_depth(0.21, 0.0, &loose, surface);
+        assert_eq!(limit, SwitchDepthLimit::Density);
+        assert!(d > 37.0 && d < 39.0, "air density limit at {d} m");
+    }
+
+    #[test]
+    fn test_candidates_filtered_and_snapped() {
+        let p = params(
+            profile(60.0, 20, gas(0.18, 0.45)),
+            vec![
+                gas(0.50, 0.0),
+                gas(1.0, 0.0),
+                gas(0.15, 0.55),
+                gas(0.49, 0.0),
+            ],
+            2,
+        );
+        let bottom = bottom_gas(&p.profile);
+        let candidates = usable_candidates(&p, &bottom);
+        // Hypoxic mix is leaner than the bottom gas; EAN49 is dominated by EAN50 (both 21 m)
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[0].switch_depth_m, 21.0);
+        assert_eq!(candidates[1].switch_depth_m, 6.0);
+        assert!((candidates[1].gas.o2_fraction - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_range_grid_generates_candidates() {
+        let mut p = params(profile(45.0, 20, gas(0.21, 0.35)), vec![], 1);
+        p.candidate_range = Some(GasMixRange {
+            min_o2_fraction: 0.3,
+            max_o2_fraction: 0.5,
+            min_he_fraction: 0.0,
+            max_he_fraction: 0.1,
+            step_fraction: 0.1,
+        });
+        let pool = candidate_pool(&p);
+        assert_eq!(pool.len(), 6);
+        assert!(pool.contains(&(0.5, 0.1)));
+    }
+
+    #[test]
+    fn test_optimizer_prefers_deco_gases() {
+        let p = params(
+            profile(45.0, 25, gas(0.21, 0.35)),
+            vec![
+                gas(0.32, 0.0),
+                gas(0.50, 0.0),
+                gas(0.80, 0.0),
+                gas(1.0, 0.0),
+            ],
+            2,
+        );
+        let result = optimize_deco_gases(p).unwrap();
+        assert!(result.objective_sec < result.baseline_objective_sec);
+        assert_eq!(
+            result.best_plan.len(),
+            3,
+            "two deco gases should be selected"
+        );
+        assert_eq!(result.objective_sec, result.profile.total_time_sec);
+        // Baseline + 4 singles + valid ordered pairs
+        assert!(result.evaluated_combinations > 5);
+        for plan in &result.best_plan[1..] {
+            assert!(plan.gas.o2_fraction > 0.21);
+        }
+    }
+
+    #[test]
+    fn test_more_gases_never_worse() {
+        let candidates = vec![gas(0.50, 0.0), gas(1.0, 0.0)];
+        let one = optimize_deco_gases(params(
+            profile(45.0, 25, gas(0.21, 0.35)),
+            candidates.clone(),
+            1,
+        ))
+        .unwrap();
+        let two =
+            optimize_deco_gases(params(profile(45.0, 25, gas(0.21, 0.35)), candidates, 2)).unwrap();
+        assert!(two.objective_sec <= one.objective_sec);
+        assert!(one.best_plan.len() <= 2);
+    }
+
+    #[test]
+    fn test_total_deco_objective() {
+        let mut p = params(profile(40.0, 25, gas(0.21, 0.0)), vec![gas(0.50, 0.0)], 1);
+        p.objective = GasOptimizerObjective::TotalDeco;
+        let result = optimize_deco_gases(p).unwrap();
+        let deco: i32 = result
+            .profile
+            .planned_stops
+            .iter()
+            .map(|s| s.duration_sec)
+            .sum();
+        assert_eq!(result.objective_sec, deco);
+        assert!(result.objective_sec <= result.baseline_objective_sec);
+    }
+
+    #[test]
+    fn test_no_deco_dive_keeps_bottom_gas() {
+        let result = optimize_deco_gases(params(
+            profile(18.0, 20, gas(0.21, 0.0)),
+            vec![gas(0.50, 0.0)],
+            1,
+        ))
+        .unwrap();
+        // No stops to shorten: ties go to the plan with fewer gases
+        assert_eq!(result.best_plan.len(), 1);
+        assert_eq!(result.objective_sec, result.baseline_objective_sec);
+    }
+
+    #[test]
+    fn test_invalid_params_rejected() {
+        let base = params(profile(45.0, 20, gas(0.21, 0.0)), vec![gas(0.5, 0.0)], 1);
+
+        let mut p = base.clone();
+        p.max_deco_gases = 0;
+        assert!(optimize_deco_gases(p).is_err());
+
+        let mut p = base.clone();
+        p.candidate_gases.clear();
+        assert!(optimize_deco_gases(p).is_err());
+
+        let mut p = base.clone();
+        p.candidate_gases = vec![gas(0.8, 0.3)];
+        assert!(optimize_deco_gases(p).is_err());
+
+        let mut p = base;
+        p.max_ppo2 = Some(0.0);
+        assert!(optimize_deco_gases(p).is_err());
+    }
+
+    #[test]
+    fn test_combination_limit() {
+        let mut p = params(profile(60.0, 20, gas(0.10, 0.70)), vec![], 4);
+        p.candidate_range = Some(GasMixRange {
+            min_o2_fraction: 0.11,
+            max_o2_fraction: 1.0,
+            min_he_fraction: 0.0,
+            max_he_fraction: 0.5,
+            step_fraction: 0.01,
+        });
+        match optimize_deco_gases(p) {
+            Err(DecoSimError::InvalidParam { msg }) => assert!(msg.contains("combinations")),
+            other => panic!("expected combination limit error, got {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,756 @@
+//! Gas consumption and reserve planning for generated profiles.
+//!
+//! Consumption is integrated per sample interval as SAC × ambient pressure,
+//! charged to the gas of the interval's starting sample, up to surfacing
+//! (the surface tail breathes air, not the cylinders). On an SCR the
+//! loop's supply rate replaces SAC; CCR intervals draw nothing. Cylinders are
+//! matched to gases by their position in the profile's gas plan, and gas
+//! volumes are treated as ideal (surface litres = water volume × pressure).
+
+use std::collections::HashMap;
+
+use super::profile_generator::ProfileGenResult;
+use super::scr::{self, ScrParams};
+use super::shared::*;
+use super::types::DecoSimError;
+use crate::metrics::SampleInput;
+
+/// Divers sharing gas on a rock-bottom ascent.
+const ROCK_BOTTOM_DIVERS: f64 = 2.0;
+/// Default time spent solving the problem at the worst point (seconds).
+const DEFAULT_PROBLEM_SOLVING_SEC: i32 = 60;
+
+/// A cylinder carried on the dive.
+#[derive(Debug, Clone)]
+pub struct CylinderInput {
+    /// Gas breathed from this cylinder (position in the profile's gas plan).
+    pub gas_index: i32,
+    /// Water volume in litres.
+    pub volume_l: f64,
+    /// Fill pressure in bar.
+    pub start_pressure_bar: f64,
+    /// Pressure that must remain at the end of the dive in bar.
+    pub reserve_pressure_bar: f64,
+}
+
+/// Gas used from a cylinder over a profile.
+#[derive(Debug, Clone)]
+pub struct GasUsage {
+    /// Gas breathed from the cylinder (position in the gas plan).
+    pub gas_index: i32,
+    /// Gas used in surface litres.
+    pub used_l: f64,
+    /// Gas available above the reserve in surface litres.
+    pub available_l: f64,
+    /// Cylinder pressure at the end of the dive in bar.
+    pub end_pressure_bar: f64,
+}
+
+impl GasUsage {
+    /// True if the cylinder stays at or above its reserve.
+    pub fn within_reserve(&self) -> bool {
+        self.used_l <= self.available_l
+    }
+}
+
+/// How the untouchable gas reserve is sized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReserveStrategy {
+    /// One third of each cylinder's fill is held in reserve.
+    Thirds,
+    /// Enough gas for two divers to ascend from the end of the bottom phase
+    /// on the planned ascent, at the stressed SAC, after solving the problem.
+    /// An SCR ascent is sized as an open-circuit bailout.
+    RockBottom,
+}
+
+/// Parameters for gas logistics on a generated profile.
+#[derive(Debug, Clone)]
+pub struct GasLogisticsParams {
+    /// Profile from `generate_dive_profile`.
+    pub profile: ProfileGenResult,
+    /// Cylinders carried; `reserve_pressure_bar` acts as a minimum reserve.
+    pub cylinders: Vec<CylinderInput>,
+    /// Surface air consumption on the bottom in L/min.
+    pub bottom_sac_l_min: f64,
+    /// Surface air consumption after the bottom phase in L/min (default: bottom SAC).
+    pub deco_sac_l_min: Option<f64>,
+    /// Reserve sizing strategy.
+    pub reserve: ReserveStrategy,
+    /// Per-diver SAC during a rock-bottom ascent in L/min (default: bottom SAC).
+    pub stressed_sac_l_min: Option<f64>,
+    /// Time at the worst point before the rock-bottom ascent starts (default 60 s).
+    pub problem_solving_sec: Option<i32>,
+    /// Surface pressure in bar (default 1.01325).
+    pub surface_pressure_bar: Option<f64>,
+    /// SCR settings the profile was generated with. Required for an SCR
+    /// profile, whose supply gas is drawn at the loop's supply rate.
+    pub scr: Option<ScrParams>,
+}
+
+/// Gas plan for one cylinder.
+#[derive(Debug, Clone)]
+pub struct CylinderPlan {
+    /// Gas breathed from the cylinder (position in the gas plan).
+    pub gas_index: i32,
+    /// Gas used over the whole profile in surface litres.
+    pub used_l: f64,
+    /// Reserve held back in surface litres.
+    pub reserve_l: f64,
+    /// Pressure corresponding to the reserve in bar.
+    pub reserve_pressure_bar: f64,
+    /// Cylinder pressure at the end of the dive in bar.
+    pub end_pressure_bar: f64,
+    /// Pressure at which the ascent must begin, for cylinders breathed at the
+    /// end of the bottom phase.
+    pub turn_pressure_bar: Option<f64>,
+    /// True if the cylinder ends the dive with its reserve intact.
+    pub feasible: bool,
+}
+
+/// Result of gas logistics planning.
+#[derive(Debug, Clone)]
+pub struct GasLogisticsResult {
+    /// One entry per input cylinder, in input order.
+    pub cylinders: Vec<CylinderPlan>,
+    /// True if every cylinder keeps its reserve and every breathed gas is carried.
+    pub feasible: bool,
+    /// Reason the plan is infeasible, if any.
+    pub message: Option<String>,
+}
+
+/// Validate cylinders against the number of gases in the plan.
+pub(crate) fn validate_cylinders(
+    cylinders: &[CylinderInput],
+    gas_count: usize,
+) -> Result<(), DecoSimError> {
+    for cyl in cylinders {
+        if cyl.gas_index < 0 || cyl.gas_index as usize >= gas_count {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!(
+                    "cylinder gas_index ({}) must refer to a gas in the plan (0..{gas_count})",
+                    cyl.gas_index
+                ),
+            });
+        }
+        if cyl.volume_l <= 0.0 {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!("cylinder volume_l ({}) must be > 0", cyl.volume_l),
+            });
+        }
+        if cyl.reserve_pressure_bar < 0.0 || cyl.start_pressure_bar <= cyl.reserve_pressure_bar {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!(
+                    "cylinder start_pressure_bar ({}) must exceed reserve_pressure_bar ({}) >= 0",
+                    cyl.start_pressure_bar, cyl.reserve_pressure_bar
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Surface litres consumed per gas index.
+///
+/// `bottom_sac_l_min` applies up to `bottom_end_t_sec`, `deco_sac_l_min`
+/// afterwards. With `scr`, the loop's supply rate applies instead. CCR
+/// samples (with a setpoint) consume nothing.
+pub(crate) fn gas_used_by_mix(
+    samples: &[SampleInput],
+    bottom_end_t_sec: i32,
+    bottom_sac_l_min: f64,
+    deco_sac_l_min: f64,
+    scr: Option<&ScrParams>,
+    surface_p: f64,
+) -> HashMap<i32, f64> {
+    let mut used: HashMap<i32, f64> = HashMap::new();
+    let mut current_gas = samples.first().and_then(|s| s.gasmix_index).unwrap_or(0);
+    for pair in samples.windows(2) {
+        let (prev, sample) = (&pair[0], &pair[1]);
+        if let Some(gas) = prev.gasmix_index {
+            current_gas = gas;
+        }
+        if prev.setpoint_ppo2.is_some() {
+            continue;
+        }
+        let dt_min = (sample.t_sec - prev.t_sec) as f64 / 60.0;
+        let avg_depth_m = ((prev.depth_m as f64 + sample.depth_m as f64) / 2.0).max(0.0);
+        let ambient_p = depth_to_pressure(avg_depth_m, surface_p);
+        let l_min = match scr {
+            Some(scr) => scr::scr_supply_l_min(scr, ambient_p),
+            None if prev.t_sec < bottom_end_t_sec => bottom_sac_l_min * ambient_p,
+            None => deco_sac_l_min * ambient_p,
+        };
+        *used.entry(current_gas).or_insert(0.0) += l_min * dt_min;
+    }
+    used
+}
+
+fn usable_l(cyl: &CylinderInput) -> f64 {
+    cyl.volume_l * (cyl.start_pressure_bar - cyl.reserve_pressure_bar)
+}
+
+/// Fraction of its gas's consumption drawn from `cyl`.
+///
+/// Cylinders sharing a gas are drawn down in proportion to their usable
+/// volume, so they all reach their reserve together.
+fn draw_share(cylinders: &[CylinderInput], cyl: &CylinderInput) -> f64 {
+    let pool: f64 = cylinders
+        .iter()
+        .filter(|c| c.gas_index == cyl.gas_index)
+        .map(usable_l)
+        .sum();
+    if pool > 0.0 {
+        usable_l(cyl) / pool
+    } else {
+        0.0
+    }
+}
+
+/// Distribute per-gas consumption over the cylinders carrying that gas.
+pub(crate) fn cylinder_usage(
+    cylinders: &[CylinderInput],
+    used_by_mix: &HashMap<i32, f64>,
+) -> Vec<GasUsage> {
+    cylinders
+        .iter()
+        .map(|cyl| {
+            let total_used = used_by_mix.get(&cyl.gas_index).copied().unwrap_or(0.0);
+            let used_l = total_used * draw_share(cylinders, cyl);
+            GasUsage {
+                gas_index: cyl.gas_index,
+                used_l,
+                available_l: usable_l(cyl),
+                end_pressure_bar: cyl.start_pressure_bar - used_l / cyl.volume_l,
+            }
+        })
+        .collect()
+}
+
+/// Plan gas volumes, reserves and turn pressures for a generated profile.
+pub fn plan_gas_logistics(params: &GasLogisticsParams) -> Result<GasLogisticsResult, DecoSimError> {
+    validate_logistics_params(params)?;
+    let profile = &params.profile;
+    let surface_p = params
+        .surface_pressure_bar
+        .unwrap_or(DEFAULT_SURFACE_PRESSURE);
+    let bottom_sac = params.bottom_sac_l_min;
+    let deco_sac = params.deco_sac_l_min.unwrap_or(bottom_sac);
+
+    let scr = params.scr.as_ref();
+    let samples = profile.dive_samples();
+    let used = gas_used_by_mix(
+        samples,
+        profile.bottom_end_t_sec,
+        bottom_sac,
+        deco_sac,
+        scr,
+        surface_p,
+    );
+    let ascent_samples: Vec<SampleInput> = samples
+        .iter()
+        .filter(|s| s.t_sec >= profile.bottom_end_t_sec)
+        .cloned()
+        .collect();
+    let ascent_used = gas_used_by_mix(&ascent_samples, 0, deco_sac, deco_sac, scr, surface_p);
+    let bottom_gas = gas_at(&profile.samples, profile.bottom_end_t_sec);
+    let rock_bottom = match params.reserve {
+        ReserveStrategy::Thirds => HashMap::new(),
+        ReserveStrategy::RockBottom => rock_bottom_by_mix(params, &ascent_samples, surface_p),
+    };
+
+    let cylinders: Vec<CylinderPlan> = params
+        .cylinders
+        .iter()
+        .map(|cyl| {
+            let share = draw_share(&params.cylinders, cyl);
+            let used_l = used.get(&cyl.gas_index).copied().unwrap_or(0.0) * share;
+            let strategy_reserve_l = match params.reserve {
+                ReserveStrategy::Thirds => cyl.volume_l * cyl.start_pressure_bar / 3.0,
+                ReserveStrategy::RockBottom => {
+                    rock_bottom.get(&cyl.gas_index).copied().unwrap_or(0.0) * share
+                }
+            };
+            let reserve_l = strategy_reserve_l.max(cyl.volume_l * cyl.reserve_pressure_bar);
+            let reserve_pressure_bar = reserve_l / cyl.volume_l;
+            let end_pressure_bar = cyl.start_pressure_bar - used_l / cyl.volume_l;
+            let turn_pressure_bar = (Some(cyl.gas_index) == bottom_gas).then(|| {
+                let ascent_l = ascent_used.get(&cyl.gas_index).copied().unwrap_or(0.0) * share;
+                let turn = reserve_pressure_bar + ascent_l / cyl.volume_l;
+                match params.reserve {
+                    // Thirds never turns later than two thirds of the fill.
+                    ReserveStrategy::Thirds => turn.max(cyl.start_pressure_bar * 2.0 / 3.0),
+                    ReserveStrategy::RockBottom => turn,
+                }
+            });
+            let bottom_end_pressure = end_pressure_bar
+                + ascent_used.get(&cyl.gas_index).copied().unwrap_or(0.0) * share / cyl.volume_l;
+            let feasible = end_pressure_bar >= reserve_pressure_bar - 1e-9
+                && turn_pressure_bar.is_none_or(|turn| bottom_end_pressure >= turn - 1e-9);
+            CylinderPlan {
+                gas_index: cyl.gas_index,
+                used_l,
+                reserve_l,
+                reserve_pressure_bar,
+                end_pressure_bar,
+                turn_pressure_bar,
+                feasible,
+            }
+        })
+        .collect();
+
+    let mut missing: Vec<i32> = used
+        .keys()
+        .chain(rock_bottom.keys())
+        .copied()
+        .filter(|g| !params.cylinders.iter().any(|c| c.gas_index == *g))
+        .collect();
+    missing.sort_unstable();
+    missing.dedup();
+
+    let message = if let Some(gas) = missing.first() {
+        Some(format!("no cylinder carries gas {gas}"))
+    } else {
+        cylinders.iter().find(|c| !c.feasible).map(|c| {
+            if c.end_pressure_bar < c.reserve_pressure_bar {
+                format!(
+                    "gas {} ends at {:.0} bar, below its {:.0} bar reserve",
+                    c.gas_index, c.end_pressure_bar, c.reserve_pressure_bar
+                )
+            } else {
+                format!(
+                    "gas {} passes its {:.0} bar turn pressure before the ascent",
+                    c.gas_index,
+                    c.turn_pressure_bar.unwrap_or(c.reserve_pressure_bar)
+                )
+            }
+        })
+    };
+
+    Ok(GasLogisticsResult {
+        feasible: message.is_none(),
+        cylinders,
+        message,
+    })
+}
+
+/// Gas breathed over the interval starting at `t_sec`.
+fn gas_at(samples: &[SampleInput], t_sec: i32) -> Option<i32> {
+    let mut gas = samples.first().and_then(|s| s.gasmix_index);
+    for sample in samples.iter().take_while(|s| s.t_sec <= t_sec) {
+        if let Some(g) = sample.gasmix_index {
+            gas = Some(g);
+        }
+    }
+    gas
+}
+
+/// Team gas needed per mix to solve a problem at the end of the bottom phase
+/// and then fly the planned ascent at the stressed SAC.
+fn rock_bottom_by_mix(
+    params: &GasLogisticsParams,
+    ascent_samples: &[SampleInput],
+    surface_p: f64,
+) -> HashMap<i32, f64> {
+    let team_sac =
+        ROCK_BOTTOM_DIVERS * params.stressed_sac_l_min.unwrap_or(params.bottom_sac_l_min);
+    let mut needed = gas_used_by_mix(ascent_samples, 0, team_sac, team_sac, None, surface_p);
+    if let Some(start) = ascent_samples.first() {
+        let gas = start
+            .gasmix_index
+            .unwrap_or_else(|| gas_at(&params.profile.samples, start.t_sec).unwrap_or(0));
+        let problem_min = params
+            .problem_solving_sec
+            .unwrap_or(DEFAULT_PROBLEM_SOLVING_SEC) as f64
+            / 60.0;
+        let ambient_p = depth_to_pressure((start.depth_m as f64).max(0.0), surface_p);
+        *needed.entry(gas).or_insert(0.0) += team_sac * ambient_p * problem_min;
+    }
+    needed
+}
+
+fn validate_logistics_params(params: &GasLogisticsParams) -> Result<(), DecoSimError> {
+    if params.profile.samples.is_empty() {
+        return Err(DecoSimError::EmptySamples {
+            msg: "No samples provided".to_string(),
+        });
+    }
+    if params.cylinders.is_empty() {
+        return Err(DecoSimError::InvalidParam {
+            msg: "at least one cylinder is required".to_string(),
+        });
+    }
+    match &params.scr {
+        Some(scr) => scr::validate_scr(scr)?,
+        None if params
+            .profile
+            .samples
+            .iter()
+            .any(|s| s.ppo2.is_some() && s.setpoint_ppo2.is_none()) =>
+        {
+            return Err(DecoSimError::InvalidParam {
+                msg: "profile has an SCR loop; scr settings are required".to_string(),
+            });
+        }
+        None => {}
+    }
+    for (name, value) in [
+        ("bottom_sac_l_min", Some(params.bottom_sac_l_min)),
+        ("deco_sac_l_min", params.deco_sac_l_min),
+        ("stressed_sac_l_min", params.stressed_sac_l_min),
+    ] {
+        if let Some(v) = value {
+            if v <= 0.0 {
+                return Err(DecoSimError::InvalidParam {
+                    msg: format!("{name} ({v}) must be > 0"),
+                });
+            }
+        }
+    }
+    if let Some(t) = params.problem_solving_sec {
+        if t < 0 {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!("problem_solving_sec ({t}) must be >= 0"),
+            });
+        }
+    }
+    validate_cylinders(&params.cylinders, params.profile.gas_mixes.len().max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deco::profile_generator::{generate_dive_profile, ProfileGenParams};
+    use crate::deco::scr::ScrMode;
+
+    fn sample(t_sec: i32, depth_m: f32, gasmix_index: Option<i32>) -> SampleInput {
+        SampleInput {
+            t_sec,
+            depth_m,
+            temp_c: 20.0,
+            setpoint_ppo2: None,
+            ceiling_m: None,
+            gf99: None,
+            gasmix_index,
+            ppo2: None,
+            tts_sec: None,
+            ndl_sec: None,
+            deco_stop_depth_m: None,
+            at_plus_five_tts_min: None,
+        }
+    }
+
+    fn cylinder(gas_index: i32, volume_l: f64) -> CylinderInput {
+        CylinderInput {
+            gas_index,
+            volume_l,
+            start_pressure_bar: 200.0,
+            reserve_pressure_bar: 50.0,
+        }
+    }
+
+    #[test]
+    fn test_consumption_scales_with_pressure() {
+        // 10 min at 20 m at 20 L/min
+        let samples = vec![sample(0, 20.0, Some(0)), sample(600, 20.0, Some(0))];
+        let used = gas_used_by_mix(&samples, 600, 20.0, 15.0, None, DEFAULT_SURFACE_PRESSURE);
+        let expected = 20.0 * depth_to_pressure(20.0, DEFAULT_SURFACE_PRESSURE) * 10.0;
+        assert!((used[&0] - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_deco_sac_and_gas_attribution() {
+        // Bottom on gas 0, then a stop where the switch to gas 1 happens at arrival
+        let samples = vec![
+            sample(0, 30.0, Some(0)),
+            sample(600, 30.0, Some(0)),
+            sample(720, 6.0, Some(1)),
+            sample(1320, 6.0, Some(1)),
+        ];
+        let used = gas_used_by_mix(&samples, 600, 20.0, 10.0, None, DEFAULT_SURFACE_PRESSURE);
+        let travel = 10.0 * depth_to_pressure(18.0, DEFAULT_SURFACE_PRESSURE) * 2.0;
+        let bottom = 20.0 * depth_to_pressure(30.0, DEFAULT_SURFACE_PRESSURE) * 10.0;
+        let stop = 10.0 * depth_to_pressure(6.0, DEFAULT_SURFACE_PRESSURE) * 10.0;
+        assert!((used[&0] - (bottom + travel)).abs() < 1e-9);
+        assert!((used[&1] - stop).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_ccr_consumes_nothing() {
+        let mut a = sample(0, 30.0, Some(0));
+        a.setpoint_ppo2 = Some(1.3);
+        a.ppo2 = Some(1.3);
+        let samples = vec![a, sample(600, 30.0, Some(0))];
+        let used = gas_used_by_mix(&samples, 600, 20.0, 20.0, None, DEFAULT_SURFACE_PRESSURE);
+        assert!(!used.contains_key(&0));
+    }
+
+    fn active_scr() -> ScrParams {
+        ScrParams {
+            mode: ScrMode::Active,
+            o2_consumption_l_min: 1.0,
+            dump_ratio: None,
+            injection_rate_l_min: Some(10.0),
+        }
+    }
+
+    #[test]
+    fn test_scr_draws_supply_rate() {
+        // 10 min on an active loop injecting 10 L/min, at any depth
+        let mut a = sample(0, 30.0, Some(0));
+        a.ppo2 = Some(0.9);
+        let samples = vec![a, sample(600, 30.0, Some(0))];
+        let scr = active_scr();
+        let used = gas_used_by_mix(
+            &samples,
+            600,
+            20.0,
+            20.0,
+            Some(&scr),
+            DEFAULT_SURFACE_PRESSURE,
+        );
+        assert!((used[&0] - 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_scr_profile_uses_supply_gas() {
+        let mut gen = profile_params(30.0, 30, &[(0.32, 0.0, None)]);
+        gen.scr = Some(active_scr());
+        let p = generate_dive_profile(gen).unwrap();
+        let surface_t = p.surface_t_sec;
+        let mut params = logistics(p, vec![cylinder(0, 24.0)], ReserveStrategy::Thirds);
+
+        // The loop state alone does not say how much supply it draws
+        assert!(matches!(
+            plan_gas_logistics(&params),
+            Err(DecoSimError::InvalidParam { .. })
+        ));
+
+        params.scr = Some(active_scr());
+        let result = plan_gas_logistics(&params).unwrap();
+        let used = result.cylinders[0].used_l;
+        assert!(used > 0.0);
+        assert!((used - 10.0 * surface_t as f64 / 60.0).abs() < 1e-6);
+
+        // A rock-bottom reserve is sized as an open-circuit bailout
+        params.reserve = ReserveStrategy::RockBottom;
+        let result = plan_gas_logistics(&params).unwrap();
+        assert!(result.cylinders[0].reserve_l > 0.0);
+    }
+
+    #[test]
+    fn test_cylinder_usage_shared_gas() {
+        let cylinders = vec![cylinder(0, 12.0), cylinder(0, 12.0), cylinder(1, 7.0)];
+        let used = HashMap::from([(0, 3000.0), (1, 1200.0)]);
+        let usage = cylinder_usage(&cylinders, &used);
+        assert!((usage[0].used_l - 1500.0).abs() < 1e-9);
+        assert!((usage[0].end_pressure_bar - 75.0).abs() < 1e-9);
+        assert!(usage[0].within_reserve());
+        assert!((usage[2].available_l - 1050.0).abs() < 1e-9);
+        assert!(!usage[2].within_reserve());
+    }
+
+    #[test]
+    fn test_validate_cylinders() {
+        assert!(validate_cylinders(&[cylinder(0, 12.0)], 1).is_ok());
+        assert!(validate_cylinders(&[cylinder(1, 12.0)], 1).is_err());
+        assert!(validate_cylinders(&[cylinder(0, 0.0)], 1).is_err());
+        let mut low = cylinder(0, 12.0);
+        low.start_pressure_bar = 40.0;
+        assert!(validate_cylinders(&[low], 1).is_err());
+    }
+
+    fn profile(depth: f64, bottom_min: i32, gases: &[(f64, f64, Option<f64>)]) -> ProfileGenResult {
+        generate_dive_profile(profile_params(depth, bottom_min, gases)).unwrap()
+    }
+
+    fn profile_params(
+        depth: f64,
+        bottom_min: i32,
+        gases: &[(f64, f64, Option<f64>)],
+    ) -> ProfileGenParams {
+        use crate::buhlmann::GasMixInput;
+        use crate::deco::profile_generator::GasSwitchPlan;
+        use crate::deco::types::DecoModel;
+
+        ProfileGenParams {
+            target_depth_m: depth,
+            bottom_time_sec: bottom_min * 60,
+            descent_rate_m_min: None,
+            ascent_rate_m_min: None,
+            gas_plan: gases
+                .iter()
+                .map(|&(o2, he, switch_depth_m)| GasSwitchPlan {
+                    gas: GasMixInput {
+                        mix_index: 0,
+                        o2_fraction: o2,
+                        he_fraction: he,
+                    },
+                    switch_depth_m,
+                    descent_switch_depth_m: None,
+                })
+                .collect(),
+            model: DecoModel::BuhlmannZhl16c,
+            surface_pressure_bar: None,
+            gf_low: Some(50),
+            gf_high: Some(80),
+            last_stop_depth_m: None,
+            stop_interval_m: None,
+            setpoint_ppo2: None,
+            thalmann_pdcs: None,
+            sample_interval_sec: None,
+            temp_c: None,
+            gas_switch_pause_sec: None,
+            max_ppo2: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            setpoint_schedule: None,
+            scr: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            safety_stop: None,
+            final_ascent_rate_m_min: None,
+            surface_tail_sec: None,
+        }
+    }
+
+    fn logistics(
+        profile: ProfileGenResult,
+        cylinders: Vec<CylinderInput>,
+        reserve: ReserveStrategy,
+    ) -> GasLogisticsParams {
+        GasLogisticsParams {
+            profile,
+            cylinders,
+            bottom_sac_l_min: 20.0,
+            deco_sac_l_min: Some(15.0),
+            reserve,
+            stressed_sac_l_min: None,
+            problem_solving_sec: None,
+            surface_pressure_bar: None,
+            scr: None,
+        }
+    }
+
+    fn zero_floor(mut cyl: CylinderInput) -> CylinderInput {
+        cyl.reserve_pressure_bar = 0.0;
+        cyl
+    }
+
+    #[test]
+    fn test_thirds_short_dive_feasible() {
+        let p = profile(18.0, 20, &[(0.21, 0.0, None)]);
+        let params = logistics(
+            p,
+            vec![zero_floor(cylinder(0, 24.0))],
+            ReserveStrategy::Thirds,
+        );
+        let result = plan_gas_logistics(&params).unwrap();
+        assert!(result.feasible, "{:?}", result.message);
+        let cyl = &result.cylinders[0];
+        assert!((cyl.reserve_pressure_bar - 200.0 / 3.0).abs() < 1e-9);
+        assert!((cyl.turn_pressure_bar.unwrap() - 400.0 / 3.0).abs() < 1e-9);
+        assert!(cyl.end_pressure_bar > cyl.reserve_pressure_bar);
+        assert!((cyl.end_pressure_bar - (200.0 - cyl.used_l / 24.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_thirds_long_dive_infeasible() {
+        let p = profile(30.0, 40, &[(0.21, 0.0, None)]);
+        let params = logistics(
+            p,
+            vec![zero_floor(cylinder(0, 12.0))],
+            ReserveStrategy::Thirds,
+        );
+        let result = plan_gas_logistics(&params).unwrap();
+        assert!(!result.feasible);
+        assert!(!result.cylinders[0].feasible);
+        assert!(result.message.is_some());
+    }
+
+    #[test]
+    fn test_rock_bottom_covers_two_divers_ascent() {
+        let p = profile(40.0, 20, &[(0.21, 0.0, None), (0.50, 0.0, Some(21.0))]);
+        let cylinders = vec![zero_floor(cylinder(0, 24.0)), zero_floor(cylinder(1, 11.0))];
+        let params = logistics(p.clone(), cylinders, ReserveStrategy::RockBottom);
+        let result = plan_gas_logistics(&params).unwrap();
+
+        // Bottom gas reserve covers one minute at depth plus the ascent to the switch
+        let bottom = &result.cylinders[0];
+        let one_minute = 2.0 * 20.0 * depth_to_pressure(40.0, DEFAULT_SURFACE_PRESSURE);
+        assert!(bottom.reserve_l > one_minute);
+        let turn = bottom.turn_pressure_bar.unwrap();
+        assert!(turn > bottom.reserve_pressure_bar);
+
+        // Deco gas needs a team reserve too, but has no turn pressure
+        let deco = &result.cylinders[1];
+        assert!(deco.reserve_l > 0.0);
+        assert!(deco.turn_pressure_bar.is_none());
+        assert!(deco.used_l > 0.0);
+
+        // A longer problem-solving time grows only the bottom gas reserve
+        let mut longer = params.clone();
+        longer.problem_solving_sec = Some(180);
+        let longer = plan_gas_logistics(&longer).unwrap();
+        assert!((longer.cylinders[0].reserve_l - bottom.reserve_l - 2.0 * one_minute).abs() < 1e-6);
+        assert!((longer.cylinders[1].reserve_l - deco.reserve_l).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_surface_tail_not_charged() {
+        let gases = [(0.21, 0.0, None), (0.50, 0.0, Some(21.0))];
+        let plan = |p: ProfileGenResult| {
+            let cylinders = vec![zero_floor(cylinder(0, 24.0)), zero_floor(cylinder(1, 11.0))];
+            plan_gas_logistics(&logistics(p, cylinders, ReserveStrategy::RockBottom)).unwrap()
+        };
+        let mut gen = profile_params(40.0, 20, &gases);
+        let base = plan(generate_dive_profile(gen.clone()).unwrap());
+        gen.surface_tail_sec = Some(600);
+        let tailed = plan(generate_dive_profile(gen).unwrap());
+
+        for (t, b) in tailed.cylinders.iter().zip(&base.cylinders) {
+            assert_eq!(t.used_l, b.used_l);
+            assert_eq!(t.reserve_l, b.reserve_l);
+        }
+    }
+
+    #[test]
+    fn test_floor_reserve_and_shared_cylinders() {
+        let p = profile(18.0, 20, &[(0.21, 0.0, None)]);
+        let params = logistics(
+            p,
+            vec![cylinder(0, 12.0), cylinder(0, 12.0)],
+            ReserveStrategy::RockBottom,
+        );
+        let result = plan_gas_logistics(&params).unwrap();
+        for cyl in &result.cylinders {
+            // Shallow rock bottom stays under the 50 bar floor
+            assert!((cyl.reserve_pressure_bar - 50.0).abs() < 1e-9);
+        }
+        assert!((result.cylinders[0].used_l - result.cylinders[1].used_l).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_missing_cylinder_for_deco_gas() {
+        let p = profile(40.0, 20, &[(0.21, 0.0, None), (0.50, 0.0, Some(21.0))]);
+        let params = logistics(p, vec![cylinder(0, 24.0)], ReserveStrategy::Thirds);
+        let result = plan_gas_logistics(&params).unwrap();
+        assert!(!result.feasible);
+        assert_eq!(result.message.as_deref(), Some("no cylinder carries gas 1"));
+    }
+
+    #[test]
+    fn test_logistics_invalid_params() {
+        let p = profile(18.0, 10, &[(0.21, 0.0, None)]);
+        let mut params = logistics(p, vec![], ReserveStrategy::Thirds);
+        assert!(plan_gas_logistics(&params).is_err());
+        params.cylinders = vec![cylinder(0, 12.0)];
+        params.bottom_sac_l_min = 0.0;
+        assert!(plan_gas_logistics(&params).is_err());
+        params.bottom_sac_l_min = 20.0;
+        params.problem_solving_sec = Some(-1);
+        assert!(plan_gas_logistics(&params).is_err());
+    }
+}
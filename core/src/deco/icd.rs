@@ -0,0 +1,329 @@
+//! Isobaric counterdiffusion (ICD) checks on gas switches.
+//!
+//! Applies the "rule of fifths": at a switch, the rise in N2 fraction should
+//! not exceed one fifth of the drop in He fraction. Switches are detected
+//! from `gasmix_index` changes, so the same analysis works on generated
+//! profiles and on logged dives. Fractions are the raw gas fractions; for
+//! CCR samples they describe the diluent, not the loop.
+
+use super::shared::*;
+use crate::buhlmann::GasMixInput;
+use crate::metrics::SampleInput;
+
+/// Depth spacing used to round suggested switch depths (metres).
+const SUGGESTED_STOP_INTERVAL: f64 = 3.0;
+
+/// Inner-ear ICD risk classification for one switch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IcdRisk {
+    /// N2 fraction does not rise.
+    None,
+    /// N2 rises by no more than one fifth of the He drop.
+    WithinRule,
+    /// N2 rises by more than one fifth of the He drop.
+    ExceedsRule,
+}
+
+/// ICD analysis of a single gas switch.
+#[derive(Debug, Clone)]
+pub struct GasSwitchIcd {
+    /// Time of the first sample on the new gas (seconds).
+    pub t_sec: i32,
+    /// Depth of the switch (metres).
+    pub depth_m: f32,
+    /// Gas mix index before the switch.
+    pub from_mix_index: i32,
+    /// Gas mix index after the switch.
+    pub to_mix_index: i32,
+    /// Change in N2 fraction (positive = more N2).
+    pub delta_n2_fraction: f64,
+    /// Change in He fraction (negative = less He).
+    pub delta_he_fraction: f64,
+    /// Change in inspired N2 partial pressure at the switch depth (bar).
+    pub delta_ppn2_bar: f64,
+    /// Change in inspired He partial pressure at the switch depth (bar).
+    pub delta_pphe_bar: f64,
+    /// Rule-of-fifths classification.
+    pub risk: IcdRisk,
+    /// Mix with the new gas's O2 and the least He that satisfies the rule,
+    /// in whole percent. Only set when the rule is exceeded.
+    pub suggested_mix: Option<GasMixInput>,
+    /// Deepest 3 m stop at which switching to the new gas does not raise the
+    /// inspired N2 partial pressure above the old gas at the actual switch
+    /// depth. Only set when the rule is exceeded and such a depth exists.
+    pub suggested_switch_depth_m: Option<f64>,
+}
+
+/// Analyse every gas switch in a profile.
+pub fn analyze_gas_switches(
+    samples: &[SampleInput],
+    gas_mixes: &[GasMixInput],
+    surface_pressure_bar: Option<f64>,
+) -> Vec<GasSwitchIcd> {
+    let surface_p = surface_pressure_bar.unwrap_or(DEFAULT_SURFACE_PRESSURE);
+    let lookup = |idx: i32| gas_mixes.iter().find(|g| g.mix_index == idx);
+
+    let mut switches = Vec::new();
+    let mut current = samples.first().and_then(|s| s.gasmix_index);
+    for sample in samples.iter().skip(1) {
+        let Some(next) = sample.gasmix_index else {
+            continue;
+        };
+        let Some(prev) = current else {
+            current = Some(next);
+            continue;
+        };
+        if next == prev {
+            continue;
+        }
+        current = Some(next);
+        let (Some(from), Some(to)) = (lookup(prev), lookup(next)) else {
+            continue;
+        };
+        switches.push(analyze_switch(sample, from, to, surface_p));
+    }
+    switches
+}
+
+fn n2_fraction(gas: &GasMixInput) -> f64 {
+    (1.0 - gas.o2_fraction - gas.he_fraction).max(0.0)
+}
+
+fn analyze_switch(
+    sample: &SampleInput,
+    from: &GasMixInput,
+    to: &GasMixInput,
+    surface_p: f64,
+) -> GasSwitchIcd {
+    let delta_n2 = n2_fraction(to) - n2_fraction(from);
+    let delta_he = to.he_fraction - from.he_fraction;
+    let depth_m = (sample.depth_m as f64).max(0.0);
+    let inspired_p = depth_to_pressure(depth_m, surface_p) - P_WATER_VAPOR;
+
+    let risk = if delta_n2 <= 1e-9 {
+        IcdRisk::None
+    } else if delta_n2 <= -delta_he / 5.0 + 1e-9 {
+        IcdRisk::WithinRule
+    } else {
+        IcdRisk::ExceedsRule
+    };
+
+    let (suggested_mix, suggested_switch_depth_m) = if risk == IcdRisk::ExceedsRule {
+        (
+            suggest_mix(from, to),
+            suggest_depth(from, to, depth_m, surface_p),
+        )
+    } else {
+        (None, None)
+    };
+
+    GasSwitchIcd {
+        t_sec: sample.t_sec,
+        depth_m: sample.depth_m,
+        from_mix_index: from.mix_index,
+        to_mix_index: to.mix_index,
+        delta_n2_fraction: delta_n2,
+        delta_he_fraction: delta_he,
+        delta_ppn2_bar: delta_n2 * inspired_p,
+        delta_pphe_bar: delta_he * inspired_p,
+        risk,
+        suggested_mix,
+        suggested_switch_depth_m,
+    }
+}
+
+/// Least He (whole percent) for the new gas's O2 that satisfies the rule.
+///
+/// With fN2' = 1 − fO2 − fHe', the rule fN2' − fN2₀ ≤ (fHe₀ − fHe') / 5
+/// gives fHe' ≥ (5·(1 − fO2 − fN2₀) − fHe₀) / 4.
+fn suggest_mix(from: &GasMixInput, to: &GasMixInput) -> Option<GasMixInput> {
+    let required = (5.0 * (1.0 - to.o2_fraction - n2_fraction(from)) - from.he_fraction) / 4.0;
+    let he = ((required.max(0.0) * 100.0) - 1e-6).ceil() / 100.0;
+    if to.o2_fraction + he > 1.0 + 1e-9 {
+        return None;
+    }
+    Some(GasMixInput {
+        mix_index: to.mix_index,
+        o2_fraction: to.o2_fraction,
+        he_fraction: he,
+    })
+}
+
+/// Deepest stop where the new gas's inspired N2 does not exceed the old
+/// gas's inspired N2 at the original switch depth.
+fn suggest_depth(
+    from: &GasMixInput,
+    to: &GasMixInput,
+    depth_m: f64,
+    surface_p: f64,
+) -> Option<f64> {
+    let fn2_to = n2_fraction(to);
+    if fn2_to <= 0.0 {
+        return None;
+    }
+    let ppn2_before = n2_fraction(from) * (depth_to_pressure(depth_m, surface_p) - P_WATER_VAPOR);
+    let max_p = ppn2_before / fn2_to + P_WATER_VAPOR;
+    let max_depth = pressure_to_depth(max_p, surface_p);
+    let stop = (max_depth / SUGGESTED_STOP_INTERVAL).floor() * SUGGESTED_STOP_INTERVAL;
+    (stop > 0.0).then_some(stop)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(t_sec: i32, depth_m: f32, gasmix_index: Option<i32>) -> SampleInput {
+        SampleInput {
+            t_sec,
+            depth_m,
+            temp_c: 20.0,
+            setpoint_ppo2: None,
+            ceiling_m: None,
+            gf99: None,
+            gasmix_index,
+            ppo2: None,
+            tts_sec: None,
+            ndl_sec: None,
+            deco_stop_depth_m: None,
+            at_plus_five_tts_min: None,
+        }
+    }
+
+    fn mix(mix_index: i32, o2: f64, he: f64) -> GasMixInput {
+        GasMixInput {
+            mix_index,
+            o2_fraction: o2,
+            he_fraction: he,
+        }
+    }
+
+    #[test]
+    fn test_detects_switches_from_samples() {
+        let gases = vec![mix(0, 0.18, 0.45), mix(1, 0.50, 0.0), mix(2, 1.0, 0.0)];
+        let samples = vec![
+            sample(0, 0.0, Some(0)),
+            sample(600, 60.0, None),
+            sample(1200, 21.0, Some(1)),
+            sample(1500, 21.0, Some(1)),
+            sample(1800, 6.0, Some(2)),
+        ];
+        let result = analyze_gas_switches(&samples, &gases, None);
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].from_mix_index, 0);
+        assert_eq!(result[0].to_mix_index, 1);
+        assert_eq!(result[0].t_sec, 1200);
+        assert_eq!(result[1].from_mix_index, 1);
+        assert_eq!(result[1].to_mix_index, 2);
+        // 50% → O2 drops N2: no ICD concern
+        assert_eq!(result[1].risk, IcdRisk::None);
+    }
+
+    #[test]
+    fn test_trimix_to_nitrox_exceeds_rule() {
+        // 18/45 → EAN50: N2 37% → 50% (+13), He 45% → 0 (−45): 13 > 9
+        let gases = vec![mix(0, 0.18, 0.45), mix(1, 0.50, 0.0)];
+        let samples = vec![sample(0, 21.0, Some(0)), sample(10, 21.0, Some(1))];
+        let result = analyze_gas_switches(&samples, &gases, None);
+        let sw = &result[0];
+        assert_eq!(sw.risk, IcdRisk::ExceedsRule);
+        assert!((sw.delta_n2_fraction - 0.13).abs() < 1e-9);
+        assert!((sw.delta_he_fraction + 0.45).abs() < 1e-9);
+        let inspired = depth_to_pressure(21.0, DEFAULT_SURFACE_PRESSURE) - P_WATER_VAPOR;
+        assert!((sw.delta_ppn2_bar - 0.13 * inspired).abs() < 1e-9);
+
+        // Suggested mix keeps 50% O2 and satisfies the rule
+        let suggested = sw.suggested_mix.as_ref().unwrap();
+        assert!((suggested.o2_fraction - 0.50).abs() < 1e-9);
+        let dn2 = (1.0 - 0.50 - suggested.he_fraction) - 0.37;
+        let dhe = suggested.he_fraction - 0.45;
+        assert!(dn2 <= -dhe / 5.0 + 1e-9);
+        // ...and is the least such He in whole percent
+        let less = suggested.he_fraction - 0.01;
+        assert!((1.0 - 0.50 - less) - 0.37 > -(less - 0.45) / 5.0);
+
+        // Suggested depth is shallower and does not raise inspired N2
+        let depth = sw.suggested_switch_depth_m.unwrap();
+        assert!(depth < 21.0);
+        let before = 0.37 * inspired;
+        let after = 0.50 * (depth_to_pressure(depth, DEFAULT_SURFACE_PRESSURE) - P_WATER_VAPOR);
+        assert!(after <= before);
+    }
+
+    #[test]
+    fn test_within_rule() {
+        let gases = vec![mix(0, 0.21, 0.40), mix(1, 0.30, 0.30)];
+        // 21/40 → 30/30: N2 39% → 40% (+1), He −10 → limit 2 → within rule
+        let samples = vec![sample(0, 40.0, Some(0)), sample(10, 40.0, Some(1))];
+        let result = analyze_gas_switches(&samples, &gases, None);
+        assert_eq!(result[0].risk, IcdRisk::WithinRule);
+        assert!(result[0].suggested_mix.is_none());
+        assert!(result[0].suggested_switch_depth_m.is_none());
+    }
+
+    #[test]
+    fn test_unknown_gas_and_no_switch() {
+        let gases = vec![mix(0, 0.21, 0.0)];
+        let samples = vec![
+            sample(0, 10.0, Some(0)),
+            sample(10, 10.0, Some(0)),
+            sample(20, 10.0, Some(5)),
+        ];
+        assert!(analyze_gas_switches(&samples, &gases, None).is_empty());
+        assert!(analyze_gas_switches(&[], &gases, None).is_empty());
+    }
+
+    #[test]
+    fn test_generated_profile_switches() {
+        use crate::deco::profile_generator::{
+            generate_dive_profile, GasSwitchPlan, ProfileGenParams,
+        };
+        use crate::deco::types::DecoModel;
+
+        let profile = generate_dive_profile(ProfileGenParams {
+            target_depth_m: 60.0,
+            bottom_time_sec: 20 * 60,
+            descent_rate_m_min: None,
+            ascent_rate_m_min: None,
+            gas_plan: vec![
+                GasSwitchPlan {
+                    gas: mix(0, 0.18, 0.45),
+                    switch_depth_m: None,
+                    descent_switch_depth_m: None,
+                },
+                GasSwitchPlan {
+                    gas: mix(0, 0.50, 0.0),
+                    switch_depth_m: Some(21.0),
+                    descent_switch_depth_m: None,
+                },
+            ],
+            model: DecoModel::BuhlmannZhl16c,
+            surface_pressure_bar: None,
+            gf_low: Some(50),
+            gf_high: Some(80),
+            last_stop_depth_m: None,
+            stop_interval_m: None,
+            setpoint_ppo2: None,
+            thalmann_pdcs: None,
+            sample_interval_sec: None,
+            temp_c: None,
+            gas_switch_pause_sec: None,
+            max_ppo2: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            setpoint_schedule: None,
+            scr: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            safety_stop: None,
+            final_ascent_rate_m_min: None,
+            surface_tail_sec: None,
+        })
+        .unwrap();
+        let result = analyze_gas_switches(&profile.samples, &profile.gas_mixes, None);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].risk, IcdRisk::ExceedsRule);
+        assert!(result[0].depth_m <= 21.0);
+    }
+}
@@ -0,0 +1,545 @@
+//! Inverse planning: longest bottom time that satisfies user limits.
+//!
+//! Each candidate bottom time is evaluated with the regular profile
+//! generator, then checked against the runtime, TTS, CNS and gas supply
+//! limits. The search brackets the answer by doubling and then bisects to
+//! whole minutes, relying on every constraint growing with bottom time.
+
+use super::gas_usage::{
+    cylinder_usage, gas_used_by_mix, validate_cylinders, CylinderInput, GasUsage,
+};
+use super::oxygen::OxygenExposure;
+use super::profile_generator::{
+    generate_after, PriorDives, ProfileGenParams, ProfileGenResult, DEFAULT_DESCENT_RATE,
+};
+use super::progress::{ComputeControl, Progress};
+use super::shared::*;
+use super::types::DecoSimError;
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// Constraint that limited the bottom time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BottomTimeConstraint {
+    /// Total runtime limit.
+    Runtime,
+    /// Time-to-surface limit at the end of the bottom phase.
+    Tts,
+    /// CNS oxygen toxicity limit.
+    Cns,
+    /// A cylinder would drop below its reserve.
+    GasSupply,
+    /// The deco planner hit its safety limit and the schedule is incomplete.
+    DecoTruncated,
+    /// No limit was reached within the search range.
+    SearchLimit,
+}
+
+/// Parameters for the maximum bottom time solver.
+#[derive(Debug, Clone)]
+pub struct MaxBottomTimeParams {
+    /// Dive to plan. `bottom_time_sec` is ignored.
+    pub profile: ProfileGenParams,
+    /// Maximum total runtime to surfacing in seconds.
+    pub max_runtime_sec: Option<i32>,
+    /// Maximum time to surface at the end of the bottom phase in seconds.
+    pub max_tts_sec: Option<i32>,
+    /// Maximum CNS clock at surfacing in percent.
+    pub max_cns_percent: Option<f64>,
+    /// Cylinders carried. Empty = gas supply is not checked.
+    pub cylinders: Vec<CylinderInput>,
+    /// Surface air consumption during descent and bottom in L/min.
+    pub bottom_sac_l_min: Option<f64>,
+    /// Surface air consumption during the ascent in L/min (default = bottom SAC).
+    pub deco_sac_l_min: Option<f64>,
+    /// Longest bottom time to consider in seconds (default 4 h).
+    pub search_limit_sec: Option<i32>,
+}
+
+/// Result of the maximum bottom time solver.
+#[derive(Debug, Clone)]
+pub struct MaxBottomTimeResult {
+    /// Longest feasible bottom time in seconds (whole minutes, descent included).
+    /// Zero if even the shortest bottom time violates a limit.
+    pub max_bottom_time_sec: i32,
+    /// Constraint that stops the bottom time from growing further.
+    pub binding_constraint: BottomTimeConstraint,
+    /// Generated profile at the maximum bottom time.
+    pub profile: Option<ProfileGenResult>,
+    /// Total runtime to surfacing at the maximum bottom time in seconds.
+    pub runtime_sec: i32,
+    /// Time to surface at the end of the bottom phase in seconds.
+    pub tts_sec: i32,
+    /// CNS clock at surfacing in percent.
+    pub cns_percent: f32,
+    /// Per-cylinder gas use at the maximum bottom time.
+    pub gas_usage: Vec<GasUsage>,
+    /// Number of profiles generated during the search.
+    pub evaluations: u32,
+}
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+const DEFAULT_SEARCH_LIMIT_SEC: i32 = 4 * 3600;
+const STEP_SEC: i32 = 60;
+
+// ============================================================================
+// Public API
+// ============================================================================
+
+/// Find the longest bottom time that keeps all given limits.
+pub fn solve_max_bottom_time(
+    params: MaxBottomTimeParams,
+) -> Result<MaxBottomTimeResult, DecoSimError> {
+    solve(params, Progress::NONE)
+}
+
+/// `solve_max_bottom_time` with cancellation and progress reporting; each
+/// evaluation gets an equal share of the search's worst case.
+pub fn solve_max_bottom_time_with_control(
+    params: MaxBottomTimeParams,
+    control: &ComputeControl,
+) -> Result<MaxBottomTimeResult, DecoSimError> {
+    let result = solve(params, control.progress())?;
+    control.finish();
+    Ok(result)
+}
+
+fn solve(
+    params: MaxBottomTimeParams,
+    progress: Progress,
+) -> Result<MaxBottomTimeResult, DecoSimError> {
+    validate_params(&params)?;
+
+    let search_limit = params.search_limit_sec.unwrap_or(DEFAULT_SEARCH_LIMIT_SEC);
+    let descent_rate = params
+        .profile
+        .descent_rate_m_min
+        .unwrap_or(DEFAULT_DESCENT_RATE);
+    let descent_sec = (params.profile.target_depth_m / descent_rate * 60.0).round() as i32;
+    // Shortest bottom time: the descent rounded up to a whole minute
+    let min_steps = ((descent_sec.max(1) + STEP_SEC - 1) / STEP_SEC).max(1);
+    let max_steps = search_limit / STEP_SEC;
+    if max_steps < min_steps {
+        return Err(DecoSimError::InvalidParam {
+            msg: format!(
+                "search_limit_sec ({search_limit}) is shorter than the descent ({descent_sec} s)"
+            ),
+        });
+    }
+
+    // The first evaluation, then at most log2(range) + 1 doublings and as
+    // many bisection steps.
+    let max_evaluations = 3 + 2 * (max_steps - min_steps).max(1).ilog2() as usize;
+    let mut evaluations = 0u32;
+    let mut evaluate = |steps: i32| -> Result<Evaluation, DecoSimError> {
+        let share = progress.part(evaluations as usize, max_evaluations);
+        evaluations += 1;
+        evaluate_bottom_time(&params, steps * STEP_SEC, share)
+    };
+
+    let first = evaluate(min_steps)?;
+    if let Some(violated) = first.violation {
+        return Ok(MaxBottomTimeResult {
+            max_bottom_time_sec: 0,
+            binding_constraint: violated,
+            profile: None,
+            runtime_sec: 0,
+            tts_sec: 0,
+            cns_percent: 0.0,
+            gas_usage: Vec::new(),
+            evaluations,
+        });
+    }
+
+    // Bracket: double until infeasible or the search limit is reached
+    let mut lo = (min_steps, first);
+    let mut hi: Option<(i32, Evaluation)> = None;
+    let mut step = 1;
+    while lo.0 < max_steps {
+        let next = (lo.0 + step).min(max_steps);
+        let eval = evaluate(next)?;
+        if eval.violation.is_some() {
+            hi = Some((next, eval));
+            break;
+        }
+        lo = (next, eval);
+        step *= 2;
+    }
+
+    // Bisect to whole minutes
+    if let Some(mut upper) = hi {
+        while upper.0 - lo.0 > 1 {
+            let mid = (lo.0 + upper.0) / 2;
+            let eval = evaluate(mid)?;
+            if eval.violation.is_some() {
+                upper = (mid, eval);
+            } else {
+                lo = (mid, eval);
+            }
+        }
+        hi = Some(upper);
+    }
+
+    let binding_constraint = hi
+        .and_then(|(_, e)| e.violation)
+        .unwrap_or(BottomTimeConstraint::SearchLimit);
+    let best = lo.1;
+    Ok(MaxBottomTimeResult {
+        max_bottom_time_sec: lo.0 * STEP_SEC,
+        binding_constraint,
+        runtime_sec: best.profile.surface_t_sec,
+        tts_sec: best.tts_sec,
+        cns_percent: best.cns_percent as f32,
+        gas_usage: best.gas_usage,
+        profile: Some(best.profile),
+        evaluations,
+    })
+}
+
+// ============================================================================
+// Evaluation
+// ============================================================================
+
+struct Evaluation {
+    profile: ProfileGenResult,
+    tts_sec: i32,
+    cns_percent: f64,
+    gas_usage: Vec<GasUsage>,
+    /// First violated constraint, in declaration order.
+    violation: Option<BottomTimeConstraint>,
+}
+
+fn evaluate_bottom_time(
+    params: &MaxBottomTimeParams,
+    bottom_time_sec: i32,
+    progress: Progress,
+) -> Result<Evaluation, DecoSimError> {
+    let mut profile_params = params.profile.clone();
+    profile_params.bottom_time_sec = bottom_time_sec;
+    let profile = generate_after(profile_params, &PriorDives::default(), progress)?;
+    let surface_p = params
+        .profile
+        .surface_pressure_bar
+        .unwrap_or(DEFAULT_SURFACE_PRESSURE);
+
+    let samples = profile.dive_samples();
+    let tts_sec = profile.surface_t_sec - profile.bottom_end_t_sec;
+    let cns_percent = OxygenExposure::cumulative(samples, &profile.gas_mixes, surface_p)
+        .last()
+        .map_or(0.0, |e| e.cns_percent);
+    let gas_usage = match params.bottom_sac_l_min {
+        Some(bottom_sac) if !params.cylinders.is_empty() => {
+            let deco_sac = params.deco_sac_l_min.unwrap_or(bottom_sac);
+            let used = gas_used_by_mix(
+                samples,
+                profile.bottom_end_t_sec,
+                bottom_sac,
+                deco_sac,
+                params.profile.scr.as_ref(),
+                surface_p,
+            );
+            cylinder_usage(&params.cylinders, &used)
+        }
+        _ => Vec::new(),
+    };
+
+    let violation = if params
+        .max_runtime_sec
+        .is_some_and(|limit| profile.surface_t_sec > limit)
+    {
+        Some(BottomTimeConstraint::Runtime)
+    } else if params.max_tts_sec.is_some_and(|limit| tts_sec > limit) {
+        Some(BottomTimeConstraint::Tts)
+    } else if params
+        .max_cns_percent
+        .is_some_and(|limit| cns_percent > limit)
+    {
+        Some(BottomTimeConstraint::Cns)
+    } else if gas_usage.iter().any(|u| !u.within_reserve()) {
+        Some(BottomTimeConstraint::GasSupply)
+    } else if profile.truncated {
+        Some(BottomTimeConstraint::DecoTruncated)
+    } else {
+        None
+    };
+
+    Ok(Evaluation {
+        profile,
+        tts_sec,
+        cns_percent,
+        gas_usage,
+        violation,
+    })
+}
+
+// ============================================================================
+// Validation
+// ============================================================================
+
+fn validate_params(params: &MaxBottomTimeParams) -> Result<(), DecoSimError> {
+    if params.profile.target_depth_m <= 0.0 {
+        return Err(DecoSimError::InvalidParam {
+            msg: format!(
+                "target_depth_m ({}) must be > 0",
+                params.profile.target_depth_m
+            ),
+        });
+    }
+    if let Some(dr) = params.profile.descent_rate_m_min {
+        if dr <= 0.0 {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!("descent_rate_m_min ({dr}) must be > 0"),
+            });
+        }
+    }
+    for (name, value) in [
+        ("max_runtime_sec", params.max_runtime_sec),
+        ("max_tts_sec", params.max_tts_sec),
+        ("search_limit_sec", params.search_limit_sec),
+    ] {
+        if let Some(v) = value {
+            if v <= 0 {
+                return Err(DecoSimError::InvalidParam {
+                    msg: format!("{name} ({v}) must be > 0"),
+                });
+            }
+        }
+    }
+    if let Some(cns) = params.max_cns_percent {
+        if cns <= 0.0 {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!("max_cns_percent ({cns}) must be > 0"),
+            });
+        }
+    }
+    for (name, value) in [
+        ("bottom_sac_l_min", params.bottom_sac_l_min),
+        ("deco_sac_l_min", params.deco_sac_l_min),
+    ] {
+        if let Some(v) = value {
+            if v <= 0.0 {
+                return Err(DecoSimError::InvalidParam {
+                    msg: format!("{name} ({v}) must be > 0"),
+                });
+            }
+        }
+    }
+    if !params.cylinders.is_empty() {
+        if params.bottom_sac_l_min.is_none() {
+            return Err(DecoSimError::InvalidParam {
+                msg: "bottom_sac_l_min is required when cylinders are given".to_string(),
+            });
+        }
+        validate_cylinders(&params.cylinders, params.profile.gas_plan.len().max(1))?;
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buhlmann::GasMixInput;
+    use crate::deco::profile_generator::GasSwitchPlan;
+    use crate::deco::types::DecoModel;
+
+    fn gas(o2: f64, he: f64) -> GasMixInput {
+        GasMixInput {
+            mix_index: 0,
+            o2_fraction: o2,
+            he_fraction: he,
+        }
+    }
+
+    fn params(depth: f64, gas_plan: Vec<GasSwitchPlan>) -> MaxBottomTimeParams {
+        MaxBottomTimeParams {
+            profile: ProfileGenParams {
+                target_depth_m: depth,
+                bottom_time_sec: 0,
+                descent_rate_m_min: None,
+                ascent_rate_m_min: None,
+                gas_plan,
+                model: DecoModel::BuhlmannZhl16c,
+                surface_pressure_bar: None,
+                gf_low: Some(50),
+                gf_high: Some(80),
+                last_stop_depth_m: None,
+                stop_interval_m: None,
+                setpoint_ppo2: None,
+                thalmann_pdcs: None,
+                sample_interval_sec: Some(30),
+                temp_c: None,
+                gas_switch_pause_sec: None,
+                max_ppo2: None,
+                deep_stops: None,
+                ascent_mode: None,
+                ceiling_margin_m: None,
+                setpoint_schedule: None,
+                scr: None,
+                buhlmann_coefficients: None,
+                custom_buhlmann_coefficients: None,
+                custom_thalmann_params: None,
+                safety_stop: None,
+                final_ascent_rate_m_min: None,
+                surface_tail_sec: None,
+            },
+            max_runtime_sec: None,
+            max_tts_sec: None,
+            max_cns_percent: None,
+            cylinders: Vec::new(),
+            bottom_sac_l_min: None,
+            deco_sac_l_min: None,
+            search_limit_sec: None,
+        }
+    }
+
+    fn runtime_at(p: &MaxBottomTimeParams, bottom_time_sec: i32) -> i32 {
+        evaluate_bottom_time(p, bottom_time_sec, Progress::NONE)
+            .unwrap()
+            .profile
+            .surface_t_sec
+    }
+
+    #[test]
+    fn test_runtime_limit_binding() {
+        let mut p = params(40.0, vec![]);
+        p.max_runtime_sec = Some(60 * 60);
+        let result = solve_max_bottom_time(p.clone()).unwrap();
+        assert_eq!(result.binding_constraint, BottomTimeConstraint::Runtime);
+        assert!(result.runtime_sec <= 3600);
+        assert_eq!(result.max_bottom_time_sec % 60, 0);
+        // One more minute breaks the limit
+        assert!(runtime_at(&p, result.max_bottom_time_sec + 60) > 3600);
+        assert_eq!(result.profile.unwrap().surface_t_sec, result.runtime_sec);
+    }
+
+    #[test]
+    fn test_surface_tail_does_not_change_result() {
+        let mut runtime = params(40.0, vec![]);
+        runtime.max_runtime_sec = Some(60 * 60);
+        let mut tts = params(45.0, vec![]);
+        tts.max_tts_sec = Some(20 * 60);
+        let mut gas = params(30.0, vec![]);
+        gas.cylinders = vec![CylinderInput {
+            gas_index: 0,
+            volume_l: 12.0,
+            start_pressure_bar: 200.0,
+            reserve_pressure_bar: 50.0,
+        }];
+        gas.bottom_sac_l_min = Some(20.0);
+
+        for p in [runtime, tts, gas] {
+            let mut tailed = p.clone();
+            tailed.profile.surface_tail_sec = Some(600);
+            let base = solve_max_bottom_time(p).unwrap();
+            let with_tail = solve_max_bottom_time(tailed).unwrap();
+            assert_eq!(with_tail.binding_constraint, base.binding_constraint);
+            assert_eq!(with_tail.max_bottom_time_sec, base.max_bottom_time_sec);
+            assert_eq!(with_tail.runtime_sec, base.runtime_sec);
+            assert_eq!(with_tail.tts_sec, base.tts_sec);
+            assert_eq!(with_tail.cns_percent, base.cns_percent);
+            let used = |r: &MaxBottomTimeResult| -> Vec<f64> {
+                r.gas_usage.iter().map(|u| u.used_l).collect()
+            };
+            assert_eq!(used(&with_tail), used(&base));
+        }
+    }
+
+    #[test]
+    fn test_tts_limit_binding() {
+        let mut p = params(45.0, vec![]);
+        p.max_tts_sec = Some(20 * 60);
+        let result = solve_max_bottom_time(p.clone()).unwrap();
+        assert_eq!(result.binding_constraint, BottomTimeConstraint::Tts);
+        assert!(result.tts_sec <= 1200);
+        let next =
+            evaluate_bottom_time(&p, result.max_bottom_time_sec + 60, Progress::NONE).unwrap();
+        assert!(next.tts_sec > 1200);
+    }
+
+    #[test]
+    fn test_gas_supply_binding() {
+        let mut p = params(30.0, vec![]);
+        p.cylinders = vec![CylinderInput {
+            gas_index: 0,
+            volume_l: 12.0,
+            start_pressure_bar: 200.0,
+            reserve_pressure_bar: 50.0,
+        }];
+        p.bottom_sac_l_min = Some(20.0);
+        let result = solve_max_bottom_time(p).unwrap();
+        assert_eq!(result.binding_constraint, BottomTimeConstraint::GasSupply);
+        assert_eq!(result.gas_usage.len(), 1);
+        assert!(result.gas_usage[0].within_reserve());
+        // 1800 L usable at 20 L/min × 4 bar → well under 25 min at depth
+        assert!(result.max_bottom_time_sec < 25 * 60);
+    }
+
+    #[test]
+    fn test_cns_binding_on_rich_nitrox() {
+        // EAN40 at 29 m: PPO2 just under 1.6
+        let mut p = params(
+            29.0,
+            vec![GasSwitchPlan {
+                gas: gas(0.40, 0.0),
+                switch_depth_m: None,
+                descent_switch_depth_m: None,
+            }],
+        );
+        p.max_cns_percent = Some(50.0);
+        p.max_runtime_sec = Some(4 * 3600);
+        let result = solve_max_bottom_time(p).unwrap();
+        assert_eq!(result.binding_constraint, BottomTimeConstraint::Cns);
+        assert!(result.cns_percent <= 50.0);
+    }
+
+    #[test]
+    fn test_search_limit_without_constraints() {
+        let mut p = params(10.0, vec![]);
+        p.search_limit_sec = Some(30 * 60);
+        let result = solve_max_bottom_time(p).unwrap();
+        assert_eq!(result.binding_constraint, BottomTimeConstraint::SearchLimit);
+        assert_eq!(result.max_bottom_time_sec, 30 * 60);
+    }
+
+    #[test]
+    fn test_infeasible_at_minimum() {
+        let mut p = params(60.0, vec![]);
+        p.max_runtime_sec = Some(60);
+        let result = solve_max_bottom_time(p).unwrap();
+        assert_eq!(result.max_bottom_time_sec, 0);
+        assert_eq!(result.binding_constraint, BottomTimeConstraint::Runtime);
+        assert!(result.profile.is_none());
+    }
+
+    #[test]
+    fn test_invalid_params_rejected() {
+        let mut p = params(30.0, vec![]);
+        p.max_tts_sec = Some(0);
+        assert!(solve_max_bottom_time(p).is_err());
+
+        let mut p = params(30.0, vec![]);
+        p.cylinders = vec![CylinderInput {
+            gas_index: 0,
+            volume_l: 12.0,
+            start_pressure_bar: 200.0,
+            reserve_pressure_bar: 50.0,
+        }];
+        assert!(
+            solve_max_bottom_time(p).is_err(),
+            "SAC is required with cylinders"
+        );
+
+        let mut p = params(30.0, vec![]);
+        p.search_limit_sec = Some(30);
+        assert!(solve_max_bottom_time(p).is_err());
+    }
+}
@@ -3,27 +3,157 @@
 //! Supports multiple deco models via the internal `DecoEngine` trait,
 //! dispatched by `DecoModel` enum at the FFI boundary.
 
+pub mod comparison;
+pub mod dive_day;
+pub mod dive_edit;
+pub mod gas_optimizer;
+pub mod gas_usage;
+pub mod icd;
+pub mod max_bottom_time;
 pub mod profile_generator;
+pub mod progress;
+pub mod runtime_table;
+pub mod schedule_eval;
+pub mod scr;
+pub mod session;
 pub mod shared;
 pub mod types;
 
 mod buhlmann_engine;
+mod cadence;
+mod deep_stops;
+mod oxygen;
 mod thalmann_engine;
 mod thalmann_params;
 
-pub use profile_generator::{GasSwitchPlan, ProfileGenParams, ProfileGenResult};
+pub use comparison::{ModelComparisonParams, ModelComparisonResult, ModelConfig, ModelRun};
+pub use dive_day::{
+    DiveDayDive, DiveDayParams, DiveDayResult, MinSurfaceIntervalParams, MinSurfaceIntervalResult,
+    PlannedDive, SurfaceIntervalTarget,
+};
+pub use dive_edit::{DiveEdit, DiveEditKind, DiveEditParams, DiveEditResult};
+pub use gas_optimizer::{
+    DecoGasCandidate, DecoGasOptimizerParams, DecoGasOptimizerResult, GasMixRange,
+    GasOptimizerObjective, SwitchDepthLimit,
+};
+pub use gas_usage::{
+    CylinderInput, CylinderPlan, GasLogisticsParams, GasLogisticsResult, GasUsage, ReserveStrategy,
+};
+pub use icd::{GasSwitchIcd, IcdRisk};
+pub use max_bottom_time::{BottomTimeConstraint, MaxBottomTimeParams, MaxBottomTimeResult};
+pub use profile_generator::{
+    GasSwitchPlan, ProfileGenParams, ProfileGenResult, SafetyStopParams, SetpointSchedule,
+    TimedSetpointSwitch,
+};
+pub use progress::{CancellationToken, ComputeControl, ProgressObserver};
+pub use runtime_table::{
+    RuntimeSegment, RuntimeTable, RuntimeTableFormat, RuntimeTableRow, UnitSystem,
+};
+pub use schedule_eval::{CeilingViolation, ScheduleEvalParams, ScheduleEvalResult, StopEvaluation};
+pub use scr::{ScrMode, ScrParams};
+pub use session::{DecoSession, DecoSessionPlan, DecoSessionSnapshot};
 pub use types::*;
 
 use buhlmann_engine::BuhlmannEngine;
+use progress::Progress;
 use thalmann_engine::ThalmannEngine;
 
 /// Run a deco simulation with the specified model and parameters.
 ///
 /// This is the main entry point for the deco engine, exposed via FFI.
 pub fn compute_deco_simulation(params: DecoSimParams) -> Result<DecoSimResult, DecoSimError> {
+    simulate(&params, Progress::NONE)
+}
+
+/// `compute_deco_simulation` with cancellation and progress reporting.
+pub fn compute_deco_simulation_with_control(
+    params: DecoSimParams,
+    control: &ComputeControl,
+) -> Result<DecoSimResult, DecoSimError> {
+    let result = simulate(&params, control.progress())?;
+    control.finish();
+    Ok(result)
+}
+
+/// Validate and run a simulation, reporting into `progress`.
+pub(crate) fn simulate(
+    params: &DecoSimParams,
+    progress: Progress,
+) -> Result<DecoSimResult, DecoSimError> {
+    validate_shared(params)?;
+    validate_ascent_mode(params)?;
+    dispatch(params, progress)
+}
+
+/// Model-independent checks: samples, deep stops and the SCR loop.
+fn validate_shared(params: &DecoSimParams) -> Result<(), DecoSimError> {
+    if params.samples.is_empty() {
+        return Err(DecoSimError::EmptySamples {
+            msg: "No samples provided".to_string(),
+        });
+    }
+    validate_options(params)
+}
+
+/// Model-independent option checks: deep stops and the SCR loop.
+fn validate_options(params: &DecoSimParams) -> Result<(), DecoSimError> {
+    if let Some(deep) = &params.deep_stops {
+        deep_stops::validate_deep_stops(deep)?;
+    }
+    if let Some(scr) = &params.scr {
+        scr::validate_scr(scr)?;
+    }
+    Ok(())
+}
+
+/// Run the selected engine. Engines validate their own model parameters.
+fn dispatch(params: &DecoSimParams, progress: Progress) -> Result<DecoSimResult, DecoSimError> {
     match params.model {
-        DecoModel::BuhlmannZhl16c => BuhlmannEngine.simulate(&params),
-        DecoModel::ThalmannElDca => ThalmannEngine.simulate(&params),
+        DecoModel::BuhlmannZhl16c => BuhlmannEngine.simulate(params, progress),
+        DecoModel::ThalmannElDca => ThalmannEngine.simulate(params, progress),
+    }
+}
+
+/// A continuous ascent follows the Bühlmann GF ceiling and has no room for
+/// deep stops; its margin must be non-negative.
+fn validate_ascent_mode(params: &DecoSimParams) -> Result<(), DecoSimError> {
+    if let Some(margin) = params.ceiling_margin_m {
+        if margin < 0.0 {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!("ceiling_margin_m ({margin}) must be >= 0"),
+            });
+        }
+    }
+    if params.ascent_mode != Some(AscentMode::Continuous) {
+        return Ok(());
+    }
+    if params.model != DecoModel::BuhlmannZhl16c {
+        return Err(DecoSimError::InvalidParam {
+            msg: "continuous ascent requires the Bühlmann model".to_string(),
+        });
+    }
+    if params.deep_stops.is_some() {
+        return Err(DecoSimError::InvalidParam {
+            msg: "deep stops cannot be combined with a continuous ascent".to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Unrounded planner-criterion ceilings at selected samples.
+///
+/// See `BuhlmannEngine::departure_ceilings`; `first_stop_depth_m` is the Baker
+/// GF anchor and is ignored by Thalmann.
+pub(crate) fn departure_ceilings(
+    params: &DecoSimParams,
+    probes: &[(usize, f64)],
+    first_stop_depth_m: f64,
+) -> Result<Vec<f64>, DecoSimError> {
+    match params.model {
+        DecoModel::BuhlmannZhl16c => {
+            BuhlmannEngine.departure_ceilings(params, probes, first_stop_depth_m)
+        }
+        DecoModel::ThalmannElDca => ThalmannEngine.departure_ceilings(params, probes),
     }
 }
 
@@ -63,6 +193,16 @@ mod tests {
             gf_high: None,
             thalmann_pdcs: None,
             plan_ascent: false,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
         };
 
         let result = compute_deco_simulation(params).unwrap();
@@ -84,6 +224,16 @@ mod tests {
             gf_high: None,
             thalmann_pdcs: None,
             plan_ascent: false,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
         };
 
         let result = compute_deco_simulation(params).unwrap();
@@ -105,6 +255,16 @@ mod tests {
             gf_high: None,
             thalmann_pdcs: None,
             plan_ascent: false,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
         };
 
         let result = compute_deco_simulation(params);
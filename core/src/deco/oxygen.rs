@@ -0,0 +1,238 @@
+//! Oxygen exposure tracking (CNS clock and pulmonary OTU).
+//!
+//! CNS uses the NOAA single-exposure limits, linearly interpolated between
+//! table points. OTU uses the Lambertsen/Clark UPTD formula.
+
+use super::shared::*;
+use crate::buhlmann::GasMixInput;
+use crate::metrics::SampleInput;
+
+/// NOAA single-exposure limits: (PPO2 in bar, limit in minutes).
+const NOAA_CNS_LIMITS: [(f64, f64); 11] = [
+    (0.6, 720.0),
+    (0.7, 570.0),
+    (0.8, 450.0),
+    (0.9, 360.0),
+    (1.0, 300.0),
+    (1.1, 240.0),
+    (1.2, 210.0),
+    (1.3, 180.0),
+    (1.4, 150.0),
+    (1.5, 120.0),
+    (1.6, 45.0),
+];
+
+/// Shortest exposure limit used when extrapolating above 1.6 bar (minutes).
+const MIN_CNS_LIMIT_MIN: f64 = 1.0;
+
+/// CNS clock accrual in percent per minute at the given PPO2.
+///
+/// Below 0.5 bar there is no accrual. Between 0.5 and 0.6 bar the 0.6 bar
+/// limit is used. Above 1.6 bar the NOAA table has no entry; the 1.5–1.6
+/// segment is extrapolated, floored at a 1-minute limit.
+pub(crate) fn cns_percent_per_min(ppo2: f64) -> f64 {
+    if ppo2 <= 0.5 {
+        return 0.0;
+    }
+    let (first_p, first_limit) = NOAA_CNS_LIMITS[0];
+    if ppo2 <= first_p {
+        return 100.0 / first_limit;
+    }
+    for pair in NOAA_CNS_LIMITS.windows(2) {
+        let (p0, l0) = pair[0];
+        let (p1, l1) = pair[1];
+        if ppo2 <= p1 {
+            let limit = l0 + (l1 - l0) * (ppo2 - p0) / (p1 - p0);
+            return 100.0 / limit;
+        }
+    }
+    let (p0, l0) = NOAA_CNS_LIMITS[NOAA_CNS_LIMITS.len() - 2];
+    let (p1, l1) = NOAA_CNS_LIMITS[NOAA_CNS_LIMITS.len() - 1];
+    let limit = (l1 + (l1 - l0) * (ppo2 - p1) / (p1 - p0)).max(MIN_CNS_LIMIT_MIN);
+    100.0 / limit
+}
+
+/// Oxygen tolerance units accrued per minute at the given PPO2.
+///
+/// OTU/min = ((PPO2 - 0.5) / 0.5)^(5/6), zero at or below 0.5 bar.
+pub(crate) fn otu_per_min(ppo2: f64) -> f64 {
+    if ppo2 <= 0.5 {
+        return 0.0;
+    }
+    ((ppo2 - 0.5) / 0.5).powf(5.0 / 6.0)
+}
+
+/// Inspired PPO2 for an open-circuit gas or a measured CCR loop.
+#[inline]
+pub(crate) fn inspired_ppo2(fo2: f64, ppo2: Option<f64>, ambient_p: f64) -> f64 {
+    match ppo2 {
+        Some(sp) => sp.clamp(0.0, ambient_p),
+        None => fo2 * ambient_p,
+    }
+}
+
+/// Accumulated oxygen exposure over a profile.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct OxygenExposure {
+    /// CNS clock in percent.
+    pub cns_percent: f64,
+    /// Pulmonary oxygen tolerance units.
+    pub otu: f64,
+    /// Highest inspired PPO2 seen (bar).
+    pub max_ppo2: f64,
+}
+
+impl OxygenExposure {
+    /// Add an exposure of `dt_sec` seconds at constant `ppo2`.
+    pub(crate) fn accumulate(&mut self, ppo2: f64, dt_sec: f64) {
+        if dt_sec <= 0.0 {
+            return;
+        }
+        let minutes = dt_sec / 60.0;
+        self.cns_percent += cns_percent_per_min(ppo2) * minutes;
+        self.otu += otu_per_min(ppo2) * minutes;
+        if ppo2 > self.max_ppo2 {
+            self.max_ppo2 = ppo2;
+        }
+    }
+
+    /// Walk a sample profile and accumulate exposure per interval.
+    ///
+    /// Follows the same interval convention as the deco engines: each interval
+    /// uses the previous sample's gas and PPO2 at the average depth.
+    /// Returns the cumulative exposure after each sample (same length as `samples`).
+    pub(crate) fn cumulative(
+        samples: &[SampleInput],
+        gas_mixes: &[GasMixInput],
+        surface_p: f64,
+    ) -> Vec<OxygenExposure> {
+        let mut gas_lookup: std::collections::HashMap<i32, f64> = std::collections::HashMap::new();
+        for mix in gas_mixes {
+            gas_lookup.insert(mix.mix_index, mix.o2_fraction);
+        }
+        let mut current_fo2 = gas_lookup.get(&0).copied().unwrap_or(AIR_FO2);
+
+        let mut exposure = OxygenExposure::default();
+        let mut out = Vec::with_capacity(samples.len());
+        for (idx, sample) in samples.iter().enumerate() {
+            if idx > 0 {
+                let prev = &samples[idx - 1];
+                let dt_sec = (sample.t_sec - prev.t_sec) as f64;
+                let avg_depth_m = ((prev.depth_m as f64 + sample.depth_m as f64) / 2.0).max(0.0);
+                let ambient_p = depth_to_pressure(avg_depth_m, surface_p);
+                let ppo2 = inspired_ppo2(current_fo2, prev.ppo2.map(|v| v as f64), ambient_p);
+                exposure.accumulate(ppo2, dt_sec);
+            }
+            if let Some(fo2) = sample.gasmix_index.and_then(|i| gas_lookup.get(&i)) {
+                current_fo2 = *fo2;
+            }
+            out.push(exposure.clone());
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(t_sec: i32, depth_m: f32, gasmix_index: Option<i32>) -> SampleInput {
+        SampleInput {
+            t_sec,
+            depth_m,
+            temp_c: 20.0,
+            setpoint_ppo2: None,
+            ceiling_m: None,
+            gf99: None,
+            gasmix_index,
+            ppo2: None,
+            tts_sec: None,
+            ndl_sec: None,
+            deco_stop_depth_m: None,
+            at_plus_five_tts_min: None,
+        }
+    }
+
+    #[test]
+    fn test_cns_table_points() {
+        assert!((cns_percent_per_min(1.0) - 100.0 / 300.0).abs() < 1e-12);
+        assert!((cns_percent_per_min(1.6) - 100.0 / 45.0).abs() < 1e-12);
+        assert_eq!(cns_percent_per_min(0.4), 0.0);
+        assert!((cns_percent_per_min(0.55) - 100.0 / 720.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_cns_interpolates_between_points() {
+        // Halfway between 1.4 (150 min) and 1.5 (120 min) → 135 min limit
+        assert!((cns_percent_per_min(1.45) - 100.0 / 135.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_cns_extrapolates_above_table() {
+        let at_16 = cns_percent_per_min(1.6);
+        let at_163 = cns_percent_per_min(1.63);
+        assert!(at_163 > at_16, "CNS rate must keep rising above 1.6 bar");
+        // Floors at a 1-minute limit
+        assert!((cns_percent_per_min(3.0) - 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_otu_formula() {
+        assert_eq!(otu_per_min(0.5), 0.0);
+        assert!((otu_per_min(1.0) - 1.0).abs() < 1e-12);
+        let expected = (0.8_f64 / 0.5).powf(5.0 / 6.0);
+        assert!((otu_per_min(1.3) - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_inspired_ppo2_oc_and_ccr() {
+        assert!((inspired_ppo2(0.32, None, 4.0) - 1.28).abs() < 1e-12);
+        assert!((inspired_ppo2(0.21, Some(1.3), 4.0) - 1.3).abs() < 1e-12);
+        // CCR setpoint cannot exceed ambient pressure
+        assert!((inspired_ppo2(0.21, Some(1.3), 1.1) - 1.1).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_cumulative_exposure_oxygen_stop() {
+        // 10 min at 6 m on pure O2 (~1.62 bar)
+        let samples = vec![sample(0, 6.0, Some(0)), sample(600, 6.0, Some(0))];
+        let gases = vec![GasMixInput {
+            mix_index: 0,
+            o2_fraction: 1.0,
+            he_fraction: 0.0,
+        }];
+        let exposure = OxygenExposure::cumulative(&samples, &gases, DEFAULT_SURFACE_PRESSURE);
+        assert_eq!(exposure.len(), 2);
+        assert_eq!(exposure[0].cns_percent, 0.0);
+        let ppo2 = depth_to_pressure(6.0, DEFAULT_SURFACE_PRESSURE);
+        let expected_cns = cns_percent_per_min(ppo2) * 10.0;
+        assert!((exposure[1].cns_percent - expected_cns).abs() < 1e-9);
+        assert!((exposure[1].otu - otu_per_min(ppo2) * 10.0).abs() < 1e-9);
+        assert!((exposure[1].max_ppo2 - ppo2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_cumulative_uses_previous_gas() {
+        // Switch to O2 at t=600: the first interval must still be on air
+        let samples = vec![
+            sample(0, 6.0, Some(0)),
+            sample(600, 6.0, Some(1)),
+            sample(1200, 6.0, None),
+        ];
+        let gases = vec![
+            GasMixInput {
+                mix_index: 0,
+                o2_fraction: AIR_FO2,
+                he_fraction: 0.0,
+            },
+            GasMixInput {
+                mix_index: 1,
+                o2_fraction: 1.0,
+                he_fraction: 0.0,
+            },
+        ];
+        let exposure = OxygenExposure::cumulative(&samples, &gases, DEFAULT_SURFACE_PRESSURE);
+        assert!(exposure[1].max_ppo2 < 0.5);
+        assert!(exposure[2].max_ppo2 > 1.5);
+    }
+}
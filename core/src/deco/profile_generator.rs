@@ -10,24 +10,76 @@
 //!    with gas switches.
 //! 3. **Pass 2**: Run deco engine with `plan_ascent: false` on the complete
 //!    profile → get per-point ceiling/GF99/TTS/NDL for charting.
+//!
+//! Travel gases are breathed on descent down to the bottom gas's descent
+//! switch depth and again on ascent from their own switch depth. Every
+//! open-circuit sample must lie within its gas's operating depth range.
 
-use super::compute_deco_simulation;
+use super::progress::{ComputeControl, Progress};
+use super::scr::{self, ScrParams};
+use super::simulate;
 use super::types::*;
-use crate::buhlmann::GasMixInput;
-use crate::deco::shared::{depth_to_pressure, DEFAULT_SURFACE_PRESSURE};
+use crate::buhlmann::{BuhlmannCoefficientSet, BuhlmannCoefficients, GasMixInput};
+use crate::deco::shared::{default_switch_depth, depth_to_pressure, DEFAULT_SURFACE_PRESSURE};
+use crate::gas;
 use crate::metrics::SampleInput;
 
 // ============================================================================
 // Input / Output Types
 // ============================================================================
 
-/// A gas mix with its planned switch depth for the ascent.
+/// A gas mix with its planned switch depths for descent and ascent.
 #[derive(Debug, Clone)]
 pub struct GasSwitchPlan {
     /// The gas mix definition.
     pub gas: GasMixInput,
     /// Depth to switch to this gas during ascent. `None` = bottom/starting gas.
     pub switch_depth_m: Option<f64>,
+    /// Depth to switch to this gas during descent. `Some(0.0)` = breathed from
+    /// the surface. `None` = not breathed on descent, except for the bottom
+    /// gas when no descent switches are planned.
+    pub descent_switch_depth_m: Option<f64>,
+}
+
+/// A manual CCR setpoint change at a dive time.
+#[derive(Debug, Clone)]
+pub struct TimedSetpointSwitch {
+    /// Dive time of the switch (seconds).
+    pub t_sec: i32,
+    /// New setpoint PPO2 in bar.
+    pub setpoint_ppo2: f64,
+}
+
+/// CCR setpoint schedule for a generated profile.
+///
+/// Depth switches fire on the first sample that reaches their depth: descent
+/// switches while descending or on the bottom, ascent switches after the
+/// bottom phase. Timed switches fire at their dive time and take effect over
+/// any depth switch on the same sample. The planner sees the setpoint at the
+/// end of the bottom phase and the ascent switches; a timed switch during the
+/// ascent is flown but not planned for.
+#[derive(Debug, Clone)]
+pub struct SetpointSchedule {
+    /// Setpoint from the surface until the first switch (bar).
+    pub surface_setpoint_ppo2: f64,
+    /// Switches applied at or below their depth on descent (auto-switch).
+    pub descent_switches: Vec<SetpointSwitch>,
+    /// Switches applied at or above their depth on ascent.
+    pub ascent_switches: Vec<SetpointSwitch>,
+    /// Manual switches by dive time.
+    pub timed_switches: Vec<TimedSetpointSwitch>,
+}
+
+/// Safety stop added to generated no-decompression ascents.
+#[derive(Debug, Clone)]
+pub struct SafetyStopParams {
+    /// Stop depth in metres (default 5.0).
+    pub depth_m: Option<f64>,
+    /// Stop duration in seconds (default 180).
+    pub duration_sec: Option<i32>,
+    /// The stop is made only on dives deeper than this, in metres
+    /// (default 10.0). Use 0 to stop on every dive.
+    pub min_dive_depth_m: Option<f64>,
 }
 
 /// Parameters for generating a synthetic dive profile.
@@ -55,14 +107,47 @@ pub struct ProfileGenParams {
     pub last_stop_depth_m: Option<f64>,
     /// Deco stop spacing in metres (default 3.0).
     pub stop_interval_m: Option<f64>,
-    /// CCR setpoint PPO2 in bar. `None` = open circuit.
+    /// CCR setpoint PPO2 in bar for the whole dive. `None` = open circuit,
+    /// unless `setpoint_schedule` is set.
     pub setpoint_ppo2: Option<f64>,
-    /// Thalmann P_DCS target (Thalmann only, default Pdcs23).
+    /// Thalmann parameter set (Thalmann only, default Pdcs23).
     pub thalmann_pdcs: Option<ThalmannPdcs>,
     /// Sample interval in seconds (default 10).
     pub sample_interval_sec: Option<i32>,
     /// Water temperature in °C (default 20.0).
     pub temp_c: Option<f32>,
+    /// Pause at each descent gas switch in seconds (default 60).
+    pub gas_switch_pause_sec: Option<i32>,
+    /// Maximum PPO2 for open-circuit gases in bar (default 1.6).
+    pub max_ppo2: Option<f64>,
+    /// Deep stops added to the planned ascent. `None` = no deep stops.
+    pub deep_stops: Option<DeepStopParams>,
+    /// Planned ascent mode (default `Staged`). `Continuous` follows the
+    /// Bühlmann ceiling instead of holding at grid stops.
+    pub ascent_mode: Option<AscentMode>,
+    /// Distance kept below the ceiling during a continuous ascent in metres
+    /// (default 0.0).
+    pub ceiling_margin_m: Option<f64>,
+    /// CCR setpoint schedule. Mutually exclusive with `setpoint_ppo2`.
+    pub setpoint_schedule: Option<SetpointSchedule>,
+    /// Semi-closed rebreather loop, fed by the planned gases. Mutually
+    /// exclusive with `setpoint_ppo2` and `setpoint_schedule`.
+    pub scr: Option<ScrParams>,
+    /// ZHL-16 coefficient set (Bühlmann only, default Zhl16c).
+    pub buhlmann_coefficients: Option<BuhlmannCoefficientSet>,
+    /// Coefficients for the `Custom` set (required with it, rejected otherwise).
+    pub custom_buhlmann_coefficients: Option<BuhlmannCoefficients>,
+    /// Parameters for the `Custom` Thalmann set (required with it, rejected
+    /// otherwise).
+    pub custom_thalmann_params: Option<ThalmannParameters>,
+    /// Safety stop on no-decompression ascents. `None` = no safety stop.
+    pub safety_stop: Option<SafetyStopParams>,
+    /// Ascent rate from the last stop (deco or safety) to the surface in
+    /// m/min (default `ascent_rate_m_min`). Staged ascents only.
+    pub final_ascent_rate_m_min: Option<f64>,
+    /// Time spent at the surface after surfacing, sampled like the dive,
+    /// in seconds (default 0).
+    pub surface_tail_sec: Option<i32>,
 }
 
 /// Result of profile generation.
@@ -75,29 +160,53 @@ pub struct ProfileGenResult {
     /// Full deco simulation result (pass 2) with per-point overlay data.
     pub deco_result: DecoSimResult,
     /// Planned deco stops from pass 1 (with depths and durations).
-    /// These are the stops used to build the ascent profile.
+    /// These are the stops used to build the ascent profile; for a continuous
+    /// ascent, the simplified stop list.
     pub planned_stops: Vec<DecoStop>,
     /// Time at end of descent phase (seconds).
     pub descent_end_t_sec: i32,
     /// Time at end of bottom phase (seconds).
     pub bottom_end_t_sec: i32,
-    /// Total dive time (seconds).
+    /// Total dive time (seconds), including any surface tail.
     pub total_time_sec: i32,
     /// True if the pass-1 deco planner hit a safety limit (e.g., max stop time)
     /// and the ascent schedule may be incomplete.
     pub truncated: bool,
+    /// Change in required stop time caused by deep stops (seconds).
+    pub deep_stop_penalty_sec: i32,
+    /// Time the safety stop starts (seconds). `None` without a safety stop.
+    pub safety_stop_start_t_sec: Option<i32>,
+    /// Time the final ascent leaves the last stop (seconds). Equals
+    /// `bottom_end_t_sec` without stops or for a continuous ascent.
+    pub final_ascent_start_t_sec: i32,
+    /// Time the diver reaches the surface (seconds), before any surface tail.
+    pub surface_t_sec: i32,
+}
+
+impl ProfileGenResult {
+    /// Samples up to surfacing, without the surface tail.
+    pub(crate) fn dive_samples(&self) -> &[SampleInput] {
+        let end = self
+            .samples
+            .partition_point(|s| s.t_sec <= self.surface_t_sec);
+        &self.samples[..end]
+    }
 }
 
 // ============================================================================
 // Constants
 // ============================================================================
 
-const DEFAULT_DESCENT_RATE: f64 = 18.0;
-const DEFAULT_ASCENT_RATE: f64 = 9.0;
-const DEFAULT_SAMPLE_INTERVAL: i32 = 10;
+pub(crate) const DEFAULT_DESCENT_RATE: f64 = 18.0;
+pub(crate) const DEFAULT_ASCENT_RATE: f64 = 9.0;
+pub(crate) const DEFAULT_SAMPLE_INTERVAL: i32 = 10;
 const DEFAULT_TEMP_C: f32 = 20.0;
 const DEFAULT_LAST_STOP_DEPTH: f64 = 3.0;
 const DEFAULT_STOP_INTERVAL: f64 = 3.0;
+const DEFAULT_GAS_SWITCH_PAUSE_SEC: i32 = 60;
+const DEFAULT_SAFETY_STOP_DEPTH: f64 = 5.0;
+const DEFAULT_SAFETY_STOP_SEC: i32 = 180;
+const DEFAULT_SAFETY_STOP_MIN_DIVE_DEPTH: f64 = 10.0;
 
 // ============================================================================
 // Public API
@@ -108,6 +217,113 @@ const DEFAULT_STOP_INTERVAL: f64 = 3.0;
 /// Uses a two-pass approach: pass 1 plans the ascent/stops, pass 2 computes
 /// the full deco overlay (ceiling, GF99, TTS, NDL) for each sample point.
 pub fn generate_dive_profile(params: ProfileGenParams) -> Result<ProfileGenResult, DecoSimError> {
+    generate_after(params, &PriorDives::default(), Progress::NONE)
+}
+
+/// `generate_dive_profile` with cancellation and progress reporting; each
+/// pass gets half of the progress range.
+pub fn generate_dive_profile_with_control(
+    params: ProfileGenParams,
+    control: &ComputeControl,
+) -> Result<ProfileGenResult, DecoSimError> {
+    let result = generate_after(params, &PriorDives::default(), control.progress())?;
+    control.finish();
+    Ok(result)
+}
+
+/// Earlier dives of the day, replayed ahead of a dive so that its tissues
+/// start loaded. Times run from the start of the day and the history ends at
+/// the surface when the next dive starts; gas indices refer to `gas_mixes`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PriorDives {
+    pub(crate) samples: Vec<SampleInput>,
+    pub(crate) gas_mixes: Vec<GasMixInput>,
+}
+
+impl PriorDives {
+    /// Day time at which the next dive starts.
+    pub(crate) fn end_t_sec(&self) -> i32 {
+        self.samples.last().map_or(0, |s| s.t_sec)
+    }
+
+    /// The history followed by a dive's samples, offset to start when the
+    /// history ends. History gas indices move past the dive's own.
+    fn prepend(
+        &self,
+        samples: &[SampleInput],
+        gas_mixes: &[GasMixInput],
+    ) -> (Vec<SampleInput>, Vec<GasMixInput>) {
+        if self.samples.is_empty() {
+            return (samples.to_vec(), gas_mixes.to_vec());
+        }
+        let shift = gas_mixes.iter().map(|g| g.mix_index + 1).max().unwrap_or(0);
+        let start = self.end_t_sec();
+        let mut day: Vec<SampleInput> = self
+            .samples
+            .iter()
+            .map(|s| SampleInput {
+                gasmix_index: s.gasmix_index.map(|g| g + shift),
+                ..s.clone()
+            })
+            .collect();
+        day.extend(samples.iter().map(|s| SampleInput {
+            t_sec: s.t_sec + start,
+            ..s.clone()
+        }));
+        let mut mixes = gas_mixes.to_vec();
+        mixes.extend(self.gas_mixes.iter().map(|g| GasMixInput {
+            mix_index: g.mix_index + shift,
+            ..g.clone()
+        }));
+        (day, mixes)
+    }
+
+    /// Drop the history's points from a day-long overlay and restore dive time.
+    fn trim(&self, mut result: DecoSimResult) -> DecoSimResult {
+        if self.samples.is_empty() {
+            return result;
+        }
+        let start = self.end_t_sec();
+        result.points.drain(..self.samples.len());
+        for p in &mut result.points {
+            p.t_sec -= start;
+        }
+        result.max_ceiling_m = result
+            .points
+            .iter()
+            .map(|p| p.ceiling_m)
+            .fold(0.0, f32::max);
+        result.max_gf99 = result.points.iter().map(|p| p.gf99).fold(0.0, f32::max);
+        result.max_tts_sec = result.points.iter().map(|p| p.tts_sec).max().unwrap_or(0);
+        result
+    }
+}
+
+/// Without planned ascent switches the engines treat every other mix as a
+/// deco gas from its MOD. Spell that out for the dive's own gases so that
+/// earlier dives' mixes are not picked up; the current gas stays the bottom
+/// gas either way. The MOD uses the dive's PPO2 limit and surface pressure.
+fn pin_ascent_switches(
+    gas_mixes: &[GasMixInput],
+    max_ppo2: f64,
+    surface_pressure: f64,
+) -> Vec<GasSwitchDepth> {
+    gas_mixes
+        .iter()
+        .filter(|g| g.o2_fraction > 0.0)
+        .map(|g| GasSwitchDepth {
+            mix_index: g.mix_index,
+            depth_m: default_switch_depth(g.o2_fraction, max_ppo2, surface_pressure),
+        })
+        .collect()
+}
+
+/// Generate a dive profile whose tissues carry the loading of `prior`.
+pub(crate) fn generate_after(
+    params: ProfileGenParams,
+    prior: &PriorDives,
+    progress: Progress,
+) -> Result<ProfileGenResult, DecoSimError> {
     // ── Validate inputs ─────────────────────────────────────────────────
     validate_params(&params)?;
 
@@ -123,37 +339,56 @@ pub fn generate_dive_profile(params: ProfileGenParams) -> Result<ProfileGenResul
         .unwrap_or(DEFAULT_SURFACE_PRESSURE);
     let last_stop_depth = params.last_stop_depth_m.unwrap_or(DEFAULT_LAST_STOP_DEPTH);
     let stop_interval = params.stop_interval_m.unwrap_or(DEFAULT_STOP_INTERVAL);
+    let switch_pause = params
+        .gas_switch_pause_sec
+        .unwrap_or(DEFAULT_GAS_SWITCH_PAUSE_SEC);
+    let max_ppo2 = params.max_ppo2.unwrap_or(gas::DEFAULT_MAX_PPO2);
+    let final_ascent_rate = params.final_ascent_rate_m_min.unwrap_or(ascent_rate);
 
     // ── Build gas mixes ─────────────────────────────────────────────────
-    let (gas_mixes, bottom_gas_index, switch_schedule) = build_gas_plan(&params.gas_plan);
+    let plan = build_gas_plan(&params.gas_plan);
+    let bottom_gas_index = plan.bottom_gas_index;
+    let mut ascent_gas_switches: Vec<GasSwitchDepth> = plan
+        .ascent_switches
+        .iter()
+        .map(|&(depth_m, mix_index)| GasSwitchDepth { mix_index, depth_m })
+        .collect();
+    if ascent_gas_switches.is_empty() && !prior.samples.is_empty() {
+        ascent_gas_switches = pin_ascent_switches(&plan.gas_mixes, max_ppo2, surface_pressure);
+    }
+
+    let ascent_setpoint_switches = params
+        .setpoint_schedule
+        .as_ref()
+        .map_or_else(Vec::new, |s| s.ascent_switches.clone());
 
     let ctx = SampleCtx {
-        setpoint_ppo2: params.setpoint_ppo2,
+        setpoint_ppo2: params.setpoint_ppo2.or(params
+            .setpoint_schedule
+            .as_ref()
+            .map(|s| s.surface_setpoint_ppo2)),
         surface_pressure,
         temp_c,
         sample_interval,
         ascent_rate,
+        final_ascent_rate,
     };
 
     // ── Phase 1: Descent + Bottom ───────────────────────────────────────
     let mut samples: Vec<SampleInput> = Vec::new();
 
-    let descent_time_sec = (params.target_depth_m / descent_rate * 60.0).round() as i32;
-    let descent_time_sec = descent_time_sec.max(1); // at least 1 second
-
-    // Descent samples
-    generate_descent(
+    // Descent samples, including any travel-gas switches
+    let descent_end_t = generate_descent(
         &mut samples,
         params.target_depth_m,
-        descent_time_sec,
-        bottom_gas_index,
+        descent_rate,
+        &plan.descent_switches,
+        switch_pause,
         &ctx,
     );
 
-    let descent_end_t = descent_time_sec;
-
     // Bottom samples (bottom_time_sec includes descent, so time at depth = total - descent)
-    let time_at_depth_sec = (params.bottom_time_sec - descent_time_sec).max(0);
+    let time_at_depth_sec = (params.bottom_time_sec - descent_end_t).max(0);
     generate_bottom(
         &mut samples,
         params.target_depth_m,
@@ -164,12 +399,19 @@ pub fn generate_dive_profile(params: ProfileGenParams) -> Result<ProfileGenResul
     );
 
     let bottom_end_t = descent_end_t + time_at_depth_sec;
+    if let Some(schedule) = &params.setpoint_schedule {
+        apply_setpoint_schedule(&mut samples, schedule, bottom_end_t, surface_pressure);
+    }
+    if let Some(scr) = &params.scr {
+        apply_scr_loop(&mut samples, scr, &plan.gas_mixes, surface_pressure);
+    }
 
     // ── Pass 1: Plan the ascent ─────────────────────────────────────────
+    let (day_samples, day_mixes) = prior.prepend(&samples, &plan.gas_mixes);
     let pass1_params = DecoSimParams {
         model: params.model,
-        samples: samples.clone(),
-        gas_mixes: gas_mixes.clone(),
+        samples: day_samples,
+        gas_mixes: day_mixes,
         surface_pressure_bar: Some(surface_pressure),
         ascent_rate_m_min: Some(ascent_rate),
         last_stop_depth_m: Some(last_stop_depth),
@@ -178,28 +420,73 @@ pub fn generate_dive_profile(params: ProfileGenParams) -> Result<ProfileGenResul
         gf_high: params.gf_high,
         thalmann_pdcs: params.thalmann_pdcs,
         plan_ascent: true,
+        ascent_gas_switches: ascent_gas_switches.clone(),
+        ascent_setpoint_switches: ascent_setpoint_switches.clone(),
+        scr: params.scr.clone(),
+        deep_stops: params.deep_stops.clone(),
+        ascent_mode: params.ascent_mode,
+        ceiling_margin_m: params.ceiling_margin_m,
+        buhlmann_coefficients: params.buhlmann_coefficients,
+        custom_buhlmann_coefficients: params.custom_buhlmann_coefficients.clone(),
+        custom_thalmann_params: params.custom_thalmann_params.clone(),
+        tts_interval_sec: None,
     };
 
-    let pass1_result = compute_deco_simulation(pass1_params)?;
+    let pass1_result = simulate(&pass1_params, progress.slice(0.0, 0.5))?;
 
     // ── Build ascent samples from deco schedule ─────────────────────────
-    generate_ascent(
-        &mut samples,
-        params.target_depth_m,
-        bottom_end_t,
-        &pass1_result.deco_stops,
-        &switch_schedule,
-        bottom_gas_index,
-        &ctx,
-    );
+    let safety_stop = if pass1_result.deco_stops.is_empty() {
+        safety_stop(params.safety_stop.as_ref(), params.target_depth_m)
+    } else {
+        None
+    };
+    let (final_ascent_start_t_sec, safety_stop_start_t_sec) = if pass1_result.ascent_path.is_empty()
+    {
+        let stops = match &safety_stop {
+            Some(stop) => vec![stop.clone()],
+            None => pass1_result.deco_stops.clone(),
+        };
+        let final_start = generate_ascent(
+            &mut samples,
+            params.target_depth_m,
+            bottom_end_t,
+            &stops,
+            &plan.ascent_switches,
+            bottom_gas_index,
+            &ctx,
+        );
+        let safety_stop_start = safety_stop.map(|stop| final_start - stop.duration_sec);
+        (final_start, safety_stop_start)
+    } else {
+        generate_continuous_ascent(
+            &mut samples,
+            bottom_end_t,
+            &pass1_result.ascent_path,
+            &plan.ascent_switches,
+            bottom_gas_index,
+            &ctx,
+        );
+        (bottom_end_t, None)
+    };
+    let surface_t_sec = samples.last().map_or(0, |s| s.t_sec);
+    generate_surface_tail(&mut samples, params.surface_tail_sec.unwrap_or(0), &ctx);
+
+    if let Some(schedule) = &params.setpoint_schedule {
+        apply_setpoint_schedule(&mut samples, schedule, bottom_end_t, surface_pressure);
+    }
+    if let Some(scr) = &params.scr {
+        apply_scr_loop(&mut samples, scr, &plan.gas_mixes, surface_pressure);
+    }
 
     let total_time_sec = samples.last().map_or(0, |s| s.t_sec);
+    validate_operating_depths(&samples, &plan.gas_mixes, max_ppo2, surface_pressure)?;
 
     // ── Pass 2: Full deco overlay ───────────────────────────────────────
+    let (day_samples, day_mixes) = prior.prepend(&samples, &plan.gas_mixes);
     let pass2_params = DecoSimParams {
         model: params.model,
-        samples: samples.clone(),
-        gas_mixes: gas_mixes.clone(),
+        samples: day_samples,
+        gas_mixes: day_mixes,
         surface_pressure_bar: Some(surface_pressure),
         ascent_rate_m_min: Some(ascent_rate),
         last_stop_depth_m: Some(last_stop_depth),
@@ -208,19 +495,52 @@ pub fn generate_dive_profile(params: ProfileGenParams) -> Result<ProfileGenResul
         gf_high: params.gf_high,
         thalmann_pdcs: params.thalmann_pdcs,
         plan_ascent: false,
+        ascent_gas_switches,
+        ascent_setpoint_switches,
+        scr: params.scr.clone(),
+        deep_stops: None,
+        ascent_mode: None,
+        ceiling_margin_m: None,
+        buhlmann_coefficients: params.buhlmann_coefficients,
+        custom_buhlmann_coefficients: params.custom_buhlmann_coefficients.clone(),
+        custom_thalmann_params: params.custom_thalmann_params.clone(),
+        tts_interval_sec: None,
     };
 
-    let deco_result = compute_deco_simulation(pass2_params)?;
+    let deco_result = prior.trim(simulate(&pass2_params, progress.slice(0.5, 1.0))?);
 
     Ok(ProfileGenResult {
         samples,
-        gas_mixes,
+        gas_mixes: plan.gas_mixes,
         deco_result,
         planned_stops: pass1_result.deco_stops,
         descent_end_t_sec: descent_end_t,
         bottom_end_t_sec: bottom_end_t,
         total_time_sec,
         truncated: pass1_result.truncated,
+        deep_stop_penalty_sec: pass1_result.deep_stop_penalty_sec,
+        safety_stop_start_t_sec,
+        final_ascent_start_t_sec,
+        surface_t_sec,
+    })
+}
+
+/// The safety stop for a no-decompression ascent from `target_depth_m`, if
+/// one applies.
+fn safety_stop(params: Option<&SafetyStopParams>, target_depth_m: f64) -> Option<DecoStop> {
+    let params = params?;
+    let depth = params.depth_m.unwrap_or(DEFAULT_SAFETY_STOP_DEPTH);
+    let min_dive_depth = params
+        .min_dive_depth_m
+        .unwrap_or(DEFAULT_SAFETY_STOP_MIN_DIVE_DEPTH);
+    if target_depth_m <= min_dive_depth || target_depth_m <= depth {
+        return None;
+    }
+    Some(DecoStop {
+        depth_m: depth as f32,
+        duration_sec: params.duration_sec.unwrap_or(DEFAULT_SAFETY_STOP_SEC),
+        gas_mix_index: -1,
+        is_deep_stop: false,
     })
 }
 
@@ -288,6 +608,62 @@ fn validate_params(params: &ProfileGenParams) -> Result<(), DecoSimError> {
             });
         }
     }
+    if let Some(pause) = params.gas_switch_pause_sec {
+        if pause < 0 {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!("gas_switch_pause_sec ({pause}) must be >= 0"),
+            });
+        }
+    }
+    if let Some(schedule) = &params.setpoint_schedule {
+        validate_setpoint_schedule(params, schedule)?;
+    }
+    if let Some(scr) = &params.scr {
+        if params.setpoint_ppo2.is_some() || params.setpoint_schedule.is_some() {
+            return Err(DecoSimError::InvalidParam {
+                msg: "scr cannot be combined with a CCR setpoint".to_string(),
+            });
+        }
+        scr::validate_scr(scr)?;
+    }
+    if let Some(stop) = &params.safety_stop {
+        if stop.depth_m.is_some_and(|d| d <= 0.0) {
+            return Err(DecoSimError::InvalidParam {
+                msg: "safety stop depth_m must be > 0".to_string(),
+            });
+        }
+        if stop.duration_sec.is_some_and(|d| d <= 0) {
+            return Err(DecoSimError::InvalidParam {
+                msg: "safety stop duration_sec must be > 0".to_string(),
+            });
+        }
+        if stop.min_dive_depth_m.is_some_and(|d| d < 0.0) {
+            return Err(DecoSimError::InvalidParam {
+                msg: "safety stop min_dive_depth_m must be >= 0".to_string(),
+            });
+        }
+    }
+    if let Some(rate) = params.final_ascent_rate_m_min {
+        if rate <= 0.0 {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!("final_ascent_rate_m_min ({rate}) must be > 0"),
+            });
+        }
+    }
+    if let Some(tail) = params.surface_tail_sec {
+        if tail < 0 {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!("surface_tail_sec ({tail}) must be >= 0"),
+            });
+        }
+    }
+    if let Some(max_ppo2) = params.max_ppo2 {
+        if max_ppo2 <= 0.0 {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!("max_ppo2 ({max_ppo2}) must be > 0"),
+            });
+        }
+    }
 
     // Validate gas plan: exactly one gas with switch_depth_m == None (bottom gas)
     // when gas_plan is non-empty; empty gas_plan defaults to air.
@@ -304,23 +680,164 @@ fn validate_params(params: &ProfileGenParams) -> Result<(), DecoSimError> {
                 ),
             });
         }
+        for gsp in &params.gas_plan {
+            gas::validate_gas_mix(&gsp.gas)?;
+        }
+        validate_descent_switches(params)?;
     }
 
     Ok(())
 }
 
+/// Descent switches must start at the surface, use distinct depths within
+/// the target depth, and end on the bottom gas.
+fn validate_descent_switches(params: &ProfileGenParams) -> Result<(), DecoSimError> {
+    let mut descent: Vec<(f64, bool)> = params
+        .gas_plan
+        .iter()
+        .filter_map(|g| {
+            g.descent_switch_depth_m
+                .map(|d| (d, g.switch_depth_m.is_none()))
+        })
+        .collect();
+    if descent.is_empty() {
+        return Ok(());
+    }
+    descent.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+    if let Some(&(depth, _)) = descent
+        .iter()
+        .find(|(d, _)| *d < 0.0 || *d > params.target_depth_m)
+    {
+        return Err(DecoSimError::InvalidParam {
+            msg: format!(
+                "descent_switch_depth_m ({depth}) must be within 0..={} m",
+                params.target_depth_m
+            ),
+        });
+    }
+    if descent[0].0 > 0.0 {
+        return Err(DecoSimError::InvalidParam {
+            msg: "one gas must have descent_switch_depth_m = 0 (breathed from the surface)"
+                .to_string(),
+        });
+    }
+    if descent.windows(2).any(|w| w[0].0 == w[1].0) {
+        return Err(DecoSimError::InvalidParam {
+            msg: "descent_switch_depth_m values must be distinct".to_string(),
+        });
+    }
+    if !descent.last().is_some_and(|&(_, is_bottom)| is_bottom) {
+        return Err(DecoSimError::InvalidParam {
+            msg: "the bottom gas must have the deepest descent_switch_depth_m".to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Setpoints must be positive with non-negative depths and times, and the
+/// schedule replaces a fixed setpoint rather than combining with it.
+fn validate_setpoint_schedule(
+    params: &ProfileGenParams,
+    schedule: &SetpointSchedule,
+) -> Result<(), DecoSimError> {
+    if params.setpoint_ppo2.is_some() {
+        return Err(DecoSimError::InvalidParam {
+            msg: "set either setpoint_ppo2 or setpoint_schedule, not both".to_string(),
+        });
+    }
+    let depth_switches = schedule
+        .descent_switches
+        .iter()
+        .chain(&schedule.ascent_switches);
+    let setpoints = std::iter::once(schedule.surface_setpoint_ppo2)
+        .chain(depth_switches.clone().map(|s| s.setpoint_ppo2))
+        .chain(schedule.timed_switches.iter().map(|s| s.setpoint_ppo2));
+    for sp in setpoints {
+        if sp <= 0.0 {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!("setpoint_ppo2 ({sp}) must be > 0"),
+            });
+        }
+    }
+    if let Some(s) = depth_switches.into_iter().find(|s| s.depth_m < 0.0) {
+        return Err(DecoSimError::InvalidParam {
+            msg: format!("setpoint switch depth_m ({}) must be >= 0", s.depth_m),
+        });
+    }
+    if let Some(s) = schedule.timed_switches.iter().find(|s| s.t_sec < 0) {
+        return Err(DecoSimError::InvalidParam {
+            msg: format!("setpoint switch t_sec ({}) must be >= 0", s.t_sec),
+        });
+    }
+    Ok(())
+}
+
+/// Reject profiles that breathe an open-circuit gas deeper than its MOD or
+/// shallower than its minimum operating depth. CCR samples (with a setpoint)
+/// are skipped; an SCR's supply gas is checked like open circuit.
+fn validate_operating_depths(
+    samples: &[SampleInput],
+    gas_mixes: &[GasMixInput],
+    max_ppo2: f64,
+    surface_pressure: f64,
+) -> Result<(), DecoSimError> {
+    for pair in samples.windows(2) {
+        let prev = &pair[0];
+        if prev.setpoint_ppo2.is_some() {
+            continue;
+        }
+        let Some(mix) = prev
+            .gasmix_index
+            .and_then(|idx| gas_mixes.iter().find(|g| g.mix_index == idx))
+        else {
+            continue;
+        };
+        let mod_m = gas::max_operating_depth(mix.o2_fraction, max_ppo2, surface_pressure);
+        let min_m =
+            gas::min_operating_depth(mix.o2_fraction, gas::DEFAULT_MIN_PPO2, surface_pressure);
+        let deepest = prev.depth_m.max(pair[1].depth_m) as f64;
+        let shallowest = prev.depth_m.min(pair[1].depth_m) as f64;
+        if deepest > mod_m + gas::OPERATING_DEPTH_TOLERANCE_M {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!(
+                    "gas {} breathed at {deepest:.1} m, deeper than its MOD of {mod_m:.1} m at PPO2 {max_ppo2}",
+                    mix.mix_index
+                ),
+            });
+        }
+        if shallowest < min_m - gas::OPERATING_DEPTH_TOLERANCE_M {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!(
+                    "gas {} breathed at {shallowest:.1} m, shallower than its minimum operating depth of {min_m:.1} m",
+                    mix.mix_index
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
 // ============================================================================
 // Gas Plan Building
 // ============================================================================
 
-/// Build gas mix list and switch schedule from user's gas plan.
+/// Gas mixes and switch schedules resolved from the user's gas plan.
+struct GasPlan {
+    gas_mixes: Vec<GasMixInput>,
+    bottom_gas_index: i32,
+    /// Ascent switches sorted by depth descending (deepest first).
+    ascent_switches: Vec<(f64, i32)>,
+    /// Descent switches sorted by depth ascending; the first is at the surface
+    /// and the last is the bottom gas.
+    descent_switches: Vec<(f64, i32)>,
+}
+
+/// Build gas mix list and switch schedules from user's gas plan.
 ///
 /// Gas mixes are re-indexed by their position in the plan (0, 1, 2, ...),
 /// so `GasSwitchPlan.gas.mix_index` is ignored in favour of positional order.
-///
-/// Returns `(gas_mixes, bottom_gas_index, switch_schedule)` where
-/// `switch_schedule` is sorted by switch depth descending (deepest first).
-fn build_gas_plan(plan: &[GasSwitchPlan]) -> (Vec<GasMixInput>, i32, Vec<(f64, i32)>) {
+fn build_gas_plan(plan: &[GasSwitchPlan]) -> GasPlan {
     if plan.is_empty() {
         // Default to air
         let air = GasMixInput {
@@ -328,11 +845,17 @@ fn build_gas_plan(plan: &[GasSwitchPlan]) -> (Vec<GasMixInput>, i32, Vec<(f64, i
             o2_fraction: 0.21,
             he_fraction: 0.0,
         };
-        return (vec![air], 0, vec![]);
+        return GasPlan {
+            gas_mixes: vec![air],
+            bottom_gas_index: 0,
+            ascent_switches: vec![],
+            descent_switches: vec![(0.0, 0)],
+        };
     }
 
     let mut gas_mixes = Vec::with_capacity(plan.len());
-    let mut switch_schedule: Vec<(f64, i32)> = Vec::new();
+    let mut ascent_switches: Vec<(f64, i32)> = Vec::new();
+    let mut descent_switches: Vec<(f64, i32)> = Vec::new();
     let mut bottom_gas_index: i32 = 0;
 
     for (i, gsp) in plan.iter().enumerate() {
@@ -344,16 +867,30 @@ fn build_gas_plan(plan: &[GasSwitchPlan]) -> (Vec<GasMixInput>, i32, Vec<(f64, i
         });
 
         if let Some(switch_depth) = gsp.switch_depth_m {
-            switch_schedule.push((switch_depth, mix_index));
+            ascent_switches.push((switch_depth, mix_index));
         } else {
             bottom_gas_index = mix_index;
         }
+        if let Some(descent_depth) = gsp.descent_switch_depth_m {
+            descent_switches.push((descent_depth, mix_index));
+        }
     }
 
     // Sort switches by depth descending (deepest switch first)
-    switch_schedule.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    ascent_switches.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
 
-    (gas_mixes, bottom_gas_index, switch_schedule)
+    // Without descent switches the bottom gas is breathed from the surface
+    if descent_switches.is_empty() {
+        descent_switches.push((0.0, bottom_gas_index));
+    }
+    descent_switches.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+    GasPlan {
+        gas_mixes,
+        bottom_gas_index,
+        ascent_switches,
+        descent_switches,
+    }
 }
 
 // ============================================================================
@@ -361,16 +898,18 @@ fn build_gas_plan(plan: &[GasSwitchPlan]) -> (Vec<GasMixInput>, i32, Vec<(f64, i
 // ============================================================================
 
 /// Shared context for sample generation, reducing argument count.
-struct SampleCtx {
-    setpoint_ppo2: Option<f64>,
-    surface_pressure: f64,
-    temp_c: f32,
-    sample_interval: i32,
-    ascent_rate: f64,
+pub(crate) struct SampleCtx {
+    pub(crate) setpoint_ppo2: Option<f64>,
+    pub(crate) surface_pressure: f64,
+    pub(crate) temp_c: f32,
+    pub(crate) sample_interval: i32,
+    pub(crate) ascent_rate: f64,
+    /// Ascent rate from the last stop to the surface.
+    pub(crate) final_ascent_rate: f64,
 }
 
 impl SampleCtx {
-    fn make_sample(&self, t_sec: i32, depth_m: f64, gasmix_index: i32) -> SampleInput {
+    pub(crate) fn make_sample(&self, t_sec: i32, depth_m: f64, gasmix_index: i32) -> SampleInput {
         let ppo2 = self.setpoint_ppo2.map(|sp| {
             let ambient = depth_to_pressure(depth_m, self.surface_pressure);
             sp.min(ambient) as f32
@@ -393,28 +932,67 @@ impl SampleCtx {
     }
 }
 
+/// Generate descent samples, switching gas and pausing at each descent
+/// switch. Returns the time at which the descent (including pauses) ends.
 fn generate_descent(
     samples: &mut Vec<SampleInput>,
     target_depth_m: f64,
-    descent_time_sec: i32,
-    gas_index: i32,
+    descent_rate: f64,
+    descent_switches: &[(f64, i32)],
+    switch_pause_sec: i32,
     ctx: &SampleCtx,
-) {
+) -> i32 {
+    let mut gas_index = descent_switches.first().map_or(0, |&(_, g)| g);
+    let waypoints = descent_switches
+        .iter()
+        .skip(1)
+        .map(|&(depth, g)| (depth.min(target_depth_m), Some(g)))
+        .chain(std::iter::once((target_depth_m, None)));
+
     // Always start at surface t=0
     samples.push(ctx.make_sample(0, 0.0, gas_index));
+    let mut depth = 0.0;
+    let mut t0 = 0;
+
+    for (next_depth, next_gas) in waypoints {
+        if next_depth > depth {
+            let segment_sec = ((next_depth - depth) / descent_rate * 60.0).round() as i32;
+            let segment_sec = segment_sec.max(1); // at least 1 second
+            let mut t = ctx.sample_interval;
+            while t < segment_sec {
+                let frac = t as f64 / segment_sec as f64;
+                samples.push(ctx.make_sample(
+                    t0 + t,
+                    depth + frac * (next_depth - depth),
+                    gas_index,
+                ));
+                t += ctx.sample_interval;
+            }
+            t0 += segment_sec;
+            depth = next_depth;
+            // The sample at the switch depth carries the new gas
+            gas_index = next_gas.unwrap_or(gas_index);
+            samples.push(ctx.make_sample(t0, depth, gas_index));
+        } else if let Some(g) = next_gas {
+            gas_index = g;
+            if let Some(last) = samples.last_mut() {
+                last.gasmix_index = Some(g);
+            }
+        }
 
-    let mut t = ctx.sample_interval;
-    while t < descent_time_sec {
-        let frac = t as f64 / descent_time_sec as f64;
-        let depth = frac * target_depth_m;
-        samples.push(ctx.make_sample(t, depth, gas_index));
-        t += ctx.sample_interval;
-    }
-
-    // Final descent sample at target depth
-    if samples.last().is_none_or(|s| s.t_sec != descent_time_sec) {
-        samples.push(ctx.make_sample(descent_time_sec, target_depth_m, gas_index));
+        // Pause on the new gas
+        if next_gas.is_some() && switch_pause_sec > 0 {
+            let pause_end = t0 + switch_pause_sec;
+            let mut t = t0 + ctx.sample_interval;
+            while t < pause_end {
+                samples.push(ctx.make_sample(t, depth, gas_index));
+                t += ctx.sample_interval;
+            }
+            samples.push(ctx.make_sample(pause_end, depth, gas_index));
+            t0 = pause_end;
+        }
     }
+    t0
 }
 
 fn generate_bottom(
@@ -438,7 +1016,9 @@ fn generate_bottom(
     }
 }
 
-fn generate_ascent(
+/// Generate a staged ascent through `deco_stops`. Returns the time the
+/// final ascent leaves the last stop (`start_t` without stops).
+pub(crate) fn generate_ascent(
     samples: &mut Vec<SampleInput>,
     start_depth_m: f64,
     start_t: i32,
@@ -446,11 +1026,12 @@ fn generate_ascent(
     switch_schedule: &[(f64, i32)],
     bottom_gas_index: i32,
     ctx: &SampleCtx,
-) {
+) -> i32 {
     let mut current_depth = start_depth_m;
     let mut current_t = start_t;
     let mut current_gas = bottom_gas_index;
     let mut switch_idx = 0; // index into switch_schedule (sorted deepest first)
+    let mut final_start_t = start_t;
 
     if deco_stops.is_empty() {
         // No deco: free ascent to surface
@@ -501,6 +1082,11 @@ fn generate_ascent(
         }
 
         // Final ascent from last stop to surface
+        final_start_t = current_t;
+        let final_ctx = SampleCtx {
+            ascent_rate: ctx.final_ascent_rate,
+            ..*ctx
+        };
         ascend_segment(
             samples,
             &mut current_depth,
@@ -509,7 +1095,7 @@ fn generate_ascent(
             &mut current_gas,
             switch_schedule,
             &mut switch_idx,
-            ctx,
+            &final_ctx,
         );
     }
 
@@ -517,6 +1103,142 @@ fn generate_ascent(
     if samples.last().is_none_or(|s| s.depth_m > 0.0) {
         samples.push(ctx.make_sample(current_t, 0.0, current_gas));
     }
+    final_start_t
+}
+
+/// Stay at the surface on the last gas for `tail_sec` after surfacing.
+fn generate_surface_tail(samples: &mut Vec<SampleInput>, tail_sec: i32, ctx: &SampleCtx) {
+    let Some(last) = samples.last() else {
+        return;
+    };
+    if tail_sec <= 0 {
+        return;
+    }
+    let (start_t, gas) = (last.t_sec, last.gasmix_index.unwrap_or(0));
+    let end_t = start_t + tail_sec;
+    let mut t = start_t + ctx.sample_interval;
+    while t < end_t {
+        samples.push(ctx.make_sample(t, 0.0, gas));
+        t += ctx.sample_interval;
+    }
+    samples.push(ctx.make_sample(end_t, 0.0, gas));
+}
+
+/// Resample a continuous ascent path at the sample interval, switching gas
+/// at the planned depths.
+pub(crate) fn generate_continuous_ascent(
+    samples: &mut Vec<SampleInput>,
+    start_t: i32,
+    path: &[AscentWaypoint],
+    switch_schedule: &[(f64, i32)],
+    bottom_gas_index: i32,
+    ctx: &SampleCtx,
+) {
+    let mut current_gas = bottom_gas_index;
+    let mut switch_idx = 0;
+    let end_offset = path.last().map_or(0, |p| p.t_offset_sec);
+    let depth_at = |offset: i32| -> f64 {
+        let i = path.partition_point(|p| p.t_offset_sec < offset).max(1);
+        let (a, b) = (&path[i - 1], &path[i.min(path.len() - 1)]);
+        let span = (b.t_offset_sec - a.t_offset_sec).max(1) as f64;
+        let frac = ((offset - a.t_offset_sec) as f64 / span).clamp(0.0, 1.0);
+        a.depth_m as f64 + frac * (b.depth_m as f64 - a.depth_m as f64)
+    };
+
+    let mut offset = ctx.sample_interval;
+    while offset < end_offset {
+        let depth = depth_at(offset);
+        check_gas_switch(depth, &mut current_gas, switch_schedule, &mut switch_idx);
+        samples.push(ctx.make_sample(start_t + offset, depth, current_gas));
+        offset += ctx.sample_interval;
+    }
+    let mut current_depth = path.last().map_or(0.0, |p| p.depth_m as f64);
+    let mut current_t = start_t + end_offset;
+    check_gas_switch(
+        current_depth,
+        &mut current_gas,
+        switch_schedule,
+        &mut switch_idx,
+    );
+    samples.push(ctx.make_sample(current_t, current_depth, current_gas));
+
+    // A truncated path stops short of the surface
+    ascend_segment(
+        samples,
+        &mut current_depth,
+        &mut current_t,
+        0.0,
+        &mut current_gas,
+        switch_schedule,
+        &mut switch_idx,
+        ctx,
+    );
+}
+
+/// Set each sample's setpoint and PPO2 from the schedule, replaying it from
+/// the surface. Samples after `bottom_end_t` are on the ascent.
+fn apply_setpoint_schedule(
+    samples: &mut [SampleInput],
+    schedule: &SetpointSchedule,
+    bottom_end_t: i32,
+    surface_pressure: f64,
+) {
+    let by_depth = |a: &&SetpointSwitch, b: &&SetpointSwitch| {
+        a.depth_m
+            .partial_cmp(&b.depth_m)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    };
+    let mut descent: Vec<&SetpointSwitch> = schedule.descent_switches.iter().collect();
+    descent.sort_by(by_depth);
+    let mut ascent: Vec<&SetpointSwitch> = schedule.ascent_switches.iter().collect();
+    ascent.sort_by(|a, b| by_depth(b, a));
+    let mut timed: Vec<&TimedSetpointSwitch> = schedule.timed_switches.iter().collect();
+    timed.sort_by_key(|s| s.t_sec);
+
+    let (mut next_descent, mut next_ascent, mut next_timed) = (0, 0, 0);
+    let mut setpoint = schedule.surface_setpoint_ppo2;
+    for sample in samples {
+        let depth = sample.depth_m as f64;
+        if sample.t_sec <= bottom_end_t {
+            while next_descent < descent.len() && depth >= descent[next_descent].depth_m {
+                setpoint = descent[next_descent].setpoint_ppo2;
+                next_descent += 1;
+            }
+        } else {
+            while next_ascent < ascent.len() && depth <= ascent[next_ascent].depth_m {
+                setpoint = ascent[next_ascent].setpoint_ppo2;
+                next_ascent += 1;
+            }
+        }
+        while next_timed < timed.len() && sample.t_sec >= timed[next_timed].t_sec {
+            setpoint = timed[next_timed].setpoint_ppo2;
+            next_timed += 1;
+        }
+        let ambient = depth_to_pressure(depth, surface_pressure);
+        let ppo2 = setpoint.min(ambient) as f32;
+        sample.setpoint_ppo2 = Some(ppo2);
+        sample.ppo2 = Some(ppo2);
+    }
+}
+
+/// Set each sample's PPO2 to the SCR loop PPO2 for the gas it breathes.
+fn apply_scr_loop(
+    samples: &mut [SampleInput],
+    scr: &ScrParams,
+    gas_mixes: &[GasMixInput],
+    surface_pressure: f64,
+) {
+    let mut fo2 = gas_mixes.first().map_or(0.21, |g| g.o2_fraction);
+    for sample in samples {
+        if let Some(mix) = sample
+            .gasmix_index
+            .and_then(|idx| gas_mixes.iter().find(|g| g.mix_index == idx))
+        {
+            fo2 = mix.o2_fraction;
+        }
+        let ambient = depth_to_pressure(sample.depth_m as f64, surface_pressure);
+        sample.ppo2 = Some(scr::scr_loop_ppo2(scr, fo2, ambient) as f32);
+    }
 }
 
 /// Ascend from current depth to target depth, checking for gas switches.
@@ -609,6 +1331,19 @@ mod tests {
             thalmann_pdcs: None,
             sample_interval_sec: None,
             temp_c: None,
+            gas_switch_pause_sec: None,
+            max_ppo2: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            setpoint_schedule: None,
+            scr: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            safety_stop: None,
+            final_ascent_rate_m_min: None,
+            surface_tail_sec: None,
         }
     }
 
@@ -744,6 +1479,7 @@ mod tests {
                 he_fraction: 0.0,
             },
             switch_depth_m: Some(21.0),
+            descent_switch_depth_m: None,
         }];
         let result = generate_dive_profile(params);
         assert!(matches!(result, Err(DecoSimError::InvalidParam { .. })));
@@ -760,6 +1496,7 @@ mod tests {
                     he_fraction: 0.0,
                 },
                 switch_depth_m: None,
+                descent_switch_depth_m: None,
             },
             GasSwitchPlan {
                 gas: GasMixInput {
@@ -768,12 +1505,29 @@ mod tests {
                     he_fraction: 0.0,
                 },
                 switch_depth_m: None,
+                descent_switch_depth_m: None,
             },
         ];
         let result = generate_dive_profile(params);
         assert!(matches!(result, Err(DecoSimError::InvalidParam { .. })));
     }
 
+    #[test]
+    fn test_invalid_gas_fractions_rejected() {
+        let mut params = air_params(30.0, 1200);
+        params.gas_plan = vec![GasSwitchPlan {
+            gas: GasMixInput {
+                mix_index: 0,
+                o2_fraction: 0.50,
+                he_fraction: 0.60,
+            },
+            switch_depth_m: None,
+            descent_switch_depth_m: None,
+        }];
+        let result = generate_dive_profile(params);
+        assert!(matches!(result, Err(DecoSimError::InvalidParam { .. })));
+    }
+
     // ── Profile shape tests ─────────────────────────────────────────────
 
     #[test]
@@ -960,6 +1714,7 @@ mod tests {
                     he_fraction: 0.35,
                 },
                 switch_depth_m: None,
+                descent_switch_depth_m: None,
             },
             GasSwitchPlan {
                 gas: GasMixInput {
@@ -968,6 +1723,7 @@ mod tests {
                     he_fraction: 0.0,
                 },
                 switch_depth_m: Some(21.0),
+                descent_switch_depth_m: None,
             },
         ];
         let result = generate_dive_profile(params).unwrap();
@@ -999,6 +1755,7 @@ mod tests {
                     he_fraction: 0.0,
                 },
                 switch_depth_m: None,
+                descent_switch_depth_m: None,
             },
             GasSwitchPlan {
                 gas: GasMixInput {
@@ -1007,6 +1764,7 @@ mod tests {
                     he_fraction: 0.0,
                 },
                 switch_depth_m: Some(21.0),
+                descent_switch_depth_m: None,
             },
         ];
         let result = generate_dive_profile(params).unwrap();
@@ -1035,6 +1793,7 @@ mod tests {
                     he_fraction: 0.35,
                 },
                 switch_depth_m: None,
+                descent_switch_depth_m: None,
             },
             GasSwitchPlan {
                 gas: GasMixInput {
@@ -1043,6 +1802,7 @@ mod tests {
                     he_fraction: 0.0,
                 },
                 switch_depth_m: Some(21.0),
+                descent_switch_depth_m: None,
             },
             GasSwitchPlan {
                 gas: GasMixInput {
@@ -1051,6 +1811,7 @@ mod tests {
                     he_fraction: 0.0,
                 },
                 switch_depth_m: Some(6.0),
+                descent_switch_depth_m: None,
             },
         ];
         let result = generate_dive_profile(params).unwrap();
@@ -1066,6 +1827,215 @@ mod tests {
         assert!(gas_indices.contains(&2), "Should use O2 (gas 2)");
     }
 
+    fn plan_gas(
+        o2: f64,
+        he: f64,
+        switch_depth_m: Option<f64>,
+        descent_switch_depth_m: Option<f64>,
+    ) -> GasSwitchPlan {
+        GasSwitchPlan {
+            gas: GasMixInput {
+                mix_index: 0,
+                o2_fraction: o2,
+                he_fraction: he,
+            },
+            switch_depth_m,
+            descent_switch_depth_m,
+        }
+    }
+
+    /// 10/70 bottom gas reached on 21/35 travel gas, EAN50 at 21 m, O2 at 6 m.
+    fn travel_params() -> ProfileGenParams {
+        let mut params = air_params(70.0, 900);
+        params.gf_low = Some(50);
+        params.gf_high = Some(80);
+        params.gas_plan = vec![
+            plan_gas(0.21, 0.35, Some(57.0), Some(0.0)),
+            plan_gas(0.10, 0.70, None, Some(30.0)),
+            plan_gas(0.50, 0.0, Some(21.0), None),
+            plan_gas(1.0, 0.0, Some(6.0), None),
+        ];
+        params
+    }
+
+    #[test]
+    fn test_travel_gas_descent_switch_and_pause() {
+        let result = generate_dive_profile(travel_params()).unwrap();
+
+        assert_eq!(result.samples[0].gasmix_index, Some(0));
+        // 30 m at 18 m/min + 60 s pause + 40 m at 18 m/min
+        assert_eq!(result.descent_end_t_sec, 100 + 60 + 133);
+
+        let at_switch: Vec<_> = result
+            .samples
+            .iter()
+            .filter(|s| s.t_sec <= result.descent_end_t_sec && (s.depth_m - 30.0).abs() < 0.01)
+            .collect();
+        assert_eq!(at_switch.first().unwrap().t_sec, 100);
+        assert_eq!(at_switch.last().unwrap().t_sec, 160);
+        assert!(at_switch.iter().all(|s| s.gasmix_index == Some(1)));
+        for s in result.samples.iter().filter(|s| s.t_sec < 100) {
+            assert_eq!(s.gasmix_index, Some(0), "travel gas above 30 m");
+        }
+        for s in result
+            .samples
+            .iter()
+            .filter(|s| s.t_sec >= 100 && s.t_sec <= result.bottom_end_t_sec)
+        {
+            assert_eq!(s.gasmix_index, Some(1), "bottom gas below 30 m");
+        }
+    }
+
+    #[test]
+    fn test_travel_gas_reused_on_ascent() {
+        let result = generate_dive_profile(travel_params()).unwrap();
+        let ascent: Vec<_> = result
+            .samples
+            .iter()
+            .filter(|s| s.t_sec > result.bottom_end_t_sec)
+            .collect();
+        for s in &ascent {
+            let depth = s.depth_m as f64;
+            let expected = if depth > 57.0 {
+                1
+            } else if depth > 21.0 {
+                0
+            } else if depth > 6.0 {
+                2
+            } else {
+                3
+            };
+            assert_eq!(
+                s.gasmix_index,
+                Some(expected),
+                "wrong gas at {depth} m (t={})",
+                s.t_sec
+            );
+        }
+    }
+
+    #[test]
+    fn test_zero_switch_pause() {
+        let mut params = travel_params();
+        params.gas_switch_pause_sec = Some(0);
+        let result = generate_dive_profile(params).unwrap();
+        assert_eq!(result.descent_end_t_sec, 100 + 133);
+    }
+
+    #[test]
+    fn test_planner_honours_shallow_switch_depth() {
+        // EAN50 planned at 12 m instead of 21 m must lengthen the planned deco
+        let deco_with_switch_at = |depth: f64| {
+            let mut params = air_params(45.0, 1200);
+            params.gf_low = Some(30);
+            params.gf_high = Some(70);
+            params.gas_plan = vec![
+                plan_gas(0.21, 0.35, None, None),
+                plan_gas(0.50, 0.0, Some(depth), None),
+            ];
+            let result = generate_dive_profile(params).unwrap();
+            result
+                .planned_stops
+                .iter()
+                .map(|s| s.duration_sec)
+                .sum::<i32>()
+        };
+        assert!(deco_with_switch_at(12.0) > deco_with_switch_at(21.0));
+    }
+
+    #[test]
+    fn test_pinned_switches_follow_ppo2_limit_and_altitude() {
+        let mix = |mix_index: i32, o2_fraction: f64| GasMixInput {
+            mix_index,
+            o2_fraction,
+            he_fraction: 0.0,
+        };
+        let mixes = [mix(0, 0.21), mix(1, 0.50), mix(2, 1.0)];
+        let depths = |max_ppo2: f64, surface_p: f64| -> Vec<f64> {
+            pin_ascent_switches(&mixes, max_ppo2, surface_p)
+                .iter()
+                .map(|s| s.depth_m)
+                .collect()
+        };
+
+        // Sea level at 1.6 matches the engines' default switch depths
+        let sea = depths(1.6, DEFAULT_SURFACE_PRESSURE);
+        assert!((sea[1] - 22.0).abs() < 1e-9, "{sea:?}");
+        assert!((sea[2] - 6.0).abs() < 1e-9, "{sea:?}");
+
+        // A lower PPO2 limit pins shallower
+        let limited = depths(1.4, DEFAULT_SURFACE_PRESSURE);
+        assert!((limited[1] - 18.0).abs() < 1e-9, "{limited:?}");
+
+        // Lower surface pressure at altitude pins deeper
+        let altitude = depths(1.6, 0.8);
+        assert!(
+            altitude.iter().zip(&sea).all(|(a, s)| a > s),
+            "{altitude:?}"
+        );
+    }
+
+    #[test]
+    fn test_hypoxic_gas_from_surface_rejected() {
+        let mut params = air_params(70.0, 600);
+        params.gas_plan = vec![plan_gas(0.10, 0.70, None, None)];
+        let result = generate_dive_profile(params);
+        assert!(
+            matches!(result, Err(DecoSimError::InvalidParam { ref msg }) if msg.contains("minimum operating depth")),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn test_switch_below_mod_rejected() {
+        let mut params = air_params(45.0, 1200);
+        params.gas_plan = vec![
+            plan_gas(0.21, 0.35, None, None),
+            plan_gas(0.50, 0.0, Some(30.0), None),
+        ];
+        let result = generate_dive_profile(params);
+        assert!(
+            matches!(result, Err(DecoSimError::InvalidParam { ref msg }) if msg.contains("MOD")),
+            "{result:?}"
+        );
+
+        // EAN32 at 30 m breathes PPO2 1.3
+        let mut params = air_params(30.0, 600);
+        params.gas_plan = vec![plan_gas(0.32, 0.0, None, None)];
+        params.max_ppo2 = Some(1.2);
+        assert!(generate_dive_profile(params.clone()).is_err());
+        params.max_ppo2 = Some(1.4);
+        assert!(generate_dive_profile(params).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_descent_switches_rejected() {
+        // No gas from the surface
+        let mut params = travel_params();
+        params.gas_plan[0].descent_switch_depth_m = Some(6.0);
+        assert!(generate_dive_profile(params).is_err());
+
+        // Bottom gas not the deepest descent gas
+        let mut params = travel_params();
+        params.gas_plan[2].descent_switch_depth_m = Some(40.0);
+        assert!(generate_dive_profile(params).is_err());
+
+        // Duplicate depths
+        let mut params = travel_params();
+        params.gas_plan[1].descent_switch_depth_m = Some(0.0);
+        assert!(generate_dive_profile(params).is_err());
+
+        // Deeper than the target
+        let mut params = travel_params();
+        params.gas_plan[1].descent_switch_depth_m = Some(80.0);
+        assert!(generate_dive_profile(params).is_err());
+
+        // Negative pause
+        let mut params = travel_params();
+        params.gas_switch_pause_sec = Some(-1);
+        assert!(generate_dive_profile(params).is_err());
+    }
+
     // ── CCR tests ───────────────────────────────────────────────────────
 
     #[test]
@@ -1102,6 +2072,357 @@ mod tests {
         );
     }
 
+    fn ccr_schedule() -> SetpointSchedule {
+        SetpointSchedule {
+            surface_setpoint_ppo2: 0.7,
+            descent_switches: vec![SetpointSwitch {
+                depth_m: 10.0,
+                setpoint_ppo2: 1.3,
+            }],
+            ascent_switches: vec![SetpointSwitch {
+                depth_m: 6.0,
+                setpoint_ppo2: 1.6,
+            }],
+            timed_switches: vec![],
+        }
+    }
+
+    #[test]
+    fn test_setpoint_schedule_by_depth() {
+        let mut params = air_params(45.0, 1800);
+        params.gas_plan = vec![plan_gas(0.18, 0.45, None, None)];
+        params.setpoint_schedule = Some(ccr_schedule());
+        let result = generate_dive_profile(params).unwrap();
+
+        for s in &result.samples {
+            let setpoint = s.setpoint_ppo2.unwrap();
+            assert_eq!(s.ppo2, Some(setpoint));
+            let ambient = depth_to_pressure(s.depth_m as f64, DEFAULT_SURFACE_PRESSURE) as f32;
+            let expected: f32 = if s.t_sec <= result.bottom_end_t_sec {
+                if s.depth_m >= 10.0 {
+                    1.3
+                } else {
+                    0.7
+                }
+            } else if s.depth_m <= 6.0 {
+                1.6
+            } else {
+                1.3
+            };
+            assert!(
+                (setpoint - expected.min(ambient)).abs() < 1e-4,
+                "t={} depth={} setpoint={setpoint}",
+                s.t_sec,
+                s.depth_m
+            );
+        }
+
+        // The planner and TTS follow the ascent switches: bumping to 1.6 from
+        // 21 m shortens the deco compared with staying on 1.3
+        let plan = |ascent_switches: Vec<SetpointSwitch>| {
+            let mut params = air_params(45.0, 1800);
+            params.gas_plan = vec![plan_gas(0.18, 0.45, None, None)];
+            let mut schedule = ccr_schedule();
+            schedule.ascent_switches = ascent_switches;
+            params.setpoint_schedule = Some(schedule);
+            generate_dive_profile(params).unwrap()
+        };
+        let bumped = plan(vec![SetpointSwitch {
+            depth_m: 21.0,
+            setpoint_ppo2: 1.6,
+        }]);
+        let fixed = plan(vec![]);
+        assert!(
+            bumped.deco_result.max_tts_sec < fixed.deco_result.max_tts_sec,
+            "TTS with 1.6 from 21 m ({}) should be below fixed 1.3 ({})",
+            bumped.deco_result.max_tts_sec,
+            fixed.deco_result.max_tts_sec
+        );
+        assert!(bumped.total_time_sec < fixed.total_time_sec);
+    }
+
+    #[test]
+    fn test_setpoint_schedule_timed_switch() {
+        let mut params = air_params(30.0, 1200);
+        let mut schedule = ccr_schedule();
+        schedule.timed_switches = vec![TimedSetpointSwitch {
+            t_sec: 600,
+            setpoint_ppo2: 1.0,
+        }];
+        params.setpoint_schedule = Some(schedule);
+        let result = generate_dive_profile(params).unwrap();
+        for s in result
+            .samples
+            .iter()
+            .filter(|s| s.t_sec <= result.bottom_end_t_sec)
+        {
+            let expected = if s.t_sec >= 600 {
+                1.0
+            } else if s.depth_m >= 10.0 {
+                1.3
+            } else {
+                0.7_f32.min(depth_to_pressure(s.depth_m as f64, DEFAULT_SURFACE_PRESSURE) as f32)
+            };
+            assert!((s.setpoint_ppo2.unwrap() - expected).abs() < 1e-4);
+        }
+    }
+
+    fn active_scr() -> ScrParams {
+        ScrParams {
+            mode: scr::ScrMode::Active,
+            o2_consumption_l_min: 1.0,
+            dump_ratio: None,
+            injection_rate_l_min: Some(10.0),
+        }
+    }
+
+    #[test]
+    fn test_scr_loop_ppo2_in_samples() {
+        let mut params = air_params(30.0, 1800);
+        params.gas_plan = vec![plan_gas(0.32, 0.0, None, None)];
+        params.scr = Some(active_scr());
+        let result = generate_dive_profile(params).unwrap();
+
+        // Active loop on EAN32: (3.2 - 1) / 9 = 0.244 at every depth
+        for s in &result.samples {
+            let ambient = depth_to_pressure(s.depth_m as f64, DEFAULT_SURFACE_PRESSURE);
+            let expected = (2.2 / 9.0 * ambient) as f32;
+            assert!((s.ppo2.unwrap() - expected).abs() < 1e-4);
+            assert_eq!(s.setpoint_ppo2, None);
+        }
+
+        // The depleted loop carries more nitrogen than open circuit on EAN32
+        let mut oc = air_params(30.0, 1800);
+        oc.gas_plan = vec![plan_gas(0.32, 0.0, None, None)];
+        let oc = generate_dive_profile(oc).unwrap();
+        assert!(result.deco_result.max_tts_sec > oc.deco_result.max_tts_sec);
+    }
+
+    #[test]
+    fn test_invalid_scr_rejected() {
+        let mut params = air_params(30.0, 600);
+        params.setpoint_ppo2 = Some(1.3);
+        params.scr = Some(active_scr());
+        assert!(generate_dive_profile(params).is_err());
+
+        let mut params = air_params(30.0, 600);
+        params.setpoint_schedule = Some(ccr_schedule());
+        params.scr = Some(active_scr());
+        assert!(generate_dive_profile(params).is_err());
+
+        let mut params = air_params(30.0, 600);
+        let mut scr = active_scr();
+        scr.injection_rate_l_min = Some(0.5);
+        params.scr = Some(scr);
+        assert!(generate_dive_profile(params).is_err());
+
+        // The supply gas keeps its operating depth limits on a loop:
+        // EAN50 has a MOD of 22 m at PPO2 1.6
+        let mut params = air_params(30.0, 600);
+        params.gas_plan = vec![plan_gas(0.50, 0.0, None, None)];
+        params.scr = Some(active_scr());
+        assert!(matches!(
+            generate_dive_profile(params),
+            Err(DecoSimError::InvalidParam { .. })
+        ));
+    }
+
+    #[test]
+    fn test_buhlmann_coefficient_set_applied() {
+        let mut params = air_params(40.0, 1800);
+        params.gf_low = Some(50);
+        params.gf_high = Some(80);
+        let c = generate_dive_profile(params.clone()).unwrap();
+
+        params.buhlmann_coefficients = Some(BuhlmannCoefficientSet::Zhl16a);
+        let a = generate_dive_profile(params.clone()).unwrap();
+        assert!(
+            a.total_time_sec < c.total_time_sec,
+            "ZHL-16A {} should be shorter than ZHL-16C {}",
+            a.total_time_sec,
+            c.total_time_sec
+        );
+
+        params.buhlmann_coefficients = Some(BuhlmannCoefficientSet::Custom);
+        assert!(matches!(
+            generate_dive_profile(params),
+            Err(DecoSimError::InvalidParam { .. })
+        ));
+    }
+
+    #[test]
+    fn test_custom_thalmann_params_applied() {
+        let mut params = air_params(40.0, 1800);
+        params.model = DecoModel::ThalmannElDca;
+        let builtin = generate_dive_profile(params.clone()).unwrap();
+
+        // XVal-He-9_023 with every surfacing M-value lowered by 10%.
+        params.thalmann_pdcs = Some(ThalmannPdcs::Custom);
+        params.custom_thalmann_params = Some(ThalmannParameters {
+            half_times_min: vec![10.0, 20.0, 20.0, 120.0, 210.0],
+            sdr: vec![1.0, 2.0, 0.67, 1.0, 1.0],
+            m0_fsw: vec![76.5, 57.6, 74.7, 37.558, 30.749],
+            beta1: vec![1.0, 1.0, 1.0, 2.0, 1.0],
+            pbovp_fsw: 0.0,
+        });
+        let custom = generate_dive_profile(params.clone()).unwrap();
+        assert!(
+            custom.total_time_sec > builtin.total_time_sec,
+            "custom {} vs built-in {}",
+            custom.total_time_sec,
+            builtin.total_time_sec
+        );
+
+        params.thalmann_pdcs = Some(ThalmannPdcs::Pdcs40);
+        assert!(matches!(
+            generate_dive_profile(params),
+            Err(DecoSimError::InvalidParam { .. })
+        ));
+    }
+
+    // ── Safety stop, final ascent and surface tail ───────────────────────
+
+    fn default_safety_stop() -> SafetyStopParams {
+        SafetyStopParams {
+            depth_m: None,
+            duration_sec: None,
+            min_dive_depth_m: None,
+        }
+    }
+
+    #[test]
+    fn test_safety_stop_on_no_deco_dive() {
+        let mut params = air_params(18.0, 1200);
+        params.safety_stop = Some(default_safety_stop());
+        let result = generate_dive_profile(params).unwrap();
+
+        assert!(result.planned_stops.is_empty());
+        let start = result.safety_stop_start_t_sec.expect("safety stop");
+        assert_eq!(result.final_ascent_start_t_sec, start + 180);
+        let held: Vec<_> = result
+            .samples
+            .iter()
+            .filter(|s| s.t_sec >= start && s.t_sec <= start + 180)
+            .collect();
+        assert!(held.iter().all(|s| (s.depth_m - 5.0).abs() < 1e-6));
+        // 5 m at the default 9 m/min after the stop
+        assert_eq!(result.surface_t_sec, start + 180 + 33);
+        assert_eq!(result.total_time_sec, result.surface_t_sec);
+    }
+
+    #[test]
+    fn test_safety_stop_skipped_when_not_required() {
+        let mut shallow = air_params(8.0, 1200);
+        shallow.safety_stop = Some(default_safety_stop());
+        let result = generate_dive_profile(shallow).unwrap();
+        assert_eq!(result.safety_stop_start_t_sec, None);
+        assert_eq!(result.final_ascent_start_t_sec, result.bottom_end_t_sec);
+
+        let mut deco = air_params(40.0, 1800);
+        deco.safety_stop = Some(default_safety_stop());
+        let with_stop = generate_dive_profile(deco.clone()).unwrap();
+        deco.safety_stop = None;
+        let without = generate_dive_profile(deco).unwrap();
+        assert_eq!(with_stop.safety_stop_start_t_sec, None);
+        assert_eq!(with_stop.total_time_sec, without.total_time_sec);
+
+        let mut always = air_params(8.0, 1200);
+        always.safety_stop = Some(SafetyStopParams {
+            depth_m: Some(3.0),
+            duration_sec: Some(60),
+            min_dive_depth_m: Some(0.0),
+        });
+        let result = generate_dive_profile(always).unwrap();
+        let start = result.safety_stop_start_t_sec.expect("safety stop");
+        assert_eq!(result.final_ascent_start_t_sec, start + 60);
+    }
+
+    #[test]
+    fn test_final_ascent_rate_from_last_stop() {
+        let mut params = air_params(40.0, 1800);
+        let normal = generate_dive_profile(params.clone()).unwrap();
+        params.final_ascent_rate_m_min = Some(3.0);
+        let slow = generate_dive_profile(params).unwrap();
+
+        assert_eq!(slow.planned_stops.len(), normal.planned_stops.len());
+        // Last stop at 3 m: 20 s at 9 m/min, 60 s at 3 m/min
+        assert_eq!(normal.surface_t_sec - normal.final_ascent_start_t_sec, 20);
+        assert_eq!(slow.surface_t_sec - slow.final_ascent_start_t_sec, 60);
+        assert_eq!(
+            slow.final_ascent_start_t_sec,
+            normal.final_ascent_start_t_sec
+        );
+    }
+
+    #[test]
+    fn test_surface_tail_samples() {
+        let mut params = air_params(18.0, 1200);
+        params.surface_tail_sec = Some(300);
+        let result = generate_dive_profile(params).unwrap();
+
+        assert_eq!(result.total_time_sec, result.surface_t_sec + 300);
+        let tail: Vec<_> = result
+            .samples
+            .iter()
+            .filter(|s| s.t_sec >= result.surface_t_sec)
+            .collect();
+        assert_eq!(tail.len(), 31);
+        assert!(tail.iter().all(|s| s.depth_m == 0.0));
+        assert_eq!(
+            result.deco_result.points.len(),
+            result.samples.len(),
+            "the overlay covers the tail"
+        );
+    }
+
+    #[test]
+    fn test_invalid_ascent_finish_rejected() {
+        let mut params = air_params(18.0, 1200);
+        params.final_ascent_rate_m_min = Some(0.0);
+        assert!(generate_dive_profile(params).is_err());
+
+        let mut params = air_params(18.0, 1200);
+        params.surface_tail_sec = Some(-1);
+        assert!(generate_dive_profile(params).is_err());
+
+        let mut params = air_params(18.0, 1200);
+        params.safety_stop = Some(SafetyStopParams {
+            duration_sec: Some(0),
+            ..default_safety_stop()
+        });
+        assert!(generate_dive_profile(params).is_err());
+
+        let mut params = air_params(18.0, 1200);
+        params.safety_stop = Some(SafetyStopParams {
+            depth_m: Some(-5.0),
+            ..default_safety_stop()
+        });
+        assert!(generate_dive_profile(params).is_err());
+    }
+
+    #[test]
+    fn test_invalid_setpoint_schedule_rejected() {
+        let mut params = air_params(30.0, 600);
+        params.setpoint_ppo2 = Some(1.3);
+        params.setpoint_schedule = Some(ccr_schedule());
+        assert!(generate_dive_profile(params).is_err());
+
+        let mut params = air_params(30.0, 600);
+        let mut schedule = ccr_schedule();
+        schedule.descent_switches[0].setpoint_ppo2 = 0.0;
+        params.setpoint_schedule = Some(schedule);
+        assert!(generate_dive_profile(params).is_err());
+
+        let mut params = air_params(30.0, 600);
+        let mut schedule = ccr_schedule();
+        schedule.timed_switches = vec![TimedSetpointSwitch {
+            t_sec: -1,
+            setpoint_ppo2: 1.0,
+        }];
+        params.setpoint_schedule = Some(schedule);
+        assert!(generate_dive_profile(params).is_err());
+    }
+
     // ── Deco integration tests ──────────────────────────────────────────
 
     #[test]
@@ -1222,6 +2543,113 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_pyle_deep_stops_in_profile() {
+        let mut params = air_params(50.0, 1500);
+        params.gf_low = Some(50);
+        params.gf_high = Some(85);
+        params.deep_stops = Some(DeepStopParams {
+            strategy: DeepStopStrategy::Pyle,
+            stop_duration_sec: Some(60),
+            custom_fractions: vec![],
+        });
+        let result = generate_dive_profile(params).unwrap();
+
+        let deep: Vec<_> = result
+            .planned_stops
+            .iter()
+            .filter(|s| s.is_deep_stop)
+            .collect();
+        assert!(!deep.is_empty(), "Pyle strategy should add deep stops");
+        // Pass 2 replays the samples with deep stops, so holds show up in
+        // the replayed profile and its own plan carries no deep stops.
+        assert!(result
+            .deco_result
+            .deco_stops
+            .iter()
+            .all(|s| !s.is_deep_stop));
+        for stop in deep {
+            let hold = result
+                .samples
+                .iter()
+                .filter(|s| s.t_sec > result.bottom_end_t_sec)
+                .filter(|s| (s.depth_m - stop.depth_m).abs() < 0.01)
+                .count();
+            assert!(hold >= 2, "Expected a hold at deep stop {} m", stop.depth_m);
+        }
+    }
+
+    #[test]
+    fn test_continuous_ascent_follows_ceiling() {
+        let mut staged = air_params(45.0, 1500);
+        staged.gf_low = Some(50);
+        staged.gf_high = Some(85);
+        staged.gas_plan = vec![
+            plan_gas(0.21, 0.0, None, None),
+            plan_gas(0.50, 0.0, Some(21.0), None),
+        ];
+        let mut continuous = staged.clone();
+        continuous.ascent_mode = Some(AscentMode::Continuous);
+        let staged = generate_dive_profile(staged).unwrap();
+        let result = generate_dive_profile(continuous.clone()).unwrap();
+
+        let ascent: Vec<_> = result
+            .samples
+            .iter()
+            .filter(|s| s.t_sec >= result.bottom_end_t_sec)
+            .collect();
+        assert!(ascent.windows(2).all(|w| w[1].depth_m <= w[0].depth_m));
+        assert_eq!(ascent.last().unwrap().depth_m, 0.0);
+        // The deco gas is picked up at its planned depth
+        assert!(ascent
+            .iter()
+            .all(|s| (s.depth_m > 21.0) == (s.gasmix_index == Some(0))));
+        // Simplified stop list on the stop grid, deepest first
+        assert!(!result.planned_stops.is_empty());
+        assert!(result
+            .planned_stops
+            .windows(2)
+            .all(|w| w[0].depth_m > w[1].depth_m));
+        assert!(result
+            .planned_stops
+            .iter()
+            .all(|s| (s.depth_m % 3.0).abs() < 0.01 && s.duration_sec > 0));
+        // Ceiling-following avoids waiting for whole grid stops to clear
+        assert!(
+            result.total_time_sec < staged.total_time_sec,
+            "continuous {} s vs staged {} s",
+            result.total_time_sec,
+            staged.total_time_sec
+        );
+        // A margin keeps the diver deeper and lengthens the ascent
+        continuous.ceiling_margin_m = Some(1.5);
+        let with_margin = generate_dive_profile(continuous).unwrap();
+        assert!(with_margin.total_time_sec > result.total_time_sec);
+    }
+
+    #[test]
+    fn test_continuous_ascent_rejected_for_thalmann() {
+        let mut params = air_params(40.0, 1200);
+        params.model = DecoModel::ThalmannElDca;
+        params.ascent_mode = Some(AscentMode::Continuous);
+        assert!(generate_dive_profile(params).is_err());
+
+        let mut params = air_params(40.0, 1200);
+        params.ascent_mode = Some(AscentMode::Continuous);
+        params.ceiling_margin_m = Some(-1.0);
+        assert!(generate_dive_profile(params).is_err());
+    }
+
+    #[test]
+    fn test_continuous_ascent_without_deco_is_direct() {
+        let mut params = air_params(18.0, 600);
+        params.ascent_mode = Some(AscentMode::Continuous);
+        let result = generate_dive_profile(params).unwrap();
+        assert!(result.planned_stops.is_empty());
+        // 18 m at 9 m/min
+        assert_eq!(result.total_time_sec - result.bottom_end_t_sec, 120);
+    }
+
     // ── Custom sample interval ──────────────────────────────────────────
 
     #[test]
@@ -1370,6 +2798,7 @@ mod tests {
                     he_fraction: 0.15,
                 },
                 switch_depth_m: None,
+                descent_switch_depth_m: None,
             }],
             model: DecoModel::BuhlmannZhl16c,
             surface_pressure_bar: None,
@@ -1381,6 +2810,19 @@ mod tests {
             thalmann_pdcs: None,
             sample_interval_sec: None,
             temp_c: None,
+            gas_switch_pause_sec: None,
+            max_ppo2: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            setpoint_schedule: None,
+            scr: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            safety_stop: None,
+            final_ascent_rate_m_min: None,
+            surface_tail_sec: None,
         };
         let result = generate_dive_profile(params).unwrap();
         let total_min = result.total_time_sec / 60;
@@ -1411,6 +2853,7 @@ mod tests {
                     he_fraction: 0.25,
                 },
                 switch_depth_m: None,
+                descent_switch_depth_m: None,
             }],
             model: DecoModel::BuhlmannZhl16c,
             surface_pressure_bar: None,
@@ -1422,6 +2865,19 @@ mod tests {
             thalmann_pdcs: None,
             sample_interval_sec: None,
             temp_c: None,
+            gas_switch_pause_sec: None,
+            max_ppo2: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            setpoint_schedule: None,
+            scr: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            safety_stop: None,
+            final_ascent_rate_m_min: None,
+            surface_tail_sec: None,
         };
         let result = generate_dive_profile(params).unwrap();
         let total_min = result.total_time_sec / 60;
@@ -1446,6 +2902,7 @@ mod tests {
                     he_fraction: 0.15,
                 },
                 switch_depth_m: None,
+                descent_switch_depth_m: None,
             }],
             model: DecoModel::ThalmannElDca,
             surface_pressure_bar: None,
@@ -1457,6 +2914,19 @@ mod tests {
             thalmann_pdcs: Some(ThalmannPdcs::Pdcs23),
             sample_interval_sec: None,
             temp_c: None,
+            gas_switch_pause_sec: None,
+            max_ppo2: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            setpoint_schedule: None,
+            scr: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            safety_stop: None,
+            final_ascent_rate_m_min: None,
+            surface_tail_sec: None,
         };
         let result = generate_dive_profile(params).unwrap();
         let total_min = result.total_time_sec / 60;
@@ -1552,6 +3022,7 @@ mod tests {
                             he_fraction: 0.35,
                         },
                         switch_depth_m: None,
+                        descent_switch_depth_m: None,
                     },
                     GasSwitchPlan {
                         gas: GasMixInput {
@@ -1560,6 +3031,7 @@ mod tests {
                             he_fraction: 0.0,
                         },
                         switch_depth_m: Some(21.0),
+                        descent_switch_depth_m: None,
                     },
                 ],
                 model: DecoModel::BuhlmannZhl16c,
@@ -1572,6 +3044,19 @@ mod tests {
                 thalmann_pdcs: None,
                 sample_interval_sec: None,
                 temp_c: None,
+                gas_switch_pause_sec: None,
+                max_ppo2: None,
+                deep_stops: None,
+                ascent_mode: None,
+                ceiling_margin_m: None,
+                setpoint_schedule: None,
+                scr: None,
+                buhlmann_coefficients: None,
+                custom_buhlmann_coefficients: None,
+                custom_thalmann_params: None,
+                safety_stop: None,
+                final_ascent_rate_m_min: None,
+                surface_tail_sec: None,
             };
             let result = generate_dive_profile(params).unwrap();
             let total_min = result.total_time_sec / 60;
@@ -1651,6 +3136,7 @@ mod tests {
                         he_fraction: 0.35,
                     },
                     switch_depth_m: None,
+                    descent_switch_depth_m: None,
                 },
                 GasSwitchPlan {
                     gas: GasMixInput {
@@ -1659,6 +3145,7 @@ mod tests {
                         he_fraction: 0.0,
                     },
                     switch_depth_m: Some(21.0),
+                    descent_switch_depth_m: None,
                 },
             ],
             model: DecoModel::BuhlmannZhl16c,
@@ -1671,6 +3158,19 @@ mod tests {
             thalmann_pdcs: None,
             sample_interval_sec: None,
             temp_c: None,
+            gas_switch_pause_sec: None,
+            max_ppo2: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            setpoint_schedule: None,
+            scr: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            safety_stop: None,
+            final_ascent_rate_m_min: None,
+            surface_tail_sec: None,
         };
 
         // DP4 uses 40 min bottom time (including descent), descent ~15.24 m/min
@@ -1743,6 +3243,7 @@ mod tests {
                     he_fraction: 0.15,
                 },
                 switch_depth_m: None,
+                descent_switch_depth_m: None,
             }],
             model: DecoModel::BuhlmannZhl16c,
             surface_pressure_bar: None,
@@ -1754,6 +3255,19 @@ mod tests {
             thalmann_pdcs: None,
             sample_interval_sec: None,
             temp_c: None,
+            gas_switch_pause_sec: None,
+            max_ppo2: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            setpoint_schedule: None,
+            scr: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            safety_stop: None,
+            final_ascent_rate_m_min: None,
+            surface_tail_sec: None,
         })
         .unwrap();
         print_profile("CCR 150ft/40min GF50/90 (DP4 ref: 77 min)", &r1);
@@ -1776,6 +3290,7 @@ mod tests {
                     he_fraction: 0.25,
                 },
                 switch_depth_m: None,
+                descent_switch_depth_m: None,
             }],
             model: DecoModel::BuhlmannZhl16c,
             surface_pressure_bar: None,
@@ -1787,6 +3302,19 @@ mod tests {
             thalmann_pdcs: None,
             sample_interval_sec: None,
             temp_c: None,
+            gas_switch_pause_sec: None,
+            max_ppo2: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            setpoint_schedule: None,
+            scr: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            safety_stop: None,
+            final_ascent_rate_m_min: None,
+            surface_tail_sec: None,
         })
         .unwrap();
         print_profile("CCR 200ft/22min-at-depth GF50/90 (DP4 ref: 76 min)", &r2);
@@ -1813,6 +3341,7 @@ mod tests {
                     he_fraction: 0.35,
                 },
                 switch_depth_m: None,
+                descent_switch_depth_m: None,
             }],
             model: DecoModel::BuhlmannZhl16c,
             surface_pressure_bar: None,
@@ -1824,6 +3353,19 @@ mod tests {
             thalmann_pdcs: None,
             sample_interval_sec: None,
             temp_c: None,
+            gas_switch_pause_sec: None,
+            max_ppo2: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            setpoint_schedule: None,
+            scr: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            safety_stop: None,
+            final_ascent_rate_m_min: None,
+            surface_tail_sec: None,
         };
         let result = generate_dive_profile(params).unwrap();
         let total_min = result.total_time_sec / 60;
@@ -1868,6 +3410,7 @@ mod tests {
                         he_fraction: 0.35,
                     },
                     switch_depth_m: None,
+                    descent_switch_depth_m: None,
                 },
                 GasSwitchPlan {
                     gas: GasMixInput {
@@ -1876,6 +3419,7 @@ mod tests {
                         he_fraction: 0.0,
                     },
                     switch_depth_m: Some(21.0), // ~70ft
+                    descent_switch_depth_m: None,
                 },
             ],
             model: DecoModel::BuhlmannZhl16c,
@@ -1888,6 +3432,19 @@ mod tests {
             thalmann_pdcs: None,
             sample_interval_sec: None,
             temp_c: None,
+            gas_switch_pause_sec: None,
+            max_ppo2: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            setpoint_schedule: None,
+            scr: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            safety_stop: None,
+            final_ascent_rate_m_min: None,
+            surface_tail_sec: None,
         };
         let result = generate_dive_profile(params).unwrap();
         let total_min = result.total_time_sec / 60;
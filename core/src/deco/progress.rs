@@ -0,0 +1,407 @@
+//! Cancellation and progress reporting for long computations.
+//!
+//! Simulations, profile generation and the batch planners (model comparison,
+//! gas optimisation, bottom-time and surface-interval searches, dive days)
+//! accept a [`ComputeControl`]. It bundles an optional [`CancellationToken`],
+//! which another thread can trip to abort the run with
+//! `DecoSimError::Cancelled`, and an optional [`ProgressObserver`] that
+//! receives the completed fraction.
+//!
+//! Internally each call hands a [`Progress`] slice to the work it delegates,
+//! so nested runs (a profile inside a bottom-time search inside a dive day)
+//! report into their share of the caller's range. Reported fractions never
+//! decrease and are throttled to whole percent steps; 1.0 is reported once
+//! the call succeeds.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::types::DecoSimError;
+
+/// Smallest increase in the completed fraction that is reported.
+const REPORT_STEP: f64 = 0.01;
+
+/// Shared flag that aborts a running computation.
+///
+/// Cancellation is cooperative: the computation checks the token between
+/// samples and planner iterations and returns `DecoSimError::Cancelled`.
+#[derive(Debug, Default)]
+pub struct CancellationToken {
+    cancelled: AtomicBool,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask every computation holding this token to stop.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Receives progress of a long computation, on the computing thread.
+pub trait ProgressObserver: Send + Sync {
+    /// Completed fraction of the call, in `[0, 1]` and non-decreasing.
+    fn on_progress(&self, fraction: f64);
+}
+
+/// Cancellation and progress hooks for one call.
+#[derive(Default)]
+pub struct ComputeControl<'a> {
+    cancel: Option<&'a CancellationToken>,
+    observer: Option<&'a dyn ProgressObserver>,
+    reported: Cell<f64>,
+}
+
+impl<'a> ComputeControl<'a> {
+    pub fn new(
+        cancel: Option<&'a CancellationToken>,
+        observer: Option<&'a dyn ProgressObserver>,
+    ) -> Self {
+        Self {
+            cancel,
+            observer,
+            reported: Cell::new(0.0),
+        }
+    }
+
+    /// The whole range of the call.
+    pub(crate) fn progress(&self) -> Progress<'_> {
+        Progress {
+            control: Some(self),
+            start: 0.0,
+            span: 1.0,
+        }
+    }
+
+    /// Report completion after a successful call.
+    pub(crate) fn finish(&self) {
+        self.report(1.0);
+    }
+
+    fn report(&self, fraction: f64) {
+        let Some(observer) = self.observer else {
+            return;
+        };
+        let fraction = fraction.clamp(0.0, 1.0);
+        let reported = self.reported.get();
+        if fraction >= reported + REPORT_STEP || (fraction >= 1.0 && reported < 1.0) {
+            self.reported.set(fraction);
+            observer.on_progress(fraction);
+        }
+    }
+}
+
+/// A share of a call's progress range, handed to delegated work.
+#[derive(Clone, Copy)]
+pub(crate) struct Progress<'a> {
+    control: Option<&'a ComputeControl<'a>>,
+    start: f64,
+    span: f64,
+}
+
+impl Progress<'_> {
+    /// No cancellation and no reporting.
+    pub(crate) const NONE: Progress<'static> = Progress {
+        control: None,
+        start: 0.0,
+        span: 1.0,
+    };
+
+    /// The sub-range `[from, to]` of this slice (fractions of this slice).
+    pub(crate) fn slice(self, from: f64, to: f64) -> Self {
+        Self {
+            start: self.start + self.span * from,
+            span: self.span * (to - from),
+            ..self
+        }
+    }
+
+    /// Sub-range for item `index` of `count` equal parts.
+    pub(crate) fn part(self, index: usize, count: usize) -> Self {
+        let count = count.max(1) as f64;
+        self.slice(index as f64 / count, (index + 1) as f64 / count)
+    }
+
+    /// Fail with `DecoSimError::Cancelled` if the token has been tripped.
+    pub(crate) fn check(self) -> Result<(), DecoSimError> {
+        match self.control.and_then(|c| c.cancel) {
+            Some(token) if token.is_cancelled() => Err(DecoSimError::Cancelled {
+                msg: "computation cancelled".to_string(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check for cancellation, then report `fraction` of this slice done.
+    pub(crate) fn step(self, fraction: f64) -> Result<(), DecoSimError> {
+        self.check()?;
+        if let Some(control) = self.control {
+            control.report(self.start + self.span * fraction.clamp(0.0, 1.0));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deco::comparison::{
+        compare_models_with_control, ModelComparisonParams, ModelConfig,
+    };
+    use crate::deco::compute_deco_simulation_with_control;
+    use crate::deco::max_bottom_time::{solve_max_bottom_time_with_control, MaxBottomTimeParams};
+    use crate::deco::profile_generator::{
+        generate_dive_profile, generate_dive_profile_with_control, GasSwitchPlan, ProfileGenParams,
+    };
+    use crate::deco::types::{DecoModel, DecoSimParams};
+    use crate::metrics::SampleInput;
+    use crate::GasMixInput;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<f64>>);
+
+    impl ProgressObserver for Recorder {
+        fn on_progress(&self, fraction: f64) {
+            self.0.lock().unwrap().push(fraction);
+        }
+    }
+
+    /// Trips a token once progress passes a threshold, like a user tapping
+    /// cancel mid-run.
+    struct CancelAt {
+        token: Arc<CancellationToken>,
+        threshold: f64,
+    }
+
+    impl ProgressObserver for CancelAt {
+        fn on_progress(&self, fraction: f64) {
+            if fraction >= self.threshold {
+                self.token.cancel();
+            }
+        }
+    }
+
+    /// A 45 m / 25 min air dive with EAN50 for deco, 2-second samples.
+    fn profile_params() -> ProfileGenParams {
+        let gas = |mix_index, o2_fraction, switch_depth_m| GasSwitchPlan {
+            gas: GasMixInput {
+                mix_index,
+                o2_fraction,
+                he_fraction: 0.0,
+            },
+            switch_depth_m,
+            descent_switch_depth_m: None,
+        };
+        ProfileGenParams {
+            target_depth_m: 45.0,
+            bottom_time_sec: 1500,
+            descent_rate_m_min: None,
+            ascent_rate_m_min: None,
+            gas_plan: vec![gas(0, 0.21, None), gas(1, 0.50, Some(21.0))],
+            model: DecoModel::BuhlmannZhl16c,
+            surface_pressure_bar: None,
+            gf_low: Some(30),
+            gf_high: Some(70),
+            last_stop_depth_m: None,
+            stop_interval_m: None,
+            setpoint_ppo2: None,
+            thalmann_pdcs: None,
+            sample_interval_sec: Some(2),
+            temp_c: None,
+            gas_switch_pause_sec: None,
+            max_ppo2: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            setpoint_schedule: None,
+            scr: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            safety_stop: None,
+            final_ascent_rate_m_min: None,
+            surface_tail_sec: None,
+        }
+    }
+
+    /// Planned-ascent simulation of generated samples at GF 30/70.
+    fn sim_params(samples: Vec<SampleInput>, gas_mixes: Vec<GasMixInput>) -> DecoSimParams {
+        DecoSimParams {
+            model: DecoModel::BuhlmannZhl16c,
+            samples,
+            gas_mixes,
+            surface_pressure_bar: None,
+            ascent_rate_m_min: None,
+            last_stop_depth_m: None,
+            stop_interval_m: None,
+            gf_low: Some(30),
+            gf_high: Some(70),
+            thalmann_pdcs: None,
+            plan_ascent: true,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
+        }
+    }
+
+    fn assert_progress_complete(recorder: &Recorder) {
+        let reports = recorder.0.lock().unwrap();
+        assert!(reports.len() > 10, "{} reports", reports.len());
+        assert!(reports.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(reports.last(), Some(&1.0));
+    }
+
+    #[test]
+    fn test_simulation_reports_progress_without_changing_results() {
+        let profile = generate_dive_profile(profile_params()).unwrap();
+        let sim = |control: &ComputeControl| {
+            compute_deco_simulation_with_control(
+                sim_params(profile.samples.clone(), profile.gas_mixes.clone()),
+                control,
+            )
+        };
+        let recorder = Recorder::default();
+        let observed = sim(&ComputeControl::new(None, Some(&recorder))).unwrap();
+        assert_progress_complete(&recorder);
+        let plain = sim(&ComputeControl::default()).unwrap();
+        assert_eq!(observed.points.len(), plain.points.len());
+        assert_eq!(observed.max_tts_sec, plain.max_tts_sec);
+        assert_eq!(observed.total_deco_time_sec, plain.total_deco_time_sec);
+    }
+
+    #[test]
+    fn test_profile_generation_cancelled_mid_run() {
+        let token = Arc::new(CancellationToken::new());
+        let observer = CancelAt {
+            token: token.clone(),
+            threshold: 0.3,
+        };
+        let control = ComputeControl::new(Some(&token), Some(&observer));
+        let err = generate_dive_profile_with_control(profile_params(), &control).unwrap_err();
+        assert!(matches!(err, DecoSimError::Cancelled { .. }));
+    }
+
+    #[test]
+    fn test_pre_cancelled_token_stops_before_work() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let recorder = Recorder::default();
+        let control = ComputeControl::new(Some(&token), Some(&recorder));
+        let err = generate_dive_profile_with_control(profile_params(), &control).unwrap_err();
+        assert!(matches!(err, DecoSimError::Cancelled { .. }));
+        assert!(recorder.0.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_batch_progress_spans_all_runs() {
+        let profile = generate_dive_profile(profile_params()).unwrap();
+        let config = |model, gf_high| ModelConfig {
+            model,
+            gf_low: Some(30),
+            gf_high,
+            thalmann_pdcs: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+        };
+        let base = sim_params(profile.samples, profile.gas_mixes);
+        let recorder = Recorder::default();
+        let control = ComputeControl::new(None, Some(&recorder));
+        let result = compare_models_with_control(
+            ModelComparisonParams {
+                base,
+                configs: vec![
+                    config(DecoModel::BuhlmannZhl16c, Some(70)),
+                    config(DecoModel::BuhlmannZhl16c, Some(85)),
+                    config(DecoModel::ThalmannElDca, None),
+                ],
+            },
+            &control,
+        )
+        .unwrap();
+        assert_eq!(result.runs.len(), 3);
+        assert_progress_complete(&recorder);
+        // The third run starts two thirds of the way through.
+        let reports = recorder.0.lock().unwrap();
+        assert!(reports.iter().any(|&f| f > 0.67 && f < 1.0));
+    }
+
+    #[test]
+    fn test_bottom_time_search_cancelled_mid_run() {
+        let token = Arc::new(CancellationToken::new());
+        let observer = CancelAt {
+            token: token.clone(),
+            threshold: 0.2,
+        };
+        let control = ComputeControl::new(Some(&token), Some(&observer));
+        let err = solve_max_bottom_time_with_control(
+            MaxBottomTimeParams {
+                profile: profile_params(),
+                max_runtime_sec: None,
+                max_tts_sec: Some(1800),
+                max_cns_percent: None,
+                cylinders: vec![],
+                bottom_sac_l_min: None,
+                deco_sac_l_min: None,
+                search_limit_sec: None,
+            },
+            &control,
+        )
+        .unwrap_err();
+        assert!(matches!(err, DecoSimError::Cancelled { .. }));
+    }
+
+    #[test]
+    fn test_slices_map_into_parent_range() {
+        let recorder = Recorder::default();
+        let control = ComputeControl::new(None, Some(&recorder));
+        let second_half = control.progress().slice(0.5, 1.0);
+        second_half.part(1, 2).step(0.5).unwrap();
+        assert_eq!(*recorder.0.lock().unwrap(), vec![0.875]);
+    }
+
+    #[test]
+    fn test_reports_are_throttled_and_monotonic() {
+        let recorder = Recorder::default();
+        let control = ComputeControl::new(None, Some(&recorder));
+        let progress = control.progress();
+        for i in 0..=1000 {
+            progress.step(i as f64 / 1000.0).unwrap();
+        }
+        progress.step(0.2).unwrap();
+        control.finish();
+        let reports = recorder.0.lock().unwrap();
+        assert!(reports.len() <= 101, "{} reports", reports.len());
+        assert!(reports.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(reports.last(), Some(&1.0));
+    }
+
+    #[test]
+    fn test_cancelled_token_fails_checks() {
+        let token = CancellationToken::new();
+        let control = ComputeControl::new(Some(&token), None);
+        assert!(control.progress().check().is_ok());
+        token.cancel();
+        assert!(token.is_cancelled());
+        assert!(matches!(
+            control.progress().step(0.5),
+            Err(DecoSimError::Cancelled { .. })
+        ));
+        assert!(Progress::NONE.check().is_ok());
+    }
+}
@@ -0,0 +1,577 @@
+//! Runtime table construction and rendering for generated dive profiles.
+//!
+//! Segments are derived from the generated samples rather than from the stop
+//! list, so descent/bottom/ascent/stop rows, gas switches and run times always
+//! match what the profile actually contains. Tables can be rendered as plain
+//! text, Markdown or CSV in metric or imperial units.
+
+use super::oxygen::{inspired_ppo2, OxygenExposure};
+use super::profile_generator::ProfileGenResult;
+use super::shared::*;
+use crate::buhlmann::GasMixInput;
+
+/// Feet per metre, for imperial rendering.
+const FT_PER_M: f64 = 3.28084;
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// Kind of segment in a runtime table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeSegment {
+    /// Travel from the surface towards the target depth.
+    Descent,
+    /// Time at the target depth.
+    Bottom,
+    /// Travel between stops (or to the surface).
+    Ascent,
+    /// Constant-depth hold during the ascent.
+    Stop,
+}
+
+impl RuntimeSegment {
+    /// Label used when rendering tables.
+    pub fn label(&self) -> &'static str {
+        match self {
+            RuntimeSegment::Descent => "Descent",
+            RuntimeSegment::Bottom => "Bottom",
+            RuntimeSegment::Ascent => "Ascent",
+            RuntimeSegment::Stop => "Stop",
+        }
+    }
+}
+
+/// Unit system for rendered tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitSystem {
+    /// Metres.
+    Metric,
+    /// Feet.
+    Imperial,
+}
+
+/// Output format for rendered tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeTableFormat {
+    /// Fixed-width columns for monospaced printouts.
+    PlainText,
+    /// GitHub-flavoured Markdown table.
+    Markdown,
+    /// Comma-separated values with a header row.
+    Csv,
+}
+
+/// A single row of a runtime table.
+#[derive(Debug, Clone)]
+pub struct RuntimeTableRow {
+    /// Segment kind.
+    pub segment: RuntimeSegment,
+    /// Depth at the start of the segment (metres).
+    pub start_depth_m: f32,
+    /// Depth at the end of the segment (metres).
+    pub depth_m: f32,
+    /// Duration of the segment (seconds).
+    pub stop_time_sec: i32,
+    /// Run time at the end of the segment (seconds from dive start).
+    pub run_time_sec: i32,
+    /// Gas mix index breathed during the segment.
+    pub gas_mix_index: i32,
+    /// Human-readable gas name (e.g. "Air", "EAN50", "Tx 21/35").
+    pub gas_label: String,
+    /// Highest inspired PPO2 during the segment (bar).
+    pub ppo2: f32,
+    /// Cumulative CNS clock at the end of the segment (percent).
+    pub cns_percent: f32,
+}
+
+/// A structured runtime table for a generated profile.
+#[derive(Debug, Clone)]
+pub struct RuntimeTable {
+    /// Rows in chronological order.
+    pub rows: Vec<RuntimeTableRow>,
+    /// Total dive time to surfacing (seconds).
+    pub total_time_sec: i32,
+    /// CNS clock at the end of the dive (percent).
+    pub cns_percent: f32,
+    /// Pulmonary oxygen toxicity units at the end of the dive.
+    pub otu: f32,
+}
+
+// ============================================================================
+// Construction
+// ============================================================================
+
+/// Build a runtime table from a generated profile.
+///
+/// Consecutive sample intervals are grouped into rows by phase (descent,
+/// bottom, ascent), by movement (travel vs hold) and by gas. Each interval
+/// is attributed to the gas of its starting sample, matching the engines.
+/// The table ends at surfacing; any surface tail is left out.
+pub fn build_runtime_table(
+    profile: &ProfileGenResult,
+    surface_pressure_bar: Option<f64>,
+) -> RuntimeTable {
+    let surface_p = surface_pressure_bar.unwrap_or(DEFAULT_SURFACE_PRESSURE);
+    let samples = profile.dive_samples();
+    let exposure = OxygenExposure::cumulative(samples, &profile.gas_mixes, surface_p);
+
+    let mut rows: Vec<RuntimeTableRow> = Vec::new();
+    let mut current_gas = profile.gas_mixes.first().map_or(0, |g| g.mix_index);
+    if let Some(gas) = samples.first().and_then(|s| s.gasmix_index) {
+        current_gas = gas;
+    }
+
+    for idx in 1..samples.len() {
+        let prev = &samples[idx - 1];
+        let sample = &samples[idx];
+        if let Some(gas) = prev.gasmix_index {
+            current_gas = gas;
+        }
+
+        let segment = if prev.t_sec < profile.descent_end_t_sec {
+            RuntimeSegment::Descent
+        } else if prev.t_sec < profile.bottom_end_t_sec {
+            RuntimeSegment::Bottom
+        } else if prev.depth_m == sample.depth_m {
+            RuntimeSegment::Stop
+        } else {
+            RuntimeSegment::Ascent
+        };
+
+        let ppo2 = segment_ppo2(prev, sample, current_gas, &profile.gas_mixes, surface_p);
+        let cns = exposure[idx].cns_percent as f32;
+
+        match rows.last_mut() {
+            Some(row)
+                if row.segment == segment
+                    && row.gas_mix_index == current_gas
+                    && !(segment == RuntimeSegment::Stop && row.depth_m != sample.depth_m) =>
+            {
+                row.depth_m = sample.depth_m;
+                row.stop_time_sec += sample.t_sec - prev.t_sec;
+                row.run_time_sec = sample.t_sec;
+                row.ppo2 = row.ppo2.max(ppo2);
+                row.cns_percent = cns;
+            }
+            _ => rows.push(RuntimeTableRow {
+                segment,
+                start_depth_m: prev.depth_m,
+                depth_m: sample.depth_m,
+                stop_time_sec: sample.t_sec - prev.t_sec,
+                run_time_sec: sample.t_sec,
+                gas_mix_index: current_gas,
+                gas_label: gas_label(current_gas, &profile.gas_mixes),
+                ppo2,
+                cns_percent: cns,
+            }),
+        }
+    }
+
+    let final_exposure = exposure.last().cloned().unwrap_or_default();
+    RuntimeTable {
+        rows,
+        total_time_sec: samples.last().map_or(0, |s| s.t_sec),
+        cns_percent: final_exposure.cns_percent as f32,
+        otu: final_exposure.otu as f32,
+    }
+}
+
+/// Highest inspired PPO2 over one sample interval.
+fn segment_ppo2(
+    prev: &crate::metrics::SampleInput,
+    sample: &crate::metrics::SampleInput,
+    gas_index: i32,
+    gas_mixes: &[GasMixInput],
+    surface_p: f64,
+) -> f32 {
+    let fo2 = gas_mixes
+        .iter()
+        .find(|g| g.mix_index == gas_index)
+        .map_or(AIR_FO2, |g| g.o2_fraction);
+    let deepest = (prev.depth_m.max(sample.depth_m) as f64).max(0.0);
+    let ambient_p = depth_to_pressure(deepest, surface_p);
+    inspired_ppo2(fo2, prev.ppo2.map(|v| v as f64), ambient_p) as f32
+}
+
+/// Conventional gas name: "Air", "O2", "EANxx" or "Tx oo/hh".
+pub(crate) fn gas_label(gas_index: i32, gas_mixes: &[GasMixInput]) -> String {
+    let Some(gas) = gas_mixes.iter().find(|g| g.mix_index == gas_index) else {
+        return "Air".to_string();
+    };
+    let o2_pct = (gas.o2_fraction * 100.0).round() as i32;
+    let he_pct = (gas.he_fraction * 100.0).round() as i32;
+    if he_pct > 0 {
+        format!("Tx {o2_pct}/{he_pct}")
+    } else if o2_pct >= 100 {
+        "O2".to_string()
+    } else if o2_pct == 21 {
+        "Air".to_string()
+    } else {
+        format!("EAN{o2_pct}")
+    }
+}
+
+// ============================================================================
+// Rendering
+// ============================================================================
+
+/// Render a runtime table in the requested format and unit system.
+pub fn render_runtime_table(
+    table: &RuntimeTable,
+    format: RuntimeTableFormat,
+    units: UnitSystem,
+) -> String {
+    match format {
+        RuntimeTableFormat::PlainText => render_plain(table, units),
+        RuntimeTableFormat::Markdown => render_markdown(table, units),
+        RuntimeTableFormat::Csv => render_csv(table, units),
+    }
+}
+
+fn depth_unit(units: UnitSystem) -> &'static str {
+    match units {
+        UnitSystem::Metric => "m",
+        UnitSystem::Imperial => "ft",
+    }
+}
+
+/// Depth in display units: whole feet, or metres with one decimal unless integral.
+fn format_depth(depth_m: f32, units: UnitSystem) -> String {
+    match units {
+        UnitSystem::Metric => {
+            let d = depth_m as f64;
+            if (d - d.round()).abs() < 0.05 {
+                format!("{}", d.round() as i32)
+            } else {
+                format!("{d:.1}")
+            }
+        }
+        UnitSystem::Imperial => format!("{}", (depth_m as f64 * FT_PER_M).round() as i32),
+    }
+}
+
+/// Duration as `m:ss`.
+fn format_time(sec: i32) -> String {
+    format!("{}:{:02}", sec / 60, sec % 60)
+}
+
+/// Cell values for a row in the order of the table header.
+fn row_cells(row: &RuntimeTableRow, units: UnitSystem) -> [String; 7] {
+    [
+        row.segment.label().to_string(),
+        format_depth(row.depth_m, units),
+        format_time(row.stop_time_sec),
+        format_time(row.run_time_sec),
+        row.gas_label.clone(),
+        format!("{:.2}", row.ppo2),
+        format!("{:.1}", row.cns_percent),
+    ]
+}
+
+fn header(units: UnitSystem) -> [String; 7] {
+    [
+        "Segment".to_string(),
+        format!("Depth ({})", depth_unit(units)),
+        "Time".to_string(),
+        "Run".to_string(),
+        "Gas".to_string(),
+        "ppO2".to_string(),
+        "CNS %".to_string(),
+    ]
+}
+
+fn render_plain(table: &RuntimeTable, units: UnitSystem) -> String {
+    let head = header(units);
+    let body: Vec<[String; 7]> = table.rows.iter().map(|r| row_cells(r, units)).collect();
+    let mut widths = head.clone().map(|h| h.chars().count());
+    for cells in &body {
+        for (w, c) in widths.iter_mut().zip(cells) {
+            *w = (*w).max(c.chars().count());
+        }
+    }
+
+    let line = |cells: &[String; 7]| -> String {
+        let mut out = String::new();
+        for (i, (cell, w)) in cells.iter().zip(&widths).enumerate() {
+            if i > 0 {
+                out.push_str("  ");
+            }
+            // Text columns left-aligned, numeric columns right-aligned.
+            if i == 0 || i == 4 {
+                out.push_str(&format!("{cell:<w$}"));
+            } else {
+                out.push_str(&format!("{cell:>w$}"));
+            }
+        }
+        out.trim_end().to_string()
+    };
+
+    let mut out = String::new();
+    out.push_str(&line(&head));
+    out.push('\n');
+    let total_width: usize = widths.iter().sum::<usize>() + 2 * (widths.len() - 1);
+    out.push_str(&"-".repeat(total_width));
+    out.push('\n');
+    for cells in &body {
+        out.push_str(&line(cells));
+        out.push('\n');
+    }
+    out.push_str(&format!(
+        "Total {}  CNS {:.1}%  OTU {:.0}\n",
+        format_time(table.total_time_sec),
+        table.cns_percent,
+        table.otu
+    ));
+    out
+}
+
+fn render_markdown(table: &RuntimeTable, units: UnitSystem) -> String {
+    let mut out = String::new();
+    out.push_str(&format!("| {} |\n", header(units).join(" | ")));
+    out.push_str("|:---|---:|---:|---:|:---|---:|---:|\n");
+    for row in &table.rows {
+        out.push_str(&format!("| {} |\n", row_cells(row, units).join(" | ")));
+    }
+    out.push_str(&format!(
+        "\n**Total** {} · **CNS** {:.1}% · **OTU** {:.0}\n",
+        format_time(table.total_time_sec),
+        table.cns_percent,
+        table.otu
+    ));
+    out
+}
+
+fn render_csv(table: &RuntimeTable, units: UnitSystem) -> String {
+    let mut out = format!(
+        "segment,depth_{},stop_time_sec,run_time_sec,gas,ppo2,cns_percent\n",
+        depth_unit(units)
+    );
+    for row in &table.rows {
+        out.push_str(&format!(
+            "{},{},{},{},{},{:.2},{:.1}\n",
+            row.segment.label(),
+            format_depth(row.depth_m, units),
+            row.stop_time_sec,
+            row.run_time_sec,
+            row.gas_label,
+            row.ppo2,
+            row.cns_percent
+        ));
+    }
+    out
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deco::profile_generator::{generate_dive_profile, GasSwitchPlan, ProfileGenParams};
+    use crate::deco::types::DecoModel;
+
+    fn gas(o2: f64, he: f64) -> GasMixInput {
+        GasMixInput {
+            mix_index: 0,
+            o2_fraction: o2,
+            he_fraction: he,
+        }
+    }
+
+    fn deco_profile(surface_tail_sec: Option<i32>) -> ProfileGenResult {
+        generate_dive_profile(ProfileGenParams {
+            target_depth_m: 45.0,
+            bottom_time_sec: 25 * 60,
+            descent_rate_m_min: None,
+            ascent_rate_m_min: None,
+            gas_plan: vec![
+                GasSwitchPlan {
+                    gas: gas(0.21, 0.35),
+                    switch_depth_m: None,
+                    descent_switch_depth_m: None,
+                },
+                GasSwitchPlan {
+                    gas: gas(0.50, 0.0),
+                    switch_depth_m: Some(21.0),
+                    descent_switch_depth_m: None,
+                },
+            ],
+            model: DecoModel::BuhlmannZhl16c,
+            surface_pressure_bar: None,
+            gf_low: Some(50),
+            gf_high: Some(80),
+            last_stop_depth_m: None,
+            stop_interval_m: None,
+            setpoint_ppo2: None,
+            thalmann_pdcs: None,
+            sample_interval_sec: None,
+            temp_c: None,
+            gas_switch_pause_sec: None,
+            max_ppo2: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            setpoint_schedule: None,
+            scr: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            safety_stop: None,
+            final_ascent_rate_m_min: None,
+            surface_tail_sec,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_gas_labels() {
+        let mixes = vec![
+            GasMixInput {
+                mix_index: 0,
+                o2_fraction: 0.21,
+                he_fraction: 0.0,
+            },
+            GasMixInput {
+                mix_index: 1,
+                o2_fraction: 0.32,
+                he_fraction: 0.0,
+            },
+            GasMixInput {
+                mix_index: 2,
+                o2_fraction: 0.18,
+                he_fraction: 0.45,
+            },
+            GasMixInput {
+                mix_index: 3,
+                o2_fraction: 1.0,
+                he_fraction: 0.0,
+            },
+        ];
+        assert_eq!(gas_label(0, &mixes), "Air");
+        assert_eq!(gas_label(1, &mixes), "EAN32");
+        assert_eq!(gas_label(2, &mixes), "Tx 18/45");
+        assert_eq!(gas_label(3, &mixes), "O2");
+        assert_eq!(gas_label(9, &mixes), "Air");
+    }
+
+    #[test]
+    fn test_rows_follow_profile_phases() {
+        let profile = deco_profile(None);
+        let table = build_runtime_table(&profile, None);
+
+        assert_eq!(table.rows[0].segment, RuntimeSegment::Descent);
+        assert_eq!(table.rows[0].run_time_sec, profile.descent_end_t_sec);
+        assert_eq!(table.rows[1].segment, RuntimeSegment::Bottom);
+        assert_eq!(table.rows[1].run_time_sec, profile.bottom_end_t_sec);
+        assert_eq!(table.total_time_sec, profile.surface_t_sec);
+        assert_eq!(
+            table.rows.last().unwrap().run_time_sec,
+            profile.surface_t_sec
+        );
+        assert_eq!(table.rows.last().unwrap().depth_m, 0.0);
+
+        // Row durations add up to the total run time
+        let sum: i32 = table.rows.iter().map(|r| r.stop_time_sec).sum();
+        assert_eq!(sum, profile.surface_t_sec);
+
+        // One stop row per planned stop, with matching durations
+        let stops: Vec<&RuntimeTableRow> = table
+            .rows
+            .iter()
+            .filter(|r| r.segment == RuntimeSegment::Stop)
+            .collect();
+        assert_eq!(stops.len(), profile.planned_stops.len());
+        for (row, planned) in stops.iter().zip(&profile.planned_stops) {
+            assert_eq!(row.depth_m, planned.depth_m);
+            assert_eq!(row.stop_time_sec, planned.duration_sec);
+        }
+    }
+
+    #[test]
+    fn test_surface_tail_left_out() {
+        let plain = build_runtime_table(&deco_profile(None), None);
+        let tailed_profile = deco_profile(Some(600));
+        assert!(tailed_profile.total_time_sec > tailed_profile.surface_t_sec);
+        let tailed = build_runtime_table(&tailed_profile, None);
+
+        assert_eq!(tailed.rows.len(), plain.rows.len());
+        assert_eq!(tailed.total_time_sec, tailed_profile.surface_t_sec);
+        assert_eq!(tailed.cns_percent, plain.cns_percent);
+        let last = tailed.rows.last().unwrap();
+        assert_eq!(last.segment, RuntimeSegment::Ascent);
+        assert_eq!(last.run_time_sec, tailed_profile.surface_t_sec);
+    }
+
+    #[test]
+    fn test_gas_switch_and_cns() {
+        let profile = deco_profile(None);
+        let table = build_runtime_table(&profile, None);
+        let shallow_stop = table
+            .rows
+            .iter()
+            .find(|r| r.segment == RuntimeSegment::Stop && r.depth_m <= 6.0)
+            .unwrap();
+        assert_eq!(shallow_stop.gas_label, "EAN50");
+        assert_eq!(table.rows[1].gas_label, "Tx 21/35");
+
+        // Bottom ppO2 on 21% at 45 m ≈ 1.16 bar
+        let expected = 0.21 * depth_to_pressure(45.0, DEFAULT_SURFACE_PRESSURE);
+        assert!((table.rows[1].ppo2 as f64 - expected).abs() < 1e-3);
+
+        // CNS is cumulative
+        for pair in table.rows.windows(2) {
+            assert!(pair[1].cns_percent >= pair[0].cns_percent);
+        }
+        assert!((table.cns_percent - table.rows.last().unwrap().cns_percent).abs() < 1e-6);
+        assert!(table.otu > 0.0);
+    }
+
+    #[test]
+    fn test_render_formats() {
+        let profile = deco_profile(None);
+        let table = build_runtime_table(&profile, None);
+
+        let text = render_runtime_table(&table, RuntimeTableFormat::PlainText, UnitSystem::Metric);
+        assert!(text.starts_with("Segment"));
+        assert!(text.contains("Depth (m)"));
+        assert!(text.contains("EAN50"));
+        assert!(text.contains("Total "));
+
+        let md = render_runtime_table(&table, RuntimeTableFormat::Markdown, UnitSystem::Metric);
+        assert!(md.starts_with("| Segment | Depth (m) |"));
+        assert_eq!(
+            md.lines().filter(|l| l.starts_with('|')).count(),
+            table.rows.len() + 2
+        );
+
+        let csv = render_runtime_table(&table, RuntimeTableFormat::Csv, UnitSystem::Imperial);
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "segment,depth_ft,stop_time_sec,run_time_sec,gas,ppo2,cns_percent"
+        );
+        assert_eq!(lines.count(), table.rows.len());
+        // 45 m bottom ≈ 148 ft
+        assert!(csv.contains("Bottom,148,"));
+    }
+
+    #[test]
+    fn test_format_helpers() {
+        assert_eq!(format_time(0), "0:00");
+        assert_eq!(format_time(125), "2:05");
+        assert_eq!(format_depth(21.0, UnitSystem::Metric), "21");
+        assert_eq!(format_depth(45.72, UnitSystem::Metric), "45.7");
+        assert_eq!(format_depth(3.0, UnitSystem::Imperial), "10");
+    }
+
+    #[test]
+    fn test_empty_profile() {
+        let mut profile = deco_profile(None);
+        profile.samples.clear();
+        let table = build_runtime_table(&profile, None);
+        assert!(table.rows.is_empty());
+        assert_eq!(table.total_time_sec, 0);
+    }
+}
@@ -0,0 +1,558 @@
+//! Evaluation of a user-authored ascent schedule against a deco model.
+//!
+//! Divers often carry run-times from other planners or tables. Given a bottom
+//! profile and a list of stops, this module:
+//!
+//! 1. Plans the model's own ascent from the end of the bottom profile.
+//! 2. Builds the user's ascent samples (same generator as the replay profiles).
+//! 3. Runs the deco engine over the complete profile for the per-point overlay.
+//! 4. Checks every stop departure against the planner's ceiling criterion and
+//!    reports GF, PPO2/CNS/OTU exposure and the difference from the model.
+
+use super::oxygen::OxygenExposure;
+use super::profile_generator::{
+    generate_ascent, SampleCtx, DEFAULT_ASCENT_RATE, DEFAULT_SAMPLE_INTERVAL,
+};
+use super::shared::*;
+use super::types::*;
+use super::{compute_deco_simulation, departure_ceilings};
+use crate::metrics::SampleInput;
+
+/// Ceiling tolerance (metres) before a departure counts as a violation.
+/// Absorbs the difference between integrating the ascent over 10 s samples
+/// and the planner's single-segment travel between stops.
+const CEILING_TOLERANCE_M: f64 = 0.1;
+
+// ============================================================================
+// Input / Output Types
+// ============================================================================
+
+/// Parameters for evaluating a user-specified ascent schedule.
+#[derive(Debug, Clone)]
+pub struct ScheduleEvalParams {
+    /// Bottom profile, gases and model settings. `samples` must end where the
+    /// ascent begins; `plan_ascent` is ignored.
+    pub sim: DecoSimParams,
+    /// User stops, deepest first. `gas_mix_index` refers to `sim.gas_mixes`
+    /// (-1 = keep the current gas).
+    pub stops: Vec<DecoStop>,
+}
+
+/// Evaluation of a single user stop.
+#[derive(Debug, Clone)]
+pub struct StopEvaluation {
+    /// The stop as supplied by the user.
+    pub stop: DecoStop,
+    /// Time of arrival at the stop (seconds from dive start).
+    pub arrival_t_sec: i32,
+    /// Time the stop is left (seconds from dive start).
+    pub departure_t_sec: i32,
+    /// GF99 on arrival at the stop.
+    pub arrival_gf99: f32,
+    /// Model ceiling at departure, evaluated for the next depth (metres, unrounded).
+    pub departure_ceiling_m: f32,
+    /// Inspired PPO2 at the stop (bar).
+    pub ppo2: f32,
+    /// True if the ceiling allowed leaving this stop for the next depth.
+    pub ceiling_ok: bool,
+}
+
+/// A departure that broke the model ceiling.
+#[derive(Debug, Clone)]
+pub struct CeilingViolation {
+    /// Time of departure (seconds from dive start).
+    pub t_sec: i32,
+    /// Depth being left (metres).
+    pub depth_m: f32,
+    /// Depth the diver ascended to (metres, 0 = surface).
+    pub next_depth_m: f32,
+    /// Model ceiling at departure (metres, unrounded).
+    pub ceiling_m: f32,
+}
+
+/// Result of evaluating a user schedule.
+#[derive(Debug, Clone)]
+pub struct ScheduleEvalResult {
+    /// Complete profile: bottom samples followed by the user's ascent.
+    pub samples: Vec<SampleInput>,
+    /// Per-point deco overlay for `samples`.
+    pub deco_result: DecoSimResult,
+    /// Per-stop evaluation, in schedule order.
+    pub stop_evaluations: Vec<StopEvaluation>,
+    /// Departures (from the bottom or from a stop) that broke the ceiling.
+    pub ceiling_violations: Vec<CeilingViolation>,
+    /// Surface GF (Thalmann: surface MPTT utilisation) on surfacing.
+    pub surfacing_gf: f32,
+    /// Highest inspired PPO2 over the whole profile (bar).
+    pub max_ppo2: f32,
+    /// CNS clock at the end of the dive (percent).
+    pub cns_percent: f32,
+    /// Pulmonary oxygen toxicity units at the end of the dive.
+    pub otu: f32,
+    /// The model's own stops planned from the end of the bottom profile.
+    pub model_stops: Vec<DecoStop>,
+    /// True if the model plan hit a planner safety limit.
+    pub model_truncated: bool,
+    /// Total runtime of the user schedule (seconds).
+    pub runtime_sec: i32,
+    /// Total runtime of the model schedule (seconds).
+    pub model_runtime_sec: i32,
+    /// `runtime_sec - model_runtime_sec`.
+    pub runtime_delta_sec: i32,
+    /// Sum of the user's stop durations (seconds).
+    pub deco_time_sec: i32,
+    /// Sum of the model's stop durations (seconds).
+    pub model_deco_time_sec: i32,
+}
+
+// ============================================================================
+// Public API
+// ============================================================================
+
+/// Simulate a user-authored ascent schedule and compare it with the model.
+pub fn evaluate_ascent_schedule(
+    params: ScheduleEvalParams,
+) -> Result<ScheduleEvalResult, DecoSimError> {
+    let bottom = &params.sim.samples;
+    let Some(last) = bottom.last() else {
+        return Err(DecoSimError::EmptySamples {
+            msg: "No samples provided".to_string(),
+        });
+    };
+    validate_stops(&params)?;
+
+    let surface_p = params
+        .sim
+        .surface_pressure_bar
+        .unwrap_or(DEFAULT_SURFACE_PRESSURE);
+    let ascent_rate = params.sim.ascent_rate_m_min.unwrap_or(DEFAULT_ASCENT_RATE);
+    let bottom_end_t = last.t_sec;
+    let bottom_depth = (last.depth_m as f64).max(0.0);
+    let bottom_gas = bottom
+        .iter()
+        .rev()
+        .find_map(|s| s.gasmix_index)
+        .unwrap_or(0);
+
+    // ── Model plan from the end of the bottom profile ──────────────────
+    let model = compute_deco_simulation(DecoSimParams {
+        plan_ascent: true,
+        ascent_gas_switches: vec![],
+        ascent_setpoint_switches: vec![],
+        scr: None,
+        deep_stops: None,
+        ascent_mode: None,
+        ceiling_margin_m: None,
+        ..params.sim.clone()
+    })?;
+
+    let ctx = SampleCtx {
+        setpoint_ppo2: last.ppo2.map(|v| v as f64),
+        surface_pressure: surface_p,
+        temp_c: last.temp_c,
+        sample_interval: DEFAULT_SAMPLE_INTERVAL,
+        ascent_rate,
+        final_ascent_rate: ascent_rate,
+    };
+
+    // ── Build both ascents with the same generator ──────────────────────
+    let mut samples = bottom.clone();
+    generate_ascent(
+        &mut samples,
+        bottom_depth,
+        bottom_end_t,
+        &params.stops,
+        &[],
+        bottom_gas,
+        &ctx,
+    );
+    let mut model_samples = bottom.clone();
+    generate_ascent(
+        &mut model_samples,
+        bottom_depth,
+        bottom_end_t,
+        &model.deco_stops,
+        &[],
+        bottom_gas,
+        &ctx,
+    );
+
+    // ── Overlay on the user profile ─────────────────────────────────────
+    let full_sim = DecoSimParams {
+        samples: samples.clone(),
+        plan_ascent: false,
+        ascent_gas_switches: vec![],
+        ascent_setpoint_switches: vec![],
+        scr: None,
+        deep_stops: None,
+        ascent_mode: None,
+        ceiling_margin_m: None,
+        ..params.sim.clone()
+    };
+    let deco_result = compute_deco_simulation(full_sim.clone())?;
+
+    // ── Locate stop arrivals/departures in the generated samples ────────
+    let bottom_end_idx = bottom.len() - 1;
+    let mut arrivals = Vec::with_capacity(params.stops.len());
+    let mut search_from = bottom_end_idx;
+    for stop in &params.stops {
+        let arrival_idx = (search_from..samples.len())
+            .find(|&i| samples[i].depth_m == stop.depth_m)
+            .unwrap_or(samples.len() - 1);
+        let departure_t = samples[arrival_idx].t_sec + stop.duration_sec;
+        let departure_idx = (arrival_idx..samples.len())
+            .find(|&i| samples[i].t_sec >= departure_t)
+            .unwrap_or(samples.len() - 1);
+        arrivals.push((arrival_idx, departure_idx));
+        search_from = departure_idx;
+    }
+
+    // One probe per departure: the bottom, then each stop.
+    let mut probes = Vec::with_capacity(params.stops.len() + 1);
+    let first_target = params.stops.first().map_or(0.0, |s| s.depth_m as f64);
+    probes.push((bottom_end_idx, first_target));
+    for (i, &(_, departure_idx)) in arrivals.iter().enumerate() {
+        let next = params.stops.get(i + 1).map_or(0.0, |s| s.depth_m as f64);
+        probes.push((departure_idx, next));
+    }
+    let model_first_stop = model.deco_stops.first().map_or(0.0, |s| s.depth_m as f64);
+    let ceilings = departure_ceilings(&full_sim, &probes, model_first_stop)?;
+
+    let mut ceiling_violations = Vec::new();
+    for (&(idx, next), &ceiling) in probes.iter().zip(&ceilings) {
+        if ceiling > next + CEILING_TOLERANCE_M {
+            ceiling_violations.push(CeilingViolation {
+                t_sec: samples[idx].t_sec,
+                depth_m: samples[idx].depth_m,
+                next_depth_m: next as f32,
+                ceiling_m: ceiling as f32,
+            });
+        }
+    }
+
+    // ── Oxygen exposure ─────────────────────────────────────────────────
+    let exposure = OxygenExposure::cumulative(&samples, &params.sim.gas_mixes, surface_p);
+    let final_exposure = exposure.last().cloned().unwrap_or_default();
+
+    let stop_evaluations = params
+        .stops
+        .iter()
+        .zip(&arrivals)
+        .enumerate()
+        .map(|(i, (stop, &(arrival_idx, departure_idx)))| {
+            let ceiling = ceilings[i + 1];
+            let next = probes[i + 1].1;
+            StopEvaluation {
+                stop: stop.clone(),
+                arrival_t_sec: samples[arrival_idx].t_sec,
+                departure_t_sec: samples[departure_idx].t_sec,
+                arrival_gf99: deco_result.points[arrival_idx].gf99,
+                departure_ceiling_m: ceiling as f32,
+                ppo2: stop_ppo2(&params.sim, &samples[arrival_idx], surface_p) as f32,
+                ceiling_ok: ceiling <= next + CEILING_TOLERANCE_M,
+            }
+        })
+        .collect();
+
+    let runtime_sec = samples.last().map_or(0, |s| s.t_sec);
+    let model_runtime_sec = model_samples.last().map_or(0, |s| s.t_sec);
+    let surfacing_gf = deco_result.points.last().map_or(0.0, |p| p.surface_gf);
+
+    Ok(ScheduleEvalResult {
+        samples,
+        deco_result,
+        stop_evaluations,
+        ceiling_violations,
+        surfacing_gf,
+        max_ppo2: final_exposure.max_ppo2 as f32,
+        cns_percent: final_exposure.cns_percent as f32,
+        otu: final_exposure.otu as f32,
+        deco_time_sec: params.stops.iter().map(|s| s.duration_sec).sum(),
+        model_deco_time_sec: model.total_deco_time_sec,
+        model_stops: model.deco_stops,
+        model_truncated: model.truncated,
+        runtime_sec,
+        model_runtime_sec,
+        runtime_delta_sec: runtime_sec - model_runtime_sec,
+    })
+}
+
+// ============================================================================
+// Helpers
+// ============================================================================
+
+fn validate_stops(params: &ScheduleEvalParams) -> Result<(), DecoSimError> {
+    let start_depth = params.sim.samples.last().map_or(0.0, |s| s.depth_m);
+    let mut prev_depth = start_depth;
+    for (i, stop) in params.stops.iter().enumerate() {
+        if stop.depth_m <= 0.0 {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!("stops[{i}].depth_m ({}) must be > 0", stop.depth_m),
+            });
+        }
+        if stop.duration_sec < 0 {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!(
+                    "stops[{i}].duration_sec ({}) must be >= 0",
+                    stop.duration_sec
+                ),
+            });
+        }
+        if stop.depth_m > prev_depth || (i > 0 && stop.depth_m == prev_depth) {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!(
+                    "stops[{i}].depth_m ({}) must be shallower than the previous depth ({prev_depth})",
+                    stop.depth_m
+                ),
+            });
+        }
+        if stop.gas_mix_index >= 0
+            && !params
+                .sim
+                .gas_mixes
+                .iter()
+                .any(|g| g.mix_index == stop.gas_mix_index)
+        {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!(
+                    "stops[{i}].gas_mix_index ({}) not found in gas_mixes",
+                    stop.gas_mix_index
+                ),
+            });
+        }
+        prev_depth = stop.depth_m;
+    }
+    Ok(())
+}
+
+/// Inspired PPO2 at a stop sample (setpoint for CCR, fO2 × ambient for OC).
+fn stop_ppo2(sim: &DecoSimParams, sample: &SampleInput, surface_p: f64) -> f64 {
+    let ambient_p = depth_to_pressure(sample.depth_m as f64, surface_p);
+    let fo2 = sample
+        .gasmix_index
+        .and_then(|i| sim.gas_mixes.iter().find(|g| g.mix_index == i))
+        .map_or(AIR_FO2, |g| g.o2_fraction);
+    super::oxygen::inspired_ppo2(fo2, sample.ppo2.map(|v| v as f64), ambient_p)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buhlmann::GasMixInput;
+
+    fn sample(t_sec: i32, depth_m: f32) -> SampleInput {
+        SampleInput {
+            t_sec,
+            depth_m,
+            temp_c: 20.0,
+            setpoint_ppo2: None,
+            ceiling_m: None,
+            gf99: None,
+            gasmix_index: Some(0),
+            ppo2: None,
+            tts_sec: None,
+            ndl_sec: None,
+            deco_stop_depth_m: None,
+            at_plus_five_tts_min: None,
+        }
+    }
+
+    fn sim(model: DecoModel) -> DecoSimParams {
+        DecoSimParams {
+            model,
+            samples: vec![sample(0, 0.0), sample(120, 40.0), sample(1800, 40.0)],
+            gas_mixes: vec![
+                GasMixInput {
+                    mix_index: 0,
+                    o2_fraction: 0.21,
+                    he_fraction: 0.0,
+                },
+                GasMixInput {
+                    mix_index: 1,
+                    o2_fraction: 0.50,
+                    he_fraction: 0.0,
+                },
+            ],
+            surface_pressure_bar: None,
+            ascent_rate_m_min: None,
+            last_stop_depth_m: None,
+            stop_interval_m: None,
+            gf_low: Some(50),
+            gf_high: Some(80),
+            thalmann_pdcs: None,
+            plan_ascent: false,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
+        }
+    }
+
+    fn stop(depth_m: f32, duration_sec: i32, gas_mix_index: i32) -> DecoStop {
+        DecoStop {
+            depth_m,
+            duration_sec,
+            gas_mix_index,
+            is_deep_stop: false,
+        }
+    }
+
+    #[test]
+    fn test_model_schedule_has_no_violations() {
+        for model in [DecoModel::BuhlmannZhl16c, DecoModel::ThalmannElDca] {
+            // Air only: the model plan switches deco gases at MOD, which a
+            // stop list with gas_mix_index = -1 would not reproduce.
+            let mut base = sim(model);
+            base.gas_mixes.truncate(1);
+            let planned = compute_deco_simulation(DecoSimParams {
+                plan_ascent: true,
+                ascent_gas_switches: vec![],
+                ascent_setpoint_switches: vec![],
+                scr: None,
+                deep_stops: None,
+                ascent_mode: None,
+                ceiling_margin_m: None,
+                ..base.clone()
+            })
+            .unwrap();
+            assert!(!planned.deco_stops.is_empty());
+
+            let result = evaluate_ascent_schedule(ScheduleEvalParams {
+                sim: base,
+                stops: planned.deco_stops.clone(),
+            })
+            .unwrap();
+            assert!(
+                result.ceiling_violations.is_empty(),
+                "{model:?}: model's own schedule flagged: {:?}",
+                result.ceiling_violations
+            );
+            assert_eq!(result.runtime_delta_sec, 0);
+            assert_eq!(result.deco_time_sec, result.model_deco_time_sec);
+            assert!(result.stop_evaluations.iter().all(|e| e.ceiling_ok));
+        }
+    }
+
+    #[test]
+    fn test_direct_ascent_violates_ceiling() {
+        let result = evaluate_ascent_schedule(ScheduleEvalParams {
+            sim: sim(DecoModel::BuhlmannZhl16c),
+            stops: vec![],
+        })
+        .unwrap();
+        assert_eq!(result.ceiling_violations.len(), 1);
+        let v = &result.ceiling_violations[0];
+        assert_eq!(v.t_sec, 1800);
+        assert_eq!(v.next_depth_m, 0.0);
+        assert!(v.ceiling_m > 0.0);
+        assert!(result.runtime_delta_sec < 0);
+        assert_eq!(result.deco_time_sec, 0);
+    }
+
+    #[test]
+    fn test_short_last_stop_flagged() {
+        let stops = vec![stop(6.0, 120, 1), stop(3.0, 60, 1)];
+        let result = evaluate_ascent_schedule(ScheduleEvalParams {
+            sim: sim(DecoModel::BuhlmannZhl16c),
+            stops,
+        })
+        .unwrap();
+        let last = result.stop_evaluations.last().unwrap();
+        assert!(
+            !last.ceiling_ok,
+            "1 min at 3 m should not clear the ceiling"
+        );
+        assert!(result
+            .ceiling_violations
+            .iter()
+            .any(|v| v.depth_m == 3.0 && v.next_depth_m == 0.0));
+    }
+
+    #[test]
+    fn test_stop_timing_and_gas() {
+        let stops = vec![stop(9.0, 180, 1), stop(6.0, 300, 1), stop(3.0, 600, 1)];
+        let result = evaluate_ascent_schedule(ScheduleEvalParams {
+            sim: sim(DecoModel::BuhlmannZhl16c),
+            stops,
+        })
+        .unwrap();
+        assert_eq!(result.stop_evaluations.len(), 3);
+        for eval in &result.stop_evaluations {
+            assert_eq!(
+                eval.departure_t_sec - eval.arrival_t_sec,
+                eval.stop.duration_sec
+            );
+            assert!(eval.arrival_gf99 > 0.0);
+        }
+        // 50% at 6 m ≈ 0.81 bar
+        let at_6 = &result.stop_evaluations[1];
+        let expected = 0.5 * depth_to_pressure(6.0, DEFAULT_SURFACE_PRESSURE);
+        assert!((at_6.ppo2 as f64 - expected).abs() < 1e-3);
+        assert_eq!(result.deco_time_sec, 1080);
+        assert!(result.cns_percent > 0.0);
+        assert!(result.otu > 0.0);
+        assert!(result.max_ppo2 > 1.0, "40 m on air ≈ 1.06 bar");
+        assert_eq!(result.samples.last().unwrap().depth_m, 0.0);
+        assert_eq!(result.deco_result.points.len(), result.samples.len());
+        assert!(result.surfacing_gf > 0.0);
+    }
+
+    #[test]
+    fn test_longer_schedule_lowers_surfacing_gf() {
+        let short = evaluate_ascent_schedule(ScheduleEvalParams {
+            sim: sim(DecoModel::BuhlmannZhl16c),
+            stops: vec![stop(6.0, 120, 1), stop(3.0, 300, 1)],
+        })
+        .unwrap();
+        let long = evaluate_ascent_schedule(ScheduleEvalParams {
+            sim: sim(DecoModel::BuhlmannZhl16c),
+            stops: vec![stop(6.0, 600, 1), stop(3.0, 1800, 1)],
+        })
+        .unwrap();
+        assert!(long.surfacing_gf < short.surfacing_gf);
+        assert!(long.runtime_delta_sec > short.runtime_delta_sec);
+    }
+
+    #[test]
+    fn test_invalid_stops_rejected() {
+        let cases = vec![
+            vec![stop(0.0, 60, -1)],
+            vec![stop(6.0, -1, -1)],
+            vec![stop(3.0, 60, -1), stop(6.0, 60, -1)],
+            vec![stop(6.0, 60, -1), stop(6.0, 60, -1)],
+            vec![stop(50.0, 60, -1)],
+            vec![stop(6.0, 60, 7)],
+        ];
+        for stops in cases {
+            let result = evaluate_ascent_schedule(ScheduleEvalParams {
+                sim: sim(DecoModel::BuhlmannZhl16c),
+                stops: stops.clone(),
+            });
+            assert!(
+                matches!(result, Err(DecoSimError::InvalidParam { .. })),
+                "stops {stops:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_empty_samples_rejected() {
+        let mut base = sim(DecoModel::BuhlmannZhl16c);
+        base.samples.clear();
+        let result = evaluate_ascent_schedule(ScheduleEvalParams {
+            sim: base,
+            stops: vec![],
+        });
+        assert!(matches!(result, Err(DecoSimError::EmptySamples { .. })));
+    }
+}
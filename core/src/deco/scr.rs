@@ -0,0 +1,214 @@
+//! Semi-closed rebreather (SCR) loop model.
+//!
+//! Steady-state O2 balance of the loop, with volumes in surface L/min. Fresh
+//! supply gas replaces what is dumped plus what is metabolised (VO2):
+//!
+//! - **Active** (constant mass flow): the supply injects `Q`, so
+//!   `FO2_loop = (Q·FO2_supply − VO2) / (Q − VO2)`, independent of depth.
+//! - **Passive**: a fixed fraction of each exhaled breath is dumped, so the
+//!   dump scales with ventilation and ambient pressure,
+//!   `V_dump = ratio · VE · P_amb` with `VE = 25 · VO2`, giving
+//!   `FO2_loop = FO2_supply − (1 − FO2_supply) · VO2 / V_dump`.
+//!
+//! The inert part of the loop keeps the supply gas's N2:He ratio, so the loop
+//! is modelled as a CCR at the loop PPO2 (see `inspired_fractions`).
+
+use super::types::DecoSimError;
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// Minute ventilation per litre of O2 consumed (ventilatory equivalent).
+const VENTILATORY_EQUIVALENT: f64 = 25.0;
+
+// ============================================================================
+// FFI Types
+// ============================================================================
+
+/// How the SCR adds fresh supply gas to the loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrMode {
+    /// A fixed fraction of each exhaled breath is dumped and replaced.
+    Passive,
+    /// Supply gas is injected at a constant mass flow.
+    Active,
+}
+
+/// Semi-closed rebreather configuration. The supply gas is the current gas mix.
+#[derive(Debug, Clone)]
+pub struct ScrParams {
+    /// How supply gas enters the loop.
+    pub mode: ScrMode,
+    /// Metabolic O2 consumption in L/min (surface equivalent).
+    pub o2_consumption_l_min: f64,
+    /// Passive only: fraction of each exhaled breath dumped (e.g. 0.1 for 1:10).
+    pub dump_ratio: Option<f64>,
+    /// Active only: supply injection rate in L/min (surface equivalent).
+    pub injection_rate_l_min: Option<f64>,
+}
+
+// ============================================================================
+// Public API
+// ============================================================================
+
+/// Validate an SCR configuration for its mode.
+pub(crate) fn validate_scr(params: &ScrParams) -> Result<(), DecoSimError> {
+    let vo2 = params.o2_consumption_l_min;
+    if vo2 <= 0.0 {
+        return Err(DecoSimError::InvalidParam {
+            msg: format!("o2_consumption_l_min ({vo2}) must be > 0"),
+        });
+    }
+    match params.mode {
+        ScrMode::Passive => match params.dump_ratio {
+            Some(r) if r > 0.0 && r <= 1.0 => Ok(()),
+            Some(r) => Err(DecoSimError::InvalidParam {
+                msg: format!("dump_ratio ({r}) must be > 0 and <= 1"),
+            }),
+            None => Err(DecoSimError::InvalidParam {
+                msg: "passive SCR requires dump_ratio".to_string(),
+            }),
+        },
+        ScrMode::Active => match params.injection_rate_l_min {
+            Some(q) if q > vo2 => Ok(()),
+            Some(q) => Err(DecoSimError::InvalidParam {
+                msg: format!("injection_rate_l_min ({q}) must exceed o2_consumption_l_min ({vo2})"),
+            }),
+            None => Err(DecoSimError::InvalidParam {
+                msg: "active SCR requires injection_rate_l_min".to_string(),
+            }),
+        },
+    }
+}
+
+/// Inspired O2 fraction in the loop for a supply gas at an ambient pressure.
+///
+/// Clamped to `0..=supply_fo2`. Assumes `params` passed `validate_scr`.
+pub fn scr_loop_o2_fraction(params: &ScrParams, supply_fo2: f64, ambient_p: f64) -> f64 {
+    let vo2 = params.o2_consumption_l_min;
+    let fo2 = match params.mode {
+        ScrMode::Passive => {
+            let ratio = params.dump_ratio.unwrap_or(1.0);
+            let dump = ratio * VENTILATORY_EQUIVALENT * vo2 * ambient_p;
+            supply_fo2 - (1.0 - supply_fo2) * vo2 / dump
+        }
+        ScrMode::Active => {
+            let q = params.injection_rate_l_min.unwrap_or(0.0);
+            (q * supply_fo2 - vo2) / (q - vo2)
+        }
+    };
+    fo2.clamp(0.0, supply_fo2)
+}
+
+/// Supply gas drawn by the loop at an ambient pressure, in surface L/min.
+///
+/// Passive: the dumped volume plus the O2 metabolised. Active: the injection
+/// rate. Assumes `params` passed `validate_scr`.
+pub(crate) fn scr_supply_l_min(params: &ScrParams, ambient_p: f64) -> f64 {
+    let vo2 = params.o2_consumption_l_min;
+    match params.mode {
+        ScrMode::Passive => {
+            let ratio = params.dump_ratio.unwrap_or(1.0);
+            ratio * VENTILATORY_EQUIVALENT * vo2 * ambient_p + vo2
+        }
+        ScrMode::Active => params.injection_rate_l_min.unwrap_or(0.0),
+    }
+}
+
+/// Loop PPO2 (bar) for a supply gas at an ambient pressure.
+pub(crate) fn scr_loop_ppo2(params: &ScrParams, supply_fo2: f64, ambient_p: f64) -> f64 {
+    scr_loop_o2_fraction(params, supply_fo2, ambient_p) * ambient_p
+}
+
+/// PPO2 for a profile interval: the SCR loop when configured, else the
+/// sample's recorded PPO2 (`None` = open circuit).
+pub(crate) fn interval_ppo2(
+    scr: Option<&ScrParams>,
+    sample_ppo2: Option<f32>,
+    fo2: f64,
+    ambient_p: f64,
+) -> Option<f64> {
+    match scr {
+        Some(scr) => Some(scr_loop_ppo2(scr, fo2, ambient_p)),
+        None => sample_ppo2.map(|v| v as f64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn passive(ratio: f64) -> ScrParams {
+        ScrParams {
+            mode: ScrMode::Passive,
+            o2_consumption_l_min: 1.0,
+            dump_ratio: Some(ratio),
+            injection_rate_l_min: None,
+        }
+    }
+
+    fn active(q: f64) -> ScrParams {
+        ScrParams {
+            mode: ScrMode::Active,
+            o2_consumption_l_min: 1.0,
+            dump_ratio: None,
+            injection_rate_l_min: Some(q),
+        }
+    }
+
+    #[test]
+    fn test_active_loop_fraction() {
+        // EAN40 at 10 L/min with 1 L/min VO2: (4 - 1) / 9 = 0.333
+        let f = scr_loop_o2_fraction(&active(10.0), 0.40, 4.0);
+        assert!((f - 3.0 / 9.0).abs() < 1e-9);
+        // Independent of depth
+        assert_eq!(f, scr_loop_o2_fraction(&active(10.0), 0.40, 1.0));
+    }
+
+    #[test]
+    fn test_passive_loop_fraction_rises_with_depth() {
+        // EAN32, 1:10 dump at 4 bar: 0.32 - 0.68 / (0.1 * 25 * 4) = 0.252
+        let deep = scr_loop_o2_fraction(&passive(0.1), 0.32, 4.0);
+        assert!((deep - 0.252).abs() < 1e-9);
+        let shallow = scr_loop_o2_fraction(&passive(0.1), 0.32, 2.0);
+        assert!(shallow < deep);
+        // A near-surface passive loop runs hypoxic and is clamped at 0
+        assert_eq!(scr_loop_o2_fraction(&passive(0.01), 0.32, 1.0), 0.0);
+        // Bigger dump ratio, closer to the supply gas
+        assert!(scr_loop_o2_fraction(&passive(0.5), 0.32, 4.0) > deep);
+    }
+
+    #[test]
+    fn test_supply_rate() {
+        // Active: the injection rate at any depth
+        assert_eq!(scr_supply_l_min(&active(10.0), 1.0), 10.0);
+        assert_eq!(scr_supply_l_min(&active(10.0), 4.0), 10.0);
+        // Passive 1:10 at 3 bar: 0.1 * 25 * 1 * 3 dumped + 1 metabolised
+        assert!((scr_supply_l_min(&passive(0.1), 3.0) - 8.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_interval_ppo2() {
+        let scr = active(10.0);
+        let ppo2 = interval_ppo2(Some(&scr), Some(1.3), 0.40, 3.0).unwrap();
+        assert!((ppo2 - 1.0).abs() < 1e-9);
+        assert_eq!(interval_ppo2(None, Some(1.25), 0.40, 3.0), Some(1.25));
+        assert_eq!(interval_ppo2(None, None, 0.40, 3.0), None);
+    }
+
+    #[test]
+    fn test_validate_scr() {
+        assert!(validate_scr(&passive(0.1)).is_ok());
+        assert!(validate_scr(&passive(0.0)).is_err());
+        assert!(validate_scr(&passive(1.5)).is_err());
+        assert!(validate_scr(&active(10.0)).is_ok());
+        assert!(validate_scr(&active(1.0)).is_err());
+        let mut p = passive(0.1);
+        p.dump_ratio = None;
+        assert!(validate_scr(&p).is_err());
+        let mut p = active(10.0);
+        p.o2_consumption_l_min = 0.0;
+        assert!(validate_scr(&p).is_err());
+    }
+}
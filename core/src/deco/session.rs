@@ -0,0 +1,566 @@
+//! Live deco computation over a profile that grows one sample at a time.
+//!
+//! The one-shot entry points re-simulate the whole sample vector on every
+//! call. A `DecoSession` instead keeps the model's tissue state between
+//! calls, so a live download preview or a replay scrubber pays for each
+//! sample once:
+//!
+//! 1. `push_sample` steps the tissues to a new sample and returns its point.
+//! 2. `snapshot` captures the state after the current sample; `restore`
+//!    rewinds the session to it.
+//! 3. `plan_ascent` plans the ascent from the current sample on demand.
+//!
+//! A session searches TTS and NDL at every sample, so its points are
+//! identical to `compute_deco_simulation` over the same samples with
+//! `tts_interval_sec = 0`; plans are identical at any cadence.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::buhlmann_engine::{BuhlmannSim, BuhlmannState};
+use super::cadence::SampleStepper;
+use super::deep_stops::DeepStopPlan;
+use super::thalmann_engine::{ThalmannSim, ThalmannState};
+use super::types::*;
+use super::{validate_ascent_mode, validate_options};
+use crate::metrics::SampleInput;
+
+/// Source of session ids, used to reject snapshots from another session.
+static NEXT_SESSION_ID: AtomicU64 = AtomicU64::new(1);
+
+// ============================================================================
+// Public Types
+// ============================================================================
+
+/// A deco simulation that accepts samples incrementally.
+///
+/// Created from a `DecoSimParams`; its `samples` are pushed as a starting
+/// prefix and may be empty. `plan_ascent` on the params is ignored — ascents
+/// are planned with `DecoSession::plan_ascent`.
+pub struct DecoSession {
+    id: u64,
+    engine: SessionEngine,
+}
+
+/// Session state captured after a sample, for rewinding a session.
+pub struct DecoSessionSnapshot {
+    session_id: u64,
+    last_sample: Option<SampleInput>,
+    sample_count: u32,
+    engine: EngineState,
+}
+
+/// Ascent planned from a session's current sample.
+#[derive(Debug, Clone)]
+pub struct DecoSessionPlan {
+    /// Time of the sample the ascent starts from (seconds).
+    pub t_sec: i32,
+    /// Depth the ascent starts from (metres).
+    pub depth_m: f32,
+    /// Planned deco stops, deepest first.
+    pub deco_stops: Vec<DecoStop>,
+    /// Sum of stop durations (seconds).
+    pub total_deco_time_sec: i32,
+    /// True if the planner hit a safety limit.
+    pub truncated: bool,
+    /// Time spent at deep stops (seconds).
+    pub deep_stop_time_sec: i32,
+    /// Extra shallow-stop time caused by deep stops (seconds).
+    pub deep_stop_penalty_sec: i32,
+    /// Continuous ascent path; empty for stop-based ascents.
+    pub ascent_path: Vec<AscentWaypoint>,
+}
+
+// ============================================================================
+// Internal State
+// ============================================================================
+
+/// The model's validated simulation, built once, and its mutable state.
+enum SessionEngine {
+    Buhlmann(Box<Live<BuhlmannSim<'static>>>),
+    Thalmann(Box<Live<ThalmannSim<'static>>>),
+}
+
+/// Engine state held by a snapshot.
+#[derive(Debug, Clone)]
+enum EngineState {
+    Buhlmann(Box<BuhlmannState>),
+    Thalmann(ThalmannState),
+}
+
+struct Live<S: SampleStepper> {
+    sim: S,
+    state: Mutex<SessionState<S::State>>,
+}
+
+#[derive(Debug, Clone)]
+struct SessionState<T> {
+    engine: T,
+    last_sample: Option<SampleInput>,
+    sample_count: u32,
+}
+
+// ============================================================================
+// Session API
+// ============================================================================
+
+impl DecoSession {
+    /// Validate the model settings and push `params.samples`.
+    pub fn new(params: DecoSimParams) -> Result<Self, DecoSimError> {
+        validate_options(&params)?;
+        validate_ascent_mode(&params)?;
+        let initial = params.samples.clone();
+        let params = DecoSimParams {
+            samples: Vec::new(),
+            plan_ascent: false,
+            ..params
+        };
+        let engine = match params.model {
+            DecoModel::BuhlmannZhl16c => {
+                let sim = BuhlmannSim::owned(params)?;
+                SessionEngine::Buhlmann(Box::new(Live::new(sim.initial_state(), sim)))
+            }
+            DecoModel::ThalmannElDca => {
+                let sim = ThalmannSim::owned(params)?;
+                SessionEngine::Thalmann(Box::new(Live::new(sim.initial_state(), sim)))
+            }
+        };
+        let session = Self {
+            id: NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed),
+            engine,
+        };
+        session.push_samples(initial)?;
+        Ok(session)
+    }
+
+    /// Step to the next sample and return its point.
+    ///
+    /// Samples must arrive in time order; equal times are allowed.
+    pub fn push_sample(&self, sample: SampleInput) -> Result<DecoSimPoint, DecoSimError> {
+        let mut points = self.push_samples(vec![sample])?;
+        Ok(points.remove(0))
+    }
+
+    /// Step through a batch of samples and return their points.
+    ///
+    /// The batch is checked for time order before any sample is applied, so
+    /// a rejected batch leaves the session unchanged.
+    pub fn push_samples(
+        &self,
+        samples: Vec<SampleInput>,
+    ) -> Result<Vec<DecoSimPoint>, DecoSimError> {
+        match &self.engine {
+            SessionEngine::Buhlmann(live) => live.push(samples),
+            SessionEngine::Thalmann(live) => live.push(samples),
+        }
+    }
+
+    /// Number of samples applied so far.
+    pub fn sample_count(&self) -> u32 {
+        match &self.engine {
+            SessionEngine::Buhlmann(live) => live.lock().sample_count,
+            SessionEngine::Thalmann(live) => live.lock().sample_count,
+        }
+    }
+
+    /// Capture the state after the current sample.
+    pub fn snapshot(&self) -> Arc<DecoSessionSnapshot> {
+        let (last_sample, sample_count, engine) = match &self.engine {
+            SessionEngine::Buhlmann(live) => {
+                let state = live.lock();
+                (
+                    state.last_sample.clone(),
+                    state.sample_count,
+                    EngineState::Buhlmann(Box::new(state.engine.clone())),
+                )
+            }
+            SessionEngine::Thalmann(live) => {
+                let state = live.lock();
+                (
+                    state.last_sample.clone(),
+                    state.sample_count,
+                    EngineState::Thalmann(state.engine.clone()),
+                )
+            }
+        };
+        Arc::new(DecoSessionSnapshot {
+            session_id: self.id,
+            last_sample,
+            sample_count,
+            engine,
+        })
+    }
+
+    /// Rewind (or fast-forward) to a snapshot taken from this session.
+    pub fn restore(&self, snapshot: Arc<DecoSessionSnapshot>) -> Result<(), DecoSimError> {
+        if snapshot.session_id != self.id {
+            return Err(DecoSimError::InvalidParam {
+                msg: "snapshot was taken from a different session".to_string(),
+            });
+        }
+        match (&self.engine, &snapshot.engine) {
+            (SessionEngine::Buhlmann(live), EngineState::Buhlmann(engine)) => {
+                live.restore(&snapshot, (**engine).clone())
+            }
+            (SessionEngine::Thalmann(live), EngineState::Thalmann(engine)) => {
+                live.restore(&snapshot, engine.clone())
+            }
+            _ => {
+                return Err(DecoSimError::InvalidParam {
+                    msg: "snapshot was taken with a different deco model".to_string(),
+                })
+            }
+        }
+        Ok(())
+    }
+
+    /// Plan the ascent from the current sample.
+    pub fn plan_ascent(&self) -> Result<DecoSessionPlan, DecoSimError> {
+        let (last, (plan, ascent_path)) = match &self.engine {
+            SessionEngine::Buhlmann(live) => {
+                live.plan(|sim, engine, last| sim.plan_ascent(engine, last))?
+            }
+            SessionEngine::Thalmann(live) => {
+                live.plan(|sim, engine, last| (sim.plan_ascent(engine, last), Vec::new()))?
+            }
+        };
+        let DeepStopPlan {
+            stops: deco_stops,
+            truncated,
+            deep_stop_time_sec,
+            penalty_sec: deep_stop_penalty_sec,
+        } = plan;
+        Ok(DecoSessionPlan {
+            t_sec: last.t_sec,
+            depth_m: last.depth_m,
+            total_deco_time_sec: deco_stops.iter().map(|s| s.duration_sec).sum(),
+            deco_stops,
+            truncated,
+            deep_stop_time_sec,
+            deep_stop_penalty_sec,
+            ascent_path,
+        })
+    }
+}
+
+impl<S: SampleStepper> Live<S> {
+    fn new(initial: S::State, sim: S) -> Self {
+        Self {
+            sim,
+            state: Mutex::new(SessionState {
+                engine: initial,
+                last_sample: None,
+                sample_count: 0,
+            }),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SessionState<S::State>> {
+        self.state.lock().expect("session state lock poisoned")
+    }
+
+    fn push(&self, samples: Vec<SampleInput>) -> Result<Vec<DecoSimPoint>, DecoSimError> {
+        let mut guard = self.lock();
+        let state = &mut *guard;
+        check_order(state.last_sample.as_ref(), &samples)?;
+        Ok(samples
+            .into_iter()
+            .map(|sample| {
+                let point = self
+                    .sim
+                    .step(&mut state.engine, state.last_sample.as_ref(), &sample);
+                state.last_sample = Some(sample);
+                state.sample_count += 1;
+                point
+            })
+            .collect())
+    }
+
+    fn restore(&self, snapshot: &DecoSessionSnapshot, engine: S::State) {
+        *self.lock() = SessionState {
+            engine,
+            last_sample: snapshot.last_sample.clone(),
+            sample_count: snapshot.sample_count,
+        };
+    }
+
+    /// Run `plan` on the state after the last sample, returning that sample.
+    fn plan<R>(
+        &self,
+        plan: impl FnOnce(&S, &S::State, &SampleInput) -> R,
+    ) -> Result<(SampleInput, R), DecoSimError> {
+        let state = self.lock();
+        let Some(last) = state.last_sample.as_ref() else {
+            return Err(DecoSimError::EmptySamples {
+                msg: "No samples pushed".to_string(),
+            });
+        };
+        Ok((last.clone(), plan(&self.sim, &state.engine, last)))
+    }
+}
+
+impl DecoSessionSnapshot {
+    /// Number of samples applied when the snapshot was taken.
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    /// Time of the last applied sample, or `None` before the first sample.
+    pub fn t_sec(&self) -> Option<i32> {
+        self.last_sample.as_ref().map(|s| s.t_sec)
+    }
+}
+
+/// Reject samples that go back in time.
+fn check_order(last: Option<&SampleInput>, samples: &[SampleInput]) -> Result<(), DecoSimError> {
+    let mut prev_t = last.map(|s| s.t_sec);
+    for sample in samples {
+        if let Some(prev_t) = prev_t {
+            if sample.t_sec < prev_t {
+                return Err(DecoSimError::InvalidParam {
+                    msg: format!(
+                        "sample at {} s is before the previous sample at {prev_t} s",
+                        sample.t_sec
+                    ),
+                });
+            }
+        }
+        prev_t = Some(sample.t_sec);
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buhlmann::GasMixInput;
+    use crate::deco::compute_deco_simulation;
+
+    fn sample(t_sec: i32, depth_m: f32) -> SampleInput {
+        SampleInput {
+            t_sec,
+            depth_m,
+            temp_c: 20.0,
+            setpoint_ppo2: None,
+            ceiling_m: None,
+            gf99: None,
+            gasmix_index: None,
+            ppo2: None,
+            tts_sec: None,
+            ndl_sec: None,
+            deco_stop_depth_m: None,
+            at_plus_five_tts_min: None,
+        }
+    }
+
+    /// 40 m for 25 min on air with EAN50, 10 s samples, ending at depth.
+    fn profile() -> Vec<SampleInput> {
+        let mut samples = Vec::new();
+        for t in (0..=2400).step_by(10) {
+            let depth = (t as f32 / 60.0 * 18.0).min(40.0);
+            samples.push(sample(t, depth));
+        }
+        samples
+    }
+
+    fn params(model: DecoModel, samples: Vec<SampleInput>) -> DecoSimParams {
+        DecoSimParams {
+            model,
+            samples,
+            gas_mixes: vec![
+                GasMixInput {
+                    mix_index: 0,
+                    o2_fraction: 0.21,
+                    he_fraction: 0.0,
+                },
+                GasMixInput {
+                    mix_index: 1,
+                    o2_fraction: 0.50,
+                    he_fraction: 0.0,
+                },
+            ],
+            surface_pressure_bar: None,
+            ascent_rate_m_min: None,
+            last_stop_depth_m: None,
+            stop_interval_m: None,
+            gf_low: Some(30),
+            gf_high: Some(80),
+            thalmann_pdcs: None,
+            plan_ascent: false,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: Some(0),
+        }
+    }
+
+    fn assert_points_eq(a: &[DecoSimPoint], b: &[DecoSimPoint]) {
+        assert_eq!(a.len(), b.len());
+        for (p, q) in a.iter().zip(b) {
+            assert_eq!(p.t_sec, q.t_sec);
+            assert_eq!(p.ceiling_m, q.ceiling_m);
+            assert_eq!(p.gf99, q.gf99);
+            assert_eq!(p.surface_gf, q.surface_gf);
+            assert_eq!(p.tts_sec, q.tts_sec);
+            assert_eq!(p.ndl_sec, q.ndl_sec);
+            assert_eq!(p.leading_compartment, q.leading_compartment);
+        }
+    }
+
+    #[test]
+    fn test_incremental_points_match_batch() {
+        for model in [DecoModel::BuhlmannZhl16c, DecoModel::ThalmannElDca] {
+            let samples = profile();
+            let batch = compute_deco_simulation(params(model, samples.clone())).unwrap();
+
+            let session = DecoSession::new(params(model, vec![])).unwrap();
+            let points: Vec<_> = samples
+                .into_iter()
+                .map(|s| session.push_sample(s).unwrap())
+                .collect();
+            assert_points_eq(&points, &batch.points);
+            assert_eq!(session.sample_count(), points.len() as u32);
+        }
+    }
+
+    #[test]
+    fn test_initial_samples_and_batches() {
+        let samples = profile();
+        let batch =
+            compute_deco_simulation(params(DecoModel::BuhlmannZhl16c, samples.clone())).unwrap();
+
+        let (head, tail) = samples.split_at(100);
+        let session = DecoSession::new(params(DecoModel::BuhlmannZhl16c, head.to_vec())).unwrap();
+        assert_eq!(session.sample_count(), 100);
+        let points = session.push_samples(tail.to_vec()).unwrap();
+        assert_points_eq(&points, &batch.points[100..]);
+    }
+
+    #[test]
+    fn test_plan_ascent_matches_batch() {
+        for model in [DecoModel::BuhlmannZhl16c, DecoModel::ThalmannElDca] {
+            let samples = profile();
+            let batch = compute_deco_simulation(DecoSimParams {
+                plan_ascent: true,
+                ..params(model, samples.clone())
+            })
+            .unwrap();
+
+            let session = DecoSession::new(params(model, samples)).unwrap();
+            let plan = session.plan_ascent().unwrap();
+            assert!(!plan.deco_stops.is_empty());
+            assert_eq!(plan.deco_stops.len(), batch.deco_stops.len());
+            for (a, b) in plan.deco_stops.iter().zip(&batch.deco_stops) {
+                assert_eq!(a.depth_m, b.depth_m);
+                assert_eq!(a.duration_sec, b.duration_sec);
+            }
+            assert_eq!(plan.total_deco_time_sec, batch.total_deco_time_sec);
+            assert_eq!(plan.t_sec, 2400);
+        }
+    }
+
+    #[test]
+    fn test_continuous_ascent_plan_has_path() {
+        let session = DecoSession::new(DecoSimParams {
+            ascent_mode: Some(AscentMode::Continuous),
+            ..params(DecoModel::BuhlmannZhl16c, profile())
+        })
+        .unwrap();
+        let plan = session.plan_ascent().unwrap();
+        assert!(!plan.ascent_path.is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_restore_rewinds() {
+        let samples = profile();
+        let batch =
+            compute_deco_simulation(params(DecoModel::BuhlmannZhl16c, samples.clone())).unwrap();
+
+        let session = DecoSession::new(params(DecoModel::BuhlmannZhl16c, vec![])).unwrap();
+        session.push_samples(samples[..120].to_vec()).unwrap();
+        let mid = session.snapshot();
+        assert_eq!(mid.sample_count(), 120);
+        assert_eq!(mid.t_sec(), Some(samples[119].t_sec));
+        let plan_mid = session.plan_ascent().unwrap();
+
+        session.push_samples(samples[120..].to_vec()).unwrap();
+        let end = session.snapshot();
+        assert!(session.plan_ascent().unwrap().total_deco_time_sec > plan_mid.total_deco_time_sec);
+
+        // Scrub back and replay
+        session.restore(mid.clone()).unwrap();
+        assert_eq!(session.sample_count(), 120);
+        assert_eq!(
+            session.plan_ascent().unwrap().total_deco_time_sec,
+            plan_mid.total_deco_time_sec
+        );
+        let replay = session.push_samples(samples[120..].to_vec()).unwrap();
+        assert_points_eq(&replay, &batch.points[120..]);
+
+        // Forward again to a later snapshot
+        session.restore(mid).unwrap();
+        session.restore(end).unwrap();
+        assert_eq!(session.sample_count(), samples.len() as u32);
+    }
+
+    #[test]
+    fn test_snapshot_from_other_session_rejected() {
+        let a = DecoSession::new(params(DecoModel::BuhlmannZhl16c, profile())).unwrap();
+        let b = DecoSession::new(params(DecoModel::BuhlmannZhl16c, profile())).unwrap();
+        assert!(matches!(
+            b.restore(a.snapshot()),
+            Err(DecoSimError::InvalidParam { .. })
+        ));
+    }
+
+    #[test]
+    fn test_out_of_order_samples_rejected() {
+        let session = DecoSession::new(params(DecoModel::BuhlmannZhl16c, vec![])).unwrap();
+        session.push_sample(sample(0, 0.0)).unwrap();
+        session.push_sample(sample(60, 10.0)).unwrap();
+        assert!(session.push_sample(sample(30, 10.0)).is_err());
+
+        // A batch going back in time is rejected as a whole
+        let result = session.push_samples(vec![sample(70, 10.0), sample(65, 10.0)]);
+        assert!(matches!(result, Err(DecoSimError::InvalidParam { .. })));
+        assert_eq!(session.sample_count(), 2);
+
+        // Equal times are allowed
+        session.push_sample(sample(60, 10.0)).unwrap();
+        assert_eq!(session.sample_count(), 3);
+    }
+
+    #[test]
+    fn test_invalid_params_and_empty_plan() {
+        let invalid = DecoSession::new(DecoSimParams {
+            gf_low: Some(90),
+            gf_high: Some(50),
+            ..params(DecoModel::BuhlmannZhl16c, vec![])
+        });
+        assert!(matches!(invalid, Err(DecoSimError::InvalidParam { .. })));
+
+        let thalmann_continuous = DecoSession::new(DecoSimParams {
+            ascent_mode: Some(AscentMode::Continuous),
+            ..params(DecoModel::ThalmannElDca, vec![])
+        });
+        assert!(thalmann_continuous.is_err());
+
+        let session = DecoSession::new(params(DecoModel::ThalmannElDca, vec![])).unwrap();
+        assert_eq!(session.sample_count(), 0);
+        assert!(session.snapshot().t_sec().is_none());
+        assert!(matches!(
+            session.plan_ascent(),
+            Err(DecoSimError::EmptySamples { .. })
+        ));
+    }
+}
@@ -21,6 +21,13 @@ pub(crate) const AIR_FN2: f64 = 0.7902;
 /// Fraction of O2 in air (for default gas).
 pub(crate) const AIR_FO2: f64 = 0.2095;
 
+/// A compartment counts as desaturated within this fraction above its
+/// surface equilibrium inert gas tension.
+pub(crate) const DESATURATION_TOLERANCE: f64 = 0.01;
+
+/// Upper bound on reported desaturation time (72 h).
+pub(crate) const MAX_DESATURATION_SEC: i32 = 72 * 3600;
+
 // ============================================================================
 // Helper Functions
 // ============================================================================
@@ -37,6 +44,16 @@ pub(crate) fn pressure_to_depth(pressure_bar: f64, surface_pressure: f64) -> f64
     ((pressure_bar - surface_pressure) / BAR_PER_METER).max(0.0)
 }
 
+/// Default ascent switch depth (metres) for a deco gas: its MOD at
+/// `max_ppo2`, with the PPO2 limit counted in atmospheres so that oxygen at
+/// 1.6 switches at 6 m at sea level.
+pub(crate) fn default_switch_depth(o2_fraction: f64, max_ppo2: f64, surface_pressure: f64) -> f64 {
+    pressure_to_depth(
+        max_ppo2 / o2_fraction * DEFAULT_SURFACE_PRESSURE,
+        surface_pressure,
+    )
+}
+
 /// Compute inspired inert gas fractions (fN2, fHe) for a given gas and ambient state.
 ///
 /// For CCR (ppo2 is Some): derives effective fractions from measured PPO2 and diluent ratio.
@@ -82,6 +99,35 @@ pub(crate) fn schreiner_step(
     p_inspired + (p_tissue - p_inspired) * (-k * dt_sec).exp()
 }
 
+/// Seconds of surface off-gassing until `desaturated` holds, to the minute.
+///
+/// Steps `offgas` in 10-minute increments and refines the last one in
+/// 1-minute steps. Capped at `MAX_DESATURATION_SEC`.
+pub(crate) fn desaturation_time_sec<T: Clone>(
+    tissues: &T,
+    mut offgas: impl FnMut(&mut T, f64),
+    desaturated: impl Fn(&T) -> bool,
+) -> i32 {
+    const COARSE_SEC: i32 = 600;
+    const FINE_SEC: i32 = 60;
+    let mut state = tissues.clone();
+    let mut elapsed = 0;
+    while !desaturated(&state) && elapsed < MAX_DESATURATION_SEC {
+        let mut next = state.clone();
+        offgas(&mut next, COARSE_SEC as f64);
+        if desaturated(&next) {
+            while !desaturated(&state) && elapsed < MAX_DESATURATION_SEC {
+                offgas(&mut state, FINE_SEC as f64);
+                elapsed += FINE_SEC;
+            }
+            break;
+        }
+        state = next;
+        elapsed += COARSE_SEC;
+    }
+    elapsed.min(MAX_DESATURATION_SEC)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -213,4 +259,19 @@ mod tests {
             "After one half-time: got {result}, expected {expected}"
         );
     }
+
+    #[test]
+    fn test_desaturation_time_to_the_minute() {
+        // 1.0 bar excess over 0.75 halving every 60 min reaches 1% of 0.75
+        // after 60 * log2(1.0 / 0.0075) = 423.4 min
+        let offgas = |p: &mut f64, dt: f64| *p = schreiner_step(*p, 0.75, 60.0, dt);
+        let done = |p: &f64| *p <= 0.75 * (1.0 + DESATURATION_TOLERANCE);
+        assert_eq!(desaturation_time_sec(&1.75, offgas, done), 424 * 60);
+        assert_eq!(desaturation_time_sec(&0.75, offgas, done), 0);
+        // Never desaturates: capped
+        assert_eq!(
+            desaturation_time_sec(&1.75, |_, _| {}, done),
+            MAX_DESATURATION_SEC
+        );
+    }
 }
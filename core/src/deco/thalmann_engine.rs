@@ -11,9 +11,16 @@
 //!
 //! Reference: NEDU TR 18-05 (Doolette, Murphy, Gerth 2018).
 
+use std::borrow::Cow;
+
+use super::cadence::{simulate_points, tts_interval, SampleStepper};
+use super::deep_stops::{plan_with_deep_stops, DeepStopPlan};
+use super::progress::Progress;
+use super::scr::{interval_ppo2, scr_loop_ppo2, ScrParams};
 use super::shared::*;
 use super::thalmann_params::*;
 use super::types::*;
+use crate::metrics::SampleInput;
 
 // ============================================================================
 // Thalmann Engine
@@ -22,7 +29,11 @@ use super::types::*;
 pub(crate) struct ThalmannEngine;
 
 impl ThalmannEngine {
-    pub(crate) fn simulate(&self, params: &DecoSimParams) -> Result<DecoSimResult, DecoSimError> {
+    pub(crate) fn simulate(
+        &self,
+        params: &DecoSimParams,
+        progress: Progress,
+    ) -> Result<DecoSimResult, DecoSimError> {
         // Validate inputs
         if params.samples.is_empty() {
             return Err(DecoSimError::EmptySamples {
@@ -30,68 +41,112 @@ impl ThalmannEngine {
             });
         }
 
-        let surface_p = params
-            .surface_pressure_bar
-            .unwrap_or(DEFAULT_SURFACE_PRESSURE);
-        let ascent_rate = params.ascent_rate_m_min.unwrap_or(9.0);
-        let last_stop_depth = params.last_stop_depth_m.unwrap_or(3.0);
-        let stop_interval = params.stop_interval_m.unwrap_or(3.0);
+        let sim = ThalmannSim::new(params)?;
+        let mut state = sim.initial_state();
+        let mut max_ceiling_m: f32 = 0.0;
+        let mut max_gf99: f32 = 0.0;
+        let mut max_tts_sec: i32 = 0;
 
-        if ascent_rate <= 0.0 || stop_interval <= 0.0 || last_stop_depth <= 0.0 {
-            return Err(DecoSimError::InvalidParam {
-                msg: format!(
-                    "ascent_rate ({ascent_rate}), stop_interval ({stop_interval}), \
-                     and last_stop_depth ({last_stop_depth}) must be > 0"
-                ),
-            });
+        let points = simulate_points(
+            &sim,
+            &mut state,
+            &params.samples,
+            sim.tts_interval,
+            progress,
+        )?;
+        for point in &points {
+            // Track maxima
+            if point.ceiling_m > max_ceiling_m {
+                max_ceiling_m = point.ceiling_m;
+            }
+            if point.gf99 > max_gf99 {
+                max_gf99 = point.gf99;
+            }
+            if point.tts_sec > max_tts_sec {
+                max_tts_sec = point.tts_sec;
+            }
         }
 
-        let thal_params = match params.thalmann_pdcs {
-            Some(ThalmannPdcs::Pdcs40) => &XVAL_HE_9_040,
-            Some(ThalmannPdcs::Pdcs50) => &XVAL_HE_9_050,
-            _ => &XVAL_HE_9_023,
+        // Deco stop planning from final state
+        let plan = if params.plan_ascent {
+            sim.plan_ascent(&state, params.samples.last().unwrap())
+        } else {
+            DeepStopPlan {
+                stops: Vec::new(),
+                truncated: false,
+                deep_stop_time_sec: 0,
+                penalty_sec: 0,
+            }
         };
-        if let Err(msg) = thal_params.validate() {
-            return Err(DecoSimError::InvalidParam { msg });
-        }
+        let DeepStopPlan {
+            stops: deco_stops,
+            truncated,
+            deep_stop_time_sec,
+            penalty_sec: deep_stop_penalty_sec,
+        } = plan;
+
+        let total_deco_time_sec: i32 = deco_stops.iter().map(|s| s.duration_sec).sum();
+
+        Ok(DecoSimResult {
+            points,
+            deco_stops,
+            total_deco_time_sec,
+            max_ceiling_m,
+            max_gf99,
+            max_tts_sec,
+            model: DecoModel::ThalmannElDca,
+            truncated,
+            deep_stop_time_sec,
+            deep_stop_penalty_sec,
+            ascent_path: Vec::new(),
+            desaturation_time_sec: sim.desaturation_time_sec(&state),
+        })
+    }
+
+    /// Unrounded MPTT ceiling depth (metres) at selected samples.
+    ///
+    /// Each probe is `(sample_index, next_depth_m)`; the Thalmann ceiling does
+    /// not depend on the destination, so `next_depth_m` is unused here.
+    /// Probes must be sorted by sample index.
+    pub(crate) fn departure_ceilings(
+        &self,
+        params: &DecoSimParams,
+        probes: &[(usize, f64)],
+    ) -> Result<Vec<f64>, DecoSimError> {
+        let surface_p = params
+            .surface_pressure_bar
+            .unwrap_or(DEFAULT_SURFACE_PRESSURE);
+        let thal_params = &ThalmannParamSet::resolve(
+            params.thalmann_pdcs,
+            params.custom_thalmann_params.as_ref(),
+        )?;
 
-        // Build gas mix lookup
         let mut gas_lookup: std::collections::HashMap<i32, (f64, f64)> =
             std::collections::HashMap::new();
         for mix in &params.gas_mixes {
             gas_lookup.insert(mix.mix_index, (mix.o2_fraction, mix.he_fraction));
         }
-        let default_gas = gas_lookup.get(&0).copied().unwrap_or((AIR_FO2, 0.0));
-        let mut current_fo2 = default_gas.0;
-        let mut current_fhe = default_gas.1;
+        let (mut current_fo2, mut current_fhe) =
+            gas_lookup.get(&0).copied().unwrap_or((AIR_FO2, 0.0));
 
         let mut tissues = ThalmannTissueState::surface_equilibrium(surface_p, thal_params);
-        let mut points = Vec::with_capacity(params.samples.len());
-        let mut max_ceiling_m: f32 = 0.0;
-        let mut max_gf99: f32 = 0.0;
-        let mut max_tts_sec: i32 = 0;
+        let mut ceilings = Vec::with_capacity(probes.len());
+        let mut probe_iter = probes.iter().peekable();
 
         for (idx, sample) in params.samples.iter().enumerate() {
-            // Update tissues for time interval
             if idx > 0 {
                 let prev = &params.samples[idx - 1];
                 let dt_sec = (sample.t_sec - prev.t_sec) as f64;
                 let avg_depth_m = ((prev.depth_m as f64 + sample.depth_m as f64) / 2.0).max(0.0);
                 let ambient_p = depth_to_pressure(avg_depth_m, surface_p);
-
                 let (fn2, fhe) = inspired_fractions(
                     current_fo2,
                     current_fhe,
-                    prev.ppo2.map(|v| v as f64),
+                    interval_ppo2(params.scr.as_ref(), prev.ppo2, current_fo2, ambient_p),
                     ambient_p,
                 );
-
-                // Convert to fsw units for tissue update
                 let ambient_fsw = bar_to_fsw(ambient_p);
                 let f_inert = fn2 + fhe;
-                let p_inspired_fsw = (ambient_fsw - PACO2_FSW) * f_inert;
-
-                // Compute rates of change for E-L linear term
                 let depth1_fsw = meters_to_fsw((prev.depth_m as f64).max(0.0));
                 let depth2_fsw = meters_to_fsw((sample.depth_m as f64).max(0.0));
                 let r_ambient_fsw = if dt_sec > 0.0 {
@@ -99,114 +154,275 @@ impl ThalmannEngine {
                 } else {
                     0.0
                 };
-                let r_inspired_fsw = r_ambient_fsw * f_inert;
-
                 tissues.update(
                     dt_sec,
-                    p_inspired_fsw,
+                    (ambient_fsw - PACO2_FSW) * f_inert,
                     ambient_fsw,
-                    r_inspired_fsw,
+                    r_ambient_fsw * f_inert,
                     r_ambient_fsw,
                     thal_params,
                 );
             }
+            if let Some(&(fo2, fhe)) = sample.gasmix_index.and_then(|i| gas_lookup.get(&i)) {
+                current_fo2 = fo2;
+                current_fhe = fhe;
+            }
 
-            // Gas switch
-            if let Some(mix_idx) = sample.gasmix_index {
-                if let Some(&(fo2, fhe)) = gas_lookup.get(&mix_idx) {
-                    current_fo2 = fo2;
-                    current_fhe = fhe;
+            while let Some(&&(probe_idx, _)) = probe_iter.peek() {
+                if probe_idx != idx {
+                    break;
                 }
+                ceilings.push(fsw_to_meters(tissues.ceiling_fsw(thal_params).max(0.0)));
+                probe_iter.next();
             }
+        }
+        Ok(ceilings)
+    }
+}
 
-            let current_depth_m = (sample.depth_m as f64).max(0.0);
-
-            // Compute ceiling
-            let ceiling_fsw = tissues.ceiling_fsw(thal_params);
-            let ceiling_depth_m = fsw_to_meters(ceiling_fsw.max(0.0));
-            let ceiling_m = round_up_to_stop(ceiling_depth_m, stop_interval);
-
-            // Utilization (maps to gf99 / surface_gf)
-            let current_depth_fsw = meters_to_fsw(current_depth_m);
-            let (util_at_depth, leading) = tissues.utilization_at(current_depth_fsw, thal_params);
-            let (surface_util, _) = tissues.utilization_at(0.0, thal_params);
-
-            // TTS and NDL
-            let pp = ThalmannPlanParams::from_engine(
-                &gas_lookup,
-                sample.gasmix_index.unwrap_or(0),
-                sample.ppo2.map(|v| v as f64),
-                surface_p,
-                ascent_rate,
-                last_stop_depth,
-                stop_interval,
-                thal_params,
-            );
+// ============================================================================
+// Sample Stepping
+// ============================================================================
 
-            let (tts_sec, ndl_sec) = if ceiling_m > 0.0 {
-                let tts = compute_tts_thalmann(&tissues, current_depth_m, &pp);
-                (tts, 0)
-            } else {
-                let ndl = compute_ndl_thalmann(&tissues, current_depth_m, &pp);
-                (0, ndl)
-            };
+/// Validated model settings for one simulation.
+///
+/// Shared by the one-shot `simulate` and by live sessions, which keep a
+/// `ThalmannState` between calls and step it one sample at a time.
+pub(crate) struct ThalmannSim<'p> {
+    params: Cow<'p, DecoSimParams>,
+    gas_lookup: std::collections::HashMap<i32, (f64, f64)>,
+    surface_p: f64,
+    ascent_rate: f64,
+    last_stop_depth: f64,
+    stop_interval: f64,
+    /// TTS/NDL cadence of `simulate` (seconds, 0 = every sample).
+    tts_interval: i32,
+}
 
-            // Track maxima
-            let ceil_f32 = ceiling_m as f32;
-            let gf99_f32 = util_at_depth as f32;
-            if ceil_f32 > max_ceiling_m {
-                max_ceiling_m = ceil_f32;
-            }
-            if gf99_f32 > max_gf99 {
-                max_gf99 = gf99_f32;
-            }
-            if tts_sec > max_tts_sec {
-                max_tts_sec = tts_sec;
-            }
+/// Tissue and breathing-gas state after the last simulated sample.
+#[derive(Debug, Clone)]
+pub(crate) struct ThalmannState {
+    tissues: ThalmannTissueState,
+    fo2: f64,
+    fhe: f64,
+}
 
-            points.push(DecoSimPoint {
-                t_sec: sample.t_sec,
-                depth_m: sample.depth_m,
-                ceiling_m: ceil_f32,
-                gf99: gf99_f32,
-                surface_gf: surface_util as f32,
-                tts_sec,
-                leading_compartment: leading as u8,
-                ndl_sec,
+impl<'p> ThalmannSim<'p> {
+    /// Validate the model parameters. Samples are not inspected.
+    pub(crate) fn new(params: &'p DecoSimParams) -> Result<Self, DecoSimError> {
+        Self::from_cow(Cow::Borrowed(params))
+    }
+
+    /// Like `new`, but the simulation keeps its own copy of `params`, so it
+    /// can outlive the caller (live sessions).
+    pub(crate) fn owned(params: DecoSimParams) -> Result<ThalmannSim<'static>, DecoSimError> {
+        ThalmannSim::from_cow(Cow::Owned(params))
+    }
+
+    fn from_cow(params: Cow<'p, DecoSimParams>) -> Result<Self, DecoSimError> {
+        let ascent_rate = params.ascent_rate_m_min.unwrap_or(9.0);
+        let last_stop_depth = params.last_stop_depth_m.unwrap_or(3.0);
+        let stop_interval = params.stop_interval_m.unwrap_or(3.0);
+
+        if ascent_rate <= 0.0 || stop_interval <= 0.0 || last_stop_depth <= 0.0 {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!(
+                    "ascent_rate ({ascent_rate}), stop_interval ({stop_interval}), \
+                     and last_stop_depth ({last_stop_depth}) must be > 0"
+                ),
             });
         }
 
-        // Deco stop planning from final state
-        let (deco_stops, truncated) = if params.plan_ascent {
-            let last_sample = params.samples.last().unwrap();
-            let current_depth_m = (last_sample.depth_m as f64).max(0.0);
-            let pp = ThalmannPlanParams::from_engine(
-                &gas_lookup,
-                last_sample.gasmix_index.unwrap_or(0),
-                last_sample.ppo2.map(|v| v as f64),
-                surface_p,
-                ascent_rate,
-                last_stop_depth,
-                stop_interval,
+        ThalmannParamSet::resolve(params.thalmann_pdcs, params.custom_thalmann_params.as_ref())?;
+
+        // Build gas mix lookup
+        let mut gas_lookup: std::collections::HashMap<i32, (f64, f64)> =
+            std::collections::HashMap::new();
+        for mix in &params.gas_mixes {
+            gas_lookup.insert(mix.mix_index, (mix.o2_fraction, mix.he_fraction));
+        }
+
+        Ok(Self {
+            surface_p: params
+                .surface_pressure_bar
+                .unwrap_or(DEFAULT_SURFACE_PRESSURE),
+            tts_interval: tts_interval(&params)?,
+            params,
+            gas_lookup,
+            ascent_rate,
+            last_stop_depth,
+            stop_interval,
+        })
+    }
+
+    /// The parameter set validated by `new`.
+    fn thal_params(&self) -> ThalmannParamSet<'_> {
+        match &self.params.custom_thalmann_params {
+            Some(custom) => ThalmannParamSet::from_parameters(custom),
+            None => {
+                ThalmannParamSet::builtin(self.params.thalmann_pdcs.unwrap_or(ThalmannPdcs::Pdcs23))
+            }
+        }
+    }
+
+    /// Surface-equilibrated tissues breathing gas 0 (air if not listed).
+    pub(crate) fn initial_state(&self) -> ThalmannState {
+        let (fo2, fhe) = self.gas_lookup.get(&0).copied().unwrap_or((AIR_FO2, 0.0));
+        ThalmannState {
+            tissues: ThalmannTissueState::surface_equilibrium(self.surface_p, &self.thal_params()),
+            fo2,
+            fhe,
+        }
+    }
+
+    /// Planner parameters for an ascent starting at `sample`.
+    fn plan_params(&self, sample: &SampleInput) -> ThalmannPlanParams<'_> {
+        ThalmannPlanParams::from_engine(
+            &self.gas_lookup,
+            sample.gasmix_index.unwrap_or(0),
+            &self.params.ascent_gas_switches,
+            sample.ppo2.map(|v| v as f64),
+            &self.params.ascent_setpoint_switches,
+            self.params.scr.as_ref(),
+            self.surface_p,
+            self.ascent_rate,
+            self.last_stop_depth,
+            self.stop_interval,
+            self.thal_params(),
+        )
+    }
+
+    /// Plan the ascent from `state` at `last_sample`, honouring deep stops.
+    pub(crate) fn plan_ascent(
+        &self,
+        state: &ThalmannState,
+        last_sample: &SampleInput,
+    ) -> DeepStopPlan {
+        let current_depth_m = (last_sample.depth_m as f64).max(0.0);
+        let pp = self.plan_params(last_sample);
+        match &self.params.deep_stops {
+            Some(deep) => plan_with_deep_stops(
+                &state.tissues,
+                current_depth_m,
+                self.stop_interval,
+                deep,
+                |t, d| plan_deco_stops_thalmann(t, d, &pp),
+                |t, d, to, secs| hold_at_depth_thalmann(t, d, to, secs, &pp),
+            ),
+            None => {
+                let (stops, truncated) =
+                    plan_deco_stops_thalmann(&state.tissues, current_depth_m, &pp);
+                DeepStopPlan {
+                    stops,
+                    truncated,
+                    deep_stop_time_sec: 0,
+                    penalty_sec: 0,
+                }
+            }
+        }
+    }
+
+    /// Seconds at the surface until the tissues in `state` are desaturated.
+    pub(crate) fn desaturation_time_sec(&self, state: &ThalmannState) -> i32 {
+        state
+            .tissues
+            .desaturation_time_sec(self.surface_p, &self.thal_params())
+    }
+}
+
+impl SampleStepper for ThalmannSim<'_> {
+    type State = ThalmannState;
+
+    fn advance(
+        &self,
+        state: &mut ThalmannState,
+        prev: Option<&SampleInput>,
+        sample: &SampleInput,
+    ) -> DecoSimPoint {
+        let thal_params = &self.thal_params();
+
+        // Update tissues for time interval
+        if let Some(prev) = prev {
+            let dt_sec = (sample.t_sec - prev.t_sec) as f64;
+            let avg_depth_m = ((prev.depth_m as f64 + sample.depth_m as f64) / 2.0).max(0.0);
+            let ambient_p = depth_to_pressure(avg_depth_m, self.surface_p);
+
+            let (fn2, fhe) = inspired_fractions(
+                state.fo2,
+                state.fhe,
+                interval_ppo2(self.params.scr.as_ref(), prev.ppo2, state.fo2, ambient_p),
+                ambient_p,
+            );
+
+            // Convert to fsw units for tissue update
+            let ambient_fsw = bar_to_fsw(ambient_p);
+            let f_inert = fn2 + fhe;
+            let p_inspired_fsw = (ambient_fsw - PACO2_FSW) * f_inert;
+
+            // Compute rates of change for E-L linear term
+            let depth1_fsw = meters_to_fsw((prev.depth_m as f64).max(0.0));
+            let depth2_fsw = meters_to_fsw((sample.depth_m as f64).max(0.0));
+            let r_ambient_fsw = if dt_sec > 0.0 {
+                (depth2_fsw - depth1_fsw) / dt_sec
+            } else {
+                0.0
+            };
+            let r_inspired_fsw = r_ambient_fsw * f_inert;
+
+            state.tissues.update(
+                dt_sec,
+                p_inspired_fsw,
+                ambient_fsw,
+                r_inspired_fsw,
+                r_ambient_fsw,
                 thal_params,
             );
-            plan_deco_stops_thalmann(&tissues, current_depth_m, &pp)
-        } else {
-            (Vec::new(), false)
-        };
+        }
 
-        let total_deco_time_sec: i32 = deco_stops.iter().map(|s| s.duration_sec).sum();
+        // Gas switch
+        if let Some(&(fo2, fhe)) = sample.gasmix_index.and_then(|i| self.gas_lookup.get(&i)) {
+            state.fo2 = fo2;
+            state.fhe = fhe;
+        }
 
-        Ok(DecoSimResult {
-            points,
-            deco_stops,
-            total_deco_time_sec,
-            max_ceiling_m,
-            max_gf99,
-            max_tts_sec,
-            model: DecoModel::ThalmannElDca,
-            truncated,
-        })
+        let tissues = &state.tissues;
+        let current_depth_m = (sample.depth_m as f64).max(0.0);
+
+        // Compute ceiling
+        let ceiling_fsw = tissues.ceiling_fsw(thal_params);
+        let ceiling_depth_m = fsw_to_meters(ceiling_fsw.max(0.0));
+        let ceiling_m = round_up_to_stop(ceiling_depth_m, self.stop_interval);
+
+        // Utilization (maps to gf99 / surface_gf)
+        let current_depth_fsw = meters_to_fsw(current_depth_m);
+        let (util_at_depth, leading) = tissues.utilization_at(current_depth_fsw, thal_params);
+        let (surface_util, _) = tissues.utilization_at(0.0, thal_params);
+
+        DecoSimPoint {
+            t_sec: sample.t_sec,
+            depth_m: sample.depth_m,
+            ceiling_m: ceiling_m as f32,
+            gf99: util_at_depth as f32,
+            surface_gf: surface_util as f32,
+            tts_sec: 0,
+            leading_compartment: leading as u8,
+            ndl_sec: 0,
+        }
+    }
+
+    fn surface_times(
+        &self,
+        state: &mut ThalmannState,
+        sample: &SampleInput,
+        point: &mut DecoSimPoint,
+    ) {
+        let current_depth_m = (sample.depth_m as f64).max(0.0);
+        let pp = self.plan_params(sample);
+        if point.ceiling_m > 0.0 {
+            point.tts_sec = compute_tts_thalmann(&state.tissues, current_depth_m, &pp);
+        } else {
+            point.ndl_sec = compute_ndl_thalmann(&state.tissues, current_depth_m, &pp);
+        }
     }
 }
 
@@ -291,6 +507,18 @@ impl ThalmannTissueState {
         }
     }
 
+    /// Seconds on air at the surface until every compartment is desaturated.
+    fn desaturation_time_sec(&self, surface_pressure_bar: f64, params: &ThalmannParamSet) -> i32 {
+        let surface_fsw = bar_to_fsw(surface_pressure_bar);
+        let p_surface = (surface_fsw - PACO2_FSW) * (1.0 - AIR_FO2);
+        let limit = p_surface * (1.0 + DESATURATION_TOLERANCE);
+        desaturation_time_sec(
+            self,
+            |t, dt| t.update(dt, p_surface, surface_fsw, 0.0, 0.0, params),
+            |t| t.p_ig.iter().all(|&p| p <= limit),
+        )
+    }
+
     /// Compute the ceiling depth in fsw using MPTT tables.
     ///
     /// For each compartment: D_i = (p_ig[i] - M0[i]) / beta1[i].
@@ -359,11 +587,15 @@ struct ThalmannPlanParams<'a> {
     gases: Vec<ThalPlanGas>,
     /// CCR setpoint PPO2 in bar. `None` = open circuit.
     ppo2: Option<f64>,
+    /// Planned CCR setpoint switches on the ascent.
+    setpoint_switches: Vec<SetpointSwitch>,
+    /// SCR loop on the current gas; overrides `ppo2`.
+    scr: Option<ScrParams>,
     surface_p: f64,
     ascent_rate_m_min: f64,
     last_stop_depth: f64,
     stop_interval: f64,
-    thal_params: &'a ThalmannParamSet,
+    thal_params: ThalmannParamSet<'a>,
 }
 
 impl ThalmannPlanParams<'_> {
@@ -394,33 +626,90 @@ impl ThalmannPlanParams<'_> {
         })
     }
 
+    /// CCR setpoint at a depth on the ascent: the shallowest planned switch
+    /// at or below this depth, else the current setpoint. `None` = open circuit.
+    fn setpoint_at_depth(&self, depth_m: f64) -> Option<f64> {
+        let current = self.ppo2?;
+        Some(
+            self.setpoint_switches
+                .iter()
+                .filter(|s| depth_m <= s.depth_m)
+                .min_by(|a, b| {
+                    a.depth_m
+                        .partial_cmp(&b.depth_m)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                })
+                .map_or(current, |s| s.setpoint_ppo2),
+        )
+    }
+
+    /// Inspired PPO2 on the ascent at `depth_m`, evaluated at `ambient_p`:
+    /// the SCR loop on the supply gas, else the CCR setpoint clamped to
+    /// ambient. `None` = open circuit.
+    fn loop_ppo2(&self, depth_m: f64, fo2: f64, ambient_p: f64) -> Option<f64> {
+        match &self.scr {
+            Some(scr) => Some(scr_loop_ppo2(scr, fo2, ambient_p)),
+            None => self.setpoint_at_depth(depth_m).map(|sp| sp.min(ambient_p)),
+        }
+    }
+
+    /// Inspired PPO2 at the current depth (no ascent switches applied).
+    fn current_ppo2(&self, fo2: f64, ambient_p: f64) -> Option<f64> {
+        match &self.scr {
+            Some(scr) => Some(scr_loop_ppo2(scr, fo2, ambient_p)),
+            None => self.ppo2.map(|sp| sp.min(ambient_p)),
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn from_engine<'a>(
         gas_lookup: &std::collections::HashMap<i32, (f64, f64)>,
         current_gas_index: i32,
+        ascent_switches: &[GasSwitchDepth],
         ppo2: Option<f64>,
+        setpoint_switches: &[SetpointSwitch],
+        scr: Option<&ScrParams>,
         surface_p: f64,
         ascent_rate: f64,
         last_stop_depth: f64,
         stop_interval: f64,
-        thal_params: &'a ThalmannParamSet,
+        thal_params: ThalmannParamSet<'a>,
     ) -> ThalmannPlanParams<'a> {
         let mut gases: Vec<ThalPlanGas> = Vec::new();
         let mut bottom_gas: Option<ThalPlanGas> = None;
+        let current_known = gas_lookup.contains_key(&current_gas_index);
 
         for (&mix_idx, &(fo2, fhe)) in gas_lookup {
-            if mix_idx == current_gas_index || mix_idx == 0 {
+            let planned = ascent_switches.iter().find(|s| s.mix_index == mix_idx);
+            if ascent_switches.is_empty() && mix_idx == 0 && mix_idx != current_gas_index {
+                // Gas 0 is the bottom gas only if the current gas is unknown;
+                // after switching off it, it is not an ascent gas.
+                if !current_known {
+                    bottom_gas = Some(ThalPlanGas {
+                        fo2,
+                        fhe,
+                        switch_depth_m: None,
+                    });
+                }
+            } else if mix_idx == current_gas_index {
                 bottom_gas = Some(ThalPlanGas {
                     fo2,
                     fhe,
                     switch_depth_m: None,
                 });
-            } else if fo2 > 0.0 {
-                let mod_m = (MAX_PPO2_SWITCH / fo2 - 1.0) * 10.0;
+            } else if let Some(planned) = planned {
+                // Explicitly planned ascent switch
+                gases.push(ThalPlanGas {
+                    fo2,
+                    fhe,
+                    switch_depth_m: Some(planned.depth_m.max(0.0)),
+                });
+            } else if ascent_switches.is_empty() && fo2 > 0.0 {
+                let mod_m = default_switch_depth(fo2, MAX_PPO2_SWITCH, surface_p);
                 gases.push(ThalPlanGas {
                     fo2,
                     fhe,
-                    switch_depth_m: Some(mod_m.max(0.0)),
+                    switch_depth_m: Some(mod_m),
                 });
             }
         }
@@ -445,6 +734,8 @@ impl ThalmannPlanParams<'_> {
         ThalmannPlanParams {
             gases,
             ppo2,
+            setpoint_switches: setpoint_switches.to_vec(),
+            scr: scr.cloned(),
             surface_p,
             ascent_rate_m_min: ascent_rate,
             last_stop_depth,
@@ -504,7 +795,7 @@ fn compute_ndl_thalmann(
     let (fn2, fhe) = inspired_fractions(
         gas.fo2,
         gas.fhe,
-        pp.ppo2.map(|sp| sp.min(ambient_p)),
+        pp.current_ppo2(gas.fo2, ambient_p),
         ambient_p,
     );
     let f_inert = fn2 + fhe;
@@ -519,8 +810,8 @@ fn compute_ndl_thalmann(
     while hi < max_time {
         let mut trial = tissues.clone();
         // At constant depth: r_inspired = 0, r_ambient = 0
-        trial.update(hi, p_inspired_fsw, ambient_fsw, 0.0, 0.0, pp.thal_params);
-        let ceil = trial.ceiling_fsw(pp.thal_params);
+        trial.update(hi, p_inspired_fsw, ambient_fsw, 0.0, 0.0, &pp.thal_params);
+        let ceil = trial.ceiling_fsw(&pp.thal_params);
         if ceil > 0.0 {
             break;
         }
@@ -536,9 +827,9 @@ fn compute_ndl_thalmann(
             ambient_fsw,
             0.0,
             0.0,
-            pp.thal_params,
+            &pp.thal_params,
         );
-        let ceil = trial.ceiling_fsw(pp.thal_params);
+        let ceil = trial.ceiling_fsw(&pp.thal_params);
         if ceil <= 0.0 {
             return max_time as i32;
         }
@@ -549,8 +840,8 @@ fn compute_ndl_thalmann(
     while (hi - lo) > 5.0 {
         let mid = (lo + hi) / 2.0;
         let mut trial = tissues.clone();
-        trial.update(mid, p_inspired_fsw, ambient_fsw, 0.0, 0.0, pp.thal_params);
-        let ceil = trial.ceiling_fsw(pp.thal_params);
+        trial.update(mid, p_inspired_fsw, ambient_fsw, 0.0, 0.0, &pp.thal_params);
+        let ceil = trial.ceiling_fsw(&pp.thal_params);
         if ceil > 0.0 {
             hi = mid;
         } else {
@@ -576,7 +867,7 @@ fn plan_deco_stops_thalmann(
     let mut truncated = false;
 
     // Determine first stop from ceiling
-    let ceiling_fsw = tissues.ceiling_fsw(pp.thal_params);
+    let ceiling_fsw = tissues.ceiling_fsw(&pp.thal_params);
     let ceiling_m = fsw_to_meters(ceiling_fsw.max(0.0));
     let mut stop_depth = round_up_to_stop(ceiling_m, pp.stop_interval);
 
@@ -607,7 +898,7 @@ fn plan_deco_stops_thalmann(
 
         // Simulate 1-minute increments until ceiling clears to next stop
         loop {
-            let ceil_fsw = tissues.ceiling_fsw(pp.thal_params);
+            let ceil_fsw = tissues.ceiling_fsw(&pp.thal_params);
             let ceil_m = fsw_to_meters(ceil_fsw.max(0.0));
 
             if ceil_m <= next_stop {
@@ -621,13 +912,13 @@ fn plan_deco_stops_thalmann(
             let (fn2, fhe) = inspired_fractions(
                 gas.fo2,
                 gas.fhe,
-                pp.ppo2.map(|sp| sp.min(ambient_p)),
+                pp.loop_ppo2(current_stop, gas.fo2, ambient_p),
                 ambient_p,
             );
             let f_inert = fn2 + fhe;
             let p_inspired_fsw = (ambient_fsw - PACO2_FSW) * f_inert;
 
-            tissues.update(60.0, p_inspired_fsw, ambient_fsw, 0.0, 0.0, pp.thal_params);
+            tissues.update(60.0, p_inspired_fsw, ambient_fsw, 0.0, 0.0, &pp.thal_params);
             stop_time_sec += 60.0;
 
             if stop_time_sec > max_total_stop_time {
@@ -645,7 +936,7 @@ fn plan_deco_stops_thalmann(
             let (fn2, fhe_pad) = inspired_fractions(
                 gas.fo2,
                 gas.fhe,
-                pp.ppo2.map(|sp| sp.min(ambient_p)),
+                pp.loop_ppo2(current_stop, gas.fo2, ambient_p),
                 ambient_p,
             );
             let f_inert = fn2 + fhe_pad;
@@ -656,7 +947,7 @@ fn plan_deco_stops_thalmann(
                 ambient_fsw,
                 0.0,
                 0.0,
-                pp.thal_params,
+                &pp.thal_params,
             );
             stop_time_sec = 60.0;
         }
@@ -664,6 +955,7 @@ fn plan_deco_stops_thalmann(
             depth_m: current_stop as f32,
             duration_sec: stop_time_sec as i32,
             gas_mix_index: -1,
+            is_deep_stop: false,
         });
 
         if current_stop <= pp.last_stop_depth {
@@ -677,6 +969,35 @@ fn plan_deco_stops_thalmann(
     (stops, truncated)
 }
 
+/// Ascend to a depth and stay there, breathing the gas available at that depth.
+fn hold_at_depth_thalmann(
+    tissues: &mut ThalmannTissueState,
+    current_depth: &mut f64,
+    target_depth: f64,
+    duration_sec: f64,
+    pp: &ThalmannPlanParams,
+) {
+    ascend_with_gas_switches_thalmann(tissues, current_depth, target_depth, pp);
+    let gas = pp.gas_at_depth(target_depth);
+    let ambient_p = depth_to_pressure(target_depth, pp.surface_p);
+    let ambient_fsw = bar_to_fsw(ambient_p);
+    let (fn2, fhe) = inspired_fractions(
+        gas.fo2,
+        gas.fhe,
+        pp.loop_ppo2(target_depth, gas.fo2, ambient_p),
+        ambient_p,
+    );
+    let p_inspired_fsw = (ambient_fsw - PACO2_FSW) * (fn2 + fhe);
+    tissues.update(
+        duration_sec,
+        p_inspired_fsw,
+        ambient_fsw,
+        0.0,
+        0.0,
+        &pp.thal_params,
+    );
+}
+
 /// Ascend from current depth to target, segmenting at gas switch boundaries.
 /// This ensures correct tissue loading when passing through switch depths.
 fn ascend_with_gas_switches_thalmann(
@@ -685,11 +1006,13 @@ fn ascend_with_gas_switches_thalmann(
     target_depth: f64,
     pp: &ThalmannPlanParams,
 ) {
-    // Collect switch depths between current and target (descending order)
+    // Collect gas and setpoint switch depths between current and target
+    // (descending order)
     let mut waypoints: Vec<f64> = pp
         .gases
         .iter()
         .filter_map(|g| g.switch_depth_m)
+        .chain(pp.setpoint_switches.iter().map(|s| s.depth_m))
         .filter(|&d| d < *current_depth && d > target_depth)
         .collect();
     waypoints.sort_by(|a, b| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
@@ -722,7 +1045,7 @@ fn ascend_to_thalmann(
     let (fn2, fhe_frac) = inspired_fractions(
         gas.fo2,
         gas.fhe,
-        pp.ppo2.map(|sp| sp.min(ambient_p)),
+        pp.loop_ppo2(target_depth, gas.fo2, ambient_p),
         ambient_p,
     );
     let f_inert = fn2 + fhe_frac;
@@ -744,7 +1067,7 @@ fn ascend_to_thalmann(
         ambient_fsw,
         r_inspired,
         r_ambient,
-        pp.thal_params,
+        &pp.thal_params,
     );
     *current_depth = target_depth;
 }
@@ -795,6 +1118,16 @@ mod tests {
             gf_high: None,
             thalmann_pdcs: None,
             plan_ascent: false,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
         }
     }
 
@@ -998,14 +1331,14 @@ mod tests {
     #[test]
     fn test_empty_samples_error() {
         let params = default_params(vec![]);
-        let result = ThalmannEngine.simulate(&params);
+        let result = ThalmannEngine.simulate(&params, Progress::NONE);
         assert!(matches!(result, Err(DecoSimError::EmptySamples { .. })));
     }
 
     #[test]
     fn test_model_field() {
         let params = default_params(vec![sample(0, 0.0), sample(600, 10.0)]);
-        let result = ThalmannEngine.simulate(&params).unwrap();
+        let result = ThalmannEngine.simulate(&params, Progress::NONE).unwrap();
         assert_eq!(result.model, DecoModel::ThalmannElDca);
     }
 
@@ -1018,7 +1351,9 @@ mod tests {
             sample(600, 10.0),
             sample(660, 0.0),
         ];
-        let result = ThalmannEngine.simulate(&default_params(samples)).unwrap();
+        let result = ThalmannEngine
+            .simulate(&default_params(samples), Progress::NONE)
+            .unwrap();
         assert_eq!(result.points.len(), 4);
         for p in &result.points {
             assert!(
@@ -1042,7 +1377,7 @@ mod tests {
         let mut params = default_params(samples);
         params.plan_ascent = true;
 
-        let result = ThalmannEngine.simulate(&params).unwrap();
+        let result = ThalmannEngine.simulate(&params, Progress::NONE).unwrap();
 
         // Should have some ceiling at depth
         let max_ceil = result
@@ -1083,10 +1418,10 @@ mod tests {
         ];
 
         let result_short = ThalmannEngine
-            .simulate(&default_params(samples_short))
+            .simulate(&default_params(samples_short), Progress::NONE)
             .unwrap();
         let result_long = ThalmannEngine
-            .simulate(&default_params(samples_long))
+            .simulate(&default_params(samples_long), Progress::NONE)
             .unwrap();
 
         let tts_short = result_short.points.last().unwrap().tts_sec;
@@ -1106,10 +1441,10 @@ mod tests {
         let samples_deep = vec![sample(0, 0.0), sample(30, 30.0), sample(120, 30.0)];
 
         let result_shallow = ThalmannEngine
-            .simulate(&default_params(samples_shallow))
+            .simulate(&default_params(samples_shallow), Progress::NONE)
             .unwrap();
         let result_deep = ThalmannEngine
-            .simulate(&default_params(samples_deep))
+            .simulate(&default_params(samples_deep), Progress::NONE)
             .unwrap();
 
         let ndl_shallow = result_shallow.points.last().unwrap().ndl_sec;
@@ -1128,7 +1463,7 @@ mod tests {
         params.plan_ascent = true;
         params.stop_interval_m = Some(3.0);
 
-        let result = ThalmannEngine.simulate(&params).unwrap();
+        let result = ThalmannEngine.simulate(&params, Progress::NONE).unwrap();
 
         for stop in &result.deco_stops {
             let remainder = (stop.depth_m as f64) % 3.0;
@@ -1148,7 +1483,9 @@ mod tests {
     #[test]
     fn test_not_truncated_normal_profile() {
         let samples = vec![sample(0, 0.0), sample(60, 20.0), sample(600, 20.0)];
-        let result = ThalmannEngine.simulate(&default_params(samples)).unwrap();
+        let result = ThalmannEngine
+            .simulate(&default_params(samples), Progress::NONE)
+            .unwrap();
         assert!(!result.truncated, "Normal profile should not be truncated");
     }
 
@@ -1157,7 +1494,7 @@ mod tests {
         let mut params = default_params(vec![sample(0, 0.0), sample(60, 10.0)]);
         params.ascent_rate_m_min = Some(-1.0);
         assert!(matches!(
-            ThalmannEngine.simulate(&params),
+            ThalmannEngine.simulate(&params, Progress::NONE),
             Err(DecoSimError::InvalidParam { .. })
         ));
     }
@@ -1168,7 +1505,7 @@ mod tests {
         let mut params = default_params(vec![sample(0, 0.0), sample(600, 20.0)]);
         params.gf_low = Some(30);
         params.gf_high = Some(85);
-        let result = ThalmannEngine.simulate(&params);
+        let result = ThalmannEngine.simulate(&params, Progress::NONE);
         assert!(result.is_ok(), "GF params should be silently ignored");
     }
 
@@ -1182,7 +1519,7 @@ mod tests {
         let mut params = default_params(samples);
         params.plan_ascent = true;
 
-        let result = ThalmannEngine.simulate(&params).unwrap();
+        let result = ThalmannEngine.simulate(&params, Progress::NONE).unwrap();
 
         let deco_min = result.total_deco_time_sec as f64 / 60.0;
         assert!(
@@ -1213,8 +1550,10 @@ mod tests {
             he_fraction: 0.79,
         }];
 
-        let result_air = ThalmannEngine.simulate(&params_air).unwrap();
-        let result_he = ThalmannEngine.simulate(&params_he).unwrap();
+        let result_air = ThalmannEngine
+            .simulate(&params_air, Progress::NONE)
+            .unwrap();
+        let result_he = ThalmannEngine.simulate(&params_he, Progress::NONE).unwrap();
 
         // Same total inert fraction → same tissue loading → same results
         assert_eq!(result_air.max_tts_sec, result_he.max_tts_sec);
@@ -1242,8 +1581,12 @@ mod tests {
             he_fraction: 0.0,
         }];
 
-        let result_air = ThalmannEngine.simulate(&params_air).unwrap();
-        let result_ean32 = ThalmannEngine.simulate(&params_ean32).unwrap();
+        let result_air = ThalmannEngine
+            .simulate(&params_air, Progress::NONE)
+            .unwrap();
+        let result_ean32 = ThalmannEngine
+            .simulate(&params_ean32, Progress::NONE)
+            .unwrap();
 
         // EAN32 has less inert gas → lower tissue loading → less deco
         let ceil_air = result_air.points.last().unwrap().ceiling_m;
@@ -1260,7 +1603,9 @@ mod tests {
     fn test_tts_positive_for_deco_dive() {
         // Deep dive that requires deco — TTS must be > 1
         let samples = vec![sample(0, 0.0), sample(120, 60.0), sample(1200, 60.0)];
-        let result = ThalmannEngine.simulate(&default_params(samples)).unwrap();
+        let result = ThalmannEngine
+            .simulate(&default_params(samples), Progress::NONE)
+            .unwrap();
         let last = result.points.last().unwrap();
         assert!(
             last.tts_sec > 1,
@@ -1273,7 +1618,9 @@ mod tests {
     fn test_ndl_positive_for_shallow_dive() {
         // Shallow dive — NDL must be > 1 (and not negative)
         let samples = vec![sample(0, 0.0), sample(60, 15.0), sample(120, 15.0)];
-        let result = ThalmannEngine.simulate(&default_params(samples)).unwrap();
+        let result = ThalmannEngine
+            .simulate(&default_params(samples), Progress::NONE)
+            .unwrap();
         let last = result.points.last().unwrap();
         assert!(
             last.ndl_sec > 1,
@@ -1351,8 +1698,12 @@ mod tests {
         let mut alt_params = default_params(samples);
         alt_params.surface_pressure_bar = Some(0.80); // ~2000m altitude
 
-        let sea_result = ThalmannEngine.simulate(&sea_params).unwrap();
-        let alt_result = ThalmannEngine.simulate(&alt_params).unwrap();
+        let sea_result = ThalmannEngine
+            .simulate(&sea_params, Progress::NONE)
+            .unwrap();
+        let alt_result = ThalmannEngine
+            .simulate(&alt_params, Progress::NONE)
+            .unwrap();
 
         // At altitude, effective depth is greater → more loading → higher ceiling
         let sea_ceil = sea_result.points.last().unwrap().ceiling_m;
@@ -1426,11 +1777,13 @@ mod tests {
                 },
             ],
             ppo2: None,
+            setpoint_switches: vec![],
+            scr: None,
             surface_p: 1.013,
             ascent_rate_m_min: 9.0,
             last_stop_depth: 3.0,
             stop_interval: 3.0,
-            thal_params: &XVAL_HE_9_023,
+            thal_params: XVAL_HE_9_023,
         };
 
         // At 30m: deeper than all switch depths → bottom gas (air)
@@ -1459,6 +1812,62 @@ mod tests {
         assert!((gas.fo2 - 0.50).abs() < 1e-6);
     }
 
+    // ── Custom parameter sets ────────────────────────────────────────────
+
+    #[test]
+    fn test_custom_params_match_builtin_set() {
+        let samples = vec![sample(0, 0.0), sample(180, 60.0), sample(1980, 60.0)];
+        let mut builtin = default_params(samples.clone());
+        builtin.plan_ascent = true;
+        builtin.thalmann_pdcs = Some(ThalmannPdcs::Pdcs40);
+
+        let mut custom = default_params(samples);
+        custom.plan_ascent = true;
+        custom.thalmann_pdcs = Some(ThalmannPdcs::Custom);
+        custom.custom_thalmann_params = Some(ThalmannParameters {
+            half_times_min: XVAL_HE_9_040.half_times_min.to_vec(),
+            sdr: XVAL_HE_9_040.sdr.to_vec(),
+            m0_fsw: XVAL_HE_9_040.m0_fsw.to_vec(),
+            beta1: XVAL_HE_9_040.beta1.to_vec(),
+            pbovp_fsw: XVAL_HE_9_040.pbovp_fsw,
+        });
+
+        let expected = ThalmannEngine.simulate(&builtin, Progress::NONE).unwrap();
+        let result = ThalmannEngine.simulate(&custom, Progress::NONE).unwrap();
+        assert_eq!(result.total_deco_time_sec, expected.total_deco_time_sec);
+        assert_eq!(result.max_ceiling_m, expected.max_ceiling_m);
+
+        // Lower surfacing M-values are more conservative.
+        for m0 in custom
+            .custom_thalmann_params
+            .as_mut()
+            .unwrap()
+            .m0_fsw
+            .iter_mut()
+        {
+            *m0 *= 0.8;
+        }
+        let conservative = ThalmannEngine.simulate(&custom, Progress::NONE).unwrap();
+        assert!(conservative.total_deco_time_sec > expected.total_deco_time_sec);
+
+        // Invalid or ambiguous selections are rejected.
+        custom.thalmann_pdcs = Some(ThalmannPdcs::Pdcs23);
+        assert!(ThalmannEngine.simulate(&custom, Progress::NONE).is_err());
+        custom.thalmann_pdcs = None;
+        assert!(ThalmannEngine.simulate(&custom, Progress::NONE).is_err());
+        custom.thalmann_pdcs = Some(ThalmannPdcs::Custom);
+        custom
+            .custom_thalmann_params
+            .as_mut()
+            .unwrap()
+            .beta1
+            .push(1.0);
+        assert!(matches!(
+            ThalmannEngine.simulate(&custom, Progress::NONE),
+            Err(DecoSimError::InvalidParam { .. })
+        ));
+    }
+
     // ── Coverage: P_DCS variant selection (lines 50-51) ──────────────────
 
     #[test]
@@ -1485,9 +1894,9 @@ mod tests {
         params_50.plan_ascent = true;
         params_50.thalmann_pdcs = Some(ThalmannPdcs::Pdcs50);
 
-        let result_23 = ThalmannEngine.simulate(&params_23).unwrap();
-        let result_40 = ThalmannEngine.simulate(&params_40).unwrap();
-        let result_50 = ThalmannEngine.simulate(&params_50).unwrap();
+        let result_23 = ThalmannEngine.simulate(&params_23, Progress::NONE).unwrap();
+        let result_40 = ThalmannEngine.simulate(&params_40, Progress::NONE).unwrap();
+        let result_50 = ThalmannEngine.simulate(&params_50, Progress::NONE).unwrap();
 
         // All should produce deco stops
         assert!(
@@ -1551,7 +1960,7 @@ mod tests {
         params.gas_mixes = gas_mixes;
         params.plan_ascent = true;
 
-        let result = ThalmannEngine.simulate(&params).unwrap();
+        let result = ThalmannEngine.simulate(&params, Progress::NONE).unwrap();
 
         // Should produce deco stops (deep dive with trimix)
         assert!(
@@ -1578,7 +1987,9 @@ mod tests {
         }];
         params_single.plan_ascent = true;
 
-        let result_single = ThalmannEngine.simulate(&params_single).unwrap();
+        let result_single = ThalmannEngine
+            .simulate(&params_single, Progress::NONE)
+            .unwrap();
 
         // With deco gas (Nx50), off-gassing is faster → shorter deco
         assert!(
@@ -1589,6 +2000,102 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_thalmann_custom_deep_stops() {
+        // 50m for 25 min on air with a custom stop at half depth
+        let mut params = DecoSimParams {
+            model: DecoModel::ThalmannElDca,
+            samples: vec![sample(0, 0.0), sample(180, 50.0), sample(1500, 50.0)],
+            gas_mixes: vec![],
+            surface_pressure_bar: None,
+            ascent_rate_m_min: Some(9.0),
+            last_stop_depth_m: Some(3.0),
+            stop_interval_m: Some(3.0),
+            gf_low: None,
+            gf_high: None,
+            thalmann_pdcs: None,
+            plan_ascent: true,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
+        };
+        let baseline = ThalmannEngine.simulate(&params, Progress::NONE).unwrap();
+        let first_required = baseline.deco_stops[0].depth_m;
+        assert!(first_required < 24.0, "first stop {first_required} m");
+
+        params.deep_stops = Some(DeepStopParams {
+            strategy: DeepStopStrategy::Custom,
+            stop_duration_sec: Some(90),
+            custom_fractions: vec![0.5],
+        });
+        let result = ThalmannEngine.simulate(&params, Progress::NONE).unwrap();
+        let first = &result.deco_stops[0];
+        assert!(first.is_deep_stop);
+        assert!((first.depth_m - 24.0).abs() < 0.01);
+        assert_eq!(first.duration_sec, 90);
+        assert_eq!(result.deep_stop_time_sec, 90);
+        assert_eq!(
+            result.total_deco_time_sec - result.deep_stop_time_sec,
+            baseline.total_deco_time_sec + result.deep_stop_penalty_sec
+        );
+    }
+
+    #[test]
+    fn test_thalmann_ascent_setpoint_switches_shorten_deco() {
+        // CCR at 1.0 on air diluent, 40m for 30 min; bump to 1.6 from 21m
+        let samples: Vec<SampleInput> = [(0, 0.0), (150, 40.0), (1800, 40.0)]
+            .into_iter()
+            .map(|(t, d)| SampleInput {
+                ppo2: Some(1.0),
+                ..sample(t, d)
+            })
+            .collect();
+        let mut params = DecoSimParams {
+            model: DecoModel::ThalmannElDca,
+            samples,
+            gas_mixes: vec![],
+            surface_pressure_bar: None,
+            ascent_rate_m_min: None,
+            last_stop_depth_m: None,
+            stop_interval_m: None,
+            gf_low: None,
+            gf_high: None,
+            thalmann_pdcs: None,
+            plan_ascent: true,
+            ascent_gas_switches: vec![],
+            ascent_setpoint_switches: vec![],
+            scr: None,
+            deep_stops: None,
+            ascent_mode: None,
+            ceiling_margin_m: None,
+            buhlmann_coefficients: None,
+            custom_buhlmann_coefficients: None,
+            custom_thalmann_params: None,
+            tts_interval_sec: None,
+        };
+        let fixed = ThalmannEngine.simulate(&params, Progress::NONE).unwrap();
+        params.ascent_setpoint_switches = vec![SetpointSwitch {
+            depth_m: 21.0,
+            setpoint_ppo2: 1.6,
+        }];
+        let bumped = ThalmannEngine.simulate(&params, Progress::NONE).unwrap();
+        assert!(fixed.total_deco_time_sec > 0);
+        assert!(
+            bumped.total_deco_time_sec < fixed.total_deco_time_sec,
+            "bumped {} s vs fixed {} s",
+            bumped.total_deco_time_sec,
+            fixed.total_deco_time_sec
+        );
+        assert!(bumped.max_tts_sec < fixed.max_tts_sec);
+    }
+
     // ── Coverage: param validation failure via simulate (line 55) ─────────
 
     #[test]
@@ -6,6 +6,8 @@
 //!
 //! Reference: NEDU TR 18-05 (Doolette, Murphy, Gerth 2018).
 
+use super::types::{DecoSimError, ThalmannParameters, ThalmannPdcs};
+
 // ============================================================================
 // Unit conversion constants
 // ============================================================================
@@ -74,26 +76,27 @@ pub(crate) const P_FVG_FSW: f64 = PVO2_FSW + PVCO2_FSW + PH2O_FSW; // 4.3
 ///
 /// Each set defines compartment half-times, saturation/desaturation ratios,
 /// and MPTT (Maximum Permissible Tissue Tension) linear coefficients.
-pub(crate) struct ThalmannParamSet {
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ThalmannParamSet<'a> {
     /// Number of tissue compartments.
     pub num_compartments: usize,
     /// On-gassing half-times in minutes, per compartment.
-    pub half_times_min: &'static [f64],
+    pub half_times_min: &'a [f64],
     /// Saturation/Desaturation Ratio per compartment.
     /// SDR > 1 means faster washout; SDR < 1 means slower washout.
     /// Must be > 0 (SDR=0 causes division by zero).
-    pub sdr: &'static [f64],
+    pub sdr: &'a [f64],
     /// Surfacing M-value (M0) in fsw, per compartment.
-    pub m0_fsw: &'static [f64],
+    pub m0_fsw: &'a [f64],
     /// MPTT slope per compartment. M_i(D) = M0[i] + beta1[i] * D.
     /// Must be > 0 (beta1=0 causes division by zero in ceiling computation).
-    pub beta1: &'static [f64],
+    pub beta1: &'a [f64],
     /// Threshold inert gas overpressure for linear washout transition (fsw).
     /// 0.0 means crossover whenever tissue is supersaturated past venous gas deficit.
     pub pbovp_fsw: f64,
 }
 
-impl ThalmannParamSet {
+impl<'a> ThalmannParamSet<'a> {
     /// Validate that all parameter arrays are consistent and values are in
     /// valid ranges. Returns an error message if invalid.
     pub(crate) fn validate(&self) -> Result<(), String> {
@@ -120,9 +123,62 @@ impl ThalmannParamSet {
             if self.beta1[i] <= 0.0 {
                 return Err(format!("beta1[{i}] must be > 0"));
             }
+            if self.m0_fsw[i] <= 0.0 {
+                return Err(format!("m0_fsw[{i}] must be > 0"));
+            }
+        }
+        if self.pbovp_fsw < 0.0 {
+            return Err("pbovp_fsw must be >= 0".to_string());
         }
         Ok(())
     }
+
+    /// Borrow a user-supplied parameter set. Call `validate` before use.
+    pub(crate) fn from_parameters(params: &'a ThalmannParameters) -> Self {
+        Self {
+            num_compartments: params.half_times_min.len(),
+            half_times_min: &params.half_times_min,
+            sdr: &params.sdr,
+            m0_fsw: &params.m0_fsw,
+            beta1: &params.beta1,
+            pbovp_fsw: params.pbovp_fsw,
+        }
+    }
+
+    /// Resolve the selected parameter set (default Pdcs23).
+    ///
+    /// `custom` is required for `Custom` and rejected otherwise.
+    pub(crate) fn resolve(
+        pdcs: Option<ThalmannPdcs>,
+        custom: Option<&'a ThalmannParameters>,
+    ) -> Result<Self, DecoSimError> {
+        let invalid = |msg: String| DecoSimError::InvalidParam { msg };
+        let set = match (pdcs.unwrap_or(ThalmannPdcs::Pdcs23), custom) {
+            (ThalmannPdcs::Custom, Some(custom)) => Self::from_parameters(custom),
+            (ThalmannPdcs::Custom, None) => {
+                return Err(invalid(
+                    "Custom Thalmann parameters require custom_thalmann_params".to_string(),
+                ))
+            }
+            (_, Some(_)) => {
+                return Err(invalid(
+                    "custom_thalmann_params require the Custom parameter set".to_string(),
+                ))
+            }
+            (pdcs, None) => Self::builtin(pdcs),
+        };
+        set.validate().map_err(invalid)?;
+        Ok(set)
+    }
+
+    /// The built-in set for `pdcs`; `Custom` falls back to Pdcs23.
+    pub(crate) fn builtin(pdcs: ThalmannPdcs) -> Self {
+        match pdcs {
+            ThalmannPdcs::Pdcs23 | ThalmannPdcs::Custom => XVAL_HE_9_023,
+            ThalmannPdcs::Pdcs40 => XVAL_HE_9_040,
+            ThalmannPdcs::Pdcs50 => XVAL_HE_9_050,
+        }
+    }
 }
 
 // ============================================================================
@@ -133,7 +189,7 @@ impl ThalmannParamSet {
 ///
 /// This is the primary parameter set for He-O2 diving to 300 fsw with
 /// 1.3 atm PO2. Includes a repetitive-group reference compartment (#4).
-pub(crate) static XVAL_HE_9_023: ThalmannParamSet = ThalmannParamSet {
+pub(crate) static XVAL_HE_9_023: ThalmannParamSet<'static> = ThalmannParamSet {
     num_compartments: 5,
     half_times_min: &[10.0, 20.0, 20.0, 120.0, 210.0],
     sdr: &[1.0, 2.0, 0.67, 1.0, 1.0],
@@ -151,7 +207,7 @@ pub(crate) static XVAL_HE_9_023: ThalmannParamSet = ThalmannParamSet {
 /// Identical to XVal-He-9_023 except for compartment 5 (slowest):
 /// half-time 200 min (vs 210), M0 38.274 (vs 34.165), β1 1.188 (vs 1.0).
 /// Less conservative — shorter shallow stops, faster off-gassing.
-pub(crate) static XVAL_HE_9_040: ThalmannParamSet = ThalmannParamSet {
+pub(crate) static XVAL_HE_9_040: ThalmannParamSet<'static> = ThalmannParamSet {
     num_compartments: 5,
     half_times_min: &[10.0, 20.0, 20.0, 120.0, 200.0],
     sdr: &[1.0, 2.0, 0.67, 1.0, 1.0],
@@ -169,7 +225,7 @@ pub(crate) static XVAL_HE_9_040: ThalmannParamSet = ThalmannParamSet {
 /// Identical to XVal-He-9_023 except for compartment 5 (slowest):
 /// half-time 190 min (vs 210), M0 40.437 (vs 34.165), β1 1.310 (vs 1.0).
 /// Least conservative of the three — significantly shorter shallow stops.
-pub(crate) static XVAL_HE_9_050: ThalmannParamSet = ThalmannParamSet {
+pub(crate) static XVAL_HE_9_050: ThalmannParamSet<'static> = ThalmannParamSet {
     num_compartments: 5,
     half_times_min: &[10.0, 20.0, 20.0, 120.0, 190.0],
     sdr: &[1.0, 2.0, 0.67, 1.0, 1.0],
@@ -266,4 +322,59 @@ mod tests {
             "10m should be {expected} fsw, got {fsw}"
         );
     }
+
+    fn parameters_from(set: &ThalmannParamSet) -> ThalmannParameters {
+        ThalmannParameters {
+            half_times_min: set.half_times_min.to_vec(),
+            sdr: set.sdr.to_vec(),
+            m0_fsw: set.m0_fsw.to_vec(),
+            beta1: set.beta1.to_vec(),
+            pbovp_fsw: set.pbovp_fsw,
+        }
+    }
+
+    #[test]
+    fn test_custom_parameters_validated() {
+        let custom = parameters_from(&XVAL_HE_9_040);
+        let set = ThalmannParamSet::resolve(Some(ThalmannPdcs::Custom), Some(&custom)).unwrap();
+        assert_eq!(set.num_compartments, 5);
+        assert_eq!(set.m0_fsw, XVAL_HE_9_040.m0_fsw);
+
+        let mut short = parameters_from(&XVAL_HE_9_023);
+        short.sdr.pop();
+        assert!(ThalmannParamSet::resolve(Some(ThalmannPdcs::Custom), Some(&short)).is_err());
+
+        let mut empty = parameters_from(&XVAL_HE_9_023);
+        empty.half_times_min.clear();
+        assert!(ThalmannParamSet::resolve(Some(ThalmannPdcs::Custom), Some(&empty)).is_err());
+
+        let mut bad_m0 = parameters_from(&XVAL_HE_9_023);
+        bad_m0.m0_fsw[2] = 0.0;
+        assert!(ThalmannParamSet::resolve(Some(ThalmannPdcs::Custom), Some(&bad_m0)).is_err());
+
+        let mut bad_pbovp = parameters_from(&XVAL_HE_9_023);
+        bad_pbovp.pbovp_fsw = -1.0;
+        assert!(ThalmannParamSet::resolve(Some(ThalmannPdcs::Custom), Some(&bad_pbovp)).is_err());
+    }
+
+    #[test]
+    fn test_resolve_builtin_and_custom() {
+        let default = ThalmannParamSet::resolve(None, None).unwrap();
+        assert_eq!(default.half_times_min, XVAL_HE_9_023.half_times_min);
+        let pdcs50 = ThalmannParamSet::resolve(Some(ThalmannPdcs::Pdcs50), None).unwrap();
+        assert_eq!(pdcs50.m0_fsw, XVAL_HE_9_050.m0_fsw);
+
+        let custom = parameters_from(&XVAL_HE_9_023);
+        let resolved =
+            ThalmannParamSet::resolve(Some(ThalmannPdcs::Custom), Some(&custom)).unwrap();
+        assert_eq!(resolved.m0_fsw, XVAL_HE_9_023.m0_fsw);
+
+        // The payload and the Custom set go together.
+        let result = ThalmannParamSet::resolve(Some(ThalmannPdcs::Pdcs23), Some(&custom));
+        assert!(matches!(result, Err(DecoSimError::InvalidParam { .. })));
+        let result = ThalmannParamSet::resolve(None, Some(&custom));
+        assert!(matches!(result, Err(DecoSimError::InvalidParam { .. })));
+        let result = ThalmannParamSet::resolve(Some(ThalmannPdcs::Custom), None);
+        assert!(matches!(result, Err(DecoSimError::InvalidParam { .. })));
+    }
 }
@@ -9,16 +9,17 @@
 /// Decompression model to use for the simulation.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DecoModel {
-    /// Bühlmann ZHL-16C with gradient factors (Baker method).
+    /// Bühlmann ZHL-16 with gradient factors (Baker method). Uses ZHL-16C
+    /// unless `DecoSimParams.buhlmann_coefficients` selects another set.
     BuhlmannZhl16c,
     /// Thalmann EL-DCA with asymmetric E-L gas kinetics and MPTT ceilings.
     ThalmannElDca,
 }
 
-/// Target probability of DCS for the Thalmann algorithm.
+/// Thalmann parameter set, chosen by target probability of DCS.
 ///
-/// Selects the XVal-He-9 parameter set from NEDU TR 18-05.
-/// Lower percentages are more conservative (longer deco).
+/// Selects the XVal-He-9 parameter set from NEDU TR 18-05, or a
+/// user-supplied set. Lower percentages are more conservative (longer deco).
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ThalmannPdcs {
     /// 2.3% target P_DCS — most conservative (NEDU TR 18-05, Table 10).
@@ -27,6 +28,76 @@ pub enum ThalmannPdcs {
     Pdcs40,
     /// 5.0% target P_DCS — least conservative (NEDU TR 18-05, Table 17).
     Pdcs50,
+    /// User-supplied parameter set (`ThalmannParameters`).
+    Custom,
+}
+
+/// A user-supplied Thalmann EL-DCA parameter set (fsw units, as in
+/// NEDU TR 18-05).
+///
+/// All per-compartment arrays must have the same length; half-times, SDRs,
+/// M0 values and MPTT slopes must be > 0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThalmannParameters {
+    /// On-gassing half-times in minutes.
+    pub half_times_min: Vec<f64>,
+    /// Saturation/desaturation ratio (washout half-time multiplier).
+    pub sdr: Vec<f64>,
+    /// Surfacing M-values (M0) in fsw.
+    pub m0_fsw: Vec<f64>,
+    /// MPTT slopes: M(D) = M0 + beta1 × D.
+    pub beta1: Vec<f64>,
+    /// Inert gas overpressure at which washout turns linear, in fsw.
+    pub pbovp_fsw: f64,
+}
+
+/// A planned ascent switch depth for one gas mix.
+#[derive(Debug, Clone)]
+pub struct GasSwitchDepth {
+    /// Gas mix index (matches `GasMixInput.mix_index`).
+    pub mix_index: i32,
+    /// Switch to this gas at or above this depth during ascent (metres).
+    pub depth_m: f64,
+}
+
+/// A planned CCR setpoint change at a depth.
+#[derive(Debug, Clone)]
+pub struct SetpointSwitch {
+    /// Depth at which the setpoint changes (metres).
+    pub depth_m: f64,
+    /// New setpoint PPO2 in bar.
+    pub setpoint_ppo2: f64,
+}
+
+/// Deep stop strategy for ascent planning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeepStopStrategy {
+    /// Pyle stops: halfway between the bottom and the first required stop,
+    /// repeated halfway to the first required stop while the gap exceeds two
+    /// stop intervals.
+    Pyle,
+    /// Stops at user-defined fractions of the starting depth.
+    Custom,
+}
+
+/// Optional deep stops inserted before the required stops.
+#[derive(Debug, Clone)]
+pub struct DeepStopParams {
+    /// How deep stop depths are chosen.
+    pub strategy: DeepStopStrategy,
+    /// Duration of each deep stop in seconds (default 120).
+    pub stop_duration_sec: Option<i32>,
+    /// Fractions (0–1) of the starting depth for `Custom` stops.
+    pub custom_fractions: Vec<f64>,
+}
+
+/// How the planned ascent approaches the surface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AscentMode {
+    /// Discrete stops on the `stop_interval_m` grid.
+    Staged,
+    /// Continuous ascent following the GF-adjusted ceiling (Bühlmann only).
+    Continuous,
 }
 
 /// Parameters for a deco simulation run.
@@ -50,10 +121,37 @@ pub struct DecoSimParams {
     pub gf_low: Option<u8>,
     /// Gradient factor high (0–100, Bühlmann only, default 100).
     pub gf_high: Option<u8>,
-    /// Thalmann P_DCS target (Thalmann only, default Pdcs23).
+    /// Thalmann parameter set (Thalmann only, default Pdcs23).
     pub thalmann_pdcs: Option<ThalmannPdcs>,
     /// If true, compute a deco schedule from the last sample to the surface.
     pub plan_ascent: bool,
+    /// Ascent gas switches for planning and TTS. Empty = every gas other than
+    /// the current one is used from its MOD at PPO2 1.6.
+    pub ascent_gas_switches: Vec<GasSwitchDepth>,
+    /// CCR setpoint switches for planning and TTS, applied at or above their
+    /// depth during ascent. Ignored for open circuit.
+    pub ascent_setpoint_switches: Vec<SetpointSwitch>,
+    /// Semi-closed rebreather loop. When set, every interval and the planned
+    /// ascent breathe the SCR loop on the current gas, and sample PPO2 is
+    /// ignored.
+    pub scr: Option<super::scr::ScrParams>,
+    /// Deep stops added to the planned ascent (`plan_ascent` only).
+    pub deep_stops: Option<DeepStopParams>,
+    /// Planned ascent mode (default `Staged`).
+    pub ascent_mode: Option<AscentMode>,
+    /// Distance kept below the ceiling during a continuous ascent in metres
+    /// (default 0.0).
+    pub ceiling_margin_m: Option<f64>,
+    /// ZHL-16 coefficient set (Bühlmann only, default Zhl16c).
+    pub buhlmann_coefficients: Option<crate::buhlmann::BuhlmannCoefficientSet>,
+    /// Coefficients for the `Custom` set (required with it, rejected otherwise).
+    pub custom_buhlmann_coefficients: Option<crate::buhlmann::BuhlmannCoefficients>,
+    /// Parameters for the `Custom` Thalmann set (required with it, rejected
+    /// otherwise).
+    pub custom_thalmann_params: Option<ThalmannParameters>,
+    /// Seconds between full TTS/NDL searches; samples in between are
+    /// interpolated (default 10; 0 = search every sample).
+    pub tts_interval_sec: Option<i32>,
 }
 
 // ============================================================================
@@ -90,6 +188,17 @@ pub struct DecoStop {
     pub duration_sec: i32,
     /// Gas mix index used at this stop (-1 if default/unchanged).
     pub gas_mix_index: i32,
+    /// True for stops added by a deep stop strategy.
+    pub is_deep_stop: bool,
+}
+
+/// A point on a continuous ascent, relative to the start of the ascent.
+#[derive(Debug, Clone)]
+pub struct AscentWaypoint {
+    /// Seconds since the ascent started.
+    pub t_offset_sec: i32,
+    /// Depth in metres.
+    pub depth_m: f32,
 }
 
 /// Complete result of a deco simulation.
@@ -112,6 +221,18 @@ pub struct DecoSimResult {
     /// True if the planner hit a safety limit (e.g., max stop time exceeded)
     /// and the deco schedule may be incomplete.
     pub truncated: bool,
+    /// Total time at deep stops in seconds (0 without deep stops).
+    pub deep_stop_time_sec: i32,
+    /// Change in required stop time caused by the deep stops in seconds.
+    pub deep_stop_penalty_sec: i32,
+    /// Continuous ascent path (empty for a staged ascent or no obligation).
+    /// `deco_stops` then holds the time spent below the ascent rate, binned
+    /// onto the stop grid.
+    pub ascent_path: Vec<AscentWaypoint>,
+    /// Time at the surface on air after the last sample until every
+    /// compartment is within 1% of surface equilibrium, in seconds (capped
+    /// at 72 h).
+    pub desaturation_time_sec: i32,
 }
 
 /// Errors that can occur during deco simulation.
@@ -123,6 +244,8 @@ pub enum DecoSimError {
     UnsupportedModel { msg: String },
     /// Invalid parameter (e.g., gf_low > gf_high).
     InvalidParam { msg: String },
+    /// The computation was cancelled through its `CancellationToken`.
+    Cancelled { msg: String },
 }
 
 impl std::fmt::Display for DecoSimError {
@@ -131,6 +254,7 @@ impl std::fmt::Display for DecoSimError {
             DecoSimError::EmptySamples { msg } => write!(f, "EmptySamples: {msg}"),
             DecoSimError::UnsupportedModel { msg } => write!(f, "UnsupportedModel: {msg}"),
             DecoSimError::InvalidParam { msg } => write!(f, "InvalidParam: {msg}"),
+            DecoSimError::Cancelled { msg } => write!(f, "Cancelled: {msg}"),
         }
     }
 }
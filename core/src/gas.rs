@@ -0,0 +1,468 @@
+//! Gas planning math: operating depths, END, density and best-mix selection.
+//!
+//! Depths are converted with the same hydrostatic model as the deco engines
+//! (`depth_to_pressure` / `pressure_to_depth`). Densities use the gas
+//! densities at 0 °C and 1 bar, as in the usual diving density guidance.
+
+use crate::buhlmann::GasMixInput;
+use crate::deco::shared::*;
+use crate::deco::DecoSimError;
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// O2 density at 0 °C and 1 bar (g/L).
+const DENSITY_O2_G_L: f64 = 1.429;
+/// N2 density at 0 °C and 1 bar (g/L).
+const DENSITY_N2_G_L: f64 = 1.251;
+/// He density at 0 °C and 1 bar (g/L).
+const DENSITY_HE_G_L: f64 = 0.1786;
+
+/// Default minimum inspired PPO2 for hypoxic mixes (bar).
+pub(crate) const DEFAULT_MIN_PPO2: f64 = 0.16;
+
+/// Default maximum PPO2 for open-circuit gases (bar).
+pub(crate) const DEFAULT_MAX_PPO2: f64 = 1.6;
+
+/// A gas may be breathed this far beyond its operating depth limits, so e.g.
+/// O2 at 1.6 bar (MOD 5.8 m) is usable at a 6 m stop.
+pub(crate) const OPERATING_DEPTH_TOLERANCE_M: f64 = 0.25;
+
+/// Tolerance for fraction sums (rounding in user input).
+const FRACTION_EPSILON: f64 = 1e-6;
+
+// ============================================================================
+// FFI Types
+// ============================================================================
+
+/// Parameters for best-mix selection.
+#[derive(Debug, Clone)]
+pub struct BestMixParams {
+    /// Deepest planned depth in metres.
+    pub target_depth_m: f64,
+    /// Maximum PPO2 at the target depth (bar).
+    pub max_ppo2: f64,
+    /// Maximum equivalent narcotic depth in metres. `None` = no END limit.
+    pub max_end_m: Option<f64>,
+    /// Count O2 as narcotic when computing END.
+    pub o2_narcotic: bool,
+    /// Maximum gas density at the target depth (g/L). `None` = no limit.
+    pub max_density_g_l: Option<f64>,
+    /// Minimum PPO2 used for the minimum operating depth (default 0.16 bar).
+    pub min_ppo2: Option<f64>,
+    /// Ambient surface pressure in bar (default 1.01325).
+    pub surface_pressure_bar: Option<f64>,
+}
+
+/// Best mixes for a target depth and their properties.
+#[derive(Debug, Clone)]
+pub struct BestMixResult {
+    /// Richest nitrox meeting all limits, or `None` if helium is required.
+    pub best_nitrox: Option<GasMixInput>,
+    /// Richest O2 / least He mix meeting all limits (nitrox when no He is needed).
+    pub best_mix: GasMixInput,
+    /// PPO2 of `best_mix` at the target depth (bar).
+    pub ppo2: f64,
+    /// END of `best_mix` at the target depth (metres).
+    pub end_m: f64,
+    /// Density of `best_mix` at the target depth (g/L).
+    pub density_g_l: f64,
+    /// Maximum operating depth of `best_mix` at `max_ppo2` (metres).
+    pub max_operating_depth_m: f64,
+    /// Minimum operating depth of `best_mix` (metres, 0 if breathable at the surface).
+    pub min_operating_depth_m: f64,
+}
+
+// ============================================================================
+// Validation
+// ============================================================================
+
+/// Validate a gas mix: fractions in range, O2 > 0 and O2 + He ≤ 1.
+pub fn validate_gas_mix(gas: &GasMixInput) -> Result<(), DecoSimError> {
+    let fo2 = gas.o2_fraction;
+    let fhe = gas.he_fraction;
+    if !fo2.is_finite() || !fhe.is_finite() {
+        return Err(DecoSimError::InvalidParam {
+            msg: format!("gas {} fractions must be finite", gas.mix_index),
+        });
+    }
+    if fo2 <= 0.0 || fo2 > 1.0 + FRACTION_EPSILON {
+        return Err(DecoSimError::InvalidParam {
+            msg: format!(
+                "gas {} o2_fraction ({fo2}) must be > 0 and <= 1",
+                gas.mix_index
+            ),
+        });
+    }
+    if !(0.0..=1.0 + FRACTION_EPSILON).contains(&fhe) {
+        return Err(DecoSimError::InvalidParam {
+            msg: format!(
+                "gas {} he_fraction ({fhe}) must be between 0 and 1",
+                gas.mix_index
+            ),
+        });
+    }
+    if fo2 + fhe > 1.0 + FRACTION_EPSILON {
+        return Err(DecoSimError::InvalidParam {
+            msg: format!(
+                "gas {} o2_fraction + he_fraction ({}) must be <= 1",
+                gas.mix_index,
+                fo2 + fhe
+            ),
+        });
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Gas Limits
+// ============================================================================
+
+fn n2_fraction(gas: &GasMixInput) -> f64 {
+    (1.0 - gas.o2_fraction - gas.he_fraction).max(0.0)
+}
+
+/// Maximum operating depth (metres) for an O2 fraction at a PPO2 limit.
+pub fn max_operating_depth(o2_fraction: f64, max_ppo2: f64, surface_pressure: f64) -> f64 {
+    if o2_fraction <= 0.0 {
+        return f64::INFINITY;
+    }
+    pressure_to_depth(max_ppo2 / o2_fraction, surface_pressure).max(0.0)
+}
+
+/// Minimum operating depth (metres) for an O2 fraction at a PPO2 floor.
+///
+/// Returns 0 when the mix is breathable at the surface.
+pub fn min_operating_depth(o2_fraction: f64, min_ppo2: f64, surface_pressure: f64) -> f64 {
+    if o2_fraction <= 0.0 {
+        return f64::INFINITY;
+    }
+    pressure_to_depth(min_ppo2 / o2_fraction, surface_pressure).max(0.0)
+}
+
+/// Narcotic fraction of a mix relative to air.
+///
+/// With O2 counted narcotic, air's narcotic fraction is 1 (N2 + O2);
+/// otherwise only N2 counts and is compared against air's N2 fraction.
+fn narcotic_ratio(gas: &GasMixInput, o2_narcotic: bool) -> f64 {
+    if o2_narcotic {
+        n2_fraction(gas) + gas.o2_fraction
+    } else {
+        n2_fraction(gas) / AIR_FN2
+    }
+}
+
+/// Equivalent narcotic depth (metres) of a mix at `depth_m`.
+pub fn equivalent_narcotic_depth(
+    gas: &GasMixInput,
+    depth_m: f64,
+    o2_narcotic: bool,
+    surface_pressure: f64,
+) -> f64 {
+    let ambient_p = depth_to_pressure(depth_m, surface_pressure);
+    let equivalent_p = ambient_p * narcotic_ratio(gas, o2_narcotic);
+    pressure_to_depth(equivalent_p, surface_pressure).max(0.0)
+}
+
+/// Deepest depth (metres) at which a mix stays within an END limit.
+pub fn max_end_depth(
+    gas: &GasMixInput,
+    max_end_m: f64,
+    o2_narcotic: bool,
+    surface_pressure: f64,
+) -> f64 {
+    let ratio = narcotic_ratio(gas, o2_narcotic);
+    if ratio <= 0.0 {
+        return f64::INFINITY;
+    }
+    pressure_to_depth(
+        depth_to_pressure(max_end_m, surface_pressure) / ratio,
+        surface_pressure,
+    )
+    .max(0.0)
+}
+
+/// Density of a mix at 1 bar (g/L).
+fn surface_density(gas: &GasMixInput) -> f64 {
+    gas.o2_fraction * DENSITY_O2_G_L
+        + n2_fraction(gas) * DENSITY_N2_G_L
+        + gas.he_fraction * DENSITY_HE_G_L
+}
+
+/// Gas density (g/L) of a mix at `depth_m`.
+pub fn gas_density(gas: &GasMixInput, depth_m: f64, surface_pressure: f64) -> f64 {
+    surface_density(gas) * depth_to_pressure(depth_m, surface_pressure)
+}
+
+/// Deepest depth (metres) at which a mix stays within a density limit.
+pub fn max_density_depth(gas: &GasMixInput, max_density_g_l: f64, surface_pressure: f64) -> f64 {
+    pressure_to_depth(max_density_g_l / surface_density(gas), surface_pressure).max(0.0)
+}
+
+// ============================================================================
+// Best Mix
+// ============================================================================
+
+/// Best mix for a target depth under PPO2, END and density limits.
+///
+/// O2 is maximised for the PPO2 limit and rounded down to a whole percent;
+/// helium is the minimum that satisfies the END and density limits, rounded
+/// up to a whole percent. Errors if no mix can satisfy all limits.
+pub fn best_mix(params: &BestMixParams) -> Result<BestMixResult, DecoSimError> {
+    let surface_p = params
+        .surface_pressure_bar
+        .unwrap_or(DEFAULT_SURFACE_PRESSURE);
+    if params.target_depth_m < 0.0 {
+        return Err(DecoSimError::InvalidParam {
+            msg: format!("target_depth_m ({}) must be >= 0", params.target_depth_m),
+        });
+    }
+    if params.max_ppo2 <= 0.0 {
+        return Err(DecoSimError::InvalidParam {
+            msg: format!("max_ppo2 ({}) must be > 0", params.max_ppo2),
+        });
+    }
+    if let Some(d) = params.max_density_g_l {
+        if d <= 0.0 {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!("max_density_g_l ({d}) must be > 0"),
+            });
+        }
+    }
+    if let Some(end) = params.max_end_m {
+        if end < 0.0 {
+            return Err(DecoSimError::InvalidParam {
+                msg: format!("max_end_m ({end}) must be >= 0"),
+            });
+        }
+    }
+    let min_ppo2 = params.min_ppo2.unwrap_or(DEFAULT_MIN_PPO2);
+
+    let ambient_p = depth_to_pressure(params.target_depth_m, surface_p);
+    let fo2 = (floor_percent(params.max_ppo2 / ambient_p)).min(1.0);
+    if fo2 <= 0.0 {
+        return Err(DecoSimError::InvalidParam {
+            msg: format!(
+                "max_ppo2 ({}) is too low for {} m",
+                params.max_ppo2, params.target_depth_m
+            ),
+        });
+    }
+
+    // Minimum helium for the END limit
+    let mut fhe: f64 = 0.0;
+    if let Some(max_end) = params.max_end_m {
+        let allowed_ratio = depth_to_pressure(max_end, surface_p) / ambient_p;
+        let required = if params.o2_narcotic {
+            1.0 - allowed_ratio
+        } else {
+            1.0 - fo2 - allowed_ratio * AIR_FN2
+        };
+        fhe = fhe.max(required);
+    }
+    // Minimum helium for the density limit: ρ(fhe) is linear in fhe
+    if let Some(max_density) = params.max_density_g_l {
+        let rho_no_he = fo2 * DENSITY_O2_G_L + (1.0 - fo2) * DENSITY_N2_G_L;
+        let required = (rho_no_he - max_density / ambient_p) / (DENSITY_N2_G_L - DENSITY_HE_G_L);
+        fhe = fhe.max(required);
+    }
+    let fhe = ceil_percent(fhe.max(0.0));
+    if fo2 + fhe > 1.0 + FRACTION_EPSILON {
+        return Err(DecoSimError::InvalidParam {
+            msg: format!(
+                "no mix satisfies the limits at {} m (needs {:.0}% O2 and {:.0}% He)",
+                params.target_depth_m,
+                fo2 * 100.0,
+                fhe * 100.0
+            ),
+        });
+    }
+
+    let mix = GasMixInput {
+        mix_index: 0,
+        o2_fraction: fo2,
+        he_fraction: fhe,
+    };
+    let best_nitrox = (fhe == 0.0).then(|| mix.clone());
+    Ok(BestMixResult {
+        best_nitrox,
+        ppo2: fo2 * ambient_p,
+        end_m: equivalent_narcotic_depth(
+            &mix,
+            params.target_depth_m,
+            params.o2_narcotic,
+            surface_p,
+        ),
+        density_g_l: gas_density(&mix, params.target_depth_m, surface_p),
+        max_operating_depth_m: max_operating_depth(fo2, params.max_ppo2, surface_p),
+        min_operating_depth_m: min_operating_depth(fo2, min_ppo2, surface_p),
+        best_mix: mix,
+    })
+}
+
+fn floor_percent(f: f64) -> f64 {
+    ((f * 100.0) + FRACTION_EPSILON).floor() / 100.0
+}
+
+fn ceil_percent(f: f64) -> f64 {
+    ((f * 100.0) - FRACTION_EPSILON).ceil() / 100.0
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SURFACE: f64 = DEFAULT_SURFACE_PRESSURE;
+
+    fn gas(o2: f64, he: f64) -> GasMixInput {
+        GasMixInput {
+            mix_index: 0,
+            o2_fraction: o2,
+            he_fraction: he,
+        }
+    }
+
+    fn params(depth: f64) -> BestMixParams {
+        BestMixParams {
+            target_depth_m: depth,
+            max_ppo2: 1.4,
+            max_end_m: Some(30.0),
+            o2_narcotic: true,
+            max_density_g_l: Some(6.2),
+            min_ppo2: None,
+            surface_pressure_bar: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_gas_mix() {
+        assert!(validate_gas_mix(&gas(0.21, 0.0)).is_ok());
+        assert!(validate_gas_mix(&gas(0.10, 0.70)).is_ok());
+        assert!(validate_gas_mix(&gas(1.0, 0.0)).is_ok());
+        assert!(validate_gas_mix(&gas(0.0, 0.5)).is_err());
+        assert!(validate_gas_mix(&gas(0.21, -0.1)).is_err());
+        assert!(validate_gas_mix(&gas(0.50, 0.60)).is_err());
+        assert!(validate_gas_mix(&gas(1.2, 0.0)).is_err());
+        assert!(validate_gas_mix(&gas(f64::NAN, 0.0)).is_err());
+    }
+
+    #[test]
+    fn test_mod() {
+        // EAN32 at 1.4 bar
+        let expected = pressure_to_depth(1.4 / 0.32, SURFACE);
+        assert!((max_operating_depth(0.32, 1.4, SURFACE) - expected).abs() < 1e-9);
+        // Pure O2 at 1.6 bar ≈ 5.8 m
+        let o2 = max_operating_depth(1.0, 1.6, SURFACE);
+        assert!(o2 > 5.7 && o2 < 5.9);
+    }
+
+    #[test]
+    fn test_min_operating_depth() {
+        assert_eq!(min_operating_depth(0.21, 0.16, SURFACE), 0.0);
+        // 10/70 needs 1.6 bar ambient for 0.16 ppO2
+        let expected = pressure_to_depth(1.6, SURFACE);
+        assert!((min_operating_depth(0.10, 0.16, SURFACE) - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_end() {
+        // Air with O2 narcotic: END equals depth
+        let air = gas(0.21, 0.0);
+        assert!((equivalent_narcotic_depth(&air, 40.0, true, SURFACE) - 40.0).abs() < 1e-9);
+        // Trimix 21/35 at 60 m: narcotic fraction 0.65
+        let tx = gas(0.21, 0.35);
+        let expected = pressure_to_depth(depth_to_pressure(60.0, SURFACE) * 0.65, SURFACE);
+        assert!((equivalent_narcotic_depth(&tx, 60.0, true, SURFACE) - expected).abs() < 1e-9);
+        // Without O2 narcotic, EAN32 is less narcotic than air
+        let ean32 = gas(0.32, 0.0);
+        assert!(equivalent_narcotic_depth(&ean32, 30.0, false, SURFACE) < 30.0);
+        // max_end_depth inverts END
+        let d = max_end_depth(&tx, 30.0, true, SURFACE);
+        assert!((equivalent_narcotic_depth(&tx, d, true, SURFACE) - 30.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_density() {
+        let air = gas(0.21, 0.0);
+        let rho = gas_density(&air, 0.0, 1.0);
+        assert!((rho - (0.21 * 1.429 + 0.79 * 1.251)).abs() < 1e-9);
+        // Air reaches 6.2 g/L near 38 m
+        let d = max_density_depth(&air, 6.2, SURFACE);
+        assert!(d > 37.0 && d < 39.0);
+        assert!((gas_density(&air, d, SURFACE) - 6.2).abs() < 1e-9);
+        // Helium lowers density
+        assert!(gas_density(&gas(0.21, 0.35), 40.0, SURFACE) < gas_density(&air, 40.0, SURFACE));
+    }
+
+    #[test]
+    fn test_best_nitrox_shallow() {
+        let result = best_mix(&params(25.0)).unwrap();
+        // 1.4 / 3.55 bar → 39%
+        assert!((result.best_mix.o2_fraction - 0.39).abs() < 1e-9);
+        assert_eq!(result.best_mix.he_fraction, 0.0);
+        assert!(result.best_nitrox.is_some());
+        assert!(result.ppo2 <= 1.4);
+        assert_eq!(result.min_operating_depth_m, 0.0);
+        assert!(result.max_operating_depth_m >= 25.0);
+    }
+
+    #[test]
+    fn test_best_trimix_deep() {
+        let result = best_mix(&params(60.0)).unwrap();
+        let mix = &result.best_mix;
+        assert!(result.best_nitrox.is_none());
+        assert!(mix.he_fraction > 0.0);
+        assert!(result.ppo2 <= 1.4 + 1e-9);
+        assert!(result.end_m <= 30.0 + 1e-9);
+        assert!(result.density_g_l <= 6.2 + 1e-9);
+        // Whole-percent mix
+        assert!(((mix.o2_fraction * 100.0) - (mix.o2_fraction * 100.0).round()).abs() < 1e-9);
+        assert!(((mix.he_fraction * 100.0) - (mix.he_fraction * 100.0).round()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_best_mix_o2_not_narcotic_needs_less_helium() {
+        let narcotic = best_mix(&params(50.0)).unwrap();
+        let mut p = params(50.0);
+        p.o2_narcotic = false;
+        let not_narcotic = best_mix(&p).unwrap();
+        assert!(not_narcotic.best_mix.he_fraction < narcotic.best_mix.he_fraction);
+    }
+
+    #[test]
+    fn test_best_mix_hypoxic_min_operating_depth() {
+        let mut p = params(100.0);
+        p.max_end_m = Some(40.0);
+        let result = best_mix(&p).unwrap();
+        assert!(result.best_mix.o2_fraction < 0.16);
+        assert!(result.min_operating_depth_m > 0.0);
+    }
+
+    #[test]
+    fn test_best_mix_density_only() {
+        let mut p = params(45.0);
+        p.max_end_m = None;
+        let result = best_mix(&p).unwrap();
+        assert!(
+            result.best_mix.he_fraction > 0.0,
+            "air-like mixes exceed 6.2 g/L at 45 m"
+        );
+        assert!(result.density_g_l <= 6.2 + 1e-9);
+    }
+
+    #[test]
+    fn test_best_mix_invalid() {
+        let mut p = params(40.0);
+        p.max_ppo2 = 0.0;
+        assert!(best_mix(&p).is_err());
+
+        let mut p = params(300.0);
+        p.max_end_m = Some(0.0);
+        assert!(best_mix(&p).is_err());
+    }
+}
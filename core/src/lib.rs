@@ -9,24 +9,41 @@
 // Allow clippy lint that triggers on generated UniFFI code
 #![allow(clippy::empty_line_after_doc_comments)]
 
+pub mod blending;
 pub mod buhlmann;
 pub mod deco;
 pub mod error;
 pub mod formula;
+pub mod gas;
 pub mod metrics;
 
 use std::collections::HashMap;
+use std::sync::Arc;
 
 uniffi::include_scaffolding!("divelog_compute");
 
 // Re-export public types for Rust consumers
-pub use buhlmann::{GasMixInput, SurfaceGfPoint};
+pub use blending::{BlendMethod, BlendParams, BlendResult, BlendStep, BlendStepKind};
+pub use buhlmann::{BuhlmannCoefficientSet, BuhlmannCoefficients, GasMixInput, SurfaceGfPoint};
 pub use deco::{
-    DecoModel, DecoSimError, DecoSimParams, DecoSimPoint, DecoSimResult, DecoStop, GasSwitchPlan,
-    ProfileGenParams, ProfileGenResult, ThalmannPdcs,
+    AscentMode, AscentWaypoint, BottomTimeConstraint, CancellationToken, CeilingViolation,
+    ComputeControl, CylinderInput, CylinderPlan, DecoGasCandidate, DecoGasOptimizerParams,
+    DecoGasOptimizerResult, DecoModel, DecoSession, DecoSessionPlan, DecoSessionSnapshot,
+    DecoSimError, DecoSimParams, DecoSimPoint, DecoSimResult, DecoStop, DeepStopParams,
+    DeepStopStrategy, DiveDayDive, DiveDayParams, DiveDayResult, DiveEdit, DiveEditKind,
+    DiveEditParams, DiveEditResult, GasLogisticsParams, GasLogisticsResult, GasMixRange,
+    GasOptimizerObjective, GasSwitchDepth, GasSwitchIcd, GasSwitchPlan, GasUsage, IcdRisk,
+    MaxBottomTimeParams, MaxBottomTimeResult, MinSurfaceIntervalParams, MinSurfaceIntervalResult,
+    ModelComparisonParams, ModelComparisonResult, ModelConfig, ModelRun, PlannedDive,
+    ProfileGenParams, ProfileGenResult, ProgressObserver, ReserveStrategy, RuntimeSegment,
+    RuntimeTable, RuntimeTableFormat, RuntimeTableRow, SafetyStopParams, ScheduleEvalParams,
+    ScheduleEvalResult, ScrMode, ScrParams, SetpointSchedule, SetpointSwitch, StopEvaluation,
+    SurfaceIntervalTarget, SwitchDepthLimit, ThalmannParameters, ThalmannPdcs, TimedSetpointSwitch,
+    UnitSystem,
 };
 pub use error::FormulaError;
 pub use formula::{compute, validate, validate_with_variables, FunctionInfo};
+pub use gas::{BestMixParams, BestMixResult};
 pub use metrics::{DepthClass, DiveInput, DiveStats, SampleInput, SegmentStats};
 
 // ============================================================================
@@ -96,14 +113,218 @@ fn compute_surface_gf(
     buhlmann::compute_surface_gf(&samples, &gas_mixes, surface_pressure_bar)
 }
 
+/// Compute Surface Gradient Factor with a selectable ZHL-16 coefficient set.
+fn compute_surface_gf_with_coefficients(
+    samples: Vec<SampleInput>,
+    gas_mixes: Vec<GasMixInput>,
+    surface_pressure_bar: Option<f64>,
+    coefficient_set: Option<BuhlmannCoefficientSet>,
+    custom_coefficients: Option<BuhlmannCoefficients>,
+) -> Result<Vec<SurfaceGfPoint>, DecoSimError> {
+    buhlmann::compute_surface_gf_with_coefficients(
+        &samples,
+        &gas_mixes,
+        surface_pressure_bar,
+        coefficient_set,
+        custom_coefficients.as_ref(),
+    )
+}
+
+/// Validate a gas mix. Returns None if valid, or error message if invalid.
+fn validate_gas_mix(gas: GasMixInput) -> Option<String> {
+    match gas::validate_gas_mix(&gas) {
+        Ok(()) => None,
+        Err(e) => Some(e.to_string()),
+    }
+}
+
+/// Maximum operating depth (metres) for an O2 fraction at a PPO2 limit.
+fn max_operating_depth(o2_fraction: f64, max_ppo2: f64, surface_pressure_bar: Option<f64>) -> f64 {
+    gas::max_operating_depth(
+        o2_fraction,
+        max_ppo2,
+        surface_pressure_bar.unwrap_or(deco::shared::DEFAULT_SURFACE_PRESSURE),
+    )
+}
+
+/// Minimum operating depth (metres) for an O2 fraction at a PPO2 floor.
+fn min_operating_depth(o2_fraction: f64, min_ppo2: f64, surface_pressure_bar: Option<f64>) -> f64 {
+    gas::min_operating_depth(
+        o2_fraction,
+        min_ppo2,
+        surface_pressure_bar.unwrap_or(deco::shared::DEFAULT_SURFACE_PRESSURE),
+    )
+}
+
+/// Equivalent narcotic depth (metres) of a mix at a depth.
+fn equivalent_narcotic_depth(
+    gas: GasMixInput,
+    depth_m: f64,
+    o2_narcotic: bool,
+    surface_pressure_bar: Option<f64>,
+) -> f64 {
+    gas::equivalent_narcotic_depth(
+        &gas,
+        depth_m,
+        o2_narcotic,
+        surface_pressure_bar.unwrap_or(deco::shared::DEFAULT_SURFACE_PRESSURE),
+    )
+}
+
+/// Gas density (g/L) of a mix at a depth.
+fn gas_density(gas: GasMixInput, depth_m: f64, surface_pressure_bar: Option<f64>) -> f64 {
+    gas::gas_density(
+        &gas,
+        depth_m,
+        surface_pressure_bar.unwrap_or(deco::shared::DEFAULT_SURFACE_PRESSURE),
+    )
+}
+
+/// Best nitrox/trimix for a target depth under PPO2, END and density limits.
+fn best_mix(params: BestMixParams) -> Result<BestMixResult, DecoSimError> {
+    gas::best_mix(&params)
+}
+
+/// Inspired O2 fraction of a semi-closed rebreather loop at a depth.
+fn scr_loop_o2_fraction(
+    params: ScrParams,
+    supply_o2_fraction: f64,
+    depth_m: f64,
+    surface_pressure_bar: Option<f64>,
+) -> Result<f64, DecoSimError> {
+    deco::scr::validate_scr(&params)?;
+    let ambient_p = deco::shared::depth_to_pressure(
+        depth_m,
+        surface_pressure_bar.unwrap_or(deco::shared::DEFAULT_SURFACE_PRESSURE),
+    );
+    Ok(deco::scr::scr_loop_o2_fraction(
+        &params,
+        supply_o2_fraction,
+        ambient_p,
+    ))
+}
+
+/// Compute a partial-pressure or continuous blending fill sequence.
+fn compute_blend(params: BlendParams) -> Result<BlendResult, DecoSimError> {
+    blending::compute_blend(&params)
+}
+
+/// Real-gas compressibility factor Z of a mix at a pressure.
+fn compressibility_factor(gas: GasMixInput, pressure_bar: f64) -> f64 {
+    blending::compressibility_factor(&gas, pressure_bar)
+}
+
 /// Run a deco simulation with the specified model and parameters.
-fn compute_deco_simulation(params: DecoSimParams) -> Result<DecoSimResult, DecoSimError> {
-    deco::compute_deco_simulation(params)
+fn compute_deco_simulation(
+    params: DecoSimParams,
+    cancel: Option<Arc<CancellationToken>>,
+    progress: Option<Box<dyn ProgressObserver>>,
+) -> Result<DecoSimResult, DecoSimError> {
+    let control = ComputeControl::new(cancel.as_deref(), progress.as_deref());
+    deco::compute_deco_simulation_with_control(params, &control)
+}
+
+/// Run one profile under several deco model configurations.
+fn compare_models(
+    params: ModelComparisonParams,
+    cancel: Option<Arc<CancellationToken>>,
+    progress: Option<Box<dyn ProgressObserver>>,
+) -> Result<ModelComparisonResult, DecoSimError> {
+    let control = ComputeControl::new(cancel.as_deref(), progress.as_deref());
+    deco::comparison::compare_models_with_control(params, &control)
 }
 
 /// Generate a synthetic dive profile with computed deco stops.
-fn generate_dive_profile(params: ProfileGenParams) -> Result<ProfileGenResult, DecoSimError> {
-    deco::profile_generator::generate_dive_profile(params)
+fn generate_dive_profile(
+    params: ProfileGenParams,
+    cancel: Option<Arc<CancellationToken>>,
+    progress: Option<Box<dyn ProgressObserver>>,
+) -> Result<ProfileGenResult, DecoSimError> {
+    let control = ComputeControl::new(cancel.as_deref(), progress.as_deref());
+    deco::profile_generator::generate_dive_profile_with_control(params, &control)
+}
+
+/// Plan repetitive dives, carrying residual gas from each dive into the next.
+fn plan_dive_day(
+    params: DiveDayParams,
+    cancel: Option<Arc<CancellationToken>>,
+    progress: Option<Box<dyn ProgressObserver>>,
+) -> Result<DiveDayResult, DecoSimError> {
+    let control = ComputeControl::new(cancel.as_deref(), progress.as_deref());
+    deco::dive_day::plan_dive_day_with_control(&params, &control)
+}
+
+/// Shortest surface interval after completed dives for the next dive's target.
+fn min_surface_interval(
+    params: MinSurfaceIntervalParams,
+    cancel: Option<Arc<CancellationToken>>,
+    progress: Option<Box<dyn ProgressObserver>>,
+) -> Result<MinSurfaceIntervalResult, DecoSimError> {
+    let control = ComputeControl::new(cancel.as_deref(), progress.as_deref());
+    deco::dive_day::min_surface_interval_with_control(&params, &control)
+}
+
+/// Evaluate a user-authored ascent schedule against the selected deco model.
+fn evaluate_ascent_schedule(
+    params: ScheduleEvalParams,
+) -> Result<ScheduleEvalResult, DecoSimError> {
+    deco::schedule_eval::evaluate_ascent_schedule(params)
+}
+
+/// Apply a what-if edit to a logged dive and replan its ascent.
+fn apply_dive_edit(params: DiveEditParams) -> Result<DiveEditResult, DecoSimError> {
+    deco::dive_edit::apply_dive_edit(params)
+}
+
+/// Choose deco gases and switch depths that minimise runtime or deco time.
+fn optimize_deco_gases(
+    params: DecoGasOptimizerParams,
+    cancel: Option<Arc<CancellationToken>>,
+    progress: Option<Box<dyn ProgressObserver>>,
+) -> Result<DecoGasOptimizerResult, DecoSimError> {
+    let control = ComputeControl::new(cancel.as_deref(), progress.as_deref());
+    deco::gas_optimizer::optimize_deco_gases_with_control(params, &control)
+}
+
+/// Find the longest bottom time that keeps runtime, TTS, CNS and gas limits.
+fn solve_max_bottom_time(
+    params: MaxBottomTimeParams,
+    cancel: Option<Arc<CancellationToken>>,
+    progress: Option<Box<dyn ProgressObserver>>,
+) -> Result<MaxBottomTimeResult, DecoSimError> {
+    let control = ComputeControl::new(cancel.as_deref(), progress.as_deref());
+    deco::max_bottom_time::solve_max_bottom_time_with_control(params, &control)
+}
+
+/// Gas volumes, reserves and turn pressures for a generated profile.
+fn plan_gas_logistics(params: GasLogisticsParams) -> Result<GasLogisticsResult, DecoSimError> {
+    deco::gas_usage::plan_gas_logistics(&params)
+}
+
+/// Rule-of-fifths ICD analysis of every gas switch in a profile.
+fn analyze_gas_switches(
+    samples: Vec<SampleInput>,
+    gas_mixes: Vec<GasMixInput>,
+    surface_pressure_bar: Option<f64>,
+) -> Vec<GasSwitchIcd> {
+    deco::icd::analyze_gas_switches(&samples, &gas_mixes, surface_pressure_bar)
+}
+
+/// Build a structured runtime table from a generated profile.
+fn build_runtime_table(
+    profile: ProfileGenResult,
+    surface_pressure_bar: Option<f64>,
+) -> RuntimeTable {
+    deco::runtime_table::build_runtime_table(&profile, surface_pressure_bar)
+}
+
+/// Render a runtime table as plain text, Markdown or CSV.
+fn render_runtime_table(
+    table: RuntimeTable,
+    format: RuntimeTableFormat,
+    units: UnitSystem,
+) -> String {
+    deco::runtime_table::render_runtime_table(&table, format, units)
 }
 
 #[cfg(test)]
@@ -147,7 +147,7 @@ const FALLBACK_PERCENT: f32 = 0.80;
 ///    change within the working phase, not the final departure
 /// 3. Δ+5 ≥ 5 check (on-gassing deferral for shallow working depths)
 /// 4. 80% of max depth fallback
-fn compute_bottom_end_t(samples: &[SampleInput], max_depth_m: f32) -> i32 {
+pub(crate) fn compute_bottom_end_t(samples: &[SampleInput], max_depth_m: f32) -> i32 {
     if samples.len() < 2 || max_depth_m <= 0.0 {
         return 0;
     }